@@ -0,0 +1,4 @@
+fn main() {
+    // The migrations are embedded by sqlx::migrate!().
+    println!("cargo:rerun-if-changed=migrations");
+}
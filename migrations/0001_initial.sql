@@ -0,0 +1,255 @@
+-- The schema as it was when we switched to migrations. Databases created
+-- before that are brought up to this state first; see sql::migrate().
+
+-- Submissions.
+CREATE TABLE IF NOT EXISTS submissions (
+    message INTEGER, -- Message ID of the submission.
+    week INTEGER NOT NULL, -- This is just an integer.
+    challenge INTEGER NOT NULL, -- See Challenge enum.
+    author INTEGER NOT NULL, -- Discord user ID of the author.
+    link TEXT NOT NULL, -- Link to the submission.
+    time INTEGER NOT NULL DEFAULT (unixepoch()), -- Time of submission.
+    votes INTEGER NOT NULL DEFAULT 0, -- Number of votes.
+    status INTEGER NOT NULL DEFAULT 0, -- See SubmissionStatus enum.
+    spoiler INTEGER NOT NULL DEFAULT 0, -- Whether the image is marked as a spoiler.
+    flag TEXT, -- Why moderators flagged this, if they did.
+    flag_warned INTEGER NOT NULL DEFAULT 0, -- Whether the author was warned about the flag.
+    by_command INTEGER NOT NULL DEFAULT 0, -- Whether this was submitted with the ‘Submit to challenge’ command, and so has no submit emoji.
+    PRIMARY KEY (message, week, challenge)
+) STRICT;
+
+-- Cached user profile data (excludes current week, obviously).
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY, -- Discord user ID.
+    nickname TEXT, -- Nickname.
+
+    -- Number of 1st, 2nd, 3rd place finishes in the Glyphs Challenge.
+    glyphs_first INTEGER NOT NULL DEFAULT 0,
+    glyphs_second INTEGER NOT NULL DEFAULT 0,
+    glyphs_third INTEGER NOT NULL DEFAULT 0,
+
+    -- Number of 1st, 2nd, 3rd place finishes in the Ambigram Challenge.
+    ambigrams_first INTEGER NOT NULL DEFAULT 0,
+    ambigrams_second INTEGER NOT NULL DEFAULT 0,
+    ambigrams_third INTEGER NOT NULL DEFAULT 0,
+
+    -- Highest ranking in either challenge.
+    highest_ranking_glyphs INTEGER NOT NULL DEFAULT 0,
+    highest_ranking_ambigrams INTEGER NOT NULL DEFAULT 0,
+
+    -- Whether the user has left the server.
+    former_member INTEGER NOT NULL DEFAULT 0,
+
+    -- Cached Discord display name and avatar, for users that
+    -- are no longer around, and when they were last updated.
+    display_name TEXT,
+    avatar_url TEXT,
+    cache_updated INTEGER NOT NULL DEFAULT 0,
+
+    -- When the nickname was last changed.
+    nickname_changed INTEGER,
+
+    -- Short bio and links shown on the profile.
+    bio TEXT,
+    link1 TEXT,
+    link2 TEXT,
+
+    -- Colour of the profile embed, as 0xRRGGBB.
+    accent_colour INTEGER,
+
+    -- Pronouns, to be shown next to the user’s name.
+    pronouns TEXT
+) STRICT;
+
+-- The current week. This is a table with a single entry.
+CREATE TABLE IF NOT EXISTS current_week (
+    week INTEGER NOT NULL
+) STRICT;
+
+-- Prevent inserting additional weeks.
+CREATE TRIGGER IF NOT EXISTS current_week_insertion
+BEFORE INSERT ON current_week
+WHEN (SELECT COUNT(*) FROM current_week) > 0
+BEGIN
+    SELECT RAISE(ABORT, 'current_week table must not contain more than one entry!');
+END;
+
+-- Start at week 0. The scheduler moves on to the next week once the
+-- current one’s deadline has passed (scheduler::advance_week), and
+-- records each week’s start and deadline (scheduler::record_week_start).
+INSERT INTO current_week (week) SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM current_week);
+
+-- Table that stores what weeks are/were regular or special.
+CREATE TABLE IF NOT EXISTS weeks (
+    week INTEGER PRIMARY KEY, -- Week number.
+
+    -- See Week enum.
+    glyph_challenge_kind INTEGER,
+    ambigram_challenge_kind INTEGER,
+
+    -- Prompts.
+    glyph_prompt TEXT,
+    ambigram_prompt TEXT,
+
+    -- Message ID of the announcement message.
+    glyph_announcement_message INTEGER,
+    ambigram_announcement_message INTEGER,
+
+    -- Message ID of the submissions panel.
+    glyph_panel_message INTEGER,
+    ambigram_panel_message INTEGER,
+
+    -- Message ID of the first hall of fame message.
+    glyph_hof_message INTEGER,
+    ambigram_hof_message INTEGER,
+
+    -- Extra notes/rules for the announcement.
+    glyph_notes TEXT,
+    ambigram_notes TEXT,
+
+    -- Deadline for submissions, if it differs from the default
+    -- schedule. This is a unix timestamp.
+    deadline INTEGER,
+
+    -- Message ID of the recap posted after the results.
+    glyph_recap_message INTEGER,
+    ambigram_recap_message INTEGER,
+
+    -- ID of the featured artist thread.
+    glyph_feature_thread INTEGER,
+    ambigram_feature_thread INTEGER,
+
+    -- When submissions for the week opened, as a unix timestamp;
+    -- recorded once the week has become the current week.
+    start INTEGER
+) STRICT;
+
+-- Table that stores future prompts.
+CREATE TABLE IF NOT EXISTS prompts (
+    challenge INTEGER NOT NULL,
+    prompt TEXT NOT NULL,
+    notes TEXT, -- Extra notes for the announcement.
+    category TEXT, -- Freeform category, for the admins’ convenience.
+    theme TEXT -- Theme of the announcement image; NULL for the default.
+) STRICT;
+
+-- Themes that announcement images can be generated with.
+CREATE TABLE IF NOT EXISTS themes (
+    challenge INTEGER NOT NULL,
+    name TEXT NOT NULL, -- Passed to the image generator.
+    PRIMARY KEY (challenge, name)
+) STRICT;
+
+-- Whether we could DM users recently. See dm::send().
+CREATE TABLE IF NOT EXISTS dm_status (
+    user INTEGER PRIMARY KEY,
+    failures INTEGER NOT NULL DEFAULT 0, -- Consecutive failed DMs.
+    last_success INTEGER, -- Unix timestamp.
+    last_failure INTEGER, -- Unix timestamp.
+    last_error TEXT
+) STRICT;
+
+-- Per-user preferences. See UserPrefs.
+CREATE TABLE IF NOT EXISTS user_prefs (
+    id INTEGER PRIMARY KEY, -- Discord user ID.
+    hide_profile INTEGER NOT NULL DEFAULT 0,
+    hide_submissions INTEGER NOT NULL DEFAULT 0,
+    nickname_only INTEGER NOT NULL DEFAULT 0,
+    dm_opt_out INTEGER NOT NULL DEFAULT 0,
+    certificates INTEGER NOT NULL DEFAULT 0
+) STRICT;
+
+-- Interactive components (buttons etc.) that we’ve sent and when
+-- they expire. The message is only known for non-ephemeral messages.
+CREATE TABLE IF NOT EXISTS components (
+    custom_id TEXT PRIMARY KEY,
+    expires INTEGER NOT NULL, -- Unix timestamp.
+    channel INTEGER,
+    message INTEGER,
+    cleaned INTEGER NOT NULL DEFAULT 0 -- Whether we’ve removed it from the message.
+) STRICT;
+
+-- Votes cast on submissions.
+CREATE TABLE IF NOT EXISTS votes (
+    message INTEGER NOT NULL, -- Message ID of the submission.
+    voter INTEGER NOT NULL, -- Discord user ID of the voter.
+    time INTEGER NOT NULL DEFAULT (unixepoch()),
+    week INTEGER, -- Week and challenge of the submission.
+    challenge INTEGER,
+    PRIMARY KEY (message, voter)
+) STRICT;
+
+-- Automatic replies to messages in the submission channels.
+CREATE TABLE IF NOT EXISTS autoresponses (
+    trigger TEXT NOT NULL, -- Case-insensitive keyword or phrase.
+    response TEXT NOT NULL
+) STRICT;
+
+-- Hosts that linked submissions may or may not come from.
+CREATE TABLE IF NOT EXISTS link_hosts (
+    host TEXT PRIMARY KEY, -- Also matches subdomains.
+    allowed INTEGER NOT NULL -- Boolean; denied otherwise.
+) STRICT;
+
+-- Admin-editable texts. See Template enum.
+CREATE TABLE IF NOT EXISTS templates (
+    name TEXT PRIMARY KEY,
+    text TEXT NOT NULL
+) STRICT;
+
+-- Constraints on entries that admins changed. See Constraints struct.
+CREATE TABLE IF NOT EXISTS challenge_constraints (
+    challenge INTEGER PRIMARY KEY,
+    min_size INTEGER NOT NULL,
+    max_size INTEGER NOT NULL, -- 0 = no limit.
+    square INTEGER NOT NULL,
+    monochrome INTEGER NOT NULL,
+    animation INTEGER NOT NULL
+) STRICT;
+
+-- Runtime settings. See Setting enum.
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+) STRICT;
+
+-- Periodic tasks run by the scheduler. See scheduler::TASKS.
+CREATE TABLE IF NOT EXISTS tasks (
+    name TEXT PRIMARY KEY,
+    last_run INTEGER, -- Unix timestamp; NULL if it never ran.
+    disabled INTEGER NOT NULL DEFAULT FALSE
+) STRICT;
+
+-- Achievements users have earned. See Achievement enum.
+CREATE TABLE IF NOT EXISTS achievements (
+    user INTEGER NOT NULL,
+    achievement INTEGER NOT NULL,
+    awarded INTEGER NOT NULL DEFAULT (unixepoch()),
+    PRIMARY KEY (user, achievement)
+) STRICT;
+
+-- Custom emoji used for achievements instead of their standard emoji.
+CREATE TABLE IF NOT EXISTS badge_emojis (
+    achievement INTEGER PRIMARY KEY, -- See Achievement enum.
+    emoji INTEGER NOT NULL -- ID of a custom emoji on the server.
+) STRICT;
+
+-- Final placements of each week, recorded when the results are published.
+CREATE TABLE IF NOT EXISTS placements (
+    message INTEGER PRIMARY KEY,
+    week INTEGER NOT NULL,
+    challenge INTEGER NOT NULL,
+    author INTEGER NOT NULL,
+    place INTEGER NOT NULL,
+    votes INTEGER NOT NULL,
+    total_votes INTEGER NOT NULL -- Votes for all entries that week.
+) STRICT;
+
+-- Seasons. At most one season has no end week.
+CREATE TABLE IF NOT EXISTS seasons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    start_week INTEGER NOT NULL,
+    end_week INTEGER,
+    closed INTEGER -- When the champion was announced and certificates sent; NULL until then.
+) STRICT;
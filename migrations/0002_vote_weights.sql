@@ -0,0 +1,16 @@
+-- Weighted votes. Weights are in percent so that tallies stay exact.
+
+-- Weight of votes by members with a role. Someone with several of these
+-- roles gets the highest weight; everyone else gets 100.
+CREATE TABLE vote_weights (
+    role INTEGER PRIMARY KEY, -- Discord role ID.
+    weight INTEGER NOT NULL
+) STRICT;
+
+-- The weight each ballot was cast with.
+ALTER TABLE votes ADD COLUMN weight INTEGER NOT NULL DEFAULT 100;
+
+-- Sum of the weights of the votes on a submission; this decides the
+-- ranking. Earlier votes all had the same weight.
+ALTER TABLE submissions ADD COLUMN score INTEGER NOT NULL DEFAULT 0;
+UPDATE submissions SET score = votes * 100;
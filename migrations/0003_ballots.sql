@@ -0,0 +1,20 @@
+-- Private ballots, for voting without reactions. See ballots.rs.
+
+-- Ballots handed out to voters; each can be cast once.
+CREATE TABLE ballots (
+    id INTEGER PRIMARY KEY,
+    voter INTEGER NOT NULL, -- Discord user ID.
+    week INTEGER NOT NULL, -- Week and challenge being voted on.
+    challenge INTEGER NOT NULL,
+    issued INTEGER NOT NULL DEFAULT (unixepoch()),
+    used INTEGER, -- When the ballot was cast; NULL until then.
+    UNIQUE (voter, week, challenge)
+) STRICT;
+
+-- Entries picked on a ballot that hasn’t been cast yet.
+CREATE TABLE ballot_choices (
+    ballot INTEGER NOT NULL,
+    menu INTEGER NOT NULL, -- Which of the ballot’s select menus this was picked in.
+    message INTEGER NOT NULL, -- Message ID of the submission.
+    PRIMARY KEY (ballot, message)
+) STRICT;
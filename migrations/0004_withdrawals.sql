@@ -0,0 +1,13 @@
+-- Submissions that were withdrawn, for spotting patterns such as mass
+-- withdrawals right before voting.
+CREATE TABLE withdrawals (
+    message INTEGER NOT NULL, -- Message ID of the submission.
+    week INTEGER NOT NULL,
+    challenge INTEGER NOT NULL,
+    author INTEGER NOT NULL,
+    submitted INTEGER NOT NULL, -- When the entry was submitted.
+    withdrawn INTEGER NOT NULL DEFAULT (unixepoch()),
+    reason INTEGER NOT NULL -- See WithdrawalReason enum.
+) STRICT;
+
+CREATE INDEX withdrawals_week ON withdrawals (week);
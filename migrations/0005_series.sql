@@ -0,0 +1,17 @@
+-- Prompts in several parts that run in consecutive weeks, e.g. an
+-- uppercase letter one week and the lowercase letter the next.
+CREATE TABLE series (
+    id INTEGER PRIMARY KEY,
+    challenge INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    parts INTEGER NOT NULL,
+    results_message INTEGER -- Message ID of the combined results, once posted.
+) STRICT;
+
+-- The series a queued prompt belongs to, and which part of it it is.
+ALTER TABLE prompts ADD COLUMN series INTEGER;
+ALTER TABLE prompts ADD COLUMN series_part INTEGER;
+
+-- The series the prompt of each week belonged to.
+ALTER TABLE weeks ADD COLUMN glyph_series INTEGER;
+ALTER TABLE weeks ADD COLUMN ambigram_series INTEGER;
@@ -0,0 +1,4 @@
+-- Whether an admin confirmed the preview of a queued prompt. Prompts that
+-- are already queued were added before there was anything to confirm.
+ALTER TABLE prompts ADD COLUMN approved INTEGER NOT NULL DEFAULT 0;
+UPDATE prompts SET approved = 1;
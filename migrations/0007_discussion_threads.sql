@@ -0,0 +1,3 @@
+-- ID of the discussion thread started on each announcement.
+ALTER TABLE weeks ADD COLUMN glyph_discussion_thread INTEGER;
+ALTER TABLE weeks ADD COLUMN ambigram_discussion_thread INTEGER;
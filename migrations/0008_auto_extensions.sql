@@ -0,0 +1,3 @@
+-- Number of times the deadline of a week was extended automatically
+-- because there were too few entries.
+ALTER TABLE weeks ADD COLUMN auto_extensions INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,35 @@
+-- Members may only have one active entry per challenge and week. Of any
+-- earlier duplicates, the latest is kept and the others are replaced the
+-- way a newer entry replaces one now: they are logged as withdrawals
+-- (reason 3 = replaced) and deleted.
+--
+-- Weeks whose hall of fame is already out stay as they were ranked; their
+-- duplicates are marked so the index below exempts them.
+ALTER TABLE submissions ADD COLUMN ranked_duplicate INTEGER NOT NULL DEFAULT 0;
+
+UPDATE submissions SET ranked_duplicate = 1
+WHERE status = 0 AND author != 0 AND EXISTS (
+    SELECT 1 FROM submissions t
+    WHERE t.week = submissions.week AND t.challenge = submissions.challenge AND t.author = submissions.author AND t.status = 0
+    AND (t.time > submissions.time OR (t.time = submissions.time AND t.message > submissions.message))
+) AND EXISTS (
+    SELECT 1 FROM weeks w
+    WHERE w.week = submissions.week
+    AND CASE submissions.challenge WHEN 0 THEN w.glyph_hof_message ELSE w.ambigram_hof_message END IS NOT NULL
+);
+
+INSERT INTO withdrawals (message, week, challenge, author, submitted, reason)
+SELECT s.message, s.week, s.challenge, s.author, s.time, 3
+FROM submissions s
+WHERE s.status = 0 AND s.author != 0 AND s.ranked_duplicate = 0 AND EXISTS (
+    SELECT 1 FROM submissions t
+    WHERE t.week = s.week AND t.challenge = s.challenge AND t.author = s.author AND t.status = 0
+    AND (t.time > s.time OR (t.time = s.time AND t.message > s.message))
+);
+
+DELETE FROM submissions
+WHERE message IN (SELECT message FROM withdrawals WHERE reason = 3);
+
+-- Purged authors are all anonymised to 0, so they are exempt.
+CREATE UNIQUE INDEX submissions_one_per_week ON submissions (week, challenge, author)
+WHERE status = 0 AND author != 0 AND ranked_duplicate = 0;
@@ -0,0 +1,20 @@
+-- Winners picking the next prompt. See prompt_picks.rs.
+
+-- Prompts admins put forward for winners to pick from.
+CREATE TABLE shortlist (
+    id INTEGER PRIMARY KEY,
+    challenge INTEGER NOT NULL,
+    prompt TEXT NOT NULL
+) STRICT;
+
+-- Winners invited to pick the next prompt.
+CREATE TABLE prompt_picks (
+    id INTEGER PRIMARY KEY,
+    week INTEGER NOT NULL, -- Week and challenge they won.
+    challenge INTEGER NOT NULL,
+    user INTEGER NOT NULL, -- Discord user ID.
+    prompt TEXT, -- What they picked or suggested; NULL until they do.
+    shortlisted INTEGER, -- The shortlist entry they picked, if any.
+    queued INTEGER, -- The queue entry it became once approved; NULL until then.
+    UNIQUE (week, challenge)
+) STRICT;
@@ -0,0 +1,29 @@
+-- Polls in which members vote on the next prompt. See polls.rs.
+
+CREATE TABLE polls (
+    id INTEGER PRIMARY KEY,
+    challenge INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    message INTEGER, -- Message ID of the poll, once posted.
+    opened INTEGER NOT NULL,
+    closes INTEGER NOT NULL,
+    closed INTEGER, -- When the poll was closed; NULL while it is open.
+    queued INTEGER -- The queue entry the winning prompt became, if any.
+) STRICT;
+
+-- The prompts to vote on in a poll, taken from the shortlist.
+CREATE TABLE poll_options (
+    poll INTEGER NOT NULL,
+    option INTEGER NOT NULL, -- Position in the poll, starting at 0.
+    prompt TEXT NOT NULL,
+    shortlisted INTEGER, -- The shortlist entry it came from.
+    PRIMARY KEY (poll, option)
+) STRICT;
+
+-- Each member’s vote in a poll; they can change it while the poll is open.
+CREATE TABLE poll_votes (
+    poll INTEGER NOT NULL,
+    voter INTEGER NOT NULL, -- Discord user ID.
+    option INTEGER NOT NULL,
+    PRIMARY KEY (poll, voter)
+) STRICT;
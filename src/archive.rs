@@ -0,0 +1,80 @@
+//! Local copies of submission images.
+//!
+//! Attachment links expire and messages can be deleted, so images are
+//! kept on disk once we’ve downloaded them, e.g. for a panel, and later
+//! uses such as portfolios read them from there.
+
+use std::path::PathBuf;
+use image::DynamicImage;
+use poise::serenity_prelude as ser;
+use crate::{err, Error, info, sql};
+use crate::sql::Challenge;
+
+/// Directory the images are stored in, as `<message>.png`.
+const ARCHIVE_DIR: &str = "./archive";
+
+fn path(message: ser::MessageId) -> PathBuf {
+    PathBuf::from(ARCHIVE_DIR).join(format!("{}.png", message))
+}
+
+/// Largest linked image we download, in bytes.
+const MAX_LINKED_IMAGE_SIZE: u64 = 25 * 1024 * 1024;
+
+/// Download the image of a submission.
+///
+/// We fetch the message again to get a fresh link instead of using the
+/// stored one, since that may have expired. Linked entries don’t have an
+/// attachment, so they are downloaded from the link they were entered with.
+async fn download(ctx: &ser::Context, challenge: Challenge, message: ser::MessageId) -> Result<DynamicImage, Error> {
+    let att = challenge.submission_channel().message(ctx, message).await?.attachments.into_iter().next();
+    let bytes = match att {
+        Some(att) => att.download().await?,
+        None => {
+            let link = sql::get_submission(message).await?.map(|s| s.link).unwrap_or_default();
+            if link.is_empty() { return Err("Submission no longer has an image".into()); }
+            let res = reqwest::get(&link).await?.error_for_status()?;
+            if res.content_length().is_some_and(|len| len > MAX_LINKED_IMAGE_SIZE) {
+                return Err(format!("Linked image at {} is too large", link).into());
+            }
+
+            res.bytes().await?.to_vec()
+        }
+    };
+
+    Ok(image::load_from_memory(&bytes)?)
+}
+
+/// Get the path of the archived image of a submission, downloading it
+/// first if we don’t have it yet.
+pub async fn submission_path(ctx: &ser::Context, challenge: Challenge, message: ser::MessageId) -> Result<PathBuf, Error> {
+    let path = path(message);
+    if path.exists() { return Ok(path); }
+
+    let img = download(ctx, challenge, message).await?;
+    std::fs::create_dir_all(ARCHIVE_DIR)?;
+
+    // Encoding is CPU-bound, so keep it off the async workers.
+    let out = path.clone();
+    tokio::task::spawn_blocking(move || img.save(out)).await??;
+    info!("Archived submission {} at {}", message, path.display());
+    Ok(path)
+}
+
+/// Get the image of a submission, from the archive if we have it, and
+/// otherwise from Discord, in which case it is added to the archive.
+pub async fn submission_image(ctx: &ser::Context, challenge: Challenge, message: ser::MessageId) -> Result<DynamicImage, Error> {
+    let path = submission_path(ctx, challenge, message).await?;
+    Ok(tokio::task::spawn_blocking(move || image::open(path)).await??)
+}
+
+/// Delete the archived images of submissions, e.g. when their author asks
+/// for their data to be deleted.
+pub async fn remove(messages: &[ser::MessageId]) {
+    for &message in messages {
+        match tokio::fs::remove_file(path(message)).await {
+            Ok(()) => info!("Deleted archived image of submission {}", message),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => err!("Could not delete archived image of submission {}: {}", message, e),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Watch the files the bot uses at runtime, such as the announcement
+//! generator and the certificate font, so admins can tell that swapping
+//! one out took effect.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use notify::{Event, RecursiveMode, Watcher};
+use poise::serenity_prelude as ser;
+use tokio::sync::mpsc;
+use crate::{commands, err, err_sync, info, preflight, sql};
+use crate::core::{begin_work, shutdown_token};
+use crate::sql::{Challenge, Setting};
+
+/// Scripts and resources used to generate announcement images.
+const CHALLENGE_ASSETS: &str = "./weekly_challenges";
+
+/// Font and background of the certificates.
+const CERTIFICATE_ASSETS: &str = "./certificates";
+
+/// Changes are reported once nothing else changed for this long, so that
+/// copying several files only results in one message.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Maximum number of files listed in the message to the admins.
+const MAX_LISTED: usize = 10;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Check whether a file is one we write ourselves, i.e. generated images
+/// and Python’s bytecode cache.
+fn is_generated(path: &Path) -> bool {
+    let previews = Path::new(commands::PREVIEW_CACHE_DIR).file_name();
+    if path.components().any(|c| c.as_os_str() == "__pycache__" || Some(c.as_os_str()) == previews) { return true; }
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false; };
+    [Challenge::Glyph, Challenge::Ambigram].into_iter().any(|c| {
+        let image = c.announcement_image_path();
+        let image = Path::new(&image).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        name == image || name.strip_suffix(".bak") == Some(image)
+    })
+}
+
+/// Start watching the asset directories. Only the first call has any effect.
+pub fn start(ctx: ser::Context) {
+    if STARTED.swap(true, Ordering::SeqCst) { return; }
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => return err_sync!("Error watching assets: {}", e),
+        };
+
+        if !(event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove()) { return; }
+        for path in event.paths {
+            if !is_generated(&path) { let _ = tx.send(path); }
+        }
+    });
+
+    let mut watcher = match watcher {
+        Ok(w) => w,
+        Err(e) => return err_sync!("Could not watch assets: {}", e),
+    };
+
+    // Watch absolute paths so we can tell which directory a file is in.
+    let mut watching = false;
+    for dir in [CHALLENGE_ASSETS, CERTIFICATE_ASSETS] {
+        let Ok(dir) = std::fs::canonicalize(dir) else { continue; };
+        match watcher.watch(&dir, RecursiveMode::Recursive) {
+            Ok(()) => watching = true,
+            Err(e) => err_sync!("Could not watch {}: {}", dir.display(), e),
+        }
+    }
+
+    if !watching { return; }
+    tokio::spawn(async move {
+        // Dropping the watcher stops it.
+        let _watcher = watcher;
+        loop {
+            let first = tokio::select! {
+                _ = shutdown_token().cancelled() => break,
+                path = rx.recv() => match path {
+                    Some(path) => path,
+                    None => break,
+                },
+            };
+
+            let mut changed = BTreeSet::from([first]);
+            while let Ok(Some(path)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await { changed.insert(path); }
+
+            let Some(_work) = begin_work() else { break; };
+            on_assets_changed(&ctx, changed).await;
+        }
+    });
+}
+
+/// Log which files changed, clear cached previews if the generator
+/// changed, and tell the admins.
+async fn on_assets_changed(ctx: &ser::Context, changed: BTreeSet<PathBuf>) {
+    let cwd = std::env::current_dir().ok();
+    let names = changed.iter()
+        .map(|p| cwd.as_ref().and_then(|cwd| p.strip_prefix(cwd).ok()).unwrap_or(p).display().to_string())
+        .collect::<Vec<_>>();
+    info!("Asset files changed: {}", names.join(", "));
+
+    let mut text = format!("Asset files changed:\n{}", names.iter()
+        .take(MAX_LISTED)
+        .map(|n| format!("- `{}`", n))
+        .collect::<Vec<_>>()
+        .join("\n")
+    );
+
+    if names.len() > MAX_LISTED { text += &format!("\n- … and {} more", names.len() - MAX_LISTED); }
+
+    // Previews are made by the generator, so they may be outdated now.
+    let generator = std::fs::canonicalize(CHALLENGE_ASSETS).ok();
+    if generator.is_some_and(|dir| changed.iter().any(|p| p.starts_with(&dir))) {
+        match sql::get_setting::<bool>(Setting::ClearPreviewsOnAssetChange).await {
+            Ok(false) => {}
+            Ok(true) => match commands::clear_preview_cache() {
+                Ok(0) => {}
+                Ok(n) => {
+                    info!("Cleared {} cached preview(s)", n);
+                    text += &format!("\n\nCleared {} cached preview(s).", n);
+                }
+                Err(e) => err!("Error clearing cached previews: {}", e),
+            },
+            Err(e) => err!("Error getting setting: {}", e),
+        }
+    }
+
+    match sql::get_setting::<bool>(Setting::AssetChangeAlerts).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => return err!("Error getting setting: {}", e),
+    }
+
+    match preflight::admin_channel().await {
+        Ok(Some(channel)) => if let Err(e) = channel.say(ctx, text).await {
+            err!("Could not tell admins about changed assets: {}", e);
+        },
+        Ok(None) => {}
+        Err(e) => err!("Error getting admin channel: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn our_own_output_is_ignored() {
+        let dir = Path::new("/srv/glyfi/weekly_challenges");
+        assert!(is_generated(&dir.join("glyph_announcement.png")));
+        assert!(is_generated(&dir.join("ambigram_announcement.png.bak")));
+        assert!(is_generated(&dir.join("previews/0-3-abc.png")));
+        assert!(is_generated(&dir.join("__pycache__/weekly_challenges.cpython-311.pyc")));
+        assert!(!is_generated(&dir.join("weekly_challenges.py")));
+        assert!(!is_generated(&dir.join("fonts/glyph_announcement.ttf")));
+    }
+}
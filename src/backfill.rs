@@ -0,0 +1,71 @@
+//! Fill the votes table from the reactions on submissions of weeks that
+//! predate it, via `glyfi backfill-votes`.
+
+use std::ops::RangeInclusive;
+use poise::serenity_prelude::{MessageId, ReactionType};
+use crate::{Error, info, sql, warn};
+use crate::discord::{DiscordApi, HttpApi};
+use crate::events::{vote_reaction, vote_weight};
+use crate::sql::Challenge;
+
+/// Parse a range of weeks given as `a..b` (inclusive), or a single week.
+pub fn parse_weeks(s: &str) -> Result<RangeInclusive<i64>, String> {
+    let parse = |w: &str| w.trim().parse::<i64>().map_err(|_| format!("‘{}’ is not a week number", w));
+    let (from, to) = match s.split_once("..") {
+        Some((from, to)) => (parse(from)?, parse(to.strip_prefix('=').unwrap_or(to))?),
+        None => (parse(s)?, parse(s)?),
+    };
+
+    if from < 0 || to < from { return Err(format!("‘{}’ is not a range of weeks", s)); }
+    Ok(from..=to)
+}
+
+/// Record a vote for everyone who reacted with `emoji` to the active
+/// submissions of some weeks. Votes that are already recorded are left
+/// alone, so this can simply be run again if it is interrupted. Votes are
+/// weighted by the roles people have now, not those they had back then.
+///
+/// Returns the number of votes added.
+pub async fn backfill_votes(api: &dyn DiscordApi, weeks: RangeInclusive<i64>, emoji: &ReactionType) -> Result<usize, Error> {
+    let mut added = 0;
+    for week in weeks {
+        for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+            let channel = challenge.submission_channel();
+            let mut week_added = 0;
+            for s in sql::get_submissions(week, challenge).await? {
+                let message = MessageId::new(s.message as u64);
+                let users = match api.reaction_users(channel, message, emoji.clone()).await {
+                    Ok(users) => users,
+                    Err(e) => {
+                        warn!("Could not get reactions on submission {}: {}", message, e);
+                        continue;
+                    }
+                };
+
+                // Votes for yourself are rejected by add_vote().
+                for user in users.into_iter().filter(|&u| u != api.current_user_id()) {
+                    let weight = vote_weight(api, user, None).await?;
+                    if sql::add_vote(message, user, weight).await? { week_added += 1; }
+                }
+            }
+
+            info!("Added {} vote(s) for {:?} in week {}", week_added, challenge, week);
+            added += week_added;
+        }
+    }
+
+    Ok(added)
+}
+
+/// Run `glyfi backfill-votes`. Without `emoji`, the current vote emoji is used.
+pub async fn run(token: &str, weeks: RangeInclusive<i64>, emoji: Option<&str>) -> Result<(), Error> {
+    let emoji = match emoji {
+        Some(e) => ReactionType::try_from(e.trim()).map_err(|_| format!("‘{}’ is not an emoji", e))?,
+        None => vote_reaction().await,
+    };
+
+    let api = HttpApi::new(token).await?;
+    let added = backfill_votes(&api, weeks.clone(), &emoji).await?;
+    info!("Added {} vote(s) in weeks {} to {}", added, weeks.start(), weeks.end());
+    Ok(())
+}
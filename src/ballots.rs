@@ -0,0 +1,169 @@
+//! Voting with private ballots instead of reactions, so nobody can see
+//! who voted for what while voting is open.
+//!
+//! When [`Setting::BallotVoting`] is enabled, everyone who took part in
+//! the last few weeks is sent a ballot by DM once voting opens, and anyone
+//! else can get one with `/ballot`. A ballot lists the entries in select
+//! menus, across several pages if there are too many for one message;
+//! picking entries does nothing until the ballot is cast, and each ballot
+//! can only be cast once.
+
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateButton, CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, Mentionable, MessageId, UserId};
+use crate::{clock, Error, info, Res, scheduler, sql};
+use crate::core::{BallotAction, InteractionID, safe_truncate, timestamp, TimestampStyle};
+use crate::discord::DiscordApi;
+use crate::dm::{DmBatch, DmKind};
+use crate::events::vote_weight;
+use crate::sql::{Challenge, IssuedBallot, Setting, Submission};
+
+/// Discord allows at most 25 options per select menu, and 5 rows of
+/// components per message; the last row holds the buttons that cast the
+/// ballot and flip through its pages.
+const ENTRIES_PER_MENU: usize = 25;
+const MENUS_PER_PAGE: usize = 4;
+
+/// Get the week that is being voted on.
+pub async fn voting_week() -> Result<i64, Error> {
+    Ok(sql::current_week().await? - 1)
+}
+
+/// Check that a ballot belongs to someone and can still be changed or cast.
+async fn check_ballot(id: i64, voter: UserId) -> Result<IssuedBallot, Error> {
+    let Some(ballot) = sql::get_issued_ballot(id).await? else { return Err("This ballot doesn’t exist".into()); };
+    if ballot.voter != voter.get() as i64 { return Err("This isn’t your ballot".into()); }
+    if ballot.used.is_some() { return Err("You have already cast this ballot".into()); }
+    if ballot.week != voting_week().await? || clock::now() >= scheduler::voting_deadline(ballot.week).await? {
+        return Err("Voting on these entries has closed".into());
+    }
+
+    Ok(ballot)
+}
+
+/// The entries someone can vote for on a ballot, i.e. everyone else’s.
+async fn ballot_entries(ballot: &IssuedBallot) -> Result<Vec<Submission>, Error> {
+    let challenge = Challenge::try_from(ballot.challenge)?;
+    let mut entries = sql::get_submissions(ballot.week, challenge).await?;
+    entries.retain(|s| s.author != ballot.voter);
+    Ok(entries)
+}
+
+/// Build the text and menus of a page of a ballot, counting from 0.
+/// Entries are listed by author and in the order they were submitted, like
+/// in the submission channel.
+pub async fn render(ballot: &IssuedBallot, page: i64) -> Result<(String, Vec<CreateActionRow>), Error> {
+    let challenge = Challenge::try_from(ballot.challenge)?;
+    let entries = ballot_entries(ballot).await?;
+    let close = scheduler::voting_deadline(ballot.week).await?;
+    let pages = entries.len().div_ceil(ENTRIES_PER_MENU * MENUS_PER_PAGE).max(1);
+    let page = page.clamp(0, pages as i64 - 1) as usize;
+    let mut text = format!(
+        "**Your ballot for the {} Challenge, week {}**\nThe entries are in {}. Pick every entry you want to vote for, then cast your ballot; nobody else can see your choices. Voting closes {}.",
+        challenge.name(),
+        ballot.week,
+        challenge.submission_channel().mention(),
+        timestamp(close, TimestampStyle::Relative),
+    );
+
+    if pages > 1 {
+        text += &format!("\n\nThere are {} entries, so they are split across {} pages; your picks on every page count. This is page {}.", entries.len(), pages, page + 1);
+    }
+
+    let mut labels = Vec::with_capacity(entries.len());
+    for (i, s) in entries.iter().enumerate() {
+        let name = sql::get_author_name(s.author).await?.unwrap_or_else(|| "Unknown".into());
+        labels.push(safe_truncate(format!("{}. {}", i + 1, name), 100));
+    }
+
+    // Menus are numbered across pages, so picks on other pages are kept.
+    let chosen = sql::get_ballot_choices(ballot.id).await?;
+    let mut rows = Vec::new();
+    for (menu, chunk) in entries.chunks(ENTRIES_PER_MENU).enumerate().skip(page * MENUS_PER_PAGE).take(MENUS_PER_PAGE) {
+        let options = chunk.iter()
+            .enumerate()
+            .map(|(i, s)| CreateSelectMenuOption::new(labels[menu * ENTRIES_PER_MENU + i].clone(), s.message.to_string())
+                .default_selection(chosen.contains(&s.message)))
+            .collect::<Vec<_>>();
+
+        let id = InteractionID::Ballot { ballot: ballot.id, action: BallotAction::Choose { menu: menu as i64 } }.encode();
+        rows.push(CreateActionRow::SelectMenu(CreateSelectMenu::new(id, CreateSelectMenuKind::String { options })
+            .placeholder("Pick entries to vote for")
+            .min_values(0)
+            .max_values(chunk.len() as u8)
+        ));
+    }
+
+    let mut buttons = vec![
+        CreateButton::new(InteractionID::Ballot { ballot: ballot.id, action: BallotAction::Cast }.encode())
+            .label("Cast ballot")
+            .style(ButtonStyle::Primary),
+    ];
+
+    if pages > 1 {
+        let button = |to: usize, label: &str, disabled: bool| CreateButton::new(InteractionID::Ballot { ballot: ballot.id, action: BallotAction::Page { page: to as i64 } }.encode())
+            .label(label)
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled);
+
+        buttons.push(button(page.saturating_sub(1), "Previous page", page == 0));
+        buttons.push(button(page + 1, "Next page", page + 1 == pages));
+    }
+
+    rows.push(CreateActionRow::Buttons(buttons));
+    Ok((text, rows))
+}
+
+/// Show another page of someone’s ballot.
+pub async fn show_page(id: i64, voter: UserId, page: i64) -> Result<(String, Vec<CreateActionRow>), Error> {
+    let ballot = check_ballot(id, voter).await?;
+    render(&ballot, page).await
+}
+
+/// Remember the entries someone picked in one of the menus of their ballot.
+pub async fn choose(id: i64, voter: UserId, menu: i64, picked: &[String]) -> Res {
+    let ballot = check_ballot(id, voter).await?;
+    let entries = ballot_entries(&ballot).await?;
+    let mut messages = Vec::with_capacity(picked.len());
+    for p in picked {
+        let message = p.parse::<i64>().map_err(|_| format!("Invalid entry ‘{}’", p))?;
+        if !entries.iter().any(|s| s.message == message) { return Err("That entry is no longer in the running".into()); }
+        messages.push(MessageId::new(message as u64));
+    }
+
+    sql::set_ballot_choices(ballot.id, menu, &messages).await
+}
+
+/// Cast someone’s ballot. Returns the number of votes recorded.
+pub async fn cast(api: &dyn DiscordApi, id: i64, voter: UserId) -> Result<usize, Error> {
+    let ballot = check_ballot(id, voter).await?;
+    let weight = vote_weight(api, voter, None).await?;
+    let votes = sql::cast_ballot(ballot.id, weight).await?.ok_or("You have already cast this ballot")?;
+    info!("{} cast ballot {} with {} vote(s)", voter, ballot.id, votes);
+    Ok(votes)
+}
+
+/// Send a ballot to everyone who took part recently and hasn’t got one for
+/// the week being voted on. Returns the number of ballots sent.
+pub async fn send_ballots(api: &dyn DiscordApi) -> Result<usize, Error> {
+    if !sql::get_setting::<bool>(Setting::BallotVoting).await? { return Ok(0); }
+    let week = voting_week().await?;
+    if week < 0 || clock::now() >= scheduler::voting_deadline(week).await? { return Ok(0); }
+
+    let voters = sql::get_active_users(scheduler::ACTIVE_WEEKS).await?;
+    let mut batch = DmBatch::new(DmKind::Optional);
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        if sql::count_submissions(week, challenge).await? == 0 { continue; }
+        for &voter in &voters {
+            let (ballot, new) = sql::issue_ballot(voter, week, challenge).await?;
+            if !new { continue; }
+            let (text, rows) = render(&ballot, 0).await?;
+            batch.send(api, voter, CreateMessage::new().content(text).components(rows)).await;
+        }
+    }
+
+    if batch.sent + batch.failed > 0 {
+        info!("Sent {} ballot(s) for week {}; {} could not be delivered", batch.sent, week, batch.failed);
+    }
+
+    Ok(batch.sent)
+}
@@ -0,0 +1,126 @@
+use std::io::Cursor;
+use ab_glyph::{FontVec, PxScale};
+use image::{ImageFormat, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use poise::serenity_prelude as ser;
+use crate::{Error, info, results, sql};
+use crate::dm::{DmBatch, DmKind};
+use crate::sql::Season;
+
+/// Font used for the certificates.
+const FONT_PATH: &str = "./certificates/font.ttf";
+
+/// Optional background image; should be [`WIDTH`] × [`HEIGHT`] pixels.
+const BACKGROUND_PATH: &str = "./certificates/background.png";
+
+/// Size of a certificate.
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 1131;
+
+/// Colours used if there is no background image.
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT: Rgba<u8> = Rgba([32, 32, 32, 255]);
+
+/// What goes on a certificate.
+pub struct Certificate {
+    pub name: String,
+    pub season: String,
+    pub submissions: i64,
+    pub best_place: Option<usize>,
+}
+
+/// Render a certificate as a PNG.
+pub fn render_certificate(cert: &Certificate) -> Result<Vec<u8>, Error> {
+    let font = FontVec::try_from_vec(std::fs::read(FONT_PATH)?)?;
+    let img = draw_certificate(&font, image::open(BACKGROUND_PATH).ok().map(|bg| bg.to_rgba8()), cert);
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png)?;
+    Ok(out.into_inner())
+}
+
+/// Draw a certificate onto a background, or a blank page if there is none.
+fn draw_certificate(font: &FontVec, background: Option<RgbaImage>, cert: &Certificate) -> RgbaImage {
+    let mut img = background.unwrap_or_else(|| RgbaImage::from_pixel(WIDTH, HEIGHT, BACKGROUND));
+    let mut lines = vec![
+        (96.0, "Certificate of Participation".to_string()),
+        (40.0, "awarded to".to_string()),
+        (80.0, cert.name.clone()),
+        (40.0, format!("for taking part in {}", cert.season)),
+        (40.0, format!("with {} submission{}", cert.submissions, if cert.submissions == 1 { "" } else { "s" })),
+    ];
+
+    if let Some(place) = cert.best_place {
+        lines.push((40.0, format!("Best placement: {}", results::ordinal(place))));
+    }
+
+    // Centre the text horizontally, and the whole block vertically.
+    let sizes: Vec<_> = lines.iter().map(|(s, l)| text_size(PxScale::from(*s), font, l)).collect();
+    let spacing = 40;
+    let total = sizes.iter().map(|(_, h)| h + spacing).sum::<u32>() - spacing;
+    let mut y = img.height().saturating_sub(total) / 2;
+    for ((scale, line), (w, h)) in lines.iter().zip(sizes) {
+        let x = img.width().saturating_sub(w) / 2;
+        draw_text_mut(&mut img, TEXT, x as i32, y as i32, PxScale::from(*scale), font, line);
+        y += h + spacing;
+    }
+
+    img
+}
+
+/// DM a certificate to everyone who took part in a season and opted in.
+/// Returns how many were sent.
+pub async fn send_certificates(ctx: &ser::Context, season: &Season) -> Result<usize, Error> {
+    let Some(end_week) = season.end_week else { return Err("Season has not ended yet".into()); };
+    let best = results::best_placements(season.start_week, end_week).await?;
+    let mut batch = DmBatch::new(DmKind::Optional);
+
+    for (user, submissions) in sql::count_submissions_by_user(season.start_week, end_week).await? {
+        if !sql::get_user_prefs(user).await?.certificates { continue; }
+
+        let name = match sql::get_display_name(user).await? {
+            Some(name) => name,
+            None => user.to_user(ctx).await?.name,
+        };
+
+        let cert = Certificate {
+            name,
+            season: season.name.clone(),
+            submissions,
+            best_place: best.get(&user).copied(),
+        };
+
+        // Rendering is CPU-bound, so keep it off the async workers.
+        let png = match tokio::task::spawn_blocking(move || render_certificate(&cert)).await? {
+            Ok(png) => png,
+            Err(e) => return Err(format!("Error rendering certificate: {}", e).into()),
+        };
+
+        // These aren’t replies to anything, so opting out of DMs covers them too.
+        batch.send(ctx, user, ser::CreateMessage::new()
+            .content(format!("Thank you for taking part in {}!", season.name))
+            .add_file(ser::CreateAttachment::bytes(png, "certificate.png"))
+        ).await;
+    }
+
+    info!("Sent {} certificate(s) for season {}", batch.sent, season.name);
+    Ok(batch.sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::golden;
+    use super::*;
+
+    #[test]
+    fn certificate_matches_golden() {
+        let font = FontVec::try_from_vec(include_bytes!("../fonts/DejaVuSans.ttf").to_vec()).unwrap();
+        let cert = Certificate {
+            name: "Ambigrammaton".into(),
+            season: "Season 3".into(),
+            submissions: 7,
+            best_place: Some(2),
+        };
+
+        golden::check("certificate", &draw_certificate(&font, None, &cert));
+    }
+}
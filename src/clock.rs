@@ -0,0 +1,114 @@
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::async_trait;
+
+/// Source of the current time for the scheduler and for anything that
+/// checks deadlines, so tests can control the time.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until the clock reaches a point in time.
+    async fn sleep_until(&self, time: DateTime<Utc>);
+}
+
+/// The real time.
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, time: DateTime<Utc>) {
+        // Negative durations can’t be converted, but those mean we’re done.
+        if let Ok(d) = (time - Utc::now()).to_std() { tokio::time::sleep(d).await; }
+    }
+}
+
+/// The clock in use.
+static CLOCK: Lazy<RwLock<Arc<dyn Clock>>> = Lazy::new(|| RwLock::new(Arc::new(SystemClock)));
+
+/// Get the clock in use.
+pub fn get() -> Arc<dyn Clock> {
+    CLOCK.read().unwrap().clone()
+}
+
+/// Get the current time.
+pub fn now() -> DateTime<Utc> {
+    get().now()
+}
+
+/// Replace the clock.
+#[cfg(test)]
+pub fn set(clock: Arc<dyn Clock>) {
+    *CLOCK.write().unwrap() = clock;
+}
+
+/// Clock that only moves when told to.
+#[cfg(test)]
+pub mod mock {
+    use chrono::{DateTime, Duration, Utc};
+    use poise::serenity_prelude::async_trait;
+    use tokio::sync::watch;
+    use super::Clock;
+
+    pub struct MockClock {
+        time: watch::Sender<DateTime<Utc>>,
+    }
+
+    impl MockClock {
+        pub fn new(time: DateTime<Utc>) -> Self {
+            Self { time: watch::Sender::new(time) }
+        }
+
+        /// Move the clock to a point in time, waking up anything that is
+        /// sleeping until then.
+        pub fn set(&self, time: DateTime<Utc>) {
+            self.time.send_replace(time);
+        }
+
+        pub fn advance(&self, by: Duration) {
+            self.time.send_modify(|t| *t += by);
+        }
+    }
+
+    #[async_trait]
+    impl Clock for MockClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.time.borrow()
+        }
+
+        async fn sleep_until(&self, time: DateTime<Utc>) {
+            let mut rx = self.time.subscribe();
+            let _ = rx.wait_for(|now| *now >= time).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use super::Clock;
+    use super::mock::MockClock;
+
+    #[tokio::test]
+    async fn mock_sleep_wakes_up_when_advanced() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let clock = std::sync::Arc::new(MockClock::new(start));
+        let sleeper = tokio::spawn({
+            let clock = clock.clone();
+            async move { clock.sleep_until(start + Duration::hours(2)).await }
+        });
+
+        clock.advance(Duration::hours(1));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(Duration::hours(1));
+        sleeper.await.unwrap();
+        assert_eq!(clock.now(), start + Duration::hours(2));
+    }
+}
@@ -1,11 +1,100 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::Ordering;
 use poise::builtins::register_application_commands;
-use poise::{ChoiceParameter, CreateReply};
-use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter};
-use crate::{Context, Error, info, Res, sql};
-use crate::core::{create_embed, DEFAULT_EMBED_COLOUR, file_mtime, handle_command_error, InteractionID};
-use crate::sql::Challenge;
+use poise::{ChoiceParameter, CreateReply, Modal};
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::{AutocompleteChoice, ButtonStyle, CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse, Mentionable, ModalInteraction, User, UserId};
+use crate::{ApplicationContext, archive, ballots, clock, config, Context, dm, err, Error, info, panel, polls, preflight, receipts, renderer, Res, results, scheduler, sql, tally, templates, warn, watermark};
+use crate::dm::DmKind;
+use crate::discord::DiscordApi;
+use crate::core::{await_modal, badge_emoji, is_admin, is_mod, COMPONENT_LIFETIME_SECS, create_embed, DEFAULT_EMBED_COLOUR, EmbedBudget, file_mtime, format_duration, format_number, handle_command_error, InteractionID, paginate, paginate_with_files, parse_colour, pick, PickerOption, parse_datetime, parse_link_channel, parse_message_id, register_components, timestamp, TimestampStyle};
+use crate::events::{accept_submission, challenge_for_channel, check_submission, migrate_vote_emoji, resync_reactions, resync_submissions, vote_reaction};
+use crate::sql::{Achievement, Challenge, Constraints, Prompt, Setting, SubmissionStatus, UserPrefs, WeekMessage};
+use crate::scheduler::PromptStatus;
+use crate::templates::Template;
+
+/// Text posted together with the announcement image.
+pub fn announcement_text(challenge: Challenge, constraints: &Constraints, notes: &[&str]) -> String {
+    let mut text = format!("## {} Challenge", challenge.name());
+    for n in notes {
+        text.push('\n');
+        text.push_str(n);
+    }
+
+    text.push_str("\n**Requirements**");
+    for line in constraints.describe() {
+        text.push_str("\n- ");
+        text.push_str(&line);
+    }
+
+    return text;
+}
+
+/// The line of an announcement that says which part of a series a prompt
+/// is, e.g. ‘Part 2 of 3’.
+pub async fn series_note(prompt: &Prompt) -> Result<Option<String>, Error> {
+    let (Some(id), Some(part)) = (prompt.series, prompt.series_part) else { return Ok(None); };
+    let Some(series) = sql::get_series(id).await? else { return Ok(None); };
+    Ok(Some(format!("**Part {} of {}** of *{}*", part, series.parts, series.name)))
+}
+
+/// Text posted together with the announcement image of a queued prompt,
+/// including which part of a series it is.
+pub async fn prompt_announcement_text(prompt: &Prompt) -> Result<String, Error> {
+    let series = series_note(prompt).await?;
+    let notes = series.as_deref().into_iter().chain(prompt.notes.as_deref()).collect::<Vec<_>>();
+    Ok(announcement_text(prompt.challenge, &sql::get_constraints(prompt.challenge).await?, &notes))
+}
+
+/// Modal for entering a prompt.
+#[derive(Debug, poise::Modal)]
+#[name = "Prompt"]
+pub struct PromptModal {
+    #[name = "Prompt"]
+    #[max_length = 200]
+    pub prompt: String,
+
+    #[name = "Notes for the announcement"]
+    #[paragraph]
+    pub notes: Option<String>,
+
+    #[name = "Category"]
+    #[max_length = 100]
+    pub category: Option<String>,
+}
+
+/// Wait for the user to submit a prompt modal with the given id; returns
+/// `None` if they don’t do so in time.
+pub async fn await_prompt_modal(ctx: &ser::Context, modal_id: String) -> Result<Option<(ModalInteraction, PromptModal)>, Error> {
+    let Some((m, mut data)) = await_modal::<PromptModal>(ctx, modal_id).await? else { return Ok(None); };
+    data.prompt = data.prompt.trim().to_string();
+    Ok(Some((m, data)))
+}
+
+/// Create the buttons attached to a prompt preview. Their IDs are returned
+/// as well so they can be registered once the preview is sent.
+pub fn prompt_preview_buttons(challenge: Challenge, path: &str, id: i64) -> Result<(Vec<CreateActionRow>, Vec<String>), Error> {
+    // Get mtime. This is just a little sanity check.
+    let mtime = file_mtime(path)?;
+    let confirm_id = InteractionID::ConfirmAnnouncement { challenge, mtime, prompt: id }.encode();
+    let regenerate_id = InteractionID::RegeneratePrompt { prompt: id }.encode();
+    let cancel_id = InteractionID::CancelPrompt { prompt: id }.encode();
+    let rows = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(&confirm_id).label("Confirm").style(ButtonStyle::Success),
+        CreateButton::new(&regenerate_id).label("Regenerate").style(ButtonStyle::Secondary),
+        CreateButton::new(&cancel_id).label("Cancel").style(ButtonStyle::Danger),
+    ])];
+
+    Ok((rows, vec![confirm_id, regenerate_id, cancel_id]))
+}
+
+/// Generate the announcement image for a prompt, optionally with a theme
+/// other than the default one.
+pub async fn generate_challenge_image(challenge: Challenge, prompt: &str, theme: Option<&str>) -> Result<String, Error> {
+    if !config::get().python_renderer { return renderer::render_announcement(challenge, prompt, theme).await; }
 
-async fn generate_challenge_image(challenge: Challenge, prompt: &str) -> Result<String, Error> {
     let name = match challenge {
         Challenge::Glyph => "glyph_announcement",
         Challenge::Ambigram => "ambigram_announcement",
@@ -14,7 +103,8 @@ async fn generate_challenge_image(challenge: Challenge, prompt: &str) -> Result<
     // Command for generating the image.
     let mut command = tokio::process::Command::new("./weekly_challenges.py");
     command.arg(name);
-    command.arg(&prompt);
+    command.arg(prompt);
+    if let Some(theme) = theme { command.arg(theme); }
     command.kill_on_drop(true);
     command.current_dir("./weekly_challenges");
     info!("Running Shell Command {:?}", command);
@@ -25,144 +115,1794 @@ async fn generate_challenge_image(challenge: Challenge, prompt: &str) -> Result<
     Ok(challenge.announcement_image_path())
 }
 
-/// Edit your nickname.
-#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
-pub async fn nickname(
+/// Directory in which preview images of queued prompts are cached.
+pub const PREVIEW_CACHE_DIR: &str = "./weekly_challenges/previews";
+
+/// Delete all cached previews, e.g. because the generator changed.
+/// Returns how many there were.
+pub fn clear_preview_cache() -> Result<usize, Error> {
+    let entries = match std::fs::read_dir(PREVIEW_CACHE_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut cleared = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "png") {
+            std::fs::remove_file(path)?;
+            cleared += 1;
+        }
+    }
+
+    Ok(cleared)
+}
+
+/// Get a preview image of a queued prompt, generating it if there
+/// isn’t one for the current text of the prompt yet.
+///
+/// This must not touch the announcement image, which may be waiting
+/// to be confirmed, so we move that out of the way while generating.
+async fn cached_preview(challenge: Challenge, id: i64, prompt: &str, theme: Option<&str>) -> Result<String, Error> {
+    let mut hasher = DefaultHasher::new();
+    (prompt, theme).hash(&mut hasher);
+    let cached = format!("{}/{}-{}-{:x}.png", PREVIEW_CACHE_DIR, challenge.raw(), id, hasher.finish());
+    if std::path::Path::new(&cached).exists() { return Ok(cached); }
+
+    std::fs::create_dir_all(PREVIEW_CACHE_DIR)?;
+    let path = challenge.announcement_image_path();
+    let backup = format!("{}.bak", path);
+    let moved = std::fs::rename(&path, &backup).is_ok();
+
+    // Renaming keeps the mtime, so a pending confirmation stays valid.
+    let res = match generate_challenge_image(challenge, prompt, theme).await {
+        Ok(path) => std::fs::rename(path, &cached).map_err(|e| e.into()),
+        Err(e) => Err(e),
+    };
+
+    if moved { std::fs::rename(&backup, &path)?; }
+    res.map(|_| cached)
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("admin_autoresponse", "admin_badge_emoji", "admin_clear_bio", "admin_constraints", "admin_disqualify", "admin_flag", "admin_hiatus", "admin_link_hosts", "admin_migrate_emoji", "admin_pause", "admin_post_panel", "admin_publish_hof", "admin_purge_user", "admin_recount", "admin_regenerate_panel", "admin_resume", "admin_resync", "admin_rules", "admin_season", "admin_setting", "admin_tasks", "admin_template", "admin_vote_audit", "admin_vote_emoji", "admin_vote_weights", "admin_withdrawals"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "autoresponse", subcommands("admin_autoresponse_add", "admin_autoresponse_list", "admin_autoresponse_remove"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_autoresponse(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Automatically reply to messages in the submission channels that contain a phrase.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "add", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_autoresponse_add(
     ctx: Context<'_>,
-    name: String,
+    #[description = "The phrase to react to (case-insensitive)"] trigger: String,
+    #[description = "The reply"] response: String,
+) -> Res {
+    let trigger = trigger.trim();
+    if trigger.is_empty() { return Err("Trigger must not be empty".into()); }
+    let id = sql::add_autoresponse(trigger, response.trim()).await?;
+    ctx.say(format!("Added automatic response {}", id)).await?;
+    Ok(())
+}
+
+/// Show all automatic responses.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "list", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_autoresponse_list(ctx: Context<'_>) -> Res {
+    const TITLE: &str = "Automatic Responses";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    let list = sql::get_autoresponses().await?;
+    if list.is_empty() { budget.line("(none)"); }
+    for (id, trigger, response) in list {
+        budget.line(format!("- **{}:** ‘{}’ → {}", id, trigger, response));
+    }
+
+    budget.send(&ctx).await
+}
+
+/// Remove an automatic response.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_autoresponse_remove(
+    ctx: Context<'_>,
+    #[description = "The ID of the response to remove"] id: i64,
+) -> Res {
+    if sql::delete_autoresponse(id).await? { ctx.say("Removed automatic response").await?; } //
+    else { ctx.say("No such automatic response").await?; }
+    Ok(())
+}
+
+/// Show a custom emoji for an achievement instead of its standard emoji.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "badge-emoji", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_badge_emoji(
+    ctx: Context<'_>,
+    #[description = "The achievement to change"] achievement: Achievement,
+    #[description = "A custom emoji from this server; omit this to use the standard emoji"] emoji: Option<String>,
+) -> Res {
+    let Some(emoji) = emoji else {
+        sql::set_badge_emoji(achievement, None).await?;
+        ctx.say(format!("{} now uses its standard emoji {}", achievement.name(), achievement.emoji())).await?;
+        return Ok(());
+    };
+
+    let Some(emoji) = ser::parse_emoji(&emoji) else { return Err("That is not a custom emoji".into()); };
+    if config::get().server.emoji(&ctx, emoji.id).await.is_err() {
+        return Err("That emoji is not from this server".into());
+    }
+
+    sql::set_badge_emoji(achievement, Some(emoji.id)).await?;
+    ctx.say(format!("{} now uses {}", achievement.name(), badge_emoji(&ctx.serenity_context().cache, achievement).await)).await?;
+    Ok(())
+}
+
+/// Remove an inappropriate bio and links from a user’s profile.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "clear-bio", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_clear_bio(
+    ctx: Context<'_>,
+    #[description = "The user whose bio to remove"] user: User,
+) -> Res {
+    sql::clear_bio(user.id).await?;
+    info!("{} cleared the bio of user {}", ctx.author().id, user.id);
+    ctx.say(format!("Cleared the bio of {}", user.name)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "constraints", subcommands("admin_constraints_reset", "admin_constraints_set"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_constraints(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Reply with the current requirements for a challenge.
+async fn show_constraints(ctx: Context<'_>, challenge: Challenge) -> Res {
+    let lines = sql::get_constraints(challenge).await?.describe();
+    ctx.say(format!("Requirements for the {} Challenge:\n- {}", challenge.name(), lines.join("\n- "))).await?;
+    Ok(())
+}
+
+/// Go back to the default requirements for entries.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "reset", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_constraints_reset(
+    ctx: Context<'_>,
+    #[description = "Which challenge’s requirements to reset"] challenge: Challenge,
+) -> Res {
+    sql::set_constraints(challenge, None).await?;
+    info!("{} reset the constraints for {:?}", ctx.author().id, challenge);
+    show_constraints(ctx, challenge).await
+}
+
+/// Show or change the requirements for entries listed in announcements.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "set", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_constraints_set(
+    ctx: Context<'_>,
+    #[description = "Which challenge’s requirements to change"] challenge: Challenge,
+    #[description = "Smallest allowed width and height, in pixels"] min_size: Option<u32>,
+    #[description = "Largest allowed width and height, in pixels (0 = no limit)"] max_size: Option<u32>,
+    #[description = "Whether the canvas must be square"] square: Option<bool>,
+    #[description = "Whether entries must be black and white"] monochrome: Option<bool>,
+    #[description = "Whether animated entries are allowed"] animation: Option<bool>,
+) -> Res {
+    if min_size.is_none() && max_size.is_none() && square.is_none() && monochrome.is_none() && animation.is_none() {
+        return show_constraints(ctx, challenge).await;
+    }
+
+    let mut c = sql::get_constraints(challenge).await?;
+    if let Some(v) = min_size { c.min_size = v; }
+    if let Some(v) = max_size { c.max_size = v; }
+    if let Some(v) = square { c.square = v; }
+    if let Some(v) = monochrome { c.monochrome = v; }
+    if let Some(v) = animation { c.animation = v; }
+    if c.max_size != 0 && c.max_size < c.min_size {
+        return Err("The largest allowed size must not be smaller than the smallest".into());
+    }
+
+    sql::set_constraints(challenge, Some(&c)).await?;
+    info!("{} changed the constraints for {:?} to {:?}", ctx.author().id, challenge, c);
+    show_constraints(ctx, challenge).await
+}
+
+/// Disqualify a submission for the current week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "disqualify", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_disqualify(
+    ctx: Context<'_>,
+    #[description = "Link to or ID of the submission message"] message: String,
+) -> Res {
+    let message = parse_message_id(&message)?;
+    let Some(challenge) = sql::disqualify_submission(message).await? else {
+        return Err("No such submission this week".into());
+    };
+
+    info!("{} disqualified submission {} for challenge {:?}", ctx.author().id, message, challenge);
+
+    // If the panel is already up, it must not show this anymore.
+    let week = sql::current_week().await?;
+    if sql::get_week_message(week, challenge, WeekMessage::Panel).await?.is_some() {
+        ctx.defer_ephemeral().await?;
+        panel::regenerate_panel(ctx.serenity_context(), week, challenge).await?;
+    }
+
+    ctx.say("Disqualified submission").await?;
+    Ok(())
+}
+
+/// Flag a submission so its author is warned before the withdrawal cutoff.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "flag", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_flag(
+    ctx: Context<'_>,
+    #[description = "Link to or ID of the submission message"] message: String,
+    #[description = "What’s wrong with the submission; this is sent to the author"] reason: String,
+) -> Res {
+    let message = parse_message_id(&message)?;
+    if !sql::flag_submission(message, &reason).await? { return Err("No such submission this week".into()); }
+    info!("{} flagged submission {}: {}", ctx.author().id, message, reason);
+    ctx.say("Flagged submission; its author will be warned before the withdrawal cutoff").await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "link-hosts", subcommands("admin_link_hosts_allow", "admin_link_hosts_deny", "admin_link_hosts_list", "admin_link_hosts_remove"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_link_hosts(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Normalise a host entered by an admin.
+fn parse_link_host(host: &str) -> Result<String, Error> {
+    let host = host.trim().trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+    if !host.contains('.') || host.contains(|c: char| c.is_whitespace() || matches!(c, '/' | ':' | '@')) {
+        return Err(format!("‘{}’ is not a host name such as ‘imgur.com’", host).into());
+    }
+
+    Ok(host)
+}
+
+/// Only accept linked submissions from a host and others that are allowed.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "allow", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_link_hosts_allow(
+    ctx: Context<'_>,
+    #[description = "The host, e.g. ‘imgur.com’; this includes its subdomains"] host: String,
+) -> Res {
+    let host = parse_link_host(&host)?;
+    sql::set_link_host(&host, true).await?;
+    info!("{} allowed linked submissions from {}", ctx.author().id, host);
+    ctx.say(format!("Allowed links to {}; links to hosts that aren’t allowed are now rejected", host)).await?;
+    Ok(())
+}
+
+/// Reject linked submissions from a host.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "deny", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_link_hosts_deny(
+    ctx: Context<'_>,
+    #[description = "The host, e.g. ‘example.com’; this includes its subdomains"] host: String,
+) -> Res {
+    let host = parse_link_host(&host)?;
+    sql::set_link_host(&host, false).await?;
+    info!("{} denied linked submissions from {}", ctx.author().id, host);
+    ctx.say(format!("Denied links to {}", host)).await?;
+    Ok(())
+}
+
+/// Show the hosts linked submissions may or may not come from.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "list", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_link_hosts_list(ctx: Context<'_>) -> Res {
+    const TITLE: &str = "Link Hosts";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    if !sql::get_setting::<bool>(Setting::LinkSubmissions).await? {
+        budget.line("*Link submissions are disabled.*");
+    }
+
+    let hosts = sql::get_link_hosts().await?;
+    if !hosts.iter().any(|(_, allowed)| *allowed) { budget.line("*No hosts are allowed, so linked submissions are rejected.*"); }
+    for (host, allowed) in hosts {
+        budget.line(format!("- {} {}", if allowed { "✅" } else { "⛔" }, host));
+    }
+
+    budget.send(&ctx).await
+}
+
+/// Remove a host from the allow or deny list.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_link_hosts_remove(
+    ctx: Context<'_>,
+    #[description = "The host to remove"] host: String,
+) -> Res {
+    let host = parse_link_host(&host)?;
+    if sql::delete_link_host(&host).await? { ctx.say(format!("Removed {}", host)).await?; } //
+    else { ctx.say(format!("{} is on neither list", host)).await?; }
+    Ok(())
+}
+
+/// Switch to a new vote emoji and tell everyone about it.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "migrate-emoji", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_migrate_emoji(
+    ctx: Context<'_>,
+    #[description = "The current vote emoji"] old: String,
+    #[description = "The emoji to vote with from now on"] new: String,
+    #[description = "Count missed reactions with the old emoji on entries that can still be voted on"] recount: Option<bool>,
+) -> Res {
+    let old = ser::ReactionType::try_from(old.trim()).map_err(|_| "The old emoji is not an emoji")?;
+    let new = new.trim();
+    check_vote_emoji(ctx, new).await?;
+
+    ctx.defer_ephemeral().await?;
+    let recount = recount.unwrap_or(false);
+    let added = migrate_vote_emoji(ctx.serenity_context(), &old, new, recount).await?;
+    info!("{} migrated the vote emoji from {} to {}", ctx.author().id, old, new);
+    let mut reply = format!("People now vote with {} and have been told about it", vote_reaction().await);
+    if recount { reply += &format!("; recounting added {} vote{}", added, if added == 1 { "" } else { "s" }); }
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Put the challenges on break.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "pause", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_pause(
+    ctx: Context<'_>,
+    #[description = "When the break ends, as ‘YYYY-MM-DD HH:MM’ in UTC"] until: String,
+) -> Res {
+    let until = parse_datetime(&until)?;
+    if until <= clock::now() { return Err("The end of the break must be in the future".into()); }
+    scheduler::pause(ctx.serenity_context(), until).await?;
+    ctx.say(format!("Paused the challenges until {}", timestamp(until, TimestampStyle::DateTime))).await?;
+    Ok(())
+}
+
+/// Put one challenge on hiatus while the other carries on, or end its hiatus.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "hiatus", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_hiatus(
+    ctx: Context<'_>,
+    #[description = "Which challenge to put on or take off hiatus"] challenge: Challenge,
+    #[description = "Whether the challenge is on hiatus"] hiatus: bool,
+) -> Res {
+    if scheduler::is_active(challenge).await? != hiatus {
+        return Err(format!("The {} Challenge is already {}", challenge.name(), if hiatus { "on hiatus" } else { "running" }).into());
+    }
+
+    scheduler::set_hiatus(ctx.serenity_context(), challenge, hiatus).await?;
+    ctx.say(format!("The {} Challenge is {}", challenge.name(), if hiatus { "now on hiatus" } else { "running again" })).await?;
+    Ok(())
+}
+
+/// Rebuild a panel from the current submissions, e.g. after a disqualification.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "regenerate-panel", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_regenerate_panel(
+    ctx: Context<'_>,
+    #[description = "The week whose panel to regenerate"] week: u64,
+    #[description = "Which challenge’s panel to regenerate"] challenge: Challenge,
+) -> Res {
+    // Downloading everything takes a while.
+    ctx.defer_ephemeral().await?;
+    panel::regenerate_panel(ctx.serenity_context(), week as i64, challenge).await?;
+    ctx.say(format!("Regenerated the {} panel for week {}", challenge.name(), week)).await?;
+    Ok(())
+}
+
+/// End a break early.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "resume", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_resume(ctx: Context<'_>) -> Res {
+    if !scheduler::is_paused().await? { return Err("The challenges are not paused".into()); }
+    scheduler::resume(ctx.serenity_context()).await?;
+    ctx.say("Resumed the challenges").await?;
+    Ok(())
+}
+
+/// Catch up on submit emoji added or removed while the bot was offline.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "resync", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_resync(ctx: Context<'_>) -> Res {
+    ctx.defer_ephemeral().await?;
+    let report = resync_submissions(ctx.serenity_context()).await?;
+    resync_reactions(ctx.serenity_context()).await?;
+    info!("{} resynced submissions: {:?}", ctx.author().id, report);
+
+    const TITLE: &str = "Resynced Submissions";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    if report.is_empty() { budget.line("Everything was up to date."); }
+    for (what, messages) in [
+        ("Added", &report.added),
+        ("Removed", &report.removed),
+        ("Rejected", &report.rejected),
+        ("Kept after the withdrawal cutoff", &report.kept),
+    ] {
+        for (channel, message) in messages {
+            budget.line(format!("- {}: https://discord.com/channels/{}/{}/{}", what, config::get().server, channel, message));
+        }
+    }
+
+    budget.send(&ctx).await
+}
+
+/// Post the panel of all submissions for a week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "post-panel", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_post_panel(
+    ctx: Context<'_>,
+    #[description = "The week whose panel to post"] week: u64,
+    #[description = "Which challenge’s panel to post"] challenge: Challenge,
+) -> Res {
+    // Downloading everything takes a while.
+    ctx.defer_ephemeral().await?;
+    panel::post_panel(ctx.serenity_context(), week as i64, challenge).await?;
+    ctx.say(format!("Posted the {} panel for week {}", challenge.name(), week)).await?;
+    Ok(())
+}
+
+/// Post the hall of fame for a week, or update it if the results changed.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "publish-hof", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_publish_hof(
+    ctx: Context<'_>,
+    #[description = "The week whose hall of fame to post"] week: u64,
+    #[description = "Which challenge’s hall of fame to post"] challenge: Challenge,
+) -> Res {
+    results::publish_hof(ctx.serenity_context(), week as i64, challenge).await?;
+    ctx.say(format!("Published the {} hall of fame for week {}", challenge.name(), week)).await?;
+    Ok(())
+}
+
+/// Delete all data stored about a user.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "purge-user", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_purge_user(
+    ctx: Context<'_>,
+    #[description = "The user whose data to delete"] user: User,
+) -> Res {
+    let submissions = sql::purge_user(user.id).await?;
+    archive::remove(&submissions).await;
+    info!("{} deleted the data of user {}", ctx.author().id, user.id);
+    ctx.say(format!("Deleted all data of {}", user.name)).await?;
+    Ok(())
+}
+
+/// Rebuild vote counts and scores from the individual votes.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "recount", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_recount(
+    ctx: Context<'_>,
+    #[description = "The week to recount; omit this to recount every week with recorded votes"] week: Option<u64>,
+) -> Res {
+    let affected = sql::recount_all_votes(week.map(|w| w as i64)).await?;
+    info!("{} recounted the votes; {} week(s) changed", ctx.author().id, affected.len());
+    if affected.is_empty() {
+        ctx.say("All vote counts were correct").await?;
+        return Ok(());
+    }
+
+    const TITLE: &str = "Corrected Vote Counts";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    budget.line("*Run `/admin publish-hof` for any of these whose results were already published.*");
+    for (week, challenge) in affected {
+        budget.line(format!("- Week {}, {} Challenge", week, challenge.name()));
+    }
+
+    budget.send(&ctx).await
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "rules", subcommands("admin_rules_edit"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_rules(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Modal for editing a template.
+#[derive(Debug, poise::Modal)]
+#[name = "Edit Text"]
+struct TemplateModal {
+    #[name = "Text"]
+    #[paragraph]
+    #[max_length = 4000]
+    text: String,
+}
+
+/// Edit the challenge rules.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "edit", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_rules_edit(
+    ctx: ApplicationContext<'_>,
+    #[description = "Which challenge’s rules to edit; omit this to edit the general rules"] challenge: Option<Challenge>,
+) -> Res {
+    edit_template(ctx, challenge.map(Template::rules_for).unwrap_or(Template::Rules)).await
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "tasks", subcommands("admin_tasks_disable", "admin_tasks_enable", "admin_tasks_list", "admin_tasks_run"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_tasks(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Suggest names of periodic tasks.
+async fn autocomplete_task(_ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    scheduler::TASKS.iter()
+        .filter(|t| t.name.contains(partial))
+        .map(|t| AutocompleteChoice::new(t.name, t.name))
+        .collect()
+}
+
+/// Look up a periodic task by name.
+fn find_task(name: &str) -> Result<&'static scheduler::Task, Error> {
+    scheduler::find_task(name.trim()).ok_or_else(|| format!("No task named ‘{}’", name.trim()).into())
+}
+
+/// List periodic tasks.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "list", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_tasks_list(ctx: Context<'_>) -> Res {
+    let mut text = String::new();
+    for task in scheduler::TASKS {
+        let state = sql::get_task_state(task.name).await?;
+        let interval = if task.interval % (24 * 60 * 60) == 0 {
+            format!("{}d", task.interval / (24 * 60 * 60))
+        } else {
+            format!("{}h", task.interval / (60 * 60))
+        };
+
+        let last_run = state.last_run
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| timestamp(t, TimestampStyle::Relative))
+            .unwrap_or_else(|| "never".into());
+
+        text += &format!(
+            "`{}`{}: {}\nEvery {}{}, last run {}\n",
+            task.name,
+            if state.disabled { " (disabled)" } else { "" },
+            task.description,
+            interval,
+            if task.pausable { " unless paused" } else { "" },
+            last_run,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(create_embed(&ctx)
+        .title("Periodic Tasks")
+        .description(text)
+    )).await?;
+    Ok(())
+}
+
+/// Run a periodic task now.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "run", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_tasks_run(
+    ctx: Context<'_>,
+    #[description = "The task to run"] #[autocomplete = "autocomplete_task"] name: String,
+) -> Res {
+    let task = find_task(&name)?;
+    ctx.defer_ephemeral().await?;
+    info!("{} ran task {} manually", ctx.author().id, task.name);
+    scheduler::run_task(ctx.serenity_context(), task).await?;
+    ctx.say(format!("Ran task `{}`", task.name)).await?;
+    Ok(())
+}
+
+/// Stop a periodic task from running automatically.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "disable", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_tasks_disable(
+    ctx: Context<'_>,
+    #[description = "The task to disable"] #[autocomplete = "autocomplete_task"] name: String,
+) -> Res {
+    let task = find_task(&name)?;
+    sql::set_task_disabled(task.name, true).await?;
+    ctx.say(format!("Disabled task `{}`", task.name)).await?;
+    Ok(())
+}
+
+/// Let a disabled periodic task run automatically again.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "enable", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_tasks_enable(
+    ctx: Context<'_>,
+    #[description = "The task to enable"] #[autocomplete = "autocomplete_task"] name: String,
+) -> Res {
+    let task = find_task(&name)?;
+    sql::set_task_disabled(task.name, false).await?;
+    ctx.say(format!("Enabled task `{}`", task.name)).await?;
+    Ok(())
+}
+
+/// Edit one of the texts the bot posts.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "template", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_template(
+    ctx: ApplicationContext<'_>,
+    #[description = "The text to edit"] template: Template,
+) -> Res {
+    edit_template(ctx, template).await
+}
+
+/// Let the user edit a template in a modal.
+async fn edit_template(ctx: ApplicationContext<'_>, template: Template) -> Res {
+    // Ask for the new text.
+    let modal_id = ctx.interaction.id.to_string();
+    let defaults = TemplateModal { text: templates::get(template).await? };
+    ctx.interaction.create_response(ctx, TemplateModal::create(Some(defaults), modal_id.clone())).await?;
+    ctx.has_sent_initial_response.store(true, Ordering::SeqCst);
+    let Some((m, data)) = await_modal::<TemplateModal>(ctx.serenity_context(), modal_id).await? else { return Ok(()); };
+
+    templates::set(template, data.text.trim()).await?;
+    m.create_response(ctx, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(format!("Updated ‘{}’", template.name()))
+            .ephemeral(true)
+    )).await?;
+    Ok(())
+}
+
+/// Show every vote on a challenge in a week and the weight it counted with.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "vote-audit", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_audit(
+    ctx: Context<'_>,
+    #[description = "Which challenge’s votes to show"] challenge: Challenge,
+    #[description = "The week whose votes to show; defaults to the current week"] week: Option<u64>,
+) -> Res {
+    let week = match week {
+        Some(w) => w as i64,
+        None => sql::current_week().await?,
+    };
+
+    let title = format!("Votes on {}s in Week {}", challenge.name(), week);
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(&title)), title.len());
+    let weights = sql::get_vote_weights().await?;
+    if weights.is_empty() { budget.line("*All votes currently count once.*"); }
+    for (role, weight) in weights {
+        budget.line(format!("*{} votes count ×{}.*", role.mention(), tally::format_weight(weight)));
+    }
+
+    let mut ballots: HashMap<i64, Vec<sql::Ballot>> = HashMap::new();
+    for b in sql::get_ballots(week, challenge).await? { ballots.entry(b.message).or_default().push(b); }
+
+    let ranking = results::ranking(week, challenge).await?;
+    if ranking.is_empty() { budget.line("(no submissions)"); }
+    for p in ranking {
+        let s = &p.submission;
+        let link = format!("https://discord.com/channels/{}/{}/{}", config::get().server, challenge.submission_channel(), s.message);
+        budget.line(format!(
+            "**#{}** {} by <@{}>: {} ({} vote{})",
+            p.place,
+            link,
+            s.author,
+            tally::format_weight(s.score),
+            s.votes,
+            if s.votes == 1 { "" } else { "s" },
+        ));
+
+        for b in ballots.get(&s.message).into_iter().flatten() {
+            let time = chrono::DateTime::from_timestamp(b.time, 0).map(|t| timestamp(t, TimestampStyle::DateTime)).unwrap_or_default();
+            budget.line(format!("- <@{}> ×{}, {}", b.voter, tally::format_weight(b.weight), time));
+        }
+    }
+
+    budget.send(&ctx).await
+}
+
+/// Change the emoji people vote with.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "vote-emoji", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_emoji(
+    ctx: Context<'_>,
+    #[description = "An emoji, or a custom emoji from this server; omit this to use 👍"] emoji: Option<String>,
+) -> Res {
+    let Some(emoji) = emoji.as_deref().map(str::trim) else {
+        sql::set_vote_emoji(None).await?;
+        info!("{} reset the vote emoji", ctx.author().id);
+        ctx.say(format!("People now vote with {}", vote_reaction().await)).await?;
+        return Ok(());
+    };
+
+    check_vote_emoji(ctx, emoji).await?;
+    sql::set_vote_emoji(Some(emoji)).await?;
+    info!("{} set the vote emoji to {}", ctx.author().id, emoji);
+    ctx.say(format!("People now vote with {}; votes already cast with the old emoji are kept", vote_reaction().await)).await?;
+    Ok(())
+}
+
+/// Check that something can be used as the vote emoji.
+async fn check_vote_emoji(ctx: Context<'_>, emoji: &str) -> Res {
+    if let Some(custom) = ser::parse_emoji(emoji) {
+        if config::get().server.emoji(&ctx, custom.id).await.is_err() {
+            return Err("That emoji is not from this server".into());
+        }
+    } else if emoji.is_empty() || emoji.chars().any(|c| c.is_ascii_alphanumeric() || c.is_whitespace()) {
+        return Err("That is not an emoji".into());
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "vote-weights", subcommands("admin_vote_weights_list", "admin_vote_weights_remove", "admin_vote_weights_set"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_weights(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Show which roles’ votes count more or less than others.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "list", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_weights_list(ctx: Context<'_>) -> Res {
+    const TITLE: &str = "Vote Weights";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    let weights = sql::get_vote_weights().await?;
+    if weights.is_empty() { budget.line("(none; all votes count once)"); }
+    for (role, weight) in weights {
+        budget.line(format!("- {}: ×{}", role.mention(), tally::format_weight(weight)));
+    }
+
+    budget.send(&ctx).await
+}
+
+/// Make votes by a role count normally again.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_weights_remove(
+    ctx: Context<'_>,
+    #[description = "The role whose votes should count once"] role: ser::Role,
+) -> Res {
+    if sql::delete_vote_weight(role.id).await? {
+        info!("{} removed the vote weight of role {}", ctx.author().id, role.id);
+        ctx.say(format!("Votes by {} now count once; votes they already cast keep their weight", role.name)).await?;
+    } else {
+        ctx.say(format!("{} has no vote weight", role.name)).await?;
+    }
+
+    Ok(())
+}
+
+/// Make votes by members with a role count more (or less). Votes already cast keep their weight.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "set", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_vote_weights_set(
+    ctx: Context<'_>,
+    #[description = "The role, e.g. the jury or server boosters"] role: ser::Role,
+    #[description = "How many votes a vote by them counts as, e.g. 1.5"] #[min = 0] #[max = 10] weight: f64,
+) -> Res {
+    let weight = (weight * 100.0).round() as i64;
+    sql::set_vote_weight(role.id, weight).await?;
+    info!("{} set the vote weight of role {} to {}", ctx.author().id, role.id, weight);
+    ctx.say(format!(
+        "Votes by {} now count ×{}; members with several weighted roles get the highest weight",
+        role.name,
+        tally::format_weight(weight),
+    )).await?;
+    Ok(())
+}
+
+/// Withdrawals this close to the deadline are highlighted.
+const LATE_WITHDRAWAL_HOURS: i64 = 24;
+
+/// Show the entries withdrawn in a week, and when.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "withdrawals", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_withdrawals(
+    ctx: Context<'_>,
+    #[description = "The week whose withdrawals to show; defaults to the current week"] week: Option<u64>,
+) -> Res {
+    let week = match week {
+        Some(w) => w as i64,
+        None => sql::current_week().await?,
+    };
+
+    let deadline = scheduler::deadline(week).await?.timestamp();
+    let withdrawals = sql::get_withdrawals(week).await?;
+    let late = withdrawals.iter().filter(|w| deadline - w.withdrawn <= LATE_WITHDRAWAL_HOURS * 3600).count();
+
+    let title = format!("Withdrawals in Week {}", week);
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(&title)), title.len());
+    budget.line(format!(
+        "{} withdrawal{}, {} of them in the last {} hours before the deadline.",
+        withdrawals.len(),
+        if withdrawals.len() == 1 { "" } else { "s" },
+        late,
+        LATE_WITHDRAWAL_HOURS,
+    ));
+
+    for w in withdrawals {
+        let time = chrono::DateTime::from_timestamp(w.withdrawn, 0).map(|t| timestamp(t, TimestampStyle::DateTime)).unwrap_or_default();
+        let before = match deadline - w.withdrawn {
+            d if d > 0 => format!("{} before the deadline", format_duration(d)),
+            d => format!("{} after the deadline", format_duration(-d)),
+        };
+
+        budget.line(format!(
+            "- {}<@{}>, {}: {}; {} after submitting, {}. *{}*",
+            if deadline - w.withdrawn <= LATE_WITHDRAWAL_HOURS * 3600 { "⚠️ " } else { "" },
+            w.author,
+            Challenge::try_from(w.challenge)?.name(),
+            time,
+            format_duration(w.withdrawn - w.submitted),
+            before,
+            sql::WithdrawalReason::try_from(w.reason)?.name(),
+        ));
+    }
+
+    budget.send(&ctx).await
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "season", subcommands("admin_season_end", "admin_season_start"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_season(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// End the current season after the last finished week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "end", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_season_end(ctx: Context<'_>) -> Res {
+    let season = sql::end_season().await?;
+    let end_week = season.end_week.unwrap_or_default();
+    ctx.say(format!(
+        "Ended season ‘{}’ after week {}; the champion will be announced and certificates sent once voting on week {} has closed",
+        season.name,
+        end_week,
+        end_week,
+    )).await?;
+
+    Ok(())
+}
+
+/// Start a new season in the current week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "start", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_season_start(
+    ctx: Context<'_>,
+    #[description = "Name of the season"] name: String,
+) -> Res {
+    let name = name.trim();
+    if name.is_empty() { return Err("Name must not be empty".into()); }
+    let season = sql::start_season(name).await?;
+    ctx.say(format!("Started season ‘{}’ in week {}", season.name, season.start_week)).await?;
+    Ok(())
+}
+
+/// Show or change a bot setting.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "setting", default_member_permissions = "ADMINISTRATOR")]
+pub async fn admin_setting(
+    ctx: Context<'_>,
+    #[description = "The setting to show or change"] setting: Setting,
+    #[description = "The new value; omit this to show the current value"] value: Option<String>,
+) -> Res {
+    match value {
+        // Hiatus has to be announced in the submission channel.
+        Some(_) if matches!(setting, Setting::GlyphActive | Setting::AmbigramActive) => {
+            return Err("Use `/admin hiatus` to put a challenge on hiatus or end it".into());
+        }
+
+        Some(value) => {
+            sql::set_setting(setting, value.trim()).await?;
+            ctx.say(format!("Set ‘{}’ to `{}`", setting.name(), value.trim())).await?;
+        }
+
+        None => {
+            let value = sql::get_setting::<String>(setting).await?;
+            ctx.say(format!("‘{}’ is currently `{}`", setting.name(), value)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Show whether a message is a submission, and its details.
+#[poise::command(context_menu_command = "Show entry info", ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn entry_info(ctx: Context<'_>, message: ser::Message) -> Res {
+    if challenge_for_channel(message.channel_id).is_none() {
+        return Err("This only works on messages in the submission channels".into());
+    }
+
+    let Some(s) = sql::get_submission(message.id).await? else {
+        ctx.say("This message is not a submission.").await?;
+        return Ok(());
+    };
+
+    let embed = create_embed(&ctx)
+        .title(format!("{} Challenge Entry", Challenge::try_from(s.challenge)?.name()))
+        .field("Entry ID", s.message.to_string(), true)
+        .field("Week", s.week.to_string(), true)
+        .field("Votes", format_number(s.votes, ctx.locale()), true)
+        .field("Status", SubmissionStatus::try_from(s.status)?.name(), true)
+        .field("Author", format!("<@{}>", s.author), true);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Submit one of your messages to the challenge.
+#[poise::command(context_menu_command = "Submit to challenge", ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn submit(ctx: Context<'_>, message: ser::Message) -> Res {
+    let Some(challenge) = challenge_for_channel(message.channel_id) else {
+        return Err("You can only submit messages in the submission channels".into());
+    };
+
+    if message.author.id != ctx.author().id {
+        return Err("You can only submit your own messages".into());
+    }
+
+    if sql::get_submission(message.id).await?.is_some() {
+        return Err("This message has already been submitted".into());
+    }
+
+    let image = check_submission(&message, challenge).await?;
+    accept_submission(ctx.serenity_context(), &message, challenge, &image, true).await?;
+    ctx.say(format!(
+        "Your {} submission has been added. You can withdraw it until {}.",
+        challenge.name().to_lowercase(),
+        timestamp(scheduler::withdrawal_cutoff().await?, TimestampStyle::DateTime),
+    )).await?;
+    Ok(())
+}
+
+/// Show the submission deadline for the current week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn deadline(ctx: Context<'_>) -> Res {
+    let deadline = scheduler::deadline(sql::current_week().await?).await?;
+    ctx.say(format!(
+        "Submissions for this week close {} ({}).",
+        timestamp(deadline, TimestampStyle::Relative),
+        timestamp(deadline, TimestampStyle::DateTime),
+    )).await?;
+    Ok(())
+}
+
+/// Show the challenge rules.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn rules(
+    ctx: Context<'_>,
+    #[description = "Which challenge to show the rules for; omit this to show all rules"] challenge: Option<Challenge>,
+) -> Res {
+    // The rules are edited by admins and can be long, so they are added
+    // line by line and split across embeds if need be.
+    const TITLE: &str = "Challenge Rules";
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(TITLE)), TITLE.len());
+    for line in templates::get(Template::Rules).await?.lines() { budget.line(line); }
+
+    for c in [Challenge::Glyph, Challenge::Ambigram] {
+        if challenge.is_some_and(|ch| ch != c) { continue; }
+        budget.line("");
+        match scheduler::is_active(c).await? {
+            true => budget.line(format!("**{} Challenge**", c.name())),
+            false => budget.line(format!("**{} Challenge** (on hiatus)", c.name())),
+        }
+
+        for line in templates::get(Template::rules_for(c)).await?.lines() { budget.line(line); }
+    }
+
+    budget.send(&ctx).await
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("leaderboard_all_time", "leaderboard_combined"))]
+pub async fn leaderboard(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Show the all-time standings of a challenge.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "all-time")]
+pub async fn leaderboard_all_time(
+    ctx: Context<'_>,
+    #[description = "Which challenge to show the standings for"] challenge: Challenge,
+) -> Res {
+    let points = [
+        sql::get_setting::<i64>(Setting::PointsFirst).await?,
+        sql::get_setting::<i64>(Setting::PointsSecond).await?,
+        sql::get_setting::<i64>(Setting::PointsThird).await?,
+    ];
+
+    let size = sql::get_setting::<usize>(Setting::LeaderboardSize).await?;
+    let standings = tally::leaderboard(&sql::get_leaderboard_rows(challenge).await?, points, size);
+
+    let title = format!("{} Challenge Leaderboard", challenge.name());
+    let mut budget = EmbedBudget::new(create_embed(&ctx).title(&title), title.len());
+    if standings.is_empty() { budget.line("Nobody has entered yet."); }
+
+    let hidden = sql::get_hidden_profiles().await?;
+    let mut place = 0;
+    for (i, s) in standings.iter().enumerate() {
+        if i == 0 || (standings[i - 1].points, standings[i - 1].submissions) != (s.points, s.submissions) { place = i + 1; }
+        if i != 0 && i % 10 == 0 { budget.break_page(); }
+        budget.line(format!(
+            "{}. {} – {} point{} (🥇 {} 🥈 {} 🥉 {}), {} submission{}",
+            place,
+            standings_name(&ctx, s.user, &hidden),
+            format_number(s.points, ctx.locale()),
+            if s.points == 1 { "" } else { "s" },
+            s.podiums[0],
+            s.podiums[1],
+            s.podiums[2],
+            format_number(s.submissions, ctx.locale()),
+            if s.submissions == 1 { "" } else { "s" },
+        ));
+    }
+
+    paginate(&ctx, budget.pages()).await
+}
+
+/// How to refer to someone in the standings. People who hide their profile
+/// are only named to themselves.
+fn standings_name(ctx: &Context<'_>, user: ser::UserId, hidden: &[ser::UserId]) -> String {
+    match hidden.contains(&user) && user != ctx.author().id {
+        true => "*(hidden profile)*".to_string(),
+        false => user.mention().to_string(),
+    }
+}
+
+/// Show the combined standings of both challenges in the current season.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "combined")]
+pub async fn leaderboard_combined(ctx: Context<'_>) -> Res {
+    if !sql::get_setting::<bool>(Setting::CombinedChampionship).await? {
+        return Err("The combined championship is not enabled".into());
+    }
+
+    let Some(season) = sql::latest_season().await? else { return Err("There are no seasons yet".into()); };
+    let end_week = match season.end_week {
+        Some(w) => w,
+        None => sql::current_week().await?,
+    };
+
+    let title = format!("Combined Standings – {}", season.name);
+    let mut budget = EmbedBudget::new(create_embed(&ctx).title(&title), title.len());
+    let standings = results::combined_standings(season.start_week, end_week).await?;
+    if standings.is_empty() { budget.line("Nobody has placed yet."); }
+
+    let hidden = sql::get_hidden_profiles().await?;
+    let mut place = 0;
+    for (i, &(user, score)) in standings.iter().enumerate() {
+        if i == 0 || standings[i - 1].1 != score { place = i + 1; }
+        if i != 0 && i % 10 == 0 { budget.break_page(); }
+        budget.line(format!("{}. {} – {} point{}", place, standings_name(&ctx, user, &hidden), format_number(score, ctx.locale()), if score == 1 { "" } else { "s" }));
+    }
+
+    paginate(&ctx, budget.pages()).await
+}
+
+/// Edit your nickname.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn nickname(
+    ctx: Context<'_>,
+    name: String,
+) -> Res {
+    // This checks that the nickname is allowed.
+    let name = name.trim();
+    sql::set_nickname(ctx.author().id, name).await?;
+    ctx.say(format!("Set your nickname to ‘{}’", name)).await?;
+    Ok(())
+}
+
+/// Suggest nicknames matching what the user typed so far.
+async fn autocomplete_nickname(_ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    match sql::search_nicknames(partial, 25).await {
+        Ok(users) => users.into_iter()
+            .map(|(id, name)| AutocompleteChoice::new(name, id.to_string()))
+            .collect(),
+        Err(e) => {
+            err!("Error searching nicknames: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// Resolve a nickname, or a user ID picked from the autocomplete list, to a user.
+async fn resolve_nickname(name: &str) -> Result<UserId, Error> {
+    if let Ok(id) = name.trim().parse::<u64>() {
+        if id != 0 { return Ok(UserId::new(id)); }
+    }
+
+    sql::find_user_by_nickname(name.trim())
+        .await?
+        .ok_or_else(|| format!("There is no user with the nickname ‘{}’", name.trim()).into())
+}
+
+/// Maximum number of weeks shown by `/calendar`.
+const MAX_CALENDAR_WEEKS: u64 = 12;
+
+/// Describe how far a deadline was moved, e.g. ‘extended by 2 days’.
+fn describe_deadline_change(moved: chrono::Duration) -> String {
+    let (verb, moved) = match moved < chrono::Duration::zero() {
+        true => ("moved up", -moved),
+        false => ("extended", moved),
+    };
+
+    match (moved.num_days(), moved.num_hours() % 24) {
+        (0, h) => format!("{} by {} hour{}", verb, h, if h == 1 { "" } else { "s" }),
+        (d, 0) => format!("{} by {} day{}", verb, d, if d == 1 { "" } else { "s" }),
+        (d, h) => format!("{} by {}d {}h", verb, d, h),
+    }
+}
+
+/// Get a private ballot for voting on a challenge.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn ballot(
+    ctx: Context<'_>,
+    #[description = "The challenge to vote on"] challenge: Challenge,
+) -> Res {
+    if !sql::get_setting::<bool>(Setting::BallotVoting).await? {
+        return Err(format!("Vote by reacting with {} to the entries you like", vote_reaction().await).into());
+    }
+
+    let week = ballots::voting_week().await?;
+    if week < 0 || sql::count_submissions(week, challenge).await? == 0 {
+        return Err("There is nothing to vote on right now".into());
+    }
+
+    let (ballot, _) = sql::issue_ballot(ctx.author().id, week, challenge).await?;
+    if ballot.used.is_some() { return Err("You have already voted on these entries".into()); }
+    let (text, rows) = ballots::render(&ballot, 0).await?;
+    ctx.send(CreateReply::default().content(text).components(rows)).await?;
+    Ok(())
+}
+
+/// Show the schedule for the next few weeks.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn calendar(
+    ctx: Context<'_>,
+    #[description = "How many weeks to show, including the current one"]
+    #[min = 1]
+    #[max = 12]
+    weeks: Option<u64>,
+) -> Res {
+    let count = weeks.unwrap_or(4).clamp(1, MAX_CALENDAR_WEEKS);
+    let admin = is_admin(&ctx).await;
+    let mut embed = create_embed(&ctx).author(CreateEmbedAuthor::new("Upcoming Weeks"));
+    for (i, w) in scheduler::calendar(count as i64).await?.into_iter().enumerate() {
+        let mut lines = vec![format!(
+            "{} – {}",
+            timestamp(w.start, TimestampStyle::DateTime),
+            timestamp(w.deadline, TimestampStyle::DateTime),
+        )];
+
+        if let Some(moved) = w.deadline_moved { lines.push(format!("Deadline {}", describe_deadline_change(moved))); }
+        if w.on_break { lines.push("The challenges are on break".into()); }
+
+        // Only say whether there is a prompt; the text is a secret until
+        // it is announced.
+        for (challenge, status) in w.prompts {
+            let status = match status {
+                PromptStatus::Special => "special week".to_string(),
+                PromptStatus::Set => "prompt ready".to_string(),
+                PromptStatus::Hiatus => "on hiatus".to_string(),
+                PromptStatus::Queued(id) if admin => format!("prompt ready (queue entry {})", id),
+                PromptStatus::Queued(_) => "prompt ready".to_string(),
+                PromptStatus::Missing => "no prompt yet".to_string(),
+            };
+
+            lines.push(format!("{}: {}", challenge.name(), status));
+        }
+
+        let name = match i {
+            0 => format!("Week {} (current)", w.week),
+            _ => format!("Week {}", w.week),
+        };
+
+        embed = embed.field(name, lines.join("\n"), false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Compare your stats with someone else’s.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn compare(
+    ctx: Context<'_>,
+    #[description = "Nickname of the user to compare with"]
+    #[autocomplete = "autocomplete_nickname"]
+    nickname: String,
+    #[description = "Nickname of the other user; omit this to compare with yourself"]
+    #[autocomplete = "autocomplete_nickname"]
+    other: Option<String>,
+) -> Res {
+    let a = match other {
+        Some(n) => resolve_nickname(&n).await?,
+        None => ctx.author().id,
+    };
+
+    let b = resolve_nickname(&nickname).await?;
+    let mut embed = create_embed(&ctx).title("Comparison");
+    for user in [a, b] {
+        let prefs = if user == ctx.author().id { UserPrefs::default() } else { sql::get_user_prefs(user).await? };
+        if prefs.hide_profile { return Err("One of these users’ profile is hidden".into()); }
+
+        let data = sql::get_user_profile(user).await?;
+        let mut lines = vec![];
+        if !prefs.nickname_only { lines.push(user.mention().to_string()); }
+        if !prefs.hide_submissions {
+            lines.push(format!("Submissions: {}", data.glyphs_submissions + data.ambigrams_submissions));
+        }
+
+        lines.push(format!("Wins: {}", data.glyphs_first + data.ambigrams_first));
+        lines.push(format!("Podiums: {}", data.glyphs_first + data.glyphs_second + data.glyphs_third
+            + data.ambigrams_first + data.ambigrams_second + data.ambigrams_third));
+        if let Some(avg) = data.average_place { lines.push(format!("Average placement: {:.1}", avg)); }
+        embed = embed.field(data.nickname.as_deref().unwrap_or("Anonymous"), lines.join("\n"), true);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Display a user profile.
+//
+// Shows the specified user profile or the user that executes it. Shows
+// the user’s UserID, nickname, amount of glyphs submitted, amount of
+// ambigrams submitted, the highest ranking in Glyph Challenge, the
+// highest ranking in ambigram challenge, & amount of 1st, 2nd, and
+// 3rd place placements.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn profile(
+    ctx: Context<'_>,
+    #[description = "Nickname of the user whose profile to show; omit this to show your own"]
+    #[autocomplete = "autocomplete_nickname"]
+    nickname: Option<String>,
+    #[description = "User whose profile to show, if they don’t have a nickname"]
+    user: Option<User>,
+) -> Res {
+    const ZWSP: &str = "\u{200B}";
+
+    let target = match (nickname, &user) {
+        (Some(_), Some(_)) => return Err("Pass either a nickname or a user, not both".into()),
+        (Some(n), None) => resolve_nickname(&n).await?,
+        (None, Some(u)) => u.id,
+        (None, None) => ctx.author().id,
+    };
+
+    // Respect the user’s privacy settings if this is someone else’s profile.
+    let own = target == ctx.author().id;
+    let prefs = if own { UserPrefs::default() } else { sql::get_user_prefs(target).await? };
+    if prefs.hide_profile { return Err("This user’s profile is hidden".into()); }
+
+    // Prefer what the server shows for them, if they’re still on it.
+    let user = match user {
+        Some(u) => u,
+        None if own => ctx.author().clone(),
+        None => target.to_user(&ctx).await?,
+    };
+
+    let member = match ctx.guild_id() {
+        Some(g) => g.member(&ctx, target).await.ok(),
+        None => None,
+    };
+
+    let data = sql::get_user_profile(target).await?;
+    let name: &str = match (data.nickname.as_ref(), &member) {
+        (Some(n), _) => n,
+        (None, _) if prefs.nickname_only => "Anonymous",
+        (None, Some(m)) => m.display_name(),
+        (None, None) => user.global_name.as_ref().unwrap_or(&user.name),
+    };
+
+    let mut author = CreateEmbedAuthor::new(match &data.pronouns {
+        Some(p) => format!("{}’s Profile ({})", name, p),
+        None => format!("{}’s Profile", name),
+    });
+    if !prefs.nickname_only { author = author.icon_url(member.as_ref().map_or_else(|| user.face(), |m| m.face())); }
+    let mut embed = create_embed(&ctx).author(author);
+    if let Some(c) = data.accent_colour { embed = embed.colour(c); }
+
+    let mut description = vec![];
+    if data.former_member { description.push("*Former member*".to_string()); }
+    if !prefs.hide_submissions && data.glyphs_submissions == 0 && data.ambigrams_submissions == 0 && data.average_place.is_none() {
+        description.push(format!("*{} taken part in any challenges yet*", if own { "You haven’t" } else { "Hasn’t" }));
+    }
+    if let Some(bio) = &data.bio { description.push(bio.clone()); }
+    if !data.links.is_empty() { description.push(data.links.join("\n")); }
+    if !description.is_empty() { embed = embed.description(description.join("\n\n")); }
+
+    // Helper to add a field.
+    fn add(embed: CreateEmbed, name: &'static str, value: i64) -> CreateEmbed {
+        embed.field(
+            name,
+            format!(
+                "{} time{}",
+                value,
+                if value == 1 { "" } else { "s" }
+            ),
+            true,
+        )
+    }
+
+    let have_glyphs_rating = data.glyphs_first != 0 ||
+        data.glyphs_second != 0 ||
+        data.glyphs_third != 0;
+
+    let have_ambigrams_rating = data.ambigrams_first != 0 ||
+        data.ambigrams_second != 0 ||
+        data.ambigrams_third != 0;
+
+    // Add submissions.
+    if !prefs.hide_submissions && (data.glyphs_submissions != 0 || data.ambigrams_submissions != 0) {
+        embed = embed.field("Submitted Glyphs", format_number(data.glyphs_submissions, ctx.locale()), true);
+        embed = embed.field("Submitted Ambigrams", format_number(data.ambigrams_submissions, ctx.locale()), true);
+        embed = embed.field(ZWSP, ZWSP, true); // Empty field.
+    }
+
+    // Add first/second/third place ratings for glyphs challenge.
+    if have_glyphs_rating {
+        embed = add(embed, "1st Place – G", data.glyphs_first);
+        embed = add(embed, "2nd Place – G", data.glyphs_second);
+        embed = add(embed, "3nd Place – G", data.glyphs_third);
+    } else {
+        embed = embed.field(
+            "Highest ranking in Glyphs Challenge",
+            format!("{}", data.highest_ranking_glyphs),
+            false,
+        );
+    }
+
+    // Add first/second/third place for ambigrams challenge.
+    if have_ambigrams_rating {
+        embed = add(embed, "1st Place – A", data.ambigrams_first);
+        embed = add(embed, "2nd Place – A", data.ambigrams_second);
+        embed = add(embed, "3nd Place – A", data.ambigrams_third);
+    } else {
+        embed = embed.field(
+            "Highest ranking in Ambigrams Challenge",
+            format!("{}", data.highest_ranking_ambigrams),
+            false,
+        );
+    }
+
+    // Add computed stats.
+    if let Some(avg) = data.average_place {
+        embed = embed.field("Average Placement", format!("{:.1}", avg), true);
+    }
+
+    if let Some(p) = data.percentile {
+        embed = embed.field("Percentile", format!("Better than {:.0}% of active participants", p), true);
+    }
+
+    if let Some(b) = &data.best_week_share {
+        embed = embed.field("Best Week", format!(
+            "{} in week {} ({}) with {:.0}% of the votes",
+            results::ordinal(b.place as usize),
+            b.week,
+            Challenge::try_from(b.challenge)?.name(),
+            b.share * 100.0,
+        ), false);
+    }
+
+    // Add achievements.
+    let achievements = sql::get_achievements(target).await?;
+    if !achievements.is_empty() {
+        let mut list = Vec::with_capacity(achievements.len());
+        for a in achievements {
+            list.push(format!("{} {}", badge_emoji(&ctx.serenity_context().cache, a).await, a.name()));
+        }
+
+        let list = list.join("\n");
+        embed = embed.field("Achievements", list, false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Maximum number of entries in a portfolio.
+const PORTFOLIO_SIZE: i64 = 12;
+
+/// Show a user’s best entries, e.g. to share them.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn portfolio(
+    ctx: Context<'_>,
+    #[description = "Nickname of the user whose portfolio to show; omit this to show your own"]
+    #[autocomplete = "autocomplete_nickname"]
+    nickname: Option<String>,
+    #[description = "Combine the entries into a single image instead of paging through them"]
+    image: Option<bool>,
+) -> Res {
+    let target = match nickname {
+        Some(n) => resolve_nickname(&n).await?,
+        None => ctx.author().id,
+    };
+
+    // Respect the user’s privacy settings if this is someone else’s portfolio.
+    let own = target == ctx.author().id;
+    let prefs = if own { UserPrefs::default() } else { sql::get_user_prefs(target).await? };
+    if prefs.hide_profile { return Err("This user’s profile is hidden".into()); }
+    if prefs.hide_submissions { return Err("This user’s submissions are hidden".into()); }
+
+    let entries = sql::get_portfolio(target, PORTFOLIO_SIZE).await?;
+    if entries.is_empty() {
+        return Err(if own { "You don’t have any ranked entries yet" } else { "This user doesn’t have any ranked entries yet" }.into());
+    }
+
+    let name = match sql::get_user_row(target).await?.and_then(|u| u.nickname) {
+        Some(n) => n,
+        None if prefs.nickname_only => "Anonymous".to_string(),
+        None => sql::get_display_name(target).await?.unwrap_or_else(|| "Anonymous".to_string()),
+    };
+
+    // This is gonna take a while...
+    ctx.defer_ephemeral().await?;
+    // Leave out entries whose image is gone rather than failing outright.
+    let mut loaded = Vec::with_capacity(entries.len());
+    let mut paths = Vec::with_capacity(entries.len());
+    for e in entries {
+        let message = ser::MessageId::new(e.message as u64);
+        match archive::submission_path(ctx.serenity_context(), Challenge::try_from(e.challenge)?, message).await {
+            Ok(path) => {
+                loaded.push(e);
+                paths.push(path);
+            }
+            Err(err) => warn!("Leaving entry {} out of the portfolio of {}: {}", message, target, err),
+        }
+    }
+
+    let entries = loaded;
+    if entries.is_empty() { return Err("None of the entries in this portfolio could be loaded".into()); }
+
+    // Blind challenges spoiler every entry, but only until voting opens.
+    let mut spoilers = Vec::with_capacity(entries.len());
+    for e in &entries {
+        spoilers.push(e.spoiler && !sql::get_setting::<bool>(Setting::blind(Challenge::try_from(e.challenge)?)).await?);
+    }
+
+    let title = format!("{}’s Portfolio", name);
+    let caption = |e: &sql::PortfolioEntry| -> Result<String, Error> { Ok(format!(
+        "{} in week {} of the {} Challenge ({} vote{})",
+        results::ordinal(e.place as usize),
+        e.week,
+        Challenge::try_from(e.challenge)?.name(),
+        e.votes,
+        if e.votes == 1 { "" } else { "s" },
+    )) };
+
+    if image == Some(true) {
+        let limit = preflight::upload_limit(ctx.serenity_context()).await? as usize;
+        let panel = tokio::task::spawn_blocking(move || -> Result<_, Error> {
+            let images = paths.iter().map(image::open).collect::<Result<Vec<_>, _>>()?;
+            panel::encode(&panel::composite(&images)?, limit)
+        }).await??
+            .ok_or("The portfolio is too large to upload")?;
+
+        let prefix = if spoilers.contains(&true) { "SPOILER_" } else { "" };
+        let file = format!("{}portfolio.{}", prefix, panel.extension);
+        let embed = create_embed(&ctx)
+            .author(CreateEmbedAuthor::new(&title))
+            .description(entries.iter().map(|e| Ok(format!("- {}", caption(e)?))).collect::<Result<Vec<_>, Error>>()?.join("\n"));
+        ctx.send(CreateReply::default().embed(embed).attachment(CreateAttachment::bytes(panel.data, file))).await?;
+        return Ok(());
+    }
+
+    // Spoilered images can’t be shown in an embed, so they are attached instead.
+    let count = entries.len();
+    let mut pages = Vec::with_capacity(count);
+    for (i, ((e, path), spoiler)) in entries.iter().zip(paths).zip(spoilers).enumerate() {
+        let mut embed = create_embed(&ctx)
+            .author(CreateEmbedAuthor::new(&title))
+            .description(caption(e)?)
+            .footer(CreateEmbedFooter::new(format!("{}/{}", i + 1, count)));
+        let file = if spoiler { "SPOILER_entry.png" } else { "entry.png" };
+        if !spoiler { embed = embed.image("attachment://entry.png"); }
+        pages.push((embed, Some(CreateAttachment::bytes(tokio::fs::read(path).await?, file))));
+    }
+
+    paginate_with_files(&ctx, pages).await
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("settings_bio", "settings_certificates", "settings_colour", "settings_delete_my_data", "settings_dms", "settings_export_my_data", "settings_privacy", "settings_pronouns", "settings_vote_reminders"))]
+pub async fn settings(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Modal for editing a profile bio.
+#[derive(Debug, poise::Modal)]
+#[name = "Edit Bio"]
+struct BioModal {
+    #[name = "Bio"]
+    #[paragraph]
+    #[max_length = 300]
+    bio: Option<String>,
+
+    #[name = "Link (e.g. portfolio)"]
+    #[max_length = 200]
+    link1: Option<String>,
+
+    #[name = "Another link (e.g. social media)"]
+    #[max_length = 200]
+    link2: Option<String>,
+}
+
+/// Set the bio and links shown on your profile.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "bio")]
+pub async fn settings_bio(ctx: ApplicationContext<'_>) -> Res {
+    let row = sql::get_user_row(ctx.author().id).await?;
+    let defaults = BioModal {
+        bio: row.as_ref().and_then(|r| r.bio.clone()),
+        link1: row.as_ref().and_then(|r| r.link1.clone()),
+        link2: row.as_ref().and_then(|r| r.link2.clone()),
+    };
+
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction.create_response(ctx, BioModal::create(Some(defaults), modal_id.clone())).await?;
+    ctx.has_sent_initial_response.store(true, Ordering::SeqCst);
+    let Some((m, data)) = await_modal::<BioModal>(ctx.serenity_context(), modal_id).await? else { return Ok(()); };
+
+    let res = sql::set_bio(ctx.author().id, data.bio.as_deref().unwrap_or(""), [
+        data.link1.as_deref().unwrap_or(""),
+        data.link2.as_deref().unwrap_or(""),
+    ]).await;
+
+    let content = match res {
+        Ok(()) => "Updated your bio".to_string(),
+        Err(e) => format!("Error: {}", e),
+    };
+
+    m.create_response(ctx, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true)
+    )).await?;
+    Ok(())
+}
+
+/// Choose whether to receive a participation certificate at the end of each season.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "certificates")]
+pub async fn settings_certificates(
+    ctx: Context<'_>,
+    #[description = "Whether to receive certificates by DM"] enabled: bool,
+) -> Res {
+    let mut prefs = sql::get_user_prefs(ctx.author().id).await?;
+    prefs.certificates = enabled;
+    sql::set_user_prefs(ctx.author().id, &prefs).await?;
+    ctx.say(if enabled { "You will receive a certificate at the end of each season you take part in." } else { "You will no longer receive certificates." }).await?;
+    Ok(())
+}
+
+/// Set the colour of your profile.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "colour")]
+pub async fn settings_colour(
+    ctx: Context<'_>,
+    #[description = "Colour as #RRGGBB; omit this to use the default colour"] colour: Option<String>,
+) -> Res {
+    let colour = colour.as_deref().map(parse_colour).transpose()?;
+    sql::set_accent_colour(ctx.author().id, colour.map(|c| c.0)).await?;
+    ctx.send(CreateReply::default().embed(create_embed(&ctx)
+        .colour(colour.unwrap_or(DEFAULT_EMBED_COLOUR))
+        .description(match colour {
+            Some(c) => format!("Set your profile colour to #{}", c.hex()),
+            None => "Reset your profile colour".to_string(),
+        })
+    )).await?;
+    Ok(())
+}
+
+/// Delete all data the bot stores about you.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "delete-my-data")]
+pub async fn settings_delete_my_data(ctx: Context<'_>) -> Res {
+    let id = InteractionID::DeleteUserData { user: ctx.author().id }.encode();
+    let reply = ctx.send(CreateReply::default()
+        .content(concat!(
+            "This will permanently delete your nickname, settings, and profile stats. ",
+            "Your past submissions will be kept, but will no longer be associated with you. ",
+            "This cannot be undone."
+        ))
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(&id).label("Delete my data").style(ButtonStyle::Danger),
+        ])])
+    ).await?;
+    register_components(&[id], COMPONENT_LIFETIME_SECS, &*reply.message().await?).await?;
+    Ok(())
+}
+
+/// Choose whether the bot may send you DMs you didn’t ask for.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "dms")]
+pub async fn settings_dms(
+    ctx: Context<'_>,
+    #[description = "Whether to receive DMs such as the welcome message"] enabled: bool,
+) -> Res {
+    let mut prefs = sql::get_user_prefs(ctx.author().id).await?;
+    prefs.dm_opt_out = !enabled;
+    sql::set_user_prefs(ctx.author().id, &prefs).await?;
+    ctx.say(if enabled { "You will receive DMs from the bot." } else { "You will no longer receive DMs from the bot." }).await?;
+    Ok(())
+}
+
+/// Choose whether you get pinged before voting closes.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "vote-reminders")]
+pub async fn settings_vote_reminders(
+    ctx: Context<'_>,
+    #[description = "Whether to get pinged before voting closes"] enabled: bool,
+) -> Res {
+    let role = sql::get_setting::<u64>(Setting::VoteReminderRole).await?;
+    if role == 0 { return Err("Vote reminders are not enabled on this server".into()); }
+
+    let member = ctx.author_member().await.ok_or("Could not find you on the server")?;
+    if enabled {
+        member.add_role(&ctx, ser::RoleId::new(role)).await?;
+        ctx.say("You will be pinged before voting closes.").await?;
+    } else {
+        member.remove_role(&ctx, ser::RoleId::new(role)).await?;
+        ctx.say("You will no longer be pinged before voting closes.").await?;
+    }
+
+    Ok(())
+}
+
+/// Get a copy of all data the bot stores about you.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "export-my-data")]
+pub async fn settings_export_my_data(ctx: Context<'_>) -> Res {
+    let user = ctx.author().id;
+    let row = sql::get_user_row(user).await?;
+    let prefs = sql::get_user_prefs(user).await?;
+    let submissions = sql::get_user_submissions(user).await?;
+    let achievements = sql::get_achievements(user).await?;
+    let mut week_starts = HashMap::new();
+    for s in &submissions {
+        if let Entry::Vacant(e) = week_starts.entry(s.week) { e.insert(sql::get_week_start(s.week).await?); }
+    }
+
+    let data = serde_json::json!({
+        "user": user.get(),
+        "profile": row.map(|r| serde_json::json!({
+            "nickname": r.nickname,
+            "display_name": r.display_name,
+            "avatar_url": r.avatar_url,
+            "former_member": r.former_member,
+            "bio": r.bio,
+            "links": [r.link1, r.link2],
+            "accent_colour": r.accent_colour,
+            "pronouns": r.pronouns,
+            "glyphs_first": r.glyphs_first,
+            "glyphs_second": r.glyphs_second,
+            "glyphs_third": r.glyphs_third,
+            "ambigrams_first": r.ambigrams_first,
+            "ambigrams_second": r.ambigrams_second,
+            "ambigrams_third": r.ambigrams_third,
+            "highest_ranking_glyphs": r.highest_ranking_glyphs,
+            "highest_ranking_ambigrams": r.highest_ranking_ambigrams,
+        })),
+        "preferences": {
+            "hide_profile": prefs.hide_profile,
+            "hide_submissions": prefs.hide_submissions,
+            "nickname_only": prefs.nickname_only,
+            "dm_opt_out": prefs.dm_opt_out,
+            "certificates": prefs.certificates,
+        },
+        "submissions": submissions.iter().map(|s| Ok(serde_json::json!({
+            "message": s.message,
+            "week": s.week,
+            "week_start": week_starts.get(&s.week).copied().flatten(),
+            "challenge": Challenge::try_from(s.challenge)?.name(),
+            "link": s.link,
+            "time": s.time,
+            "votes": s.votes,
+            "status": s.status,
+        }))).collect::<Result<Vec<_>, Error>>()?,
+        "achievements": achievements.iter().map(|a| a.name()).collect::<Vec<_>>(),
+    });
+
+    // Send it via DM so it doesn’t get lost when the ephemeral reply goes away.
+    let json = serde_json::to_vec_pretty(&data)?;
+    dm::send(ctx.serenity_context(), user, DmKind::Essential, CreateMessage::new()
+        .content("Here is all the data stored about you.")
+        .add_file(CreateAttachment::bytes(json, "glyfi-data.json"))
+    ).await?;
+
+    ctx.say("Sent you a DM with your data.").await?;
+    Ok(())
+}
+
+/// Control what other users can see about you.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "privacy")]
+pub async fn settings_privacy(
+    ctx: Context<'_>,
+    #[description = "Hide your profile from other users"] hide_profile: Option<bool>,
+    #[description = "Hide your submission history from other users"] hide_submissions: Option<bool>,
+    #[description = "Show only your nickname to other users"] nickname_only: Option<bool>,
+) -> Res {
+    let mut prefs = sql::get_user_prefs(ctx.author().id).await?;
+    let changed = hide_profile.is_some() || hide_submissions.is_some() || nickname_only.is_some();
+    if let Some(v) = hide_profile { prefs.hide_profile = v; }
+    if let Some(v) = hide_submissions { prefs.hide_submissions = v; }
+    if let Some(v) = nickname_only { prefs.nickname_only = v; }
+    if changed { sql::set_user_prefs(ctx.author().id, &prefs).await?; }
+
+    // Show the current settings.
+    fn yes_no(b: bool) -> &'static str { if b { "Yes" } else { "No" } }
+    let embed = create_embed(&ctx)
+        .author(CreateEmbedAuthor::new("Privacy Settings"))
+        .field("Hide profile", yes_no(prefs.hide_profile), true)
+        .field("Hide submissions", yes_no(prefs.hide_submissions), true)
+        .field("Nickname only", yes_no(prefs.nickname_only), true);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Set the pronouns shown on your profile and in announcements.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "pronouns")]
+pub async fn settings_pronouns(
+    ctx: Context<'_>,
+    #[description = "Your pronouns, e.g. ‘they/them’; omit this to remove them"] pronouns: Option<String>,
+) -> Res {
+    sql::set_pronouns(ctx.author().id, pronouns.as_deref()).await?;
+    match pronouns.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => ctx.say(format!("Set your pronouns to ‘{}’", p)).await?,
+        None => ctx.say("Removed your pronouns").await?,
+    };
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("queue_add", "queue_list", "queue_poll", "queue_preview_all", "queue_remove", "queue_series", "queue_shortlist", "queue_show", "queue_theme"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Add a glyph/ambigram prompt to the queue.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "add", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_add(
+    ctx: ApplicationContext<'_>,
+    #[description = "Which challenge to set the prompt for"] challenge: Challenge,
 ) -> Res {
-    // Name must not be empty, must not include only whitespace
-    // and must not be longer than 200 characters.
-    let name = name.trim();
-    if name.is_empty() || name.len() > 200 {
-        return Err("Name must not be empty and contain at most 200 characters".into());
+    // Ask for the prompt.
+    let modal_id = ctx.interaction.id.to_string();
+    ctx.interaction.create_response(ctx, PromptModal::create(None, modal_id.clone())).await?;
+    ctx.has_sent_initial_response.store(true, Ordering::SeqCst);
+    let Some((m, data)) = await_prompt_modal(ctx.serenity_context(), modal_id).await? else { return Ok(()); };
+
+    // This is gonna take a while...
+    m.defer_ephemeral(ctx).await?;
+    let path = generate_challenge_image(challenge, &data.prompt, None).await?;
+
+    // Save prompt.
+    let id = sql::add_prompt(challenge, &data.prompt, data.notes.as_deref(), data.category.as_deref()).await?;
+
+    // Reply with the image.
+    let mut text = prompt_announcement_text(&sql::get_prompt(id).await?).await?;
+    if !scheduler::is_active(challenge).await? {
+        text = format!("*The {} Challenge is on hiatus, so this stays in the queue until it is back.*\n\n{}", challenge.name(), text);
     }
 
-    // Set nickname.
-    sql::set_nickname(ctx.author().id, name).await?;
-    ctx.say(format!("Set your nickname to ‘{}’", name)).await?;
+    let (buttons, ids) = prompt_preview_buttons(challenge, &path, id)?;
+    let message = m.edit_response(ctx, EditInteractionResponse::new()
+        .content(text)
+        .new_attachment(watermark::preview_attachment(&path, "preview.png").await?)
+        .components(buttons)
+    ).await?;
+    register_components(&ids, COMPONENT_LIFETIME_SECS, &message).await?;
     Ok(())
 }
 
-/// Display your user profile.
-//
-// Shows the specified user profile or the user that executes it. Shows
-// the user’s UserID, nickname, amount of glyphs submitted, amount of
-// ambigrams submitted, the highest ranking in Glyph Challenge, the
-// highest ranking in ambigram challenge, & amount of 1st, 2nd, and
-// 3rd place placements.
-#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
-pub async fn profile(ctx: Context<'_>) -> Res {
-    const ZWSP: &str = "\u{200B}";
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("preview_next"))]
+pub async fn preview(_ctx: Context<'_>) -> Res { unreachable!(); }
 
-    let data = sql::get_user_profile(ctx.author().id).await?;
-    let name: &str = data.nickname.as_ref()
-        .or(ctx.author().global_name.as_ref())
-        .unwrap_or(&ctx.author().name)
-        .as_str();
+/// See the next prompt shortly before it is announced.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "next")]
+pub async fn preview_next(
+    ctx: Context<'_>,
+    #[description = "Which challenge to preview"] challenge: Challenge,
+) -> Res {
+    let role = sql::get_setting::<u64>(Setting::PreviewRole).await?;
+    if role == 0 { return Err("Previews are not enabled on this server".into()); }
+    if !scheduler::is_active(challenge).await? {
+        return Err(format!("The {} Challenge is on hiatus", challenge.name()).into());
+    }
 
-    let mut embed = create_embed(&ctx);
-    embed = embed.author(CreateEmbedAuthor::new(format!("{}’s Profile", name))
-        .icon_url(ctx.author().face())
-    );
+    let member = ctx.author_member().await.ok_or("Could not find you on the server")?;
+    if !member.roles.contains(&ser::RoleId::new(role)) {
+        return Err("You don’t have access to previews".into());
+    }
 
-    // Helper to add a field.
-    fn add(embed: CreateEmbed, name: &'static str, value: i64) -> CreateEmbed {
-        embed.field(
-            name,
-            format!(
-                "{} time{}",
-                value,
-                if value == 1 { "" } else { "s" }
-            ),
-            true,
-        )
+    // The next prompt is announced when the current week ends.
+    let announcement = scheduler::deadline(sql::current_week().await?).await?;
+    let hours = sql::get_setting::<i64>(Setting::PreviewHours).await?;
+    let opens = announcement - chrono::Duration::hours(hours);
+    if clock::now() < opens {
+        return Err(format!("The preview opens {}", timestamp(opens, TimestampStyle::Relative)).into());
     }
 
-    let have_glyphs_rating = data.glyphs_first != 0 ||
-        data.glyphs_second != 0 ||
-        data.glyphs_third != 0;
+    let Some(&(id, _)) = sql::get_prompts(challenge).await?.first() else {
+        return Err("There is no prompt in the queue yet".into());
+    };
 
-    let have_ambigrams_rating = data.ambigrams_first != 0 ||
-        data.ambigrams_second != 0 ||
-        data.ambigrams_third != 0;
+    let prompt = sql::get_prompt(id).await?;
+    info!("{} previewed the next {:?} prompt ({})", ctx.author().id, challenge, id);
 
-    // Add submissions.
-    if data.glyphs_submissions != 0 || data.ambigrams_submissions != 0 {
-        embed = embed.field("Submitted Glyphs", format!("{}", data.glyphs_submissions), true);
-        embed = embed.field("Submitted Ambigrams", format!("{}", data.ambigrams_submissions), true);
-        embed = embed.field(ZWSP, ZWSP, true); // Empty field.
+    let mut embed = create_embed(&ctx)
+        .title(format!("Next {} Challenge", challenge.name()))
+        .field("Prompt", &prompt.prompt, false);
+    if let Some(part) = series_note(&prompt).await? {
+        embed = embed.field("Series", part, false);
     }
-
-    // Add first/second/third place ratings for glyphs challenge.
-    if have_glyphs_rating {
-        embed = add(embed, "1st Place – G", data.glyphs_first);
-        embed = add(embed, "2nd Place – G", data.glyphs_second);
-        embed = add(embed, "3nd Place – G", data.glyphs_third);
-    } else {
-        embed = embed.field(
-            "Highest ranking in Glyphs Challenge",
-            format!("{}", data.highest_ranking_glyphs),
-            false,
-        );
+    if let Some(notes) = &prompt.notes {
+        embed = embed.field("Notes", notes, false);
     }
 
-    // Add first/second/third place for ambigrams challenge.
-    if have_ambigrams_rating {
-        embed = add(embed, "1st Place – A", data.ambigrams_first);
-        embed = add(embed, "2nd Place – A", data.ambigrams_second);
-        embed = add(embed, "3nd Place – A", data.ambigrams_third);
-    } else {
-        embed = embed.field(
-            "Highest ranking in Ambigrams Challenge",
-            format!("{}", data.highest_ranking_ambigrams),
-            false,
-        );
-    }
+    embed = embed
+        .field("Announced", timestamp(announcement, TimestampStyle::Relative), false)
+        .footer(CreateEmbedFooter::new("Please keep this to yourself until it is announced."));
 
     ctx.send(CreateReply::default().embed(embed)).await?;
     Ok(())
 }
 
-#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("queue_add", "queue_list", "queue_remove", "queue_show"), default_member_permissions = "ADMINISTRATOR")]
-pub async fn queue(ctx: Context<'_>) -> Res { unreachable!(); }
+/// Let members vote on prompts from the shortlist now, instead of waiting
+/// for the next scheduled poll.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "poll", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_poll(
+    ctx: Context<'_>,
+    #[description = "Which challenge to hold the poll for"] challenge: Challenge,
+) -> Res {
+    let id = polls::open_poll(ctx.serenity_context(), challenge).await?;
+    ctx.say(format!("Opened poll {} for the {} Challenge", id, challenge.name())).await?;
+    Ok(())
+}
 
-/// Add a glyph/ambigram prompt to the queue.
-#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "add", default_member_permissions = "ADMINISTRATOR")]
-pub async fn queue_add(
+/// Page through previews of every queued prompt for a challenge.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "preview-all", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_preview_all(
     ctx: Context<'_>,
-    #[description = "Which challenge to set the prompt for"] challenge: Challenge,
-    #[description = "The prompt for the challenge"] prompt: String,
+    #[description = "Which challenge to preview the queue for"] challenge: Challenge,
 ) -> Res {
+    let prompts = sql::get_prompts(challenge).await?;
+    if prompts.is_empty() { return Err("The queue is empty".into()); }
+
     // This is gonna take a while...
     ctx.defer_ephemeral().await?;
-    let path = generate_challenge_image(challenge, &prompt).await?;
-
-    // Save prompt.
-    let id = sql::add_prompt(challenge, &prompt).await?;
-
-    // Get mtime. This is just a little sanity check.
-    let mtime = file_mtime(&path)?;
+    let count = prompts.len();
+    let mut pages = Vec::with_capacity(count);
+    for (i, (id, prompt)) in prompts.into_iter().enumerate() {
+        let theme = sql::get_prompt(id).await?.theme;
+        let path = cached_preview(challenge, id, &prompt, theme.as_deref()).await?;
+        let embed = create_embed(&ctx)
+            .title(format!("{} ({}/{})", prompt, i + 1, count))
+            .footer(CreateEmbedFooter::new(format!("ID: {}", id)))
+            .image("attachment://preview.png");
+        pages.push((embed, Some(watermark::preview_attachment(&path, "preview.png").await?)));
+    }
 
-    // Reply with the image.
-    ctx.send(CreateReply::default()
-        .attachment(CreateAttachment::path(path).await?)
-        .components(vec![CreateActionRow::Buttons(vec![
-            /*CreateButton::new(format!(
-                "{}:{}:{}:{}",
-                InteractionID::ConfirmAnnouncement.raw(),
-                challenge.raw(),
-                mtime,
-                id
-            )).label("Confirm").style(ButtonStyle::Success),*/
-            CreateButton::new(format!(
-                "{}:{}",
-                InteractionID::CancelPrompt.raw(),
-                id
-            )).label("Cancel").style(ButtonStyle::Danger),
-        ])])
-    ).await?;
-    Ok(())
+    paginate_with_files(&ctx, pages).await
 }
 
 /// Show the current queue for a challenge.
@@ -171,29 +1911,49 @@ pub async fn queue_list(
     ctx: Context<'_>,
     #[description = "Which challenge to show the queue for"] challenge: Challenge,
 ) -> Res {
-    // Get the queue.
-    let queue = sql::get_prompts(challenge)
-        .await?
-        .iter().map(|p| format!("- **{}:** {}", p.0, p.1))
-        .collect::<Vec<_>>()
-        .join("\n");
-
     // Create embed.
-    let embed = create_embed(&ctx)
-        .author(CreateEmbedAuthor::new(format!("Queue for {}", challenge.name())))
-        .description(queue);
+    let title = format!("Queue for {}", challenge.name());
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(&title)), title.len());
+
+    // Add the queue.
+    for (i, p) in sql::get_prompts(challenge).await?.into_iter().enumerate() {
+        if i != 0 && i % 20 == 0 { budget.break_page(); }
+        let entry = sql::get_prompt(p.0).await?;
+        let mut line = format!("- **{}:** {}", p.0, p.1);
+        if let Some(part) = series_note(&entry).await? { line.push_str(&format!(" ({})", part)); }
+        if !entry.approved { line.push_str(" *(not confirmed)*"); }
+        budget.line(line);
+    }
 
     // Send it.
-    ctx.send(CreateReply::default().embed(embed)).await?;
-    Ok(())
+    paginate(&ctx, budget.pages()).await
+}
+
+/// Use the given queue entry, or let the user choose one.
+async fn pick_queue_entry(ctx: &Context<'_>, id: Option<i64>, prompt: &str) -> Result<Option<i64>, Error> {
+    if id.is_some() { return Ok(id); }
+
+    let mut options = vec![];
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        for (id, prompt) in sql::get_prompts(challenge).await? {
+            options.push(PickerOption::new(prompt, id).description(format!("{} Challenge, ID {}", challenge.name(), id)));
+        }
+    }
+
+    match pick(ctx, prompt, options).await? {
+        Some(v) => Ok(Some(v.parse()?)),
+        None => Ok(None),
+    }
 }
 
 /// Remove an entry from a queue.
 #[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
 pub async fn queue_remove(
     ctx: Context<'_>,
-    #[description = "The ID of the entry to remove"] id: i64,
+    #[description = "The ID of the entry to remove; omit this to choose from a list"] id: Option<i64>,
 ) -> Res {
+    let Some(id) = pick_queue_entry(&ctx, id, "Entry to remove:").await? else { return Ok(()); };
+
     // Remove it.
     let changed = sql::delete_prompt(id).await?;
 
@@ -203,18 +1963,168 @@ pub async fn queue_remove(
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "series", subcommands("queue_series_create", "queue_series_dissolve"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_series(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Group queue entries into a series that runs in consecutive weeks.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "create", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_series_create(
+    ctx: Context<'_>,
+    #[description = "Which challenge the series is for"] challenge: Challenge,
+    #[description = "Name of the series, shown in announcements and its results"] name: String,
+    #[description = "IDs of the queue entries for the parts, in order, separated by commas"] entries: String,
+) -> Res {
+    let ids = entries.split(',')
+        .map(|id| id.trim().parse::<i64>().map_err(|_| format!("Invalid queue entry ID ‘{}’", id.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let series = sql::create_series(challenge, name.trim(), &ids).await?;
+    ctx.say(format!(
+        "Created series ‘{}’ (ID {}) with {} parts; they will run in consecutive weeks",
+        series.name, series.id, series.parts,
+    )).await?;
+    Ok(())
+}
+
+/// Ungroup the queue entries of a series.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "dissolve", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_series_dissolve(
+    ctx: Context<'_>,
+    #[description = "The ID of the series"] id: i64,
+) -> Res {
+    let series = sql::get_series(id).await?.ok_or("No such series")?;
+    let count = sql::dissolve_series(id).await?;
+    ctx.say(format!("Dissolved series ‘{}’; {} queue entr{} ungrouped", series.name, count, if count == 1 { "y was" } else { "ies were" })).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "shortlist", subcommands("queue_shortlist_add", "queue_shortlist_list", "queue_shortlist_remove"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_shortlist(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Put forward a prompt for winners to pick.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "add", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_shortlist_add(
+    ctx: Context<'_>,
+    #[description = "Which challenge the prompt is for"] challenge: Challenge,
+    #[description = "The prompt"] #[max_length = 200] prompt: String,
+) -> Res {
+    let prompt = prompt.trim();
+    if prompt.is_empty() { return Err("Prompt must not be empty".into()); }
+    let id = sql::add_to_shortlist(challenge, prompt).await?;
+    ctx.say(format!("Added ‘{}’ to the shortlist for the {} Challenge (ID {})", prompt, challenge.name(), id)).await?;
+    Ok(())
+}
+
+/// Show the prompts winners can pick from.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "list", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_shortlist_list(
+    ctx: Context<'_>,
+    #[description = "Which challenge to show the shortlist for"] challenge: Challenge,
+) -> Res {
+    let title = format!("Shortlist for {}", challenge.name());
+    let mut budget = EmbedBudget::new(create_embed(&ctx).author(CreateEmbedAuthor::new(&title)), title.len());
+    let shortlist = sql::get_shortlist(challenge).await?;
+    if shortlist.is_empty() { budget.line("The shortlist is empty; winners can only suggest their own prompts."); }
+    for (id, prompt) in shortlist {
+        budget.line(format!("- **{}:** {}", id, prompt));
+    }
+
+    paginate(&ctx, budget.pages()).await
+}
+
+/// Take a prompt off the shortlist.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_shortlist_remove(
+    ctx: Context<'_>,
+    #[description = "The ID of the prompt to remove"] id: i64,
+) -> Res {
+    if sql::remove_from_shortlist(id).await? { ctx.say("Removed prompt from the shortlist").await?; }
+    else { ctx.say("No such prompt").await?; }
+    Ok(())
+}
+
 /// Preview an entry in the queue.
 #[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "show", default_member_permissions = "ADMINISTRATOR")]
 pub async fn queue_show(
     ctx: Context<'_>,
-    #[description = "The ID of the entry to preview"] id: i64,
+    #[description = "The ID of the entry to preview; omit this to choose from a list"] id: Option<i64>,
 ) -> Res {
+    let Some(id) = pick_queue_entry(&ctx, id, "Entry to preview:").await? else { return Ok(()); };
     ctx.defer_ephemeral().await?;
     let entry = sql::get_prompt(id).await?;
-    let path = generate_challenge_image(entry.0, &entry.1).await?;
-    ctx.send(CreateReply::default()
-        .attachment(CreateAttachment::path(path).await?)
-    ).await?;
+    let themes = sql::get_themes(entry.challenge).await?;
+    if themes.is_empty() {
+        let path = generate_challenge_image(entry.challenge, &entry.prompt, None).await?;
+        ctx.send(CreateReply::default()
+            .content(prompt_announcement_text(&entry).await?)
+            .attachment(watermark::preview_attachment(&path, "preview.png").await?)
+        ).await?;
+        return Ok(());
+    }
+
+    // Render the prompt with every theme so they can be compared.
+    let choices = std::iter::once(None).chain(themes.iter().map(|t| Some(t.as_str()))).collect::<Vec<_>>();
+    let mut reply = CreateReply::default().content(format!(
+        "{}\n\nCurrent theme: {}",
+        prompt_announcement_text(&entry).await?,
+        entry.theme.as_deref().unwrap_or("default"),
+    ));
+
+    for theme in &choices {
+        let path = generate_challenge_image(entry.challenge, &entry.prompt, *theme).await?;
+        let name = format!("{}.png", theme.unwrap_or("default"));
+        reply = reply.attachment(watermark::preview_attachment(&path, &name).await?);
+    }
+
+    // The last image generated is the one that gets confirmed, so make
+    // sure it uses the theme the prompt currently has.
+    if entry.theme.as_deref() != *choices.last().unwrap() {
+        generate_challenge_image(entry.challenge, &entry.prompt, entry.theme.as_deref()).await?;
+    }
+
+    ctx.send(reply).await?;
+    let options = choices.iter()
+        .map(|t| PickerOption::new(t.unwrap_or("Default"), t.unwrap_or("")))
+        .collect();
+    let Some(theme) = pick(&ctx, "Theme to use for this prompt:", options).await? else { return Ok(()); };
+    let theme = Some(theme.as_str()).filter(|t| !t.is_empty());
+    sql::set_prompt_theme(id, theme).await?;
+
+    // Regenerate the image so the one that gets confirmed uses the new theme.
+    if theme != entry.theme.as_deref() {
+        generate_challenge_image(entry.challenge, &entry.prompt, theme).await?;
+    }
+
+    ctx.say(format!("Entry {} now uses the {} theme", id, theme.unwrap_or("default"))).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "theme", subcommands("queue_theme_add", "queue_theme_remove"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_theme(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Make a theme available for announcement images.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "add", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_theme_add(
+    ctx: Context<'_>,
+    #[description = "Which challenge the theme is for"] challenge: Challenge,
+    #[description = "Name of the theme, as understood by the image generator"] name: String,
+) -> Res {
+    let name = name.trim();
+    if name.is_empty() { return Err("Theme name must not be empty".into()); }
+    sql::add_theme(challenge, name).await?;
+    ctx.say(format!("Added theme ‘{}’ for the {} Challenge", name, challenge.name())).await?;
+    Ok(())
+}
+
+/// Remove a theme; prompts that use it go back to the default theme.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+pub async fn queue_theme_remove(
+    ctx: Context<'_>,
+    #[description = "Which challenge the theme is for"] challenge: Challenge,
+    #[description = "Name of the theme"] name: String,
+) -> Res {
+    if sql::remove_theme(challenge, name.trim()).await? { ctx.say("Removed theme").await?; }
+    else { ctx.say("No such theme").await?; }
     Ok(())
 }
 
@@ -225,6 +2135,14 @@ pub async fn update(ctx: Context<'_>) -> Res {
     Ok(())
 }
 
+/// Show the entries you’ve voted for, by reaction or ballot, and take back votes.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
+pub async fn votes(ctx: Context<'_>) -> Res {
+    let (text, rows) = receipts::render(ctx.author().id).await?;
+    ctx.send(CreateReply::default().content(text).components(rows)).await?;
+    Ok(())
+}
+
 /// Show stats for a week.
 //
 // Info shown are: That week’s glyph/ambigram, message link to
@@ -232,19 +2150,338 @@ pub async fn update(ctx: Context<'_>) -> Res {
 // in that week, how many people voted for that week’s submissions,
 // message link to that week’s submissions post, top 3 winner names,
 // message link to that week’s hall of fame, & the announcement image
-// used for that week.
+// used for that week. Moderators also see withdrawals, disqualifications,
+// flags, and a summary of the votes.
 #[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error")]
 pub async fn weekinfo(
     ctx: Context<'_>,
     #[description = "Which challenge to get stats for"] challenge: Challenge,
     #[description = "The week whose stats to retrieve"] week: Option<u64>,
 ) -> Res {
-    /*let info = sql::weekinfo(week).await?;
+    let info = sql::weekinfo(week).await?;
+    let submissions = sql::count_submissions(info.week, challenge).await?;
+
     let mut embed = create_embed(&ctx);
-    embed = embed.author(CreateEmbedAuthor::new(format!("Stats for Week {}", info.week)));
-    embed = embed.field("Submissions", format!("{}", info.submissions), true);*/
-    todo!();
+    embed = embed.author(CreateEmbedAuthor::new(format!("{} Challenge – Week {}", challenge.name(), info.week)));
+    let prompt = match info.prompt(challenge) {
+        Some(_) if !sql::is_prompt_announced(info.week, challenge).await? && !is_admin(&ctx).await => "(unannounced)",
+        Some(p) => p,
+        None => "(none)",
+    };
+
+    embed = embed.field("Prompt", prompt, true);
+    embed = embed.field("Submissions", format_number(submissions, ctx.locale()), true);
+    if info.week == sql::current_week().await? && !scheduler::is_active(challenge).await? {
+        embed = embed.field("Status", "On hiatus", true);
+    }
+    if let Some(notes) = info.notes(challenge) {
+        embed = embed.field("Notes", notes, false);
+    }
+
+    let start = scheduler::week_start(info.week).await?;
+    let deadline = scheduler::deadline(info.week).await?;
+    embed = embed.field("Dates", format!(
+        "{} – {}",
+        timestamp(start, TimestampStyle::DateTime),
+        timestamp(deadline, TimestampStyle::DateTime),
+    ), false);
+
+    // Turnout.
+    let turnout = sql::get_turnout(info.week, challenge).await?;
+    if turnout.ballots > 0 {
+        let entries = &turnout.votes_per_entry;
+        embed = embed.field("Voters", format_number(turnout.voters, ctx.locale()), true);
+        embed = embed.field("Votes cast", format_number(turnout.ballots, ctx.locale()), true);
+        if let (Some(min), Some(max)) = (entries.first(), entries.last()) {
+            embed = embed.field("Votes per entry", format!(
+                "{} – {} (median {})",
+                format_number(*min, ctx.locale()),
+                format_number(*max, ctx.locale()),
+                format_number(entries[entries.len() / 2], ctx.locale()),
+            ), true);
+        }
+
+        embed = embed.field("Voters who also submitted", format!(
+            "{:.0}%",
+            turnout.voters_who_submitted as f64 * 100.0 / turnout.voters as f64,
+        ), true);
+    }
+
+    if is_mod(&ctx).await? { embed = weekinfo_moderation(embed, info.week, challenge).await?; }
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Add the parts of `/weekinfo` that only moderators see.
+async fn weekinfo_moderation(embed: CreateEmbed, week: i64, challenge: Challenge) -> Result<CreateEmbed, Error> {
+    let mut withdrawals: Vec<(&str, usize)> = vec![];
+    for w in sql::get_withdrawals(week).await?.into_iter().filter(|w| w.challenge == challenge as i64) {
+        let reason = sql::WithdrawalReason::try_from(w.reason)?.name();
+        match withdrawals.iter_mut().find(|(r, _)| *r == reason) {
+            Some((_, n)) => *n += 1,
+            None => withdrawals.push((reason, 1)),
+        }
+    }
+
+    let withdrawals = match withdrawals.is_empty() {
+        true => "None".to_string(),
+        false => withdrawals.iter().map(|(r, n)| format!("{}: {}", r, n)).collect::<Vec<_>>().join("\n"),
+    };
+
+    // Summarise the vote audit; `/admin vote-audit` has the details.
+    let authors = sql::get_submissions(week, challenge).await?.into_iter()
+        .map(|s| (s.message, s.author))
+        .collect::<HashMap<_, _>>();
+    let ballots = sql::get_ballots(week, challenge).await?;
+    let weighted = ballots.iter().filter(|b| b.weight != sql::DEFAULT_VOTE_WEIGHT).count();
+    let own = ballots.iter().filter(|b| authors.get(&b.message) == Some(&b.voter)).count();
+
+    let counts = sql::get_moderation_counts(week, challenge).await?;
+    Ok(embed
+        .field("Withdrawals", withdrawals, true)
+        .field("Disqualified", counts.disqualified.to_string(), true)
+        .field("Flagged", counts.flagged.to_string(), true)
+        .field("Vote audit", format!(
+            "{} vote{}, {} with a role weight, {} on the voter’s own entry",
+            ballots.len(),
+            if ballots.len() == 1 { "" } else { "s" },
+            weighted,
+            own,
+        ), false))
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", subcommands("week_advance", "week_announced", "week_deadline", "week_extend_deadline", "week_notes", "week_set", "week_show"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn week(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Move on to the next week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "advance", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_advance(
+    ctx: Context<'_>,
+    #[description = "Advance even though this week’s deadline hasn’t passed yet"] early: Option<bool>,
+) -> Res {
+    let deadline = scheduler::deadline(sql::current_week().await?).await?;
+    if clock::now() < deadline && !early.unwrap_or(false) {
+        return Err(format!(
+            "This week’s submissions are open until {}; set ‘early’ to advance anyway",
+            timestamp(deadline, TimestampStyle::DateTime),
+        ).into());
+    }
+
+    let week = scheduler::advance_week().await?;
+    info!("{} advanced to week {}", ctx.author().id, week);
+    ctx.say(format!(
+        "It is now week {}; submissions close {}",
+        week,
+        timestamp(scheduler::deadline(week).await?, TimestampStyle::DateTime),
+    )).await?;
+    Ok(())
+}
+
+/// Record the announcement of this week’s prompt, which shows it to non-admins.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "announced", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_announced(
+    ctx: Context<'_>,
+    #[description = "Which challenge was announced"] challenge: Challenge,
+    #[description = "Link to or ID of the announcement message"] message: String,
+    #[description = "The queue entry that was announced; defaults to the next one, unless this is a special week"] entry: Option<i64>,
+) -> Res {
+    let channel = parse_link_channel(&message);
+    let message = parse_message_id(&message)?;
+    ctx.say(record_announcement(ctx.serenity_context(), challenge, channel, message, entry).await?).await?;
+    Ok(())
+}
+
+/// Record the announcement of this week’s prompt, taking the prompt off the
+/// queue and opening a discussion thread if enabled. `channel` is where the
+/// announcement was posted, if known. Returns the reply to the admin.
+pub(crate) async fn record_announcement(
+    api: &dyn DiscordApi,
+    challenge: Challenge,
+    channel: Option<ser::ChannelId>,
+    message: ser::MessageId,
+    entry: Option<i64>,
+) -> Result<String, Error> {
+    let week = sql::current_week().await?;
+    if !scheduler::is_active(challenge).await? {
+        return Err(format!("The {} Challenge is on hiatus; end it with `/admin hiatus` first", challenge.name()).into());
+    }
+
+    // The announced prompt is taken off the queue, like the calendar assumes.
+    let special = sql::get_weeks(week, week).await?.first().is_some_and(|w| w.kind(challenge) == sql::Week::Special);
+    let entry = match entry {
+        Some(entry) => Some(entry),
+        None if special => None,
+        None => sql::get_prompts(challenge).await?.first().map(|&(id, _)| id),
+    };
+
+    let mut taken = None;
+    if let Some(entry) = entry {
+        let prompt = sql::get_prompt(entry).await?;
+        if prompt.challenge != challenge { return Err("That queue entry is for the other challenge".into()); }
+        sql::take_prompt(week, &prompt).await?;
+        taken = Some(prompt);
+    }
+
+    sql::set_week_message(week, challenge, WeekMessage::Announcement, message).await?;
+    let mut reply = format!("Recorded the announcement for the {} Challenge in week {}", challenge.name(), week);
+    if let Some(prompt) = taken {
+        reply.push_str(&format!("; took entry {} (‘{}’) off the queue", prompt.id, prompt.prompt));
+    }
+
+    if sql::get_setting::<bool>(Setting::DiscussionThreads).await?
+        && sql::get_week_message(week, challenge, WeekMessage::DiscussionThread).await?.is_none()
+    {
+        match channel {
+            Some(channel) => {
+                let thread = open_discussion_thread(api, week, challenge, channel, message).await?;
+                reply.push_str(&format!(" and opened {}", thread.mention()));
+            }
+            None => reply.push_str("; pass a link instead of an ID to open a discussion thread"),
+        }
+    }
+
+    Ok(reply)
+}
+
+/// Change the current week, e.g. to correct a mistake.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "set", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_set(
+    ctx: Context<'_>,
+    #[description = "The week to make the current one"] #[min = 0] week: u32,
+) -> Res {
+    let old = sql::current_week().await?;
+    sql::set_current_week(week as i64).await?;
+    scheduler::record_week_start().await?;
+    info!("{} changed the current week from {} to {}", ctx.author().id, old, week);
+    ctx.say(format!("Changed the current week from {} to {}", old, week)).await?;
+    Ok(())
+}
+
+/// Show the current week and its schedule.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "show", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_show(ctx: Context<'_>) -> Res {
+    let week = sql::current_week().await?;
+    let info = sql::weekinfo(Some(week as u64)).await?;
+    let deadline = scheduler::deadline(week).await?;
+    let mut embed = create_embed(&ctx)
+        .title(format!("Week {}", week))
+        .field("Started", timestamp(scheduler::week_start(week).await?, TimestampStyle::DateTime), true)
+        .field("Deadline", format!(
+            "{} ({})",
+            timestamp(deadline, TimestampStyle::DateTime),
+            timestamp(deadline, TimestampStyle::Relative),
+        ), true);
+
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        let mut value = format!(
+            "Prompt: {}\nEntries: {}",
+            info.prompt(challenge).unwrap_or("not announced yet"),
+            sql::count_submissions(week, challenge).await?,
+        );
+
+        if !scheduler::is_active(challenge).await? { value.push_str("\nOn hiatus"); }
+        embed = embed.field(format!("{} Challenge", challenge.name()), value, false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Start the discussion thread for a week on its announcement.
+async fn open_discussion_thread(
+    api: &dyn DiscordApi,
+    week: i64,
+    challenge: Challenge,
+    channel: ser::ChannelId,
+    announcement: ser::MessageId,
+) -> Result<ser::ChannelId, Error> {
+    let name = format!("Week {} discussion – {} Challenge", week, challenge.name());
+    let thread = api.create_thread(channel, announcement, name).await?;
+    sql::set_week_message(week, challenge, WeekMessage::DiscussionThread, ser::MessageId::new(thread.get())).await?;
+    info!("Opened discussion thread for {:?} in week {}", challenge, week);
+    Ok(thread)
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "deadline", subcommands("week_deadline_clear", "week_deadline_set"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_deadline(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Override the submission deadline of a week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "set", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_deadline_set(
+    ctx: Context<'_>,
+    #[description = "The week whose deadline to change"] week: u64,
+    #[description = "The new deadline, as ‘YYYY-MM-DD HH:MM’ in UTC"] deadline: String,
+) -> Res {
+    let deadline = parse_datetime(&deadline)?;
+    sql::set_week_deadline(week as i64, Some(deadline.timestamp())).await?;
+    ctx.say(format!("Set the deadline of week {} to {}", week, timestamp(deadline, TimestampStyle::DateTime))).await?;
+    Ok(())
+}
+
+/// Reset the submission deadline of a week to the default schedule.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "clear", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_deadline_clear(
+    ctx: Context<'_>,
+    #[description = "The week whose deadline to reset"] week: u64,
+) -> Res {
+    sql::set_week_deadline(week as i64, None).await?;
+    let deadline = scheduler::deadline(week as i64).await?;
+    ctx.say(format!("Reset the deadline of week {} to {}", week, timestamp(deadline, TimestampStyle::DateTime))).await?;
+    Ok(())
+}
+
+/// Give everyone more time to submit this week.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "extend-deadline", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_extend_deadline(
+    ctx: Context<'_>,
+    #[description = "How many hours to add"] #[min = 1] #[max = 168] hours: u32,
+    #[description = "Added to the notice, e.g. why the deadline was extended"] reason: Option<String>,
+) -> Res {
+    ctx.defer_ephemeral().await?;
+    let (deadline, problems) = scheduler::extend_deadline(ctx.serenity_context(), hours as i64, reason.as_deref().map(str::trim).filter(|r| !r.is_empty())).await?;
+    let mut reply = format!("Extended the deadline to {}, along with voting on last week’s entries", timestamp(deadline, TimestampStyle::DateTime));
+    if !problems.is_empty() {
+        reply += &format!("\nCouldn’t update {}; please check whether they show the old deadline", problems.join(", "));
+    }
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "notes", subcommands("week_notes_clear", "week_notes_set"), default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_notes(_ctx: Context<'_>) -> Res { unreachable!(); }
+
+/// Attach notes or extra rules to a week’s announcement.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "set", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_notes_set(
+    ctx: Context<'_>,
+    #[description = "Which challenge the notes are for"] challenge: Challenge,
+    #[description = "The notes"] notes: String,
+    #[description = "The week to set the notes for; defaults to the current week"] week: Option<u64>,
+) -> Res {
+    let week = match week {
+        Some(w) => w as i64,
+        None => sql::current_week().await?,
+    };
+
+    sql::set_week_notes(week, challenge, Some(notes.trim())).await?;
+    ctx.say(format!("Set notes for the {} Challenge in week {}", challenge.name(), week)).await?;
+    Ok(())
+}
 
+/// Remove the notes from a week’s announcement.
+#[poise::command(slash_command, ephemeral, guild_only, on_error = "handle_command_error", rename = "clear", default_member_permissions = "ADMINISTRATOR")]
+pub async fn week_notes_clear(
+    ctx: Context<'_>,
+    #[description = "Which challenge to clear the notes for"] challenge: Challenge,
+    #[description = "The week to clear the notes for; defaults to the current week"] week: Option<u64>,
+) -> Res {
+    let week = match week {
+        Some(w) => w as i64,
+        None => sql::current_week().await?,
+    };
 
+    sql::set_week_notes(week, challenge, None).await?;
+    ctx.say(format!("Cleared notes for the {} Challenge in week {}", challenge.name(), week)).await?;
     Ok(())
-}
\ No newline at end of file
+}
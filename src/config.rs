@@ -0,0 +1,108 @@
+//! What differs between deployments of the bot: its token, and the server,
+//! channels, and emoji it works with. This is read from a TOML file at
+//! startup (see `config.example.toml`), so the bot can be run against a
+//! test server without recompiling.
+
+use std::path::Path;
+use std::sync::OnceLock;
+use poise::serenity_prelude::{ChannelId, EmojiId, GuildId};
+use serde::Deserialize;
+use crate::Error;
+
+/// Environment variable that overrides the token in the config file, so
+/// the token doesn’t have to be stored alongside the rest.
+const TOKEN_VAR: &str = "GLYFI_TOKEN";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Bot token; may instead be set with `GLYFI_TOKEN`.
+    #[serde(default)]
+    pub token: String,
+
+    /// The server the bot runs on.
+    pub server: GuildId,
+
+    /// Channels that submissions are posted in.
+    pub glyph_submission_channel: ChannelId,
+    pub ambigram_submission_channel: ChannelId,
+
+    /// Custom emoji people react with to submit an entry.
+    pub submit_emoji: EmojiId,
+
+    /// Generate announcement images with `weekly_challenges.py` instead of
+    /// the built-in renderer. This is the default until deployments have
+    /// templates that match the existing images.
+    #[serde(default = "default_python_renderer")]
+    pub python_renderer: bool,
+}
+
+fn default_python_renderer() -> bool { true }
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+impl Config {
+    /// Read the config from a file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+        let mut config: Config = toml::from_str(&text)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+        if let Ok(token) = std::env::var(TOKEN_VAR) { config.token = token; }
+        if config.token.is_empty() {
+            return Err(format!("No bot token; set ‘token’ in {} or {}", path.display(), TOKEN_VAR).into());
+        }
+
+        Ok(config)
+    }
+}
+
+/// Make a config available to the rest of the bot. This must be called
+/// before anything that talks to Discord.
+pub fn init(config: Config) -> Result<&'static Config, Error> {
+    CONFIG.set(config).map_err(|_| "The config was already loaded")?;
+    Ok(get())
+}
+
+/// Get the config.
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("Config not loaded")
+}
+
+/// Get the config if it has been loaded yet.
+pub fn try_get() -> Option<&'static Config> {
+    CONFIG.get()
+}
+
+/// Use a fixed config for the tests.
+#[cfg(test)]
+pub fn init_for_tests() -> &'static Config {
+    CONFIG.get_or_init(|| Config {
+        token: "test-token".into(),
+        server: GuildId::new(1),
+        glyph_submission_channel: ChannelId::new(2),
+        ambigram_submission_channel: ChannelId::new(3),
+        submit_emoji: EmojiId::new(4),
+        python_renderer: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_is_valid() {
+        let config: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        assert_eq!(config.server, GuildId::new(1234567890123456789));
+        assert_eq!(config.submit_emoji, EmojiId::new(1234567890123456789));
+    }
+
+    #[test]
+    fn python_renderer_is_the_default() {
+        let text = include_str!("../config.example.toml").replace("python_renderer = true", "");
+        let config: Config = toml::from_str(&text).unwrap();
+        assert!(config.python_renderer);
+    }
+}
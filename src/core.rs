@@ -1,43 +1,250 @@
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU64;
 use std::str::FromStr;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use std::sync::atomic::AtomicBool;
-use poise::{CreateReply};
-use poise::serenity_prelude::{CacheHttp, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, UserId};
-use crate::{__glyfi_terminate_bot, Context, Error, Res};
-use crate::sql::__glyfi_fini_db;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use poise::{CreateReply, Modal};
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::{ButtonStyle, CreateAttachment, Colour, ComponentInteractionCollector, ComponentInteractionDataKind, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, InteractionId, MessageId, ModalInteraction, ModalInteractionCollector, UserId};
+use crate::{AppState, config, Context, dm, Error, preflight, Res, sql};
+use crate::discord::DiscordApi;
+use crate::logging::{self, Level};
+use crate::redact;
+use crate::sql::{Achievement, Challenge, Setting};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::task_tracker::TaskTrackerToken;
+use tokio_util::task::TaskTracker;
 
 /// Default colour to use for embeds.
 pub const DEFAULT_EMBED_COLOUR: Colour = Colour::from_rgb(176, 199, 107);
 
-/// Button ids.
-#[derive(Clone, Copy, Debug)]
-#[repr(u8)]
+/// How long to wait for in-flight work to finish when shutting down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Event handlers, commands, and jobs that are currently running.
+static IN_FLIGHT: Lazy<TaskTracker> = Lazy::new(TaskTracker::new);
+
+/// Cancelled once we start shutting down.
+static SHUTDOWN: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+
+/// Interactions we’ve already started processing. Discord may deliver an
+/// interaction again after a reconnect; it can only be responded to for
+/// 15 minutes, so there is no point in remembering it for longer.
+static SEEN_INTERACTIONS: Lazy<mini_moka::sync::Cache<InteractionId, ()>> = Lazy::new(|| {
+    mini_moka::sync::Cache::builder()
+        .max_capacity(10_000)
+        .time_to_live(Duration::from_secs(15 * 60))
+        .build()
+});
+
+/// Minimum delay between two messages in a bulk send.
+const BULK_SEND_DELAY: Duration = Duration::from_millis(1000);
+
+/// Up to this much random delay is added on top of [`BULK_SEND_DELAY`].
+const BULK_SEND_JITTER_MS: u64 = 500;
+
+/// If Discord told us to slow down, the time until which we should.
+static RATELIMITED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// How long buttons etc. on messages we send stay usable, in seconds.
+pub const COMPONENT_LIFETIME_SECS: i64 = 24 * 60 * 60;
+
+/// Version of the custom ID format; see [`InteractionID::encode()`]. Bump
+/// this when the payload of an existing kind changes, so that components
+/// on old messages are rejected instead of misread.
+const INTERACTION_ID_VERSION: u32 = 1;
+
+/// Custom ID of the opt-out button in DMs sent before IDs were versioned.
+const LEGACY_OPT_OUT_DMS_ID: &str = "4";
+
+/// What a button or select menu does, along with the data it needs.
+#[derive(Clone, Debug, PartialEq)]
 pub enum InteractionID {
-    ConfirmAnnouncement = 0,
-    CancelPrompt = 1,
+    /// Approve a queued prompt; `mtime` is that of the announcement image
+    /// the preview showed.
+    ConfirmAnnouncement { challenge: Challenge, mtime: u64, prompt: i64 },
+    CancelPrompt { prompt: i64 },
+    DeleteUserData { user: UserId },
+    RegeneratePrompt { prompt: i64 },
+    OptOutDms,
+
+    /// Flip the pages shown in response to a command invocation.
+    Paginate { invocation: u64, action: PageAction },
+
+    /// One of the menus of a picker shown in response to a command invocation.
+    Pick { invocation: u64, menu: usize },
+
+    Ballot { ballot: i64, action: BallotAction },
+
+    /// A winner picking the next prompt, or an admin reviewing their pick.
+    PromptPick { pick: i64, action: PickAction },
+
+    /// Vote for one of the prompts in a poll.
+    PollVote { poll: i64, option: i64 },
+
+    /// Take back a vote from a vote receipt.
+    RemoveVote { message: MessageId },
 }
 
-impl InteractionID {
-    pub fn raw(self) -> u8 {
-        self as _
+/// Buttons of a paginated message.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PageAction {
+    First,
+    Prev,
+    Jump,
+    Next,
+    Last,
+}
+
+/// Components of a ballot.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BallotAction {
+    /// One of the menus to pick entries from.
+    Choose { menu: i64 },
+
+    /// Show another page of menus, if there are too many entries for one.
+    Page { page: i64 },
+    Cast,
+}
+
+/// Components of an invitation to pick the next prompt, and of the
+/// message that asks admins to review the pick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PickAction {
+    Choose,
+    Suggest,
+    Approve,
+    Reject,
+}
+
+impl PageAction {
+    fn name(self) -> &'static str {
+        match self {
+            PageAction::First => "first",
+            PageAction::Prev => "prev",
+            PageAction::Jump => "jump",
+            PageAction::Next => "next",
+            PageAction::Last => "last",
+        }
+    }
+}
+
+impl FromStr for PageAction {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [PageAction::First, PageAction::Prev, PageAction::Jump, PageAction::Next, PageAction::Last]
+            .into_iter()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| format!("Unknown page action ‘{}’", s).into())
+    }
+}
+
+impl PickAction {
+    fn name(self) -> &'static str {
+        match self {
+            PickAction::Choose => "choose",
+            PickAction::Suggest => "suggest",
+            PickAction::Approve => "approve",
+            PickAction::Reject => "reject",
+        }
     }
 }
 
-impl FromStr for InteractionID {
+impl FromStr for PickAction {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [PickAction::Choose, PickAction::Suggest, PickAction::Approve, PickAction::Reject]
+            .into_iter()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| format!("Unknown pick action ‘{}’", s).into())
+    }
+}
+
+impl InteractionID {
+    /// Encode this as a custom ID, i.e. `v1:` followed by the kind and its
+    /// fields, separated by colons.
+    pub fn encode(&self) -> String {
+        use InteractionID::*;
+        let fields = match self {
+            ConfirmAnnouncement { challenge, mtime, prompt } => format!("confirm:{}:{}:{}", challenge.raw(), mtime, prompt),
+            CancelPrompt { prompt } => format!("cancel:{}", prompt),
+            DeleteUserData { user } => format!("delete-data:{}", user),
+            RegeneratePrompt { prompt } => format!("regenerate:{}", prompt),
+            OptOutDms => "opt-out-dms".to_string(),
+            Paginate { invocation, action } => format!("page:{}:{}", invocation, action.name()),
+            Pick { invocation, menu } => format!("pick:{}:{}", invocation, menu),
+            Ballot { ballot, action: BallotAction::Choose { menu } } => format!("ballot:{}:{}", ballot, menu),
+            Ballot { ballot, action: BallotAction::Page { page } } => format!("ballot:{}:page:{}", ballot, page),
+            Ballot { ballot, action: BallotAction::Cast } => format!("ballot:{}:cast", ballot),
+            PromptPick { pick, action } => format!("prompt-pick:{}:{}", pick, action.name()),
+            PollVote { poll, option } => format!("poll:{}:{}", poll, option),
+            RemoveVote { message } => format!("unvote:{}", message),
+        };
+
+        format!("v{}:{}", INTERACTION_ID_VERSION, fields)
+    }
+
+    /// Parse a custom ID made by [`encode()`]. IDs from before it existed,
+    /// or from another version, are rejected with a message that can be
+    /// shown to the user, except for the opt-out button in old DMs, which
+    /// people can’t get again.
+    ///
+    /// [`encode()`]: InteractionID::encode
+    pub fn decode(s: &str) -> Result<Self, Error> {
         use InteractionID::*;
-        match s.split(':').next() {
-            Some("0") => Ok(ConfirmAnnouncement),
-            Some("1") => Ok(CancelPrompt),
-            id => Err(format!("Unknown interaction ID '{:?}'. Did you forget to update from_str()?", id).into())
+        fn field<T: FromStr>(parts: &mut std::str::Split<'_, char>) -> Result<T, Error> {
+            parts.next()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| "Invalid interaction ID".into())
+        }
+
+        if s == LEGACY_OPT_OUT_DMS_ID { return Ok(OptOutDms); }
+        let mut parts = s.split(':');
+        if parts.next() != Some(&format!("v{}", INTERACTION_ID_VERSION)) {
+            return Err("This no longer works since the bot was updated. Please run the command again.".into());
         }
+
+        let id = match parts.next().unwrap_or_default() {
+            "confirm" => ConfirmAnnouncement {
+                challenge: field(&mut parts)?,
+                mtime: field(&mut parts)?,
+                prompt: field(&mut parts)?,
+            },
+            "cancel" => CancelPrompt { prompt: field(&mut parts)? },
+            "delete-data" => DeleteUserData { user: UserId::from(field::<NonZeroU64>(&mut parts)?) },
+            "regenerate" => RegeneratePrompt { prompt: field(&mut parts)? },
+            "opt-out-dms" => OptOutDms,
+            "page" => Paginate { invocation: field(&mut parts)?, action: field(&mut parts)? },
+            "pick" => Pick { invocation: field(&mut parts)?, menu: field(&mut parts)? },
+            "ballot" => {
+                let ballot = field(&mut parts)?;
+                let action = match parts.next() {
+                    Some("cast") => BallotAction::Cast,
+                    Some("page") => BallotAction::Page { page: field(&mut parts)? },
+                    Some(menu) => BallotAction::Choose { menu: menu.parse().map_err(|_| "Invalid interaction ID")? },
+                    None => return Err("Invalid interaction ID".into()),
+                };
+
+                Ballot { ballot, action }
+            }
+            "prompt-pick" => PromptPick { pick: field(&mut parts)?, action: field(&mut parts)? },
+            "poll" => PollVote { poll: field(&mut parts)?, option: field(&mut parts)? },
+            "unvote" => RemoveVote { message: MessageId::from(field::<NonZeroU64>(&mut parts)?) },
+            kind => return Err(format!("Unknown interaction ‘{}’", kind).into()),
+        };
+
+        if parts.next().is_some() { return Err("Invalid interaction ID".into()); }
+        Ok(id)
     }
 }
 
-/// Logging macros. These macros log an informational or error
-/// message. Depending on the program stage, the message will
-/// be displayed in the terminal or sent to Discord; The `sync`
-/// variants always log to the terminal.
+/// Logging macros. These macros log a message at a certain level; see
+/// [`crate::logging`] for where it ends up. The `sync` variants can be
+/// used outside of async code.
 #[macro_export]
 macro_rules! info {
     ($arg:expr) => { $crate::core::__glyfi_log_internal(&*($arg)).await };
@@ -50,6 +257,18 @@ macro_rules! info_sync {
     ($fmt:literal $(,$arg:expr)*) => { $crate::core::__glyfi_log_internal_sync(format!($fmt $(,$arg)*).as_str()) };
 }
 
+#[macro_export]
+macro_rules! warn {
+    ($arg:expr) => { $crate::core::__glyfi_log_internal_warning(&*($arg)).await };
+    ($fmt:literal $(,$arg:expr)*) => { $crate::core::__glyfi_log_internal_warning(format!($fmt $(,$arg)*).as_str()).await };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($arg:expr) => { $crate::core::__glyfi_log_internal_debug(&*($arg)).await };
+    ($fmt:literal $(,$arg:expr)*) => { $crate::core::__glyfi_log_internal_debug(format!($fmt $(,$arg)*).as_str()).await };
+}
+
 #[macro_export]
 macro_rules! err {
     ($arg:expr) => { $crate::core::__glyfi_log_internal_error(&*($arg)).await };
@@ -63,13 +282,106 @@ macro_rules! err_sync {
 }
 
 /// Logging.
-pub async fn __glyfi_log_internal_error(e: &str) { eprintln!("[Error]: {}", e); }
+pub async fn __glyfi_log_internal_error(e: &str) { logging::log(Level::Error, e); }
+
+pub async fn __glyfi_log_internal_warning(e: &str) { logging::log(Level::Warn, e); }
+
+pub async fn __glyfi_log_internal(e: &str) { logging::log(Level::Info, e); }
 
-pub async fn __glyfi_log_internal(e: &str) { eprintln!("[Info]: {}", e); }
+pub async fn __glyfi_log_internal_debug(e: &str) { logging::log(Level::Debug, e); }
 
-pub fn __glyfi_log_internal_error_sync(e: &str) { eprintln!("[Error]: {}", e); }
+pub fn __glyfi_log_internal_error_sync(e: &str) { logging::log(Level::Error, e); }
 
-pub fn __glyfi_log_internal_sync(e: &str) { eprintln!("[Info]: {}", e); }
+pub fn __glyfi_log_internal_sync(e: &str) { logging::log(Level::Info, e); }
+
+/// Parse a colour given as ‘#RRGGBB’ or ‘RRGGBB’.
+pub fn parse_colour(s: &str) -> Result<Colour, Error> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid colour ‘{}’; use the format #RRGGBB", s.trim()).into());
+    }
+
+    Ok(Colour::new(u32::from_str_radix(hex, 16)?))
+}
+
+/// How a Discord timestamp is displayed; each user sees it in their own
+/// time zone and locale.
+#[derive(Copy, Clone, Debug)]
+pub enum TimestampStyle {
+    /// E.g. ‘20 April 2021 16:20’.
+    DateTime,
+
+    /// E.g. ‘in 2 days’.
+    Relative,
+}
+
+/// Format a time as Discord timestamp markup.
+pub fn timestamp(time: DateTime<Utc>, style: TimestampStyle) -> String {
+    let style = match style {
+        TimestampStyle::DateTime => 'F',
+        TimestampStyle::Relative => 'R',
+    };
+
+    format!("<t:{}:{}>", time.timestamp(), style)
+}
+
+/// Point every Discord timestamp for `old` in some text, e.g. a channel
+/// topic, at `new` instead, keeping its style. Returns `None` if the text
+/// doesn’t mention `old`.
+pub fn replace_timestamp(text: &str, old: DateTime<Utc>, new: DateTime<Utc>) -> Option<String> {
+    let (old, new) = (format!("<t:{}", old.timestamp()), format!("<t:{}", new.timestamp()));
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut found = false;
+    while let Some(i) = rest.find(&old) {
+        let after = &rest[i + old.len()..];
+        out.push_str(&rest[..i]);
+
+        // Don’t match a longer timestamp that merely starts with this one.
+        if after.starts_with('>') || after.starts_with(':') {
+            out.push_str(&new);
+            found = true;
+        } else {
+            out.push_str(&old);
+        }
+
+        rest = after;
+    }
+
+    out.push_str(rest);
+    found.then_some(out)
+}
+
+/// Format a number with the digit group separator of a Discord locale,
+/// e.g. ‘12,345’ in English and ‘12.345’ in German.
+pub fn format_number(n: i64, locale: Option<&str>) -> String {
+    let sep = match locale.unwrap_or("en-US").split('-').next().unwrap_or("en") {
+        "bg" | "cs" | "fi" | "fr" | "hu" | "lt" | "no" | "pl" | "ru" | "sv" | "uk" => "\u{202F}",
+        "da" | "de" | "el" | "es" | "hr" | "id" | "it" | "nl" | "pt" | "ro" | "tr" | "vi" => ".",
+        _ => ",",
+    };
+
+    let digits = n.unsigned_abs().to_string();
+    let mut out = String::with_capacity(digits.len() * 2);
+    if n < 0 { out.push('-'); }
+    for (i, c) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i).is_multiple_of(3) { out.push_str(sep); }
+        out.push(c);
+    }
+
+    out
+}
+
+/// Format a duration in seconds roughly, e.g. ‘2d 3h’, ‘5h 12m’, or ‘40m’.
+pub fn format_duration(secs: i64) -> String {
+    let (days, hours, mins) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    match (days, hours) {
+        (0, 0) if mins == 0 => "<1m".into(),
+        (0, 0) => format!("{}m", mins),
+        (0, _) => format!("{}h {}m", hours, mins),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
 
 /// Create an embed with some default settings applied to id.
 pub fn create_embed(ctx: &Context<'_>) -> CreateEmbed {
@@ -89,6 +401,49 @@ pub fn create_embed(ctx: &Context<'_>) -> CreateEmbed {
     return embed;
 }
 
+/// Parse a date and time given by a user. The time is optional
+/// and defaults to midnight. Everything is in UTC.
+pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, Error> {
+    let s = s.trim();
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") { return Ok(dt.and_utc()); }
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") { return Ok(d.and_hms_opt(0, 0, 0).unwrap().and_utc()); }
+    Err(format!("Invalid date ‘{}’. Expected ‘YYYY-MM-DD HH:MM’ (UTC)", s).into())
+}
+
+/// Parse a message ID given by a user, either as-is or as a message link.
+pub fn parse_message_id(s: &str) -> Result<MessageId, Error> {
+    s.trim()
+        .rsplit('/')
+        .next()
+        .and_then(|id| id.parse::<u64>().ok())
+        .filter(|id| *id != 0)
+        .map(MessageId::new)
+        .ok_or_else(|| format!("Invalid message ID or link ‘{}’", s).into())
+}
+
+/// Get the channel of a message from a link to it. A bare message ID
+/// doesn’t say which channel the message is in.
+pub fn parse_link_channel(s: &str) -> Option<ser::ChannelId> {
+    let mut parts = s.trim().rsplit('/');
+    parts.next()?;
+    let channel = parts.next()?.parse::<u64>().ok().filter(|id| *id != 0)?;
+    parts.next()?;
+    Some(ser::ChannelId::new(channel))
+}
+
+/// Wait for the user to submit the modal with the given id; returns
+/// `None` if they don’t do so in time. The caller is responsible for
+/// responding to the returned interaction.
+pub async fn await_modal<M: Modal>(ctx: &ser::Context, modal_id: String) -> Result<Option<(ModalInteraction, M)>, Error> {
+    let Some(m) = ModalInteractionCollector::new(&ctx.shard)
+        .filter(move |m| m.data.custom_id == modal_id)
+        .timeout(Duration::from_secs(15 * 60))
+        .await else { return Ok(None); };
+
+    let data = M::parse(m.data.clone())?;
+    Ok(Some((m, data)))
+}
+
 /// Get the mtime of a file.
 pub fn file_mtime(path: &str) -> Result<u64, Error> {
     Ok(std::fs::metadata(path)?
@@ -104,19 +459,26 @@ pub async fn handle_command_error(e: poise::FrameworkError<'_, crate::Data, Erro
         return;
     };
 
+    // A check that failed without an error has already dealt with the
+    // interaction, e.g. because it was a duplicate.
+    if let poise::FrameworkError::CommandCheckFailed { error: None, .. } = e { return; }
+
     match ctx {
         Context::Application(a) => {
             // Log the entire command string so we have a record of it.
-            err!("In invocation of command: {}", a.invocation_string());
+            err!("In invocation of command: {}", redact::invocation(ctx));
 
             // Get the nested error, if possible.
+            let failed = matches!(e, poise::FrameworkError::Command { .. });
             let command_error = match e {
                 poise::FrameworkError::Command { error, .. } => error.to_string(),
-                _ => "".to_string()
+                poise::FrameworkError::CommandCheckFailed { error: Some(error), .. } => error.to_string(),
+                e => e.to_string(),
             };
 
             // Log it in case sending it fails.
             err!(command_error);
+            if failed { note_command_failure(a, &command_error).await; }
 
             // Send a reply to the user, informing them of the error; if that in turn
             // causes an error, there is nothing we can do, so just log and ignore it.
@@ -124,7 +486,7 @@ pub async fn handle_command_error(e: poise::FrameworkError<'_, crate::Data, Erro
                 a,
                 CreateReply::default()
                     .ephemeral(true)
-                    .content(safe_truncate(format!("Error: {}", command_error), 2000)),
+                    .content(safe_truncate(format!("Error: {}", command_error), MESSAGE_MAX_CONTENT)),
             ).await {
                 err!(e.to_string());
             }
@@ -135,30 +497,480 @@ pub async fn handle_command_error(e: poise::FrameworkError<'_, crate::Data, Erro
     }
 }
 
+/// Window in which failures of a command are counted.
+const FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Recent failures of a command.
+#[derive(Default)]
+struct CommandFailures {
+    /// When each failure happened, and the error.
+    recent: VecDeque<(Instant, String)>,
+
+    /// When we last told the admins about this command.
+    alerted: Option<Instant>,
+}
+
+/// Failures of each command, by name.
+static COMMAND_FAILURES: Lazy<Mutex<HashMap<String, CommandFailures>>> = Lazy::new(Default::default);
+
+/// Record that a command failed. If it failed more than `threshold` times
+/// within [`FAILURE_WINDOW`], and we haven’t said so in that time, returns
+/// a summary of the errors, most common first.
+fn record_command_failure(command: &str, error: &str, threshold: usize, now: Instant) -> Option<String> {
+    let mut failures = COMMAND_FAILURES.lock().unwrap();
+    let f = failures.entry(command.to_string()).or_default();
+    while f.recent.front().is_some_and(|(t, _)| now.duration_since(*t) > FAILURE_WINDOW) { f.recent.pop_front(); }
+    f.recent.push_back((now, error.to_string()));
+
+    if threshold == 0 || f.recent.len() <= threshold { return None; }
+    if f.alerted.is_some_and(|t| now.duration_since(t) < FAILURE_WINDOW) { return None; }
+    f.alerted = Some(now);
+
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for (_, e) in &f.recent {
+        match counts.iter_mut().find(|(m, _)| *m == e) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((e, 1)),
+        }
+    }
+
+    counts.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    let errors = counts.iter().map(|(e, n)| format!("- {}× {}", n, e)).collect::<Vec<_>>().join("\n");
+    Some(format!(
+        "/{} failed {} times in the last {} minutes:\n{}",
+        command,
+        f.recent.len(),
+        FAILURE_WINDOW.as_secs() / 60,
+        errors,
+    ))
+}
+
+/// Tell the admins if a command keeps failing, e.g. because something it
+/// depends on is broken. This goes to the admin channel, or to the bot
+/// owners if there is none.
+///
+/// Errors caused by users, such as invalid arguments, count as well, so
+/// the threshold shouldn’t be too low.
+async fn note_command_failure(ctx: crate::ApplicationContext<'_>, error: &str) {
+    let threshold = match sql::get_setting::<u64>(Setting::CommandFailureAlerts).await {
+        Ok(threshold) => threshold as usize,
+        Err(e) => {
+            err!("Error getting command failure threshold: {}", e);
+            return;
+        }
+    };
+
+    let command = &ctx.command().qualified_name;
+    let Some(summary) = record_command_failure(command, &redact::scrub(error), threshold, Instant::now()) else { return; };
+    let summary = safe_truncate(summary, 1900);
+    match preflight::admin_channel().await {
+        Ok(Some(_)) => return preflight::alert_admins(ctx.serenity_context(), &summary).await,
+        Ok(None) => {}
+        Err(e) => err!("Error getting admin channel: {}", e),
+    }
+
+    err!("{}", summary);
+    for &owner in &ctx.framework.options().owners {
+        let message = CreateMessage::new().content(format!("⚠️ {}", summary));
+        if let Err(e) = dm::send(ctx.serenity_context(), owner, dm::DmKind::Essential, message).await {
+            err!("Could not tell owner {} about failing command: {}", owner, e);
+        }
+    }
+}
+
+/// Check whether the user running a command is an administrator.
+pub async fn is_admin(ctx: &Context<'_>) -> bool {
+    ctx.author_member().await
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.administrator())
+}
+
+/// Check whether the user running a command may see moderation details,
+/// i.e. is an administrator or has the moderator role.
+pub async fn is_mod(ctx: &Context<'_>) -> Result<bool, Error> {
+    let Some(member) = ctx.author_member().await else { return Ok(false); };
+    if member.permissions.is_some_and(|p| p.administrator()) { return Ok(true); }
+
+    let role = sql::get_setting::<u64>(Setting::ModRole).await?;
+    Ok(role != 0 && member.roles.contains(&ser::RoleId::new(role)))
+}
+
 pub async fn log_command(ctx: Context<'_>) {
     info!(
         "{} invoked command {}",
-        ctx.author().name,
-        ctx.invocation_string()
+        ctx.author().id,
+        redact::invocation(ctx)
     );
+
+    // Opportunistically refresh the user’s cached name.
+    if let Err(e) = crate::sql::update_user_cache(ctx.author()).await {
+        err!("Error updating cached name of {}: {}", ctx.author().id, e);
+    }
+}
+
+/// Start tracking the components of a message we sent, so they are
+/// rejected once they expire, and removed from the message if we can
+/// still edit it then.
+pub async fn register_components(ids: &[String], lifetime_secs: i64, message: &ser::Message) -> Res {
+    for id in ids {
+        crate::sql::register_component(id, lifetime_secs, message.channel_id, message.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the emoji to show for an achievement: the custom emoji admins
+/// configured for it if we can use it, and its standard emoji otherwise.
+pub async fn badge_emoji(cache: &ser::Cache, achievement: Achievement) -> String {
+    let id = match crate::sql::get_badge_emoji(achievement).await {
+        Ok(Some(id)) => id,
+        Ok(None) => return achievement.emoji().to_string(),
+        Err(e) => {
+            err!("Error getting badge emoji for {:?}: {}", achievement, e);
+            return achievement.emoji().to_string();
+        }
+    };
+
+    // The emoji may have been deleted, or be unavailable because
+    // the server lost a boost level.
+    cache.guild(config::get().server)
+        .and_then(|g| g.emojis.get(&id).filter(|e| e.available).map(|e| e.to_string()))
+        .unwrap_or_else(|| achievement.emoji().to_string())
 }
 
 /// Report an error resulting from a user misusing a command/function.
-pub async fn report_user_error(ctx: impl CacheHttp, user: UserId, s: &str) {
-    info!("User Error ({}): {}", user, s);
-
-    // Helper for error handling.
-    async fn aux(ctx: &impl CacheHttp, user: UserId, s: &str) -> Res {
-        // Attempt to DM the user about this.
-        let ch = user.create_dm_channel(&ctx).await?;
-        ch.send_message(&ctx, CreateMessage::new().content(format!("Error: {}", s))).await?;
+pub async fn report_user_error(api: &dyn DiscordApi, user: UserId, s: &str) {
+    info!("User Error ({}): {}", user, redact::dm(s));
+
+    // Attempt to DM the user about this.
+    let message = CreateMessage::new().content(safe_truncate(format!("Error: {}", s), MESSAGE_MAX_CONTENT));
+    if let Err(e) = crate::dm::send(api, user, crate::dm::DmKind::Essential, message).await {
+        err!("Error trying to notify user {} about an error: {}", user, e);
+    }
+}
+
+/// Discord’s limit on the text of a message, in characters.
+pub(crate) const MESSAGE_MAX_CONTENT: usize = 2000;
+
+/// Discord’s limits on embeds, in characters.
+const EMBED_MAX_DESCRIPTION: usize = 4096;
+const EMBED_MAX_TOTAL: usize = 6000;
+const MESSAGE_MAX_EMBEDS: usize = 10;
+
+/// Room left for the footer set by [`create_embed()`]; guild names are at
+/// most 100 characters long.
+const EMBED_FOOTER_RESERVE: usize = 100;
+
+/// Builds embeds while keeping track of Discord’s limits, starting a new
+/// embed (and message) whenever the current one would be too large.
+pub struct EmbedBudget {
+    /// Every embed starts out as a copy of this.
+    base: CreateEmbed,
+    base_len: usize,
+
+    /// Finished embeds and their lengths.
+    done: Vec<(CreateEmbed, usize)>,
+
+    description: String,
+    len: usize,
+}
+
+impl EmbedBudget {
+    /// Create a budget whose embeds all start out as `base`; `base_len` is
+    /// the number of characters in its title and author.
+    pub fn new(base: CreateEmbed, base_len: usize) -> Self {
+        let base_len = base_len + EMBED_FOOTER_RESERVE;
+        Self {
+            base,
+            base_len,
+            done: vec![],
+            description: String::new(),
+            len: base_len,
+        }
+    }
+
+    /// Finish the current embed and start a new one.
+    fn flush(&mut self) {
+        if self.description.is_empty() { return; }
+        let embed = self.base.clone().description(std::mem::take(&mut self.description));
+        self.done.push((embed, self.len));
+        self.len = self.base_len;
+    }
+
+    /// Start a new embed, e.g. to put a fixed number of entries on each page.
+    pub fn break_page(&mut self) {
+        self.flush();
+    }
+
+    /// Add a line to the description.
+    pub fn line(&mut self, line: impl Into<String>) {
+        let line = safe_truncate(line.into(), EMBED_MAX_DESCRIPTION - 1);
+        let n = line.chars().count() + 1;
+        if self.description.chars().count() + n > EMBED_MAX_DESCRIPTION || self.len + n > EMBED_MAX_TOTAL { self.flush(); }
+        if !self.description.is_empty() { self.description.push('\n'); }
+        self.description.push_str(&line);
+        self.len += n;
+    }
+
+    /// Get the embeds, one per page, for [`paginate()`].
+    pub fn pages(mut self) -> Vec<CreateEmbed> {
+        self.flush();
+        if self.done.is_empty() { return vec![self.base]; }
+        self.done.into_iter().map(|(e, _)| e).collect()
+    }
+
+    /// Get the embeds, grouped into messages.
+    pub fn finish(mut self) -> Vec<Vec<CreateEmbed>> {
+        self.flush();
+        if self.done.is_empty() { return vec![vec![self.base]]; }
+
+        let mut messages: Vec<Vec<CreateEmbed>> = vec![];
+        let mut total = EMBED_MAX_TOTAL;
+        for (embed, len) in self.done {
+            match messages.last_mut() {
+                Some(m) if m.len() < MESSAGE_MAX_EMBEDS && total + len <= EMBED_MAX_TOTAL => {
+                    m.push(embed);
+                    total += len;
+                }
+
+                _ => {
+                    messages.push(vec![embed]);
+                    total = len;
+                }
+            }
+        }
+
+        messages
+    }
+
+    /// Send the embeds in reply to a command.
+    pub async fn send(self, ctx: &Context<'_>) -> Res {
+        for embeds in self.finish() {
+            ctx.send(CreateReply { embeds, ..Default::default() }).await?;
+        }
+
         Ok(())
     }
+}
+
+/// How long the buttons of a paginated message work, in seconds. This is
+/// a bit less than the 15 minutes an interaction token is valid for, so
+/// we can still edit the reply to remove them afterwards.
+const PAGINATION_LIFETIME_SECS: u64 = 14 * 60;
+
+/// Modal for jumping to a page.
+#[derive(Debug, poise::Modal)]
+#[name = "Go to Page"]
+struct PageModal {
+    #[name = "Page"]
+    #[max_length = 5]
+    page: String,
+}
+
+/// Buttons for flipping through pages.
+fn page_buttons(invocation: u64, page: usize, count: usize) -> Vec<CreateActionRow> {
+    let button = |action: PageAction, label: String, disabled: bool| CreateButton::new(InteractionID::Paginate { invocation, action }.encode())
+        .label(label)
+        .style(ButtonStyle::Secondary)
+        .disabled(disabled);
+
+    vec![CreateActionRow::Buttons(vec![
+        button(PageAction::First, "⏮".into(), page == 0),
+        button(PageAction::Prev, "◀".into(), page == 0),
+        button(PageAction::Jump, format!("{} / {}", page + 1, count), false),
+        button(PageAction::Next, "▶".into(), page + 1 == count),
+        button(PageAction::Last, "⏭".into(), page + 1 == count),
+    ])]
+}
+
+/// Send embeds as pages that the invoker can flip through with buttons.
+///
+/// The buttons go through [`InteractionID::Paginate`] and are handled
+/// here rather than in the event handler; once they expire, they are
+/// removed from the message.
+pub async fn paginate(ctx: &Context<'_>, pages: Vec<CreateEmbed>) -> Res {
+    paginate_with_files(ctx, pages.into_iter().map(|p| (p, None)).collect()).await
+}
+
+/// Same as [`paginate()`], but each page can come with a file, e.g. an
+/// image that its embed shows via `attachment://`.
+pub async fn paginate_with_files(ctx: &Context<'_>, pages: Vec<(CreateEmbed, Option<CreateAttachment>)>) -> Res {
+    let count = pages.len();
+    if count <= 1 {
+        let (embeds, files): (Vec<_>, Vec<_>) = pages.into_iter().unzip();
+        ctx.send(CreateReply { embeds, attachments: files.into_iter().flatten().collect(), ..Default::default() }).await?;
+        return Ok(());
+    }
+
+    // Contents of a page when flipping to it.
+    let update = |page: usize| {
+        let (embed, file) = &pages[page];
+        CreateInteractionResponseMessage::new()
+            .embed(embed.clone())
+            .files(file.clone())
+    };
+
+    let invocation = ctx.id();
+    let mut page = 0;
+    let mut first = CreateReply::default()
+        .embed(pages[0].0.clone())
+        .components(page_buttons(invocation, page, count));
+    if let Some(file) = &pages[0].1 { first = first.attachment(file.clone()); }
+    let reply = ctx.send(first).await?;
+
+    // Register the buttons so they are rejected once we stop listening.
+    let ids = [PageAction::First, PageAction::Prev, PageAction::Jump, PageAction::Next, PageAction::Last]
+        .map(|action| InteractionID::Paginate { invocation, action }.encode());
+    register_components(&ids, PAGINATION_LIFETIME_SECS as i64, &*reply.message().await?).await?;
+
+    let end = std::time::Instant::now() + Duration::from_secs(PAGINATION_LIFETIME_SECS);
+    loop {
+        let Some(i) = ComponentInteractionCollector::new(&ctx.serenity_context().shard)
+            .filter(move |i| matches!(
+                InteractionID::decode(&i.data.custom_id),
+                Ok(InteractionID::Paginate { invocation: id, .. }) if id == invocation
+            ))
+            .timeout(end.saturating_duration_since(std::time::Instant::now()))
+            .await else { break; };
+
+        // Only the invoker gets to flip pages.
+        if i.user.id != ctx.author().id {
+            i.create_response(ctx, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Only the person who ran this command can flip pages.")
+                    .ephemeral(true)
+            )).await?;
+            continue;
+        }
+
+        // Figure out where to go.
+        let Ok(InteractionID::Paginate { action, .. }) = InteractionID::decode(&i.data.custom_id) else { continue; };
+        match action {
+            PageAction::First => page = 0,
+            PageAction::Prev => page = page.saturating_sub(1),
+            PageAction::Next => page = (page + 1).min(count - 1),
+            PageAction::Last => page = count - 1,
+            PageAction::Jump => {
+                let modal_id = format!("{}:modal", i.id);
+                i.create_response(ctx, PageModal::create(None, modal_id.clone())).await?;
+                let Some((m, data)) = await_modal::<PageModal>(ctx.serenity_context(), modal_id).await? else { continue; };
+                match data.page.trim().parse::<usize>() {
+                    Ok(p) if (1..=count).contains(&p) => page = p - 1,
+                    _ => {
+                        m.create_response(ctx, CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("Page must be a number between 1 and {}", count))
+                                .ephemeral(true)
+                        )).await?;
+                        continue;
+                    }
+                }
+
+                m.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+                    update(page).components(page_buttons(invocation, page, count))
+                )).await?;
+                continue;
+            }
+        }
+
+        i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+            update(page).components(page_buttons(invocation, page, count))
+        )).await?;
+    }
+
+    // Remove the buttons once they no longer work.
+    reply.edit(*ctx, CreateReply::default().embed(pages[page].0.clone()).components(vec![])).await?;
+    Ok(())
+}
+
+/// How long a picker waits for a choice, in seconds. Like pagination,
+/// this stays below the lifetime of the interaction token.
+const PICKER_LIFETIME_SECS: u64 = 14 * 60;
+
+/// Discord allows this many options per select menu, and this many menus per message.
+const PICKER_OPTIONS_PER_MENU: usize = 25;
+const PICKER_MAX_MENUS: usize = 5;
+
+/// An option in a picker.
+pub struct PickerOption {
+    pub label: String,
+    pub value: String,
+    pub description: Option<String>,
+}
+
+impl PickerOption {
+    pub fn new(label: impl Into<String>, value: impl ToString) -> Self {
+        Self { label: label.into(), value: value.to_string(), description: None }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Let the invoker pick one of several options from select menus.
+///
+/// Returns the value of the chosen option, or `None` if they didn’t
+/// choose one in time. The menus go through [`InteractionID::Pick`] and
+/// are handled here rather than in the event handler.
+pub async fn pick(ctx: &Context<'_>, prompt: &str, options: Vec<PickerOption>) -> Result<Option<String>, Error> {
+    if options.is_empty() { return Err("There is nothing to choose from".into()); }
+    if options.len() > PICKER_OPTIONS_PER_MENU * PICKER_MAX_MENUS {
+        return Err("There are too many options to choose from; please specify one directly".into());
+    }
+
+    // Build the menus; labels etc. are limited to 100 characters.
+    let invocation = ctx.id();
+    let mut labels = HashMap::new();
+    let mut menus = vec![];
+    let mut ids = vec![];
+    for (n, chunk) in options.chunks(PICKER_OPTIONS_PER_MENU).enumerate() {
+        let id = InteractionID::Pick { invocation, menu: n }.encode();
+        ids.push(id.clone());
+        let placeholder = if options.len() > PICKER_OPTIONS_PER_MENU {
+            format!("Options {}–{}", n * PICKER_OPTIONS_PER_MENU + 1, n * PICKER_OPTIONS_PER_MENU + chunk.len())
+        } else {
+            "Choose…".to_string()
+        };
+
+        let chunk = chunk.iter().map(|o| {
+            labels.insert(o.value.clone(), o.label.clone());
+            let mut opt = CreateSelectMenuOption::new(safe_truncate(o.label.clone(), 100), safe_truncate(o.value.clone(), 100));
+            if let Some(d) = &o.description { opt = opt.description(safe_truncate(d.clone(), 100)); }
+            opt
+        }).collect();
+
+        menus.push(CreateActionRow::SelectMenu(CreateSelectMenu::new(id, CreateSelectMenuKind::String { options: chunk })
+            .placeholder(placeholder)
+        ));
+    }
+
+    let reply = ctx.send(CreateReply::default().content(prompt).components(menus)).await?;
+    register_components(&ids, PICKER_LIFETIME_SECS as i64, &*reply.message().await?).await?;
+    let author = ctx.author().id;
+    let choice = ComponentInteractionCollector::new(&ctx.serenity_context().shard)
+        .filter(move |i| i.user.id == author && matches!(
+            InteractionID::decode(&i.data.custom_id),
+            Ok(InteractionID::Pick { invocation: id, .. }) if id == invocation
+        ))
+        .timeout(Duration::from_secs(PICKER_LIFETIME_SECS))
+        .await;
 
-    match aux(&ctx, user, s).await {
-        Err(e) => err!("Error trying to notify user about error '{}': {}", s, e),
-        _ => {}
+    let Some(i) = choice else {
+        reply.edit(*ctx, CreateReply::default().content("No option was chosen in time.").components(vec![])).await?;
+        return Ok(None);
     };
+
+    let ComponentInteractionDataKind::StringSelect { values } = &i.data.kind else { return Err("Unexpected component".into()); };
+    let value = values.first().cloned().ok_or("No option was chosen")?;
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(format!("{} **{}**", prompt, labels.get(&value).map(String::as_str).unwrap_or(&value)))
+            .components(vec![])
+    )).await?;
+
+    Ok(Some(value))
 }
 
 /// Truncate a string w/o panicking.
@@ -181,8 +993,84 @@ pub fn safe_truncate(mut s: String, mut len: usize) -> String {
     unreachable!();
 }
 
+/// Remember that we hit a rate limit, so bulk sends back off until it
+/// is over. Called by the event handler when serenity reports one.
+pub fn note_ratelimit(info: &ser::RatelimitInfo) {
+    let until = Instant::now() + info.timeout;
+    let mut limited = RATELIMITED_UNTIL.lock().unwrap();
+    if limited.is_none_or(|t| t < until) { *limited = Some(until); }
+}
+
+/// Spaces out messages when sending many of them in a row, e.g. DMs to
+/// all participants or several panel images. Call [`Pacer::wait()`]
+/// before each send.
+#[derive(Default)]
+pub struct Pacer {
+    sent: bool,
+}
+
+impl Pacer {
+    pub fn new() -> Self { Self::default() }
+
+    /// Wait until the next message can be sent.
+    pub async fn wait(&mut self) {
+        // Back off entirely while we’re rate-limited.
+        let limited = *RATELIMITED_UNTIL.lock().unwrap();
+        if let Some(until) = limited {
+            tokio::time::sleep_until(until.into()).await;
+        }
+
+        // The first message can go out right away.
+        if !std::mem::replace(&mut self.sent, true) { return; }
+
+        // Jitter keeps several bulk sends from hitting the API in lockstep.
+        let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+        let jitter = Duration::from_millis(random % (BULK_SEND_JITTER_MS + 1));
+        tokio::time::sleep(BULK_SEND_DELAY + jitter).await;
+    }
+}
+
+/// Register some work that [`terminate()`] should wait for; the work is
+/// done once the returned token is dropped. Returns None if we’re already
+/// shutting down, in which case the work should not be started at all.
+pub fn begin_work() -> Option<TaskTrackerToken> {
+    if SHUTDOWN.is_cancelled() { return None; }
+    Some(IN_FLIGHT.token())
+}
+
+/// Get a token that is cancelled once we start shutting down; long-running
+/// work should check it and stop early.
+pub fn shutdown_token() -> &'static CancellationToken {
+    &SHUTDOWN
+}
+
+/// Check whether an interaction is a redelivery of one we already
+/// processed, and remember it if not.
+pub fn is_duplicate_interaction(id: InteractionId) -> bool {
+    if SEEN_INTERACTIONS.contains_key(&id) { return true; }
+    SEEN_INTERACTIONS.insert(id, ());
+    false
+}
+
+/// Reject commands while we’re shutting down or that we’ve already seen,
+/// and keep track of the ones that are running.
+pub async fn track_command(ctx: Context<'_>) -> Result<bool, Error> {
+    if let Context::Application(a) = ctx {
+        if is_duplicate_interaction(a.interaction.id) { return Ok(false); }
+    }
+
+    let Some(work) = begin_work() else {
+        ctx.say("The bot is shutting down; please try again in a minute.").await?;
+        return Ok(false);
+    };
+
+    // This is dropped once the command has finished.
+    ctx.set_invocation_data(work).await;
+    Ok(true)
+}
+
 /// Terminate the bot gracefully.
-pub async fn terminate() {
+pub async fn terminate(state: &AppState) {
     // Don’t terminate twice.
     static TERMINATION_LOCK: AtomicBool = AtomicBool::new(false);
     if TERMINATION_LOCK.compare_exchange(
@@ -192,19 +1080,113 @@ pub async fn terminate() {
         std::sync::atomic::Ordering::SeqCst,
     ).is_err() { return; }
 
+    // Let running handlers and jobs finish, but don’t start new ones.
+    info_sync!("Waiting for in-flight tasks...");
+    SHUTDOWN.cancel();
+    IN_FLIGHT.close();
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, IN_FLIGHT.wait()).await.is_err() {
+        info_sync!("Gave up waiting for {} task(s)", IN_FLIGHT.len());
+    }
+
     // Shutdown asynchronously running code.
-    unsafe {
-        /*info_sync!("Shutting down worker tasks...");
-        if let Some(tsk) = TASK.as_ref() { tsk.abort_handle().abort(); }*/
+    info_sync!("Shutting down worker tasks...");
+    crate::scheduler::stop();
 
-        info_sync!("Shutting down bot...");
-        __glyfi_terminate_bot().await;
+    info_sync!("Shutting down bot...");
+    if let Some(shards) = state.shard_manager.get() { shards.shutdown_all().await; }
 
-        info_sync!("Shutting down DB...");
-        __glyfi_fini_db().await;
-    }
+    info_sync!("Shutting down DB...");
+    state.pool.close().await;
 
     // Exit the process.
     info_sync!("Exiting...");
     std::process::exit(0);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_failures_are_reported_once_per_window() {
+        let start = Instant::now();
+        let fail = |secs: u64, error: &str| record_command_failure("test-failures", error, 3, start + Duration::from_secs(secs));
+
+        assert!(fail(0, "Script not found").is_none());
+        assert!(fail(1, "Script not found").is_none());
+        assert!(fail(2, "Timed out").is_none());
+        let summary = fail(3, "Script not found").expect("no alert after 4 failures");
+        assert!(summary.starts_with("/test-failures failed 4 times"));
+        assert!(summary.contains("- 3× Script not found\n- 1× Timed out"));
+
+        // Not again within the same window.
+        assert!(fail(4, "Script not found").is_none());
+
+        // Old failures no longer count.
+        let later = FAILURE_WINDOW.as_secs() + 10;
+        assert!(fail(later, "Script not found").is_none());
+        assert!(fail(later + 1, "Script not found").is_none());
+        assert!(fail(later + 2, "Script not found").is_none());
+        assert!(fail(later + 3, "Script not found").is_some());
+    }
+
+    #[test]
+    fn message_links_name_the_channel() {
+        let link = "https://discord.com/channels/1/22/333";
+        assert_eq!(parse_message_id(link).unwrap(), MessageId::new(333));
+        assert_eq!(parse_link_channel(link), Some(ser::ChannelId::new(22)));
+        assert_eq!(parse_link_channel("333"), None);
+        assert_eq!(parse_link_channel("https://discord.com/channels/1/x/333"), None);
+    }
+
+    #[test]
+    fn interaction_ids_round_trip() {
+        let ids = [
+            InteractionID::ConfirmAnnouncement { challenge: Challenge::Ambigram, mtime: 1700000000, prompt: 5 },
+            InteractionID::CancelPrompt { prompt: 5 },
+            InteractionID::DeleteUserData { user: UserId::new(42) },
+            InteractionID::RegeneratePrompt { prompt: 7 },
+            InteractionID::OptOutDms,
+            InteractionID::Paginate { invocation: 99, action: PageAction::Jump },
+            InteractionID::Pick { invocation: 99, menu: 2 },
+            InteractionID::Ballot { ballot: 3, action: BallotAction::Choose { menu: 1 } },
+            InteractionID::Ballot { ballot: 3, action: BallotAction::Page { page: 2 } },
+            InteractionID::Ballot { ballot: 3, action: BallotAction::Cast },
+            InteractionID::PromptPick { pick: 8, action: PickAction::Suggest },
+            InteractionID::PromptPick { pick: 8, action: PickAction::Reject },
+            InteractionID::PollVote { poll: 2, option: 4 },
+            InteractionID::RemoveVote { message: MessageId::new(1234) },
+        ];
+
+        for id in ids {
+            assert_eq!(InteractionID::decode(&id.encode()).unwrap(), id);
+        }
+
+        // The opt-out button in old DMs still works.
+        assert_eq!(InteractionID::decode("4").unwrap(), InteractionID::OptOutDms);
+
+        // Other legacy IDs, unknown kinds, and malformed payloads are rejected.
+        for bad in ["0:0:1:2", "", "v1:frobnicate", "v1:cancel", "v1:cancel:x", "v1:cancel:1:2", "v1:page:1:sideways", "v2:cancel:1", "v1:delete-data:0", "v1:unvote:0"] {
+            assert!(InteractionID::decode(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn timestamps_are_replaced_in_any_style() {
+        let at = |ts| DateTime::from_timestamp(ts, 0).unwrap();
+        let topic = "Deadline: <t:1000:F> (<t:1000:R>), not <t:10001:F> or <t:1000>";
+        assert_eq!(
+            replace_timestamp(topic, at(1000), at(4600)).unwrap(),
+            "Deadline: <t:4600:F> (<t:4600:R>), not <t:10001:F> or <t:4600>",
+        );
+
+        assert_eq!(replace_timestamp("No deadline here <t:10001:F>", at(1000), at(4600)), None);
+    }
+
+    #[test]
+    fn durations_are_rounded_down() {
+        assert_eq!(format_duration(30), "<1m");
+        assert_eq!(format_duration(40 * 60 + 59), "40m");
+        assert_eq!(format_duration(5 * 3600 + 12 * 60), "5h 12m");
+        assert_eq!(format_duration(2 * 86400 + 3 * 3600 + 59 * 60), "2d 3h");
+    }
+}
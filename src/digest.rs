@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::Mentionable;
+use crate::{clock, Error, info, preflight, Res, results, sql};
+use crate::core::{DEFAULT_EMBED_COLOUR, format_number};
+use crate::sql::{Challenge, Setting};
+
+/// Number of users listed as most active.
+const MOST_ACTIVE: i64 = 5;
+
+/// Maximum number of newcomers mentioned by name.
+const MAX_NEWCOMERS: usize = 10;
+
+/// Start of the digest period that contains `month_index` (months since
+/// year 0); periods are aligned to the start of the year.
+fn period_start(month_index: i32, months: i32) -> Result<DateTime<Utc>, Error> {
+    let idx = month_index - month_index.rem_euclid(months);
+    Utc.with_ymd_and_hms(idx.div_euclid(12), idx.rem_euclid(12) as u32 + 1, 1, 0, 0, 0)
+        .single()
+        .ok_or_else(|| "Invalid digest period".into())
+}
+
+/// Draw a sequence of numbers as a row of bars.
+fn sparkline(values: &[i64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values.iter().map(|&v| BARS[(v.max(0) * 7 / max) as usize]).collect()
+}
+
+/// Build the digest embed for a time range.
+async fn digest_embed(from: DateTime<Utc>, to: DateTime<Utc>) -> Result<ser::CreateEmbed, Error> {
+    let (from_ts, to_ts) = (from.timestamp(), to.timestamp());
+    let last_month = to - chrono::Duration::days(1);
+    let title = if from.month() == last_month.month() && from.year() == last_month.year() {
+        format!("Stats for {}", from.format("%B %Y"))
+    } else {
+        format!("Stats for {} – {}", from.format("%B %Y"), last_month.format("%B %Y"))
+    };
+
+    let mut embed = ser::CreateEmbed::new()
+        .colour(DEFAULT_EMBED_COLOUR)
+        .title(title);
+
+    // Most active participants.
+    let active = sql::get_most_active_users(from_ts, to_ts, MOST_ACTIVE).await?;
+    if active.is_empty() { return Ok(embed.description("No submissions in this period.")); }
+    let list = active.iter()
+        .enumerate()
+        .map(|(i, (u, n))| format!("{}. {} – {} submission{}", i + 1, u.mention(), n, if *n == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>()
+        .join("\n");
+    embed = embed.field("Most active", list, false);
+
+    // Who won how often.
+    let votes = sql::get_votes_by_week(from_ts, to_ts).await?;
+    let mut wins: HashMap<i64, i64> = HashMap::new();
+    for (week, _) in &votes {
+        for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+            for p in results::ranking(*week, challenge).await?.iter().take_while(|p| p.place == 1) {
+                if p.submission.author == sql::PURGED_AUTHOR { continue; }
+                *wins.entry(p.submission.author).or_default() += 1;
+            }
+        }
+    }
+
+    if !wins.is_empty() {
+        let mut wins = wins.into_iter().collect::<Vec<_>>();
+        wins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let list = wins.iter()
+            .map(|(u, n)| format!("<@{}>: {} win{}", u, n, if *n == 1 { "" } else { "s" }))
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Wins", list, false);
+    }
+
+    // Welcome new participants.
+    let newcomers = sql::get_newcomers(from_ts, to_ts).await?;
+    if !newcomers.is_empty() {
+        let mut list = newcomers.iter()
+            .take(MAX_NEWCOMERS)
+            .map(|u| u.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if newcomers.len() > MAX_NEWCOMERS {
+            list.push_str(&format!(" and {} more", newcomers.len() - MAX_NEWCOMERS));
+        }
+
+        embed = embed.field("Newcomers", list, false);
+    }
+
+    // Votes per week.
+    if let (Some(first), Some(last)) = (votes.first(), votes.last()) {
+        let counts = votes.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        embed = embed.field("Votes per week", format!(
+            "Week {} – {}: {} ({} votes in total)",
+            first.0,
+            last.0,
+            sparkline(&counts),
+            format_number(counts.iter().sum::<i64>(), None),
+        ), false);
+    }
+
+    Ok(embed)
+}
+
+/// Post the stats digest for the last period if it hasn’t been posted yet.
+pub async fn post_digest_if_due(ctx: &ser::Context) -> Res {
+    let channel = sql::get_setting::<u64>(Setting::DigestChannel).await?;
+    if channel == 0 { return Ok(()); }
+
+    let months = sql::get_setting::<i32>(Setting::DigestMonths).await?;
+    let now = clock::now();
+    let current = now.year() * 12 + now.month0() as i32;
+    let start = period_start(current, months)?;
+
+    // Don’t post anything for periods before the digest was enabled.
+    let Some(last) = sql::get_last_digest().await? else {
+        return sql::set_last_digest(start.timestamp()).await;
+    };
+
+    if last >= start.timestamp() { return Ok(()); }
+    let prev = period_start(current - months, months)?;
+    let embed = digest_embed(prev, start).await?;
+    preflight::preflight_post(ctx, ser::ChannelId::new(channel), "the stats digest", None).await?;
+    ser::ChannelId::new(channel).send_message(ctx, ser::CreateMessage::new().embed(embed)).await?;
+    sql::set_last_digest(start.timestamp()).await?;
+    info!("Posted stats digest for {} – {}", prev, start);
+    Ok(())
+}
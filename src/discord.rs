@@ -0,0 +1,365 @@
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::{async_trait, ChannelId, CreateMessage, EditMessage, GetMessages, Message, MessageId, ReactionType, RoleId, UserId};
+use crate::{config, Error, Res};
+
+/// The Discord operations the submission and voting flows need. This is
+/// implemented for the serenity context, and by [`mock::MockDiscord`] so
+/// the flows can be tested without talking to Discord.
+#[async_trait]
+pub trait DiscordApi: Send + Sync {
+    /// Get our own user ID.
+    fn current_user_id(&self) -> UserId;
+
+    async fn get_message(&self, channel: ChannelId, message: MessageId) -> Result<Message, Error>;
+
+    /// Get the last messages in a channel, newest first; at most 100.
+    async fn recent_messages(&self, channel: ChannelId, limit: u8) -> Result<Vec<Message>, Error>;
+
+    async fn send_message(&self, channel: ChannelId, message: CreateMessage) -> Result<MessageId, Error>;
+    async fn edit_message(&self, channel: ChannelId, message: MessageId, edit: EditMessage) -> Res;
+
+    /// Add a reaction as ourselves.
+    async fn add_reaction(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Res;
+
+    /// Remove someone’s reaction; `None` means our own.
+    async fn delete_reaction(&self, channel: ChannelId, message: MessageId, user: Option<UserId>, emoji: ReactionType) -> Res;
+
+    /// Get everyone who reacted to a message with an emoji.
+    async fn reaction_users(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Result<Vec<UserId>, Error>;
+
+    /// Get the roles someone has on the server.
+    async fn member_roles(&self, user: UserId) -> Result<Vec<RoleId>, Error>;
+
+    async fn send_dm(&self, user: UserId, message: CreateMessage) -> Result<MessageId, Error>;
+
+    /// Start a thread on a message; returns the thread’s channel.
+    async fn create_thread(&self, channel: ChannelId, message: MessageId, name: String) -> Result<ChannelId, Error>;
+}
+
+#[async_trait]
+impl DiscordApi for ser::Context {
+    fn current_user_id(&self) -> UserId {
+        self.cache.current_user().id
+    }
+
+    async fn get_message(&self, channel: ChannelId, message: MessageId) -> Result<Message, Error> {
+        Ok(channel.message(self, message).await?)
+    }
+
+    async fn recent_messages(&self, channel: ChannelId, limit: u8) -> Result<Vec<Message>, Error> {
+        Ok(channel.messages(self, GetMessages::new().limit(limit)).await?)
+    }
+
+    async fn send_message(&self, channel: ChannelId, message: CreateMessage) -> Result<MessageId, Error> {
+        Ok(channel.send_message(self, message).await?.id)
+    }
+
+    async fn edit_message(&self, channel: ChannelId, message: MessageId, edit: EditMessage) -> Res {
+        channel.edit_message(self, message, edit).await?;
+        Ok(())
+    }
+
+    async fn add_reaction(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Res {
+        Ok(channel.create_reaction(self, message, emoji).await?)
+    }
+
+    async fn delete_reaction(&self, channel: ChannelId, message: MessageId, user: Option<UserId>, emoji: ReactionType) -> Res {
+        Ok(channel.delete_reaction(self, message, user, emoji).await?)
+    }
+
+    async fn reaction_users(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Result<Vec<UserId>, Error> {
+        all_reaction_users(&self.http, channel, message, emoji).await
+    }
+
+    async fn member_roles(&self, user: UserId) -> Result<Vec<RoleId>, Error> {
+        Ok(config::get().server.member(self, user).await?.roles)
+    }
+
+    async fn send_dm(&self, user: UserId, message: CreateMessage) -> Result<MessageId, Error> {
+        let dm = user.create_dm_channel(self).await?;
+        Ok(dm.send_message(self, message).await?.id)
+    }
+
+    async fn create_thread(&self, channel: ChannelId, message: MessageId, name: String) -> Result<ChannelId, Error> {
+        Ok(channel.create_thread_from_message(self, message, ser::CreateThread::new(name)).await?.id)
+    }
+}
+
+/// Get everyone who reacted to a message with an emoji. Discord returns
+/// at most 100 users at a time.
+async fn all_reaction_users(http: &ser::Http, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Result<Vec<UserId>, Error> {
+    let mut users = Vec::new();
+    loop {
+        let page = channel.reaction_users(http, message, emoji.clone(), Some(100), users.last().copied()).await?;
+        let done = page.len() < 100;
+        users.extend(page.into_iter().map(|u| u.id));
+        if done { return Ok(users); }
+    }
+}
+
+/// Talks to Discord over HTTP only, for command-line tools that run
+/// without connecting to the gateway. Serenity queues requests so that
+/// they stay within Discord’s rate limits.
+pub struct HttpApi {
+    http: ser::Http,
+    me: UserId,
+}
+
+impl HttpApi {
+    pub async fn new(token: &str) -> Result<Self, Error> {
+        let http = ser::Http::new(token);
+        let me = http.get_current_user().await?.id;
+        Ok(Self { http, me })
+    }
+}
+
+#[async_trait]
+impl DiscordApi for HttpApi {
+    fn current_user_id(&self) -> UserId {
+        self.me
+    }
+
+    async fn get_message(&self, channel: ChannelId, message: MessageId) -> Result<Message, Error> {
+        Ok(channel.message(&self.http, message).await?)
+    }
+
+    async fn recent_messages(&self, channel: ChannelId, limit: u8) -> Result<Vec<Message>, Error> {
+        Ok(channel.messages(&self.http, GetMessages::new().limit(limit)).await?)
+    }
+
+    async fn send_message(&self, channel: ChannelId, message: CreateMessage) -> Result<MessageId, Error> {
+        Ok(channel.send_message(&self.http, message).await?.id)
+    }
+
+    async fn edit_message(&self, channel: ChannelId, message: MessageId, edit: EditMessage) -> Res {
+        channel.edit_message(&self.http, message, edit).await?;
+        Ok(())
+    }
+
+    async fn add_reaction(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Res {
+        Ok(channel.create_reaction(&self.http, message, emoji).await?)
+    }
+
+    async fn delete_reaction(&self, channel: ChannelId, message: MessageId, user: Option<UserId>, emoji: ReactionType) -> Res {
+        Ok(channel.delete_reaction(&self.http, message, user, emoji).await?)
+    }
+
+    async fn reaction_users(&self, channel: ChannelId, message: MessageId, emoji: ReactionType) -> Result<Vec<UserId>, Error> {
+        all_reaction_users(&self.http, channel, message, emoji).await
+    }
+
+    async fn member_roles(&self, user: UserId) -> Result<Vec<RoleId>, Error> {
+        Ok(config::get().server.member(&self.http, user).await?.roles)
+    }
+
+    async fn send_dm(&self, user: UserId, message: CreateMessage) -> Result<MessageId, Error> {
+        let dm = user.create_dm_channel(&self.http).await?;
+        Ok(dm.send_message(&self.http, message).await?.id)
+    }
+
+    async fn create_thread(&self, channel: ChannelId, message: MessageId, name: String) -> Result<ChannelId, Error> {
+        Ok(channel.create_thread_from_message(&self.http, message, ser::CreateThread::new(name)).await?.id)
+    }
+}
+
+/// In-memory stand-in for Discord.
+#[cfg(test)]
+pub mod mock {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+    use poise::serenity_prelude::{async_trait, Attachment, ChannelId, CreateMessage, EditMessage, Message, MessageId, Reaction, ReactionType, RoleId, Timestamp, User, UserId};
+    use serde_json::{json, Value};
+    use crate::{clock, Error, Res};
+    use super::DiscordApi;
+
+    /// A message we sent, serialised the way it would be sent to Discord.
+    pub type Sent = (ChannelId, Value);
+
+    pub struct MockDiscord {
+        pub me: UserId,
+        pub messages: Mutex<HashMap<MessageId, Message>>,
+        pub sent: Mutex<Vec<Sent>>,
+        pub dms: Mutex<Vec<(UserId, Value)>>,
+
+        /// Edits of our messages, in the order they were made.
+        pub edits: Mutex<Vec<(MessageId, Value)>>,
+
+        /// Message, user, and emoji of each reaction that is present.
+        pub reactions: Mutex<HashSet<(MessageId, UserId, String)>>,
+
+        /// Roles of members; everyone else has none.
+        pub roles: Mutex<HashMap<UserId, Vec<RoleId>>>,
+
+        /// Threads we started: the thread, the message it is on, and its name.
+        pub threads: Mutex<Vec<(ChannelId, MessageId, String)>>,
+
+        /// Users whose DMs are closed; sending them one fails.
+        pub closed_dms: Mutex<HashSet<UserId>>,
+        next_id: AtomicU64,
+    }
+
+    impl MockDiscord {
+        pub fn new() -> Self {
+            Self {
+                me: UserId::new(1),
+                messages: Mutex::default(),
+                sent: Mutex::default(),
+                dms: Mutex::default(),
+                edits: Mutex::default(),
+                reactions: Mutex::default(),
+                roles: Mutex::default(),
+                threads: Mutex::default(),
+                closed_dms: Mutex::default(),
+                next_id: AtomicU64::new(1000),
+            }
+        }
+
+        fn next_id(&self) -> u64 {
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        }
+
+        /// Post a message as a user.
+        pub fn post(&self, channel: ChannelId, author: UserId, attachments: Vec<Attachment>) -> Message {
+            let mut user = User::default();
+            user.id = author;
+            user.name = format!("user{}", author);
+
+            let mut message = Message::default();
+            message.id = MessageId::new(self.next_id());
+            message.channel_id = channel;
+            message.timestamp = Timestamp::from_unix_timestamp(clock::now().timestamp()).unwrap();
+            message.author = user;
+            message.attachments = attachments;
+            self.messages.lock().unwrap().insert(message.id, message.clone());
+            message
+        }
+
+        /// Create an image attachment.
+        pub fn image(&self, filename: &str) -> Attachment {
+            self.image_sized(filename, 512, 512)
+        }
+
+        /// Create an image attachment with the given dimensions.
+        pub fn image_sized(&self, filename: &str, width: u32, height: u32) -> Attachment {
+            let id = self.next_id();
+            serde_json::from_value(json!({
+                "id": id.to_string(),
+                "filename": filename,
+                "height": height,
+                "width": width,
+                "proxy_url": format!("https://media.example/{}", id),
+                "url": format!("https://cdn.example/{}/{}", id, filename),
+                "size": 1024,
+                "content_type": "image/png",
+            })).unwrap()
+        }
+
+        /// Create the event for a user reacting to a message, and record the
+        /// reaction as Discord would before delivering the event.
+        pub fn react(&self, message: &Message, user: UserId, emoji: ReactionType, added: bool) -> Reaction {
+            let key = (message.id, user, emoji.to_string());
+            match added {
+                true => self.reactions.lock().unwrap().insert(key),
+                false => self.reactions.lock().unwrap().remove(&key),
+            };
+
+            serde_json::from_value(json!({
+                "user_id": user.to_string(),
+                "channel_id": message.channel_id.to_string(),
+                "message_id": message.id.to_string(),
+                "emoji": emoji,
+            })).unwrap()
+        }
+
+        /// Check whether a user’s reaction is present on a message.
+        pub fn has_reaction(&self, message: MessageId, user: UserId, emoji: &ReactionType) -> bool {
+            self.reactions.lock().unwrap().contains(&(message, user, emoji.to_string()))
+        }
+    }
+
+    #[async_trait]
+    impl DiscordApi for MockDiscord {
+        fn current_user_id(&self) -> UserId {
+            self.me
+        }
+
+        async fn get_message(&self, _: ChannelId, message: MessageId) -> Result<Message, Error> {
+            self.messages.lock().unwrap().get(&message).cloned().ok_or_else(|| "Unknown Message".into())
+        }
+
+        async fn recent_messages(&self, channel: ChannelId, limit: u8) -> Result<Vec<Message>, Error> {
+            let mut messages = self.messages.lock().unwrap().values()
+                .filter(|m| m.channel_id == channel)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            messages.sort_by_key(|m| std::cmp::Reverse(m.id));
+            messages.truncate(limit as usize);
+
+            // Discord includes a summary of the reactions.
+            let reactions = self.reactions.lock().unwrap();
+            for m in &mut messages {
+                let mut emoji = reactions.iter().filter(|(id, _, _)| *id == m.id).map(|(_, _, e)| e.as_str()).collect::<Vec<_>>();
+                emoji.sort();
+                emoji.dedup();
+                m.reactions = emoji.into_iter().map(|e| serde_json::from_value(json!({
+                    "count": reactions.iter().filter(|(id, _, r)| *id == m.id && r == e).count(),
+                    "me": reactions.contains(&(m.id, self.me, e.to_string())),
+                    "emoji": ReactionType::try_from(e).unwrap(),
+                })).unwrap()).collect();
+            }
+
+            Ok(messages)
+        }
+
+        async fn send_message(&self, channel: ChannelId, message: CreateMessage) -> Result<MessageId, Error> {
+            self.sent.lock().unwrap().push((channel, serde_json::to_value(message)?));
+            Ok(MessageId::new(self.next_id()))
+        }
+
+        async fn edit_message(&self, _: ChannelId, message: MessageId, edit: EditMessage) -> Res {
+            self.edits.lock().unwrap().push((message, serde_json::to_value(edit)?));
+            Ok(())
+        }
+
+        async fn add_reaction(&self, _: ChannelId, message: MessageId, emoji: ReactionType) -> Res {
+            self.reactions.lock().unwrap().insert((message, self.me, emoji.to_string()));
+            Ok(())
+        }
+
+        async fn delete_reaction(&self, _: ChannelId, message: MessageId, user: Option<UserId>, emoji: ReactionType) -> Res {
+            // Like Discord, match custom emoji by ID alone, whatever their name.
+            let user = user.unwrap_or(self.me);
+            self.reactions.lock().unwrap().retain(|(m, u, e)| !(*m == message && *u == user && match (ReactionType::try_from(e.as_str()), &emoji) {
+                (Ok(ReactionType::Custom { id: a, .. }), ReactionType::Custom { id: b, .. }) => a == *b,
+                _ => *e == emoji.to_string(),
+            }));
+            Ok(())
+        }
+
+        async fn reaction_users(&self, _: ChannelId, message: MessageId, emoji: ReactionType) -> Result<Vec<UserId>, Error> {
+            let emoji = emoji.to_string();
+            Ok(self.reactions.lock().unwrap().iter()
+                .filter(|(m, _, e)| *m == message && *e == emoji)
+                .map(|&(_, u, _)| u)
+                .collect())
+        }
+
+        async fn member_roles(&self, user: UserId) -> Result<Vec<RoleId>, Error> {
+            Ok(self.roles.lock().unwrap().get(&user).cloned().unwrap_or_default())
+        }
+
+        async fn send_dm(&self, user: UserId, message: CreateMessage) -> Result<MessageId, Error> {
+            if self.closed_dms.lock().unwrap().contains(&user) { return Err("Cannot send messages to this user".into()); }
+            self.dms.lock().unwrap().push((user, serde_json::to_value(message)?));
+            Ok(MessageId::new(self.next_id()))
+        }
+
+        async fn create_thread(&self, _: ChannelId, message: MessageId, name: String) -> Result<ChannelId, Error> {
+            let thread = ChannelId::new(self.next_id());
+            self.threads.lock().unwrap().push((thread, message, name));
+            Ok(thread)
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::{CreateMessage, UserId};
+use crate::{err, Error, sql, warn};
+use crate::core::Pacer;
+use crate::discord::DiscordApi;
+
+/// How often to try sending a DM before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled for every further attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Optional DMs are no longer sent to users whose DMs failed this many
+/// times in a row, e.g. because they don’t accept DMs from the server.
+const MAX_CONSECUTIVE_FAILURES: i64 = 3;
+
+/// What a DM is for; decides whether users who opted out of DMs get it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmKind {
+    /// Replies to something the user did, or notices about their own
+    /// entries. Always sent.
+    Essential,
+
+    /// Anything else, e.g. the welcome message. Not sent to users that
+    /// opted out of DMs.
+    Optional,
+}
+
+/// Outcome of sending a DM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmResult {
+    Sent,
+    Skipped,
+    Failed,
+}
+
+/// Check whether an error might go away if we try again.
+fn is_transient(e: &Error) -> bool {
+    match e.downcast_ref::<ser::Error>() {
+        None => false,
+        Some(ser::Error::Http(ser::HttpError::Request(_))) => true,
+        Some(ser::Error::Http(e)) => e.status_code().is_some_and(|c| c.is_server_error()),
+        Some(_) => false,
+    }
+}
+
+/// Check whether a user should get a DM of this kind.
+async fn should_send(user: UserId, kind: DmKind) -> Result<bool, Error> {
+    if kind == DmKind::Essential { return Ok(true); }
+    if sql::get_user_prefs(user).await?.dm_opt_out { return Ok(false); }
+    Ok(sql::get_dm_failures(user).await? < MAX_CONSECUTIVE_FAILURES)
+}
+
+/// Send a DM to a user, retrying transient failures, and record whether
+/// it could be delivered.
+pub async fn send(api: &dyn DiscordApi, user: UserId, kind: DmKind, message: CreateMessage) -> Result<DmResult, Error> {
+    if !should_send(user, kind).await? { return Ok(DmResult::Skipped); }
+
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    let res = loop {
+        match api.send_dm(user, message.clone()).await {
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                warn!("Retrying DM to {} after error: {}", user, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            res => break res,
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            sql::record_dm_result(user, None).await?;
+            Ok(DmResult::Sent)
+        }
+        Err(e) => {
+            sql::record_dm_result(user, Some(&e.to_string())).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Sends DMs to many users, spacing them out; errors are logged rather
+/// than returned so one user with closed DMs doesn’t stop the rest.
+pub struct DmBatch {
+    kind: DmKind,
+    pacer: Pacer,
+    pub sent: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl DmBatch {
+    pub fn new(kind: DmKind) -> Self {
+        Self { kind, pacer: Pacer::new(), sent: 0, skipped: 0, failed: 0 }
+    }
+
+    /// Send the next DM in the batch.
+    pub async fn send(&mut self, api: &dyn DiscordApi, user: UserId, message: CreateMessage) -> DmResult {
+        self.pacer.wait().await;
+        let res = match send(api, user, self.kind, message).await {
+            Ok(res) => res,
+            Err(e) => {
+                err!("Could not send DM to {}: {}", user, e);
+                DmResult::Failed
+            }
+        };
+
+        match res {
+            DmResult::Sent => self.sent += 1,
+            DmResult::Skipped => self.skipped += 1,
+            DmResult::Failed => self.failed += 1,
+        }
+
+        res
+    }
+}
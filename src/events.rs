@@ -1,8 +1,17 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use poise::{ChoiceParameter, Modal};
 use poise::serenity_prelude::*;
-use crate::{err, info, info_sync, Res, sql};
-use crate::core::{file_mtime, InteractionID, report_user_error};
-use crate::server_data::{AMBIGRAM_SUBMISSION_CHANNEL_ID, GLYPH_SUBMISSION_CHANNEL_ID, SUBMIT_EMOJI_ID};
-use crate::sql::Challenge;
+use tokio::sync::mpsc;
+use crate::{archive, ballots, clock, config, dm, err, Error, info, info_sync, polls, preflight, prompt_picks, receipts, Res, scheduler, sql, tally, warn, watermark};
+use crate::discord::DiscordApi;
+use crate::dm::DmKind;
+use crate::commands::{await_prompt_modal, generate_challenge_image, prompt_announcement_text, prompt_preview_buttons, PromptModal};
+use crate::core::{await_modal, BallotAction, begin_work, COMPONENT_LIFETIME_SECS, file_mtime, InteractionID, is_duplicate_interaction, note_ratelimit, PickAction, register_components, report_user_error, timestamp, TimestampStyle};
+use crate::sql::{Achievement, Challenge, Setting, WithdrawalReason};
+use crate::templates;
+use crate::templates::{Template, with_rules};
 
 pub struct GlyfiEvents;
 
@@ -32,87 +41,1187 @@ macro_rules! run {
     }
 }
 
-/// Mark that the announcement image for a challenge has been acknowledged.
-async fn act_on_confirm_announcement(ctx: &Context, i: &mut ComponentInteraction) -> Res {
-    let mut it = i.data.custom_id.split(':').skip(1);
-    let challenge = it.next().ok_or("Invalid interaction ID")?.parse::<Challenge>()?;
-    let time = it.next().ok_or("Invalid interaction ID")?.parse::<u64>()?;
-    let id = it.next().ok_or("Invalid interaction ID")?.parse::<i64>()?;
-
+/// Approve a queued prompt once its preview has been checked.
+async fn act_on_confirm_announcement(ctx: &Context, i: &mut ComponentInteraction, challenge: Challenge, time: u64, id: i64) -> Res {
     // Check that the file is not out of date.
     let path = challenge.announcement_image_path();
     let mtime = file_mtime(&path)?;
     if time != mtime {
         info!("Refusing to accept outdated announcement image for {:?}. Please regenerate it.", challenge);
+        reply_ephemeral!(ctx, i, "This preview is out of date. Please regenerate it.")?;
         return Ok(());
     }
 
-    // Save prompt.
-    reply_ephemeral!(ctx, i, "Confirmed.")?;
+    // The prompt stays in the queue; just get rid of the buttons.
+    let content = match sql::approve_prompt(id).await? {
+        true => format!("Confirmed; this is entry {} in the queue.", id),
+        false => "Entry has been cancelled.".to_string(),
+    };
+
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(vec![])
+    )).await?;
     Ok(())
 }
 
-async fn act_on_cancel_prompt(ctx: &Context, i: &mut ComponentInteraction) -> Res {
-    let mut it = i.data.custom_id.split(':').skip(1);
-    let id = it.next().ok_or("Invalid interaction ID")?.parse::<i64>()?;
+/// Let the user edit a prompt and regenerate its preview.
+async fn act_on_regenerate_prompt(ctx: &Context, i: &mut ComponentInteraction, id: i64) -> Res {
+    let entry = sql::get_prompt(id).await?;
+
+    // Show the modal, prefilled with the current values.
+    let modal_id = i.id.to_string();
+    i.create_response(ctx, PromptModal::create(Some(PromptModal {
+        prompt: entry.prompt,
+        notes: entry.notes,
+        category: entry.category,
+    }), modal_id.clone())).await?;
+    let Some((m, data)) = await_prompt_modal(ctx, modal_id).await? else { return Ok(()); };
+
+    // This edits the message the button was on.
+    m.create_response(ctx, CreateInteractionResponse::Acknowledge).await?;
+    if !sql::update_prompt(id, &data.prompt, data.notes.as_deref(), data.category.as_deref()).await? {
+        m.edit_response(ctx, EditInteractionResponse::new()
+            .content("Entry has been cancelled.")
+            .components(vec![])
+        ).await?;
+        return Ok(());
+    }
+
+    let entry = sql::get_prompt(id).await?;
+    let path = generate_challenge_image(entry.challenge, &entry.prompt, entry.theme.as_deref()).await?;
+    let (buttons, ids) = prompt_preview_buttons(entry.challenge, &path, id)?;
+    let message = m.edit_response(ctx, EditInteractionResponse::new()
+        .content(prompt_announcement_text(&entry).await?)
+        .clear_attachments()
+        .new_attachment(watermark::preview_attachment(&path, "preview.png").await?)
+        .components(buttons)
+    ).await?;
+    register_components(&ids, COMPONENT_LIFETIME_SECS, &message).await?;
+    Ok(())
+}
 
+async fn act_on_cancel_prompt(ctx: &Context, i: &mut ComponentInteraction, id: i64) -> Res {
     let changed = sql::delete_prompt(id).await?;
-    reply_ephemeral!(ctx, i, "{}", if changed { "Cancelled." } else { "Entry has already been cancelled." })?;
+    if changed { info!("{} cancelled queue entry {}", i.user.id, id); }
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(if changed { "Cancelled; the entry was removed from the queue." } else { "Entry has already been cancelled." })
+            .components(vec![])
+    )).await?;
+    Ok(())
+}
+
+/// Delete a user’s data after they confirmed that they want that.
+async fn act_on_delete_user_data(ctx: &Context, i: &mut ComponentInteraction, user: UserId) -> Res {
+    if user != i.user.id { return Err("You can only delete your own data".into()); }
+
+    let submissions = sql::purge_user(user).await?;
+    archive::remove(&submissions).await;
+    info!("Deleted data of user {} at their request", user);
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content("Your data has been deleted.")
+            .components(vec![])
+    )).await?;
+    Ok(())
+}
+
+/// Stop sending DMs to a user.
+async fn act_on_opt_out_dms(ctx: &Context, i: &mut ComponentInteraction) -> Res {
+    let mut prefs = sql::get_user_prefs(i.user.id).await?;
+    prefs.dm_opt_out = true;
+    sql::set_user_prefs(i.user.id, &prefs).await?;
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .components(vec![])
+    )).await?;
+    i.create_followup(ctx, CreateInteractionResponseFollowup::new()
+        .content("You won’t receive any more messages like this. Use `/settings dms` on the server to change this.")
+    ).await?;
+    Ok(())
+}
+
+/// Pick entries on a ballot, or cast it.
+async fn act_on_ballot(ctx: &Context, i: &mut ComponentInteraction, id: i64, action: BallotAction) -> Res {
+    match action {
+        BallotAction::Cast => {
+            let votes = ballots::cast(ctx, id, i.user.id).await?;
+            let (receipt, rows) = receipts::render(i.user.id).await?;
+            i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("Your ballot has been cast with {} vote{}. Thanks for voting!\n\n{}", votes, if votes == 1 { "" } else { "s" }, receipt))
+                    .components(rows)
+            )).await?;
+        }
+
+        BallotAction::Choose { menu } => {
+            let ComponentInteractionDataKind::StringSelect { values } = &i.data.kind else { return Err("Unexpected component".into()); };
+            ballots::choose(id, i.user.id, menu, values).await?;
+            i.create_response(ctx, CreateInteractionResponse::Acknowledge).await?;
+        }
+
+        BallotAction::Page { page } => {
+            let (text, rows) = ballots::show_page(id, i.user.id, page).await?;
+            i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().content(text).components(rows)
+            )).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Let a winner pick the next prompt, or an admin review their pick.
+async fn act_on_prompt_pick(ctx: &Context, i: &mut ComponentInteraction, id: i64, action: PickAction) -> Res {
+    let thanks = |prompt: &str| CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(format!("Thanks! The admins will look at ‘{}’ and let you know.", prompt))
+            .components(vec![])
+    );
+
+    match action {
+        PickAction::Choose => {
+            let ComponentInteractionDataKind::StringSelect { values } = &i.data.kind else { return Err("Unexpected component".into()); };
+            let value = values.first().ok_or("Nothing was chosen")?;
+            let prompt = prompt_picks::choose(ctx, id, i.user.id, value).await?;
+            i.create_response(ctx, thanks(&prompt)).await?;
+        }
+
+        PickAction::Suggest => {
+            let modal_id = i.id.to_string();
+            i.create_response(ctx, prompt_picks::SuggestionModal::create(None, modal_id.clone())).await?;
+            let Some((m, data)) = await_modal::<prompt_picks::SuggestionModal>(ctx, modal_id).await? else { return Ok(()); };
+
+            // We already responded to the button, so errors go to the modal.
+            let prompt = data.prompt.trim();
+            match prompt_picks::suggest(ctx, id, i.user.id, prompt).await {
+                Ok(()) => m.create_response(ctx, thanks(prompt)).await?,
+                Err(e) => m.create_response(ctx, CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("Error processing interaction: {}", e))
+                        .ephemeral(true)
+                )).await?,
+            }
+        }
+
+        PickAction::Approve | PickAction::Reject => {
+            let admin = i.member.as_ref().and_then(|m| m.permissions).is_some_and(|p| p.administrator());
+            if !admin { return Err("Only admins can review prompts".into()); }
+            let content = prompt_picks::review(ctx, id, action == PickAction::Approve).await?;
+            i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("{} ({})", content, i.user.mention()))
+                    .components(vec![])
+            )).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Vote in a prompt poll.
+async fn act_on_poll_vote(ctx: &Context, i: &mut ComponentInteraction, poll: i64, option: i64) -> Res {
+    let prompt = polls::vote(poll, i.user.id, option).await?;
+    reply_ephemeral!(ctx, i, "You voted for ‘{}’. You can change your vote until the poll closes.", prompt)?;
+    Ok(())
+}
+
+/// Take back a vote from a vote receipt, and show the updated receipt.
+async fn act_on_remove_vote(ctx: &Context, i: &mut ComponentInteraction, message: MessageId) -> Res {
+    receipts::remove_vote(ctx, i.user.id, message).await?;
+    let (text, rows) = receipts::render(i.user.id).await?;
+    i.create_response(ctx, CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(text)
+            .components(rows)
+    )).await?;
+    Ok(())
+}
+
+/// Send a primer about the challenges to a new member.
+async fn send_welcome_dm(ctx: &Context, user: &User) -> Res {
+    let text = templates::render(Template::Welcome, &[
+        ("name", user.global_name.as_ref().unwrap_or(&user.name)),
+        ("glyph_channel", &Challenge::Glyph.submission_channel().mention().to_string()),
+        ("ambigram_channel", &Challenge::Ambigram.submission_channel().mention().to_string()),
+    ]).await?;
+
+    dm::send(ctx, user.id, DmKind::Optional, CreateMessage::new()
+        .content(text)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(InteractionID::OptOutDms.encode())
+                .label("Don’t send me messages like this")
+                .style(ButtonStyle::Secondary),
+        ])])
+    ).await?;
+    Ok(())
+}
+
+/// Reply to a user’s first submission and award them the achievement.
+async fn celebrate_first_submission(api: &dyn DiscordApi, message: &Message) -> Res {
+    if !sql::award_achievement(message.author.id, Achievement::FirstSubmission).await? { return Ok(()); }
+    let text = templates::render(Template::FirstSubmission, &[
+        ("user", &message.author.mention().to_string()),
+    ]).await?;
+
+    api.send_message(message.channel_id, CreateMessage::new()
+        .content(text)
+        .reference_message(message)
+    ).await?;
+    Ok(())
+}
+
+/// Automatic responses that were sent recently, per channel, so we
+/// don’t reply to the same question over and over.
+static AUTORESPONSE_COOLDOWN: Lazy<mini_moka::sync::Cache<(ChannelId, i64), ()>> = Lazy::new(|| {
+    mini_moka::sync::Cache::builder()
+        .time_to_live(std::time::Duration::from_secs(10 * 60))
+        .build()
+});
+
+/// The last kind of event (added or removed) we processed for each
+/// reaction. Discord may deliver reaction events again after a reconnect,
+/// so adding the same reaction twice in a row means we’ve seen it already.
+static LAST_REACTION_EVENT: Lazy<mini_moka::sync::Cache<(MessageId, UserId, String), bool>> = Lazy::new(|| {
+    mini_moka::sync::Cache::builder()
+        .max_capacity(10_000)
+        .time_to_live(std::time::Duration::from_secs(60 * 60))
+        .build()
+});
+
+/// Check whether a reaction event is a redelivery of one we already
+/// processed, and remember it if not.
+pub(crate) fn is_duplicate_reaction(r: &Reaction, added: bool) -> bool {
+    let Some(user) = r.user_id else { return false; };
+    let key = (r.message_id, user, r.emoji.to_string());
+    if LAST_REACTION_EVENT.get(&key) == Some(added) { return true; }
+    LAST_REACTION_EVENT.insert(key, added);
+    false
+}
+
+/// Forget the reactions on a message after they were cleared, so adding
+/// them again isn’t mistaken for a redelivery. Without `emoji`, all of
+/// them are forgotten.
+pub(crate) fn forget_reactions(message: MessageId, emoji: Option<&ReactionType>) {
+    let emoji = emoji.map(|e| e.to_string());
+    let keys = LAST_REACTION_EVENT.iter()
+        .map(|e| e.key().clone())
+        .filter(|(m, _, e)| *m == message && emoji.as_ref().is_none_or(|emoji| e == emoji))
+        .collect::<Vec<_>>();
+    for key in keys { LAST_REACTION_EVENT.invalidate(&key); }
+}
+
+/// Reply to messages that match an automatic response.
+async fn autorespond(ctx: &Context, message: &Message) -> Res {
+    let content = message.content.to_lowercase();
+    for (id, trigger, response) in sql::get_autoresponses().await? {
+        if !content.contains(&trigger.to_lowercase()) { continue; }
+        if AUTORESPONSE_COOLDOWN.contains_key(&(message.channel_id, id)) { continue; }
+        AUTORESPONSE_COOLDOWN.insert((message.channel_id, id), ());
+        message.reply(ctx, response).await?;
+
+        // One reply per message is enough.
+        break;
+    }
+
     Ok(())
 }
 
 /// Get the confirm emoji.
 fn confirm_reaction() -> ReactionType { return ReactionType::Unicode("✅".into()); }
 
+/// Emoji people vote with unless admins chose another one.
+const DEFAULT_VOTE_EMOJI: &str = "👍";
+
+/// Get the emoji people vote with.
+pub async fn vote_reaction() -> ReactionType {
+    let emoji = match sql::get_vote_emoji().await {
+        Ok(emoji) => emoji,
+        Err(e) => {
+            err!("Error getting vote emoji: {}", e);
+            None
+        }
+    };
+
+    emoji.and_then(|e| ReactionType::try_from(e.as_str()).ok())
+        .unwrap_or_else(|| ReactionType::Unicode(DEFAULT_VOTE_EMOJI.into()))
+}
+
+/// Check whether an emoji is the one people vote with. Custom emoji are
+/// compared by ID only, since their name can change.
+async fn is_vote_reaction(emoji: &ReactionType) -> bool {
+    match (emoji, vote_reaction().await) {
+        (ReactionType::Custom { id: a, .. }, ReactionType::Custom { id: b, .. }) => *a == b,
+        (a, b) => *a == b,
+    }
+}
+
+/// Switch the vote emoji from `old` to `new` and let people know in the
+/// submission channels. Our own reactions on the submissions of this week
+/// and of the week being voted on are replaced with the new emoji.
+///
+/// Votes cast with the old emoji are kept. With `recount`, reactions with
+/// the old emoji on those submissions are read again, so votes made while
+/// we weren’t listening count too. Returns the number of votes added.
+pub async fn migrate_vote_emoji(api: &dyn DiscordApi, old: &ReactionType, new: &str, recount: bool) -> Result<usize, Error> {
+    if !is_vote_reaction(old).await {
+        return Err(format!("{} is not the current vote emoji", old).into());
+    }
+
+    sql::set_vote_emoji(Some(new)).await?;
+    let new = vote_reaction().await;
+    let week = sql::current_week().await?;
+    let weeks = [week - 1, week].into_iter().filter(|&w| w >= 0).collect::<Vec<_>>();
+    let mut added = 0;
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        let channel = challenge.submission_channel();
+        let seeded = sql::get_setting::<bool>(Setting::seed_reactions(challenge)).await?;
+        let mut submissions = Vec::new();
+        for &week in &weeks { submissions.extend(sql::get_submissions(week, challenge).await?); }
+        for s in submissions {
+            let message = MessageId::new(s.message as u64);
+            if recount {
+                match api.reaction_users(channel, message, old.clone()).await {
+                    Ok(users) => for user in users.into_iter().filter(|&u| u != api.current_user_id()) {
+                        let weight = vote_weight(api, user, None).await?;
+                        if sql::add_vote(message, user, weight).await? { added += 1; }
+                    },
+                    Err(e) => err!("Error getting reactions on submission {}: {}", message, e),
+                }
+            }
+
+            if seeded {
+                if let Err(e) = api.delete_reaction(channel, message, None, old.clone()).await {
+                    err!("Error removing old vote emoji from submission {}: {}", message, e);
+                }
+
+                if let Err(e) = api.add_reaction(channel, message, new.clone()).await {
+                    err!("Error adding new vote emoji to submission {}: {}", message, e);
+                }
+            }
+        }
+
+        let notice = format!("We now vote with {} instead of {}. Votes you’ve already cast still count.", new, old);
+        if let Err(e) = api.send_message(channel, CreateMessage::new().content(notice)).await {
+            err!("Error announcing the new vote emoji in {}: {}", channel, e);
+        }
+    }
+
+    Ok(added)
+}
+
+/// Add our reactions to an accepted submission: the confirmation, and the
+/// vote emoji if that is enabled for the challenge and we don’t vote with
+/// ballots.
+///
+/// Reacting with an emoji we’ve already added does nothing, so this is
+/// safe to call again for the same submission.
+async fn seed_reactions(api: &dyn DiscordApi, channel: ChannelId, message: MessageId, challenge: Challenge) -> Res {
+    api.add_reaction(channel, message, confirm_reaction()).await?;
+    if sql::get_setting::<bool>(Setting::seed_reactions(challenge)).await?
+        && !sql::get_setting::<bool>(Setting::BallotVoting).await? {
+        api.add_reaction(channel, message, vote_reaction().await).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the weight of someone’s vote from their roles, in percent. Their
+/// roles are only looked up if some role has a weight; `member` saves
+/// looking them up if we have it anyway.
+///
+/// If we can’t get their roles, e.g. because they left, the vote counts
+/// with the default weight.
+pub async fn vote_weight(api: &dyn DiscordApi, voter: UserId, member: Option<&Member>) -> Result<i64, Error> {
+    let weights = sql::get_vote_weights().await?;
+    if weights.is_empty() { return Ok(sql::DEFAULT_VOTE_WEIGHT); }
+    let roles = match member {
+        Some(m) => m.roles.clone(),
+        None => match api.member_roles(voter).await {
+            Ok(roles) => roles,
+            Err(e) => {
+                warn!("Could not get the roles of {} to weight their vote: {}", voter, e);
+                vec![]
+            }
+        },
+    };
+
+    Ok(tally::vote_weight(&weights, &roles))
+}
+
+/// Check whether voting on a submission has closed. This is also true if
+/// the message isn’t a submission (anymore).
+async fn voting_closed(message: MessageId) -> Result<bool, Error> {
+    let Some(s) = sql::get_submission(message).await? else { return Ok(true); };
+    Ok(clock::now() >= scheduler::voting_deadline(s.week).await?)
+}
+
+/// Check whether vote reactions on a submission currently count, i.e.
+/// voting doesn’t use ballots and hasn’t closed yet.
+async fn reactions_are_votes(message: MessageId) -> bool {
+    // Reactions don’t count while voting uses ballots.
+    match sql::get_setting::<bool>(Setting::BallotVoting).await {
+        Ok(false) => {}
+        Ok(true) => return false,
+        Err(e) => {
+            err!("Error checking whether voting uses ballots: {}", e);
+            return false;
+        }
+    }
+
+    // The results are final once voting on the entry’s week has closed.
+    match voting_closed(message).await {
+        Ok(closed) => !closed,
+        Err(e) => {
+            err!("Error checking whether voting on {} has closed: {}", message, e);
+            false
+        }
+    }
+}
+
+/// Count or take back a vote on a submission.
+async fn record_vote(api: &dyn DiscordApi, r: &Reaction, added: bool) {
+    let Some(voter) = r.user_id else { return; };
+    if challenge_for_channel(r.channel_id).is_none() { return; }
+
+    // Don’t count the vote emoji we seeded ourselves.
+    if voter == api.current_user_id() { return; }
+    if !reactions_are_votes(r.message_id).await { return; }
+
+    let res = match added {
+        true => match vote_weight(api, voter, r.member.as_ref()).await {
+            Ok(weight) => sql::add_vote(r.message_id, voter, weight).await,
+            Err(e) => Err(e),
+        },
+        false => sql::remove_vote(r.message_id, voter).await,
+    };
+
+    if let Err(e) = res { err!("Error recording vote of {} on {}: {}", voter, r.message_id, e); }
+}
+
+/// Re-add our reactions to all of this week’s submissions, e.g. in case
+/// the bot was offline when some of them were made or the setting changed.
+pub async fn resync_reactions(ctx: &Context) -> Res {
+    let week = sql::current_week().await?;
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        for s in sql::get_submissions(week, challenge).await? {
+            let message = MessageId::new(s.message as u64);
+            if let Err(e) = seed_reactions(ctx, challenge.submission_channel(), message, challenge).await {
+                err!("Error adding reactions to submission {}: {}", message, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of recent messages in each submission channel that
+/// [`resync_submissions()`] looks at.
+const RESYNC_MESSAGES: u8 = 100;
+
+/// What [`resync_submissions()`] changed.
+#[derive(Clone, Debug, Default)]
+pub struct ResyncReport {
+    /// Submissions whose submit emoji we missed.
+    pub added: Vec<(ChannelId, MessageId)>,
+
+    /// Submissions whose submit emoji was taken back.
+    pub removed: Vec<(ChannelId, MessageId)>,
+
+    /// Messages that were submitted but aren’t valid submissions; their
+    /// submit emoji was removed and the author told why.
+    pub rejected: Vec<(ChannelId, MessageId)>,
+
+    /// Submissions whose submit emoji was taken back after the withdrawal
+    /// cutoff, so they stay in.
+    pub kept: Vec<(ChannelId, MessageId)>,
+}
+
+impl ResyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.rejected.is_empty() && self.kept.is_empty()
+    }
+}
+
+/// Reconcile this week’s messages in the submission channels with the
+/// submissions we have, in case someone added or removed the submit emoji
+/// while we weren’t listening. Only the most recent messages are checked.
+pub async fn resync_submissions(api: &dyn DiscordApi) -> Result<ResyncReport, Error> {
+    let week = sql::current_week().await?;
+    let start = scheduler::week_start(week).await?.timestamp();
+    let closed = clock::now() >= scheduler::withdrawal_cutoff().await?;
+    let mut report = ResyncReport::default();
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        let channel = challenge.submission_channel();
+        // Entries made with the command never had a submit emoji to lose.
+        let active = sql::get_reaction_submissions(week, challenge).await?;
+
+        for message in api.recent_messages(channel, RESYNC_MESSAGES).await? {
+            if message.author.bot || message.timestamp.unix_timestamp() < start { continue; }
+
+            // Only the author’s own submit emoji counts.
+            let submit = message.reactions.iter().find(|r| is_submit_reaction(&r.reaction_type));
+            let submitted = match submit {
+                Some(r) => match api.reaction_users(channel, message.id, r.reaction_type.clone()).await {
+                    Ok(users) => users.contains(&message.author.id),
+                    Err(e) => {
+                        err!("Error getting reactions on {}: {}", message.id, e);
+                        continue;
+                    }
+                },
+                None => false,
+            };
+
+            match (submitted, active.contains(&message.id)) {
+                (true, false) => {
+                    // Don’t bring back entries that were disqualified or withdrawn otherwise.
+                    if sql::get_submission(message.id).await?.is_some() { continue; }
+                    match check_submission(&message, challenge).await {
+                        Ok(image) => {
+                            accept_submission(api, &message, challenge, &image, false).await?;
+                            report.added.push((channel, message.id));
+                        }
+
+                        Err(e) => {
+                            report_user_error(api, message.author.id, &e).await;
+                            if let Some(r) = submit {
+                                let res = api.delete_reaction(channel, message.id, Some(message.author.id), r.reaction_type.clone()).await;
+                                if let Err(e) = res { err!("Error removing reaction: {}", e); }
+                            }
+
+                            report.rejected.push((channel, message.id));
+                        }
+                    }
+                }
+
+                (false, true) if closed => report.kept.push((channel, message.id)),
+                (false, true) => {
+                    withdraw_submission(api, &message, challenge, WithdrawalReason::Resync).await?;
+                    report.removed.push((channel, message.id));
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Get the challenge whose submissions are posted in a channel.
+pub fn challenge_for_channel(channel: ChannelId) -> Option<Challenge> {
+    [Challenge::Glyph, Challenge::Ambigram].into_iter().find(|c| c.submission_channel() == channel)
+}
+
+/// Check whether an attachment is marked as a spoiler.
+fn is_spoiler(att: &Attachment) -> bool {
+    att.filename.starts_with("SPOILER_")
+}
+
+/// The image of a submission.
+pub struct SubmittedImage {
+    pub url: String,
+    pub spoiler: bool,
+}
+
+/// Find the first link in a message, if any.
+fn find_link(content: &str) -> Option<&str> {
+    content.split(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '|'))
+        .find(|w| w.starts_with("https://") || w.starts_with("http://"))
+}
+
+/// Get the host of a link, without the port.
+fn link_host(link: &str) -> Option<String> {
+    let rest = link.strip_prefix("https://").or_else(|| link.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?.trim_end_matches('.');
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Check a host against the allow and deny lists, given as (host, allowed).
+/// Entries also match subdomains, and denying a host takes precedence.
+/// Links must come from an allowed host, so without any, none are accepted.
+pub fn check_link_host(host: &str, hosts: &[(String, bool)]) -> Result<(), String> {
+    let matches = |h: &str| host == h || host.ends_with(&format!(".{}", h));
+    if hosts.iter().any(|(h, allowed)| !allowed && matches(h)) {
+        return Err(format!("Links to {} are not allowed", host));
+    }
+
+    let allowed = hosts.iter().filter(|(_, allowed)| *allowed).map(|(h, _)| h.as_str()).collect::<Vec<_>>();
+    if allowed.is_empty() {
+        return Err("No hosts are allowed for linked images yet; please upload your entry instead".into());
+    }
+
+    if !allowed.iter().any(|h| matches(h)) {
+        return Err(format!("Linked images must be hosted on one of: {}", allowed.join(", ")));
+    }
+
+    Ok(())
+}
+
+/// Check the link in a message that has no attachments, if link
+/// submissions are enabled.
+async fn check_link(message: &Message, challenge: Challenge) -> Result<(SubmittedImage, Option<(u32, u32)>, bool), String> {
+    let Some(link) = find_link(&message.content) else {
+        return Err(with_rules("Submissions must contain exactly one image", challenge).await);
+    };
+
+    let enabled = match sql::get_setting::<bool>(Setting::LinkSubmissions).await {
+        Ok(enabled) => enabled,
+        Err(e) => {
+            err!("Error checking whether link submissions are enabled: {}", e);
+            false
+        }
+    };
+
+    if !enabled {
+        return Err(with_rules("Submissions must be uploaded as images, not linked", challenge).await);
+    }
+
+    if !link.starts_with("https://") {
+        return Err(with_rules("Linked images must use https", challenge).await);
+    }
+
+    let Some(host) = link_host(link) else {
+        return Err(with_rules(&format!("‘{}’ is not a valid link", link), challenge).await);
+    };
+
+    let hosts = match sql::get_link_hosts().await {
+        Ok(hosts) => hosts,
+        Err(e) => {
+            err!("Error getting link hosts: {}", e);
+            return Err("Sorry, linked submissions can’t be checked right now; please try again later".into());
+        }
+    };
+
+    if let Err(e) = check_link_host(&host, &hosts) {
+        return Err(with_rules(&e, challenge).await);
+    }
+
+    // Discord only tells us the size if it embedded the image.
+    let size = message.embeds.iter().find_map(|e| {
+        let (width, height) = e.image.as_ref().map(|i| (i.width, i.height))
+            .or_else(|| e.thumbnail.as_ref().map(|t| (t.width, t.height)))?;
+        Some((width?, height?))
+    });
+
+    let gif = link.split(['?', '#']).next().unwrap_or(link).to_lowercase().ends_with(".gif");
+    let spoiler = message.content.split("||").skip(1).step_by(2).any(|s| s.contains(link));
+    Ok((SubmittedImage { url: link.to_string(), spoiler }, size, gif))
+}
+
+/// Check that a message can be submitted, and get the image it contains.
+///
+/// On error, returns a message for the user that includes the rules.
+pub async fn check_submission(message: &Message, challenge: Challenge) -> Result<SubmittedImage, String> {
+    match scheduler::is_active(challenge).await {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("The {} Challenge is on hiatus, so it isn’t taking submissions right now", challenge.name())),
+        Err(e) => err!("Error checking whether {:?} is active: {}", challenge, e),
+    }
+
+    // Entries are only taken while this week’s submissions are open, and
+    // only for images posted since they opened.
+    let window = match sql::current_week().await {
+        Ok(week) => scheduler::submission_window(week).await,
+        Err(e) => Err(e),
+    };
+
+    match window {
+        Ok((_, end)) if clock::now() >= end => return Err(format!(
+            "Submissions for this week’s {} Challenge closed {}, so your entry wasn’t accepted. The next prompt will be announced soon",
+            challenge.name(),
+            timestamp(end, TimestampStyle::Relative),
+        )),
+        Ok((start, _)) if message.timestamp.unix_timestamp() < start.timestamp() => return Err(
+            "This was posted before this week’s challenge started, so it can’t be entered. Please post it again to enter it".into()
+        ),
+        Ok(_) => {}
+        Err(e) => err!("Error getting the submission window: {}", e),
+    }
+
+    // Check the message for attachments; without any, it may be a link.
+    let (image, size, gif) = match message.attachments.as_slice() {
+        [att] => {
+            // Error if the attachment is not an image.
+            //
+            // There doesn’t really seem to be a way of checking what an attachment
+            // actually is (excepting checking the mime type, which I’m not willing
+            // to do), so checking whether the height exists, which it only should
+            // for images, will have to do.
+            let (Some(width), Some(height)) = (att.width, att.height) else {
+                return Err(with_rules("Submissions must contain only images", challenge).await);
+            };
+
+            let image = SubmittedImage { url: att.url.clone(), spoiler: is_spoiler(att) };
+            (image, Some((width, height)), att.filename.to_lowercase().ends_with(".gif"))
+        }
+
+        [] => check_link(message, challenge).await?,
+        _ => return Err(with_rules("Submissions must contain exactly one image", challenge).await),
+    };
+
+    // Only check what we can tell without downloading the image; whether
+    // a WebP is animated, for instance, can’t be seen from its name.
+    let constraints = match sql::get_constraints(challenge).await {
+        Ok(c) => c,
+        Err(e) => {
+            err!("Error getting constraints for {:?}: {}", challenge, e);
+            challenge.default_constraints()
+        }
+    };
+
+    match size {
+        Some((width, height)) => if let Err(e) = constraints.check_size(width, height) {
+            return Err(with_rules(&e, challenge).await);
+        },
+
+        // Discord may not have embedded a link yet, in which case we can’t
+        // tell whether it meets the constraints.
+        None if constraints.min_size != 0 || constraints.max_size != 0 || constraints.square => return Err(with_rules(
+            "Discord hasn’t shown a preview of your linked image yet, so we can’t check its size. Please wait for the preview to appear and submit it again",
+            challenge,
+        ).await),
+
+        None => {}
+    }
+
+    if !constraints.animation && gif {
+        return Err(with_rules("Animated submissions are not allowed for this challenge", challenge).await);
+    }
+
+    // Blind challenges keep entries hidden until voting opens.
+    let blind = match sql::get_setting::<bool>(Setting::blind(challenge)).await {
+        Ok(blind) => blind,
+        Err(e) => {
+            err!("Error checking whether {:?} is blind: {}", challenge, e);
+            false
+        }
+    };
+
+    if blind && !image.spoiler {
+        return Err(with_rules("Submissions for this challenge must be marked as spoilers", challenge).await);
+    }
+
+    // Unless a new entry replaces the old one, there can only be one.
+    if !replaces_entries().await {
+        match sql::get_current_entry(message.author.id, challenge).await {
+            Ok(Some(existing)) if existing != message.id => return Err(format!(
+                "You’ve already [entered]({}) this week’s {} Challenge. To enter this instead, first withdraw that entry by removing your reaction from it",
+                submission_link(challenge, existing),
+                challenge.name(),
+            )),
+            Ok(_) => {}
+            Err(e) => err!("Error checking for earlier entries by {}: {}", message.author.id, e),
+        }
+    }
+
+    Ok(image)
+}
+
+/// Whether a second entry in a week replaces the first, instead of being rejected.
+async fn replaces_entries() -> bool {
+    match sql::get_setting::<bool>(Setting::ReplaceEntries).await {
+        Ok(replace) => replace,
+        Err(e) => {
+            err!("Error checking whether entries are replaced: {}", e);
+            false
+        }
+    }
+}
+
+/// Link to a submission message.
+pub(crate) fn submission_link(challenge: Challenge, message: MessageId) -> String {
+    format!("https://discord.com/channels/{}/{}/{}", config::get().server, challenge.submission_channel(), message)
+}
+
+/// Add a submission that has passed [`check_submission()`] and confirm it.
+/// `by_command` is set if it was submitted with the command rather than
+/// the submit emoji.
+///
+/// Only fails if the submission could not be added; anything after
+/// that is merely logged.
+pub async fn accept_submission(
+    api: &dyn DiscordApi,
+    message: &Message,
+    challenge: Challenge,
+    image: &SubmittedImage,
+    by_command: bool,
+) -> Res {
+    let user = message.author.id;
+    let replaced = match sql::get_current_entry(user, challenge).await? {
+        Some(old) if old != message.id && replaces_entries().await => {
+            sql::replace_submission(old, message.id, challenge, &image.url, image.spoiler, by_command).await?.then_some(old)
+        }
+        _ => None,
+    };
+
+    if replaced.is_none() && !sql::add_submission(message.id, challenge, user, &image.url, image.spoiler, by_command).await? {
+        info!("Ignoring duplicate submission {} from {}", message.id, user);
+        return Ok(());
+    }
+
+    if let Some(old) = replaced {
+        info!("Submission {} from {} replaced {}", message.id, user, old);
+        retire_replaced_entry(api, challenge, user, old).await;
+    }
+
+    // Refresh the author’s cached name while we’re at it.
+    if let Err(e) = sql::update_user_cache(&message.author).await {
+        err!("Error updating cached name of {}: {}", user, e);
+    }
+
+    // Done.
+    info!("Added submission {} from {} for challenge {:?}", message.id, user, challenge);
+    if let Err(e) = seed_reactions(api, message.channel_id, message.id, challenge).await {
+        err!("Error reacting to submission: {}", e);
+    }
+
+    // Celebrate if this is their first submission ever.
+    match sql::count_user_submissions(user).await {
+        Ok(1) => if let Err(e) = celebrate_first_submission(api, message).await {
+            err!("Error celebrating first submission of {}: {}", user, e);
+        },
+        Ok(_) => {}
+        Err(e) => err!("{}", e),
+    }
+
+    Ok(())
+}
+
+/// Clean up after an entry was replaced by a newer one, and tell the author.
+async fn retire_replaced_entry(api: &dyn DiscordApi, challenge: Challenge, author: UserId, old: MessageId) {
+    // Take the author’s reaction off too, so a resync doesn’t bring it back.
+    let channel = challenge.submission_channel();
+    let submit = ReactionType::Custom { animated: false, id: config::get().submit_emoji, name: None };
+    for (user, emoji) in [(Some(author), submit), (None, confirm_reaction()), (None, vote_reaction().await)] {
+        let _ = api.delete_reaction(channel, old, user, emoji).await;
+    }
+
+    let text = format!(
+        "Your new entry for the {} Challenge replaced your [earlier one]({}), which no longer counts.",
+        challenge.name(),
+        submission_link(challenge, old),
+    );
+
+    if let Err(e) = dm::send(api, author, DmKind::Essential, CreateMessage::new().content(text)).await {
+        err!("Could not tell {} that their entry was replaced: {}", author, e);
+    }
+}
+
+/// Check if a reaction is the submit emoji.
+fn is_submit_reaction(emoji: &ReactionType) -> bool {
+    matches!(emoji, ReactionType::Custom { id, .. } if *id == config::get().submit_emoji)
+}
+
 /// Check if we care about a reaction event.
-async fn match_relevant_reaction_event(ctx: &Context, r: &Reaction) -> Option<(
+async fn match_relevant_reaction_event(api: &dyn DiscordApi, r: &Reaction) -> Option<(
     UserId,
     Message,
     Challenge,
 )> {
     // Ignore anything that isn’t the emoji we care about.
-    if !matches!(r.emoji, ReactionType::Custom {id: SUBMIT_EMOJI_ID, .. }) { return None; };
+    if !is_submit_reaction(&r.emoji) { return None; };
 
     // Make sure we have all the information we need.
-    let Some(user) = r.user_id else { return None; };
-    let Ok(message) = r.message(&ctx).await else { return None; };
+    let user = r.user_id?;
+    let Ok(message) = api.get_message(r.channel_id, r.message_id).await else { return None; };
 
     // Ignore this outside of the submission channels.
-    let challenge = match message.channel_id {
-        GLYPH_SUBMISSION_CHANNEL_ID => Challenge::Glyph,
-        AMBIGRAM_SUBMISSION_CHANNEL_ID => Challenge::Ambigram,
-        _ => return None
-    };
+    let challenge = challenge_for_channel(message.channel_id)?;
 
     return Some((user, message, challenge));
 }
 
+/// Maximum number of reaction events waiting to be processed per channel;
+/// handlers wait for room in the queue once it is full.
+const REACTION_QUEUE_SIZE: usize = 256;
+
+/// What happened to the reactions on a message.
+enum ReactionChange {
+    Added(Reaction),
+    Removed(Reaction),
+
+    /// A moderator cleared one emoji.
+    EmojiCleared(Reaction),
+
+    /// A moderator cleared all reactions on a message.
+    AllCleared(MessageId),
+}
+
+/// A reaction event waiting to be processed.
+struct ReactionEvent {
+    ctx: Context,
+    channel: ChannelId,
+    change: ReactionChange,
+}
+
+impl ReactionEvent {
+    /// The reaction this is about, for coalescing; clearing reactions
+    /// isn’t about any single one.
+    fn key(&self) -> Option<(MessageId, Option<UserId>, String)> {
+        match &self.change {
+            ReactionChange::Added(r) | ReactionChange::Removed(r) => Some((r.message_id, r.user_id, r.emoji.to_string())),
+            ReactionChange::EmojiCleared(_) | ReactionChange::AllCleared(_) => None,
+        }
+    }
+}
+
+/// Reaction events are processed in order, one channel at a time, so a
+/// burst of reactions doesn’t spawn lots of handlers that all wait on the
+/// DB and run into rate limits.
+static REACTION_QUEUES: Lazy<Mutex<HashMap<ChannelId, mpsc::Sender<ReactionEvent>>>> = Lazy::new(Default::default);
+
+/// Queue a reaction event for processing.
+async fn enqueue_reaction(ctx: Context, channel: ChannelId, change: ReactionChange) {
+    // We only care about reactions in the submission channels.
+    if challenge_for_channel(channel).is_none() { return; }
+    let queue = REACTION_QUEUES.lock().unwrap()
+        .entry(channel)
+        .or_insert_with(spawn_reaction_worker)
+        .clone();
+
+    if queue.send(ReactionEvent { ctx, channel, change }).await.is_err() {
+        err!("Reaction queue closed; dropping reaction event in {}", channel);
+    }
+}
+
+/// Start a worker that processes queued reaction events.
+fn spawn_reaction_worker() -> mpsc::Sender<ReactionEvent> {
+    let (tx, mut rx) = mpsc::channel::<ReactionEvent>(REACTION_QUEUE_SIZE);
+    tokio::spawn(async move {
+        while let Some(first) = rx.recv().await {
+            let Some(_work) = begin_work() else { break; };
+
+            // Grab everything else that is waiting as well.
+            let mut batch = vec![first];
+            while let Ok(ev) = rx.try_recv() { batch.push(ev); }
+            for ev in coalesce_reactions(batch) {
+                match ev.change {
+                    ReactionChange::Added(r) => on_reaction_add(&ev.ctx, r).await,
+                    ReactionChange::Removed(r) => on_reaction_remove(&ev.ctx, r).await,
+                    ReactionChange::EmojiCleared(r) => on_reaction_emoji_cleared(&ev.ctx, r).await,
+                    ReactionChange::AllCleared(message) => on_reactions_cleared(&ev.ctx, ev.channel, message).await,
+                }
+            }
+        }
+    });
+
+    tx
+}
+
+/// Only keep the last event for each reaction in a batch; e.g. if someone
+/// adds and removes a vote before we get to it, the vote is just removed.
+/// Cleared reactions are always kept, in order.
+fn coalesce_reactions(batch: Vec<ReactionEvent>) -> Vec<ReactionEvent> {
+    let mut last = HashMap::new();
+    for (i, ev) in batch.iter().enumerate() {
+        if let Some(key) = ev.key() { last.insert(key, i); }
+    }
+
+    batch.into_iter()
+        .enumerate()
+        .filter(|(i, ev)| ev.key().is_none_or(|key| last[&key] == *i))
+        .map(|(_, ev)| ev)
+        .collect()
+}
+
+/// Check whether a user added the submit emoji.
+pub(crate) async fn on_reaction_add(api: &dyn DiscordApi, r: Reaction) {
+    if is_vote_reaction(&r.emoji).await { return record_vote(api, &r, true).await; }
+    let Some((user, message, challenge)) =
+        match_relevant_reaction_event(api, &r).await else { return; };
+
+    // Helper to remove the reaction on error and return.
+    macro_rules! remove_reaction {
+        ($api:expr, $r:expr) => {
+            let res = $api.delete_reaction($r.channel_id, $r.message_id, $r.user_id, $r.emoji.clone()).await;
+            if let Err(e) = res { err!("Error removing reaction: {}", e); }
+            return;
+        };
+    }
+
+    // If someone reacted w/ this emoji to someone else’s message, remove it.
+    if user != message.author.id { remove_reaction!(api, r); }
+
+    // Make sure this is actually a valid submission.
+    let image = match check_submission(&message, challenge).await {
+        Ok(image) => image,
+        Err(e) => {
+            report_user_error(api, user, &e).await;
+            remove_reaction!(api, r);
+        }
+    };
+
+    run!(api, user, accept_submission(api, &message, challenge, &image, false).await, "Error adding submission");
+}
+
+/// Check whether a user removed the submit emoji.
+pub(crate) async fn on_reaction_remove(api: &dyn DiscordApi, r: Reaction) {
+    if is_vote_reaction(&r.emoji).await { return record_vote(api, &r, false).await; }
+
+    // Check if we care about this.
+    let Some((user, message, challenge)) =
+        match_relevant_reaction_event(api, &r).await else { return; };
+
+    // If the reaction that was removed is not the reaction of the
+    // user that sent the message (which I guess can happen if there
+    // is ever some amount of downtime on our part?) then ignore it.
+    if user != message.author.id { return; };
+
+    // Entries can no longer be withdrawn once the cutoff has passed.
+    match scheduler::withdrawal_cutoff().await {
+        Ok(cutoff) if clock::now() >= cutoff => {
+            report_user_error(api, user, "The withdrawal cutoff for this week has passed, so your entry stays in.").await;
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => err!("Error getting withdrawal cutoff: {}", e),
+    }
+
+    run!(api, user, withdraw_submission(api, &message, challenge, WithdrawalReason::Author).await, "Error removing submission");
+}
+
+/// Remove a submission whose submit emoji is gone.
+async fn withdraw_submission(api: &dyn DiscordApi, message: &Message, challenge: Challenge, reason: WithdrawalReason) -> Res {
+    if !sql::remove_submission(message.id, challenge, reason).await? { return Ok(()); }
+
+    // Done.
+    info!("Removed submission {} from {} for challenge {:?}", message.id, message.author.id, challenge);
+
+    // Remove our confirmation reaction. This is allowed to fail in case
+    // it was already removed somehow.
+    let _ = api.delete_reaction(message.channel_id, message.id, None, confirm_reaction()).await;
+    let _ = api.delete_reaction(message.channel_id, message.id, None, vote_reaction().await).await;
+    Ok(())
+}
+
+/// Drop the votes on a submission whose vote reactions were cleared,
+/// unless they don’t count (anymore).
+async fn clear_votes(message: MessageId) {
+    if !reactions_are_votes(message).await { return; }
+    if let Err(e) = sql::clear_votes(message).await {
+        err!("Error clearing votes on {}: {}", message, e);
+    }
+}
+
+/// Remove a submission whose submit emoji a moderator cleared. Returns
+/// whether it was removed; entries stay in after the withdrawal cutoff.
+async fn remove_cleared_submission(message: MessageId, challenge: Challenge) -> bool {
+    match scheduler::withdrawal_cutoff().await {
+        Ok(cutoff) if clock::now() >= cutoff => {
+            info!("Keeping submission {} whose submit emoji was cleared after the withdrawal cutoff", message);
+            return false;
+        }
+        Ok(_) => {}
+        Err(e) => err!("Error getting withdrawal cutoff: {}", e),
+    }
+
+    match sql::remove_submission(message, challenge, WithdrawalReason::ReactionsCleared).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            err!("Error removing submission {}: {}", message, e);
+            false
+        }
+    }
+}
+
+/// Put our confirmation back on a message if it is still a submission.
+async fn restore_confirmation(api: &dyn DiscordApi, channel: ChannelId, message: MessageId, challenge: Challenge) {
+    match sql::is_submission(message, challenge).await {
+        Ok(true) => {
+            if let Err(e) = api.add_reaction(channel, message, confirm_reaction()).await {
+                err!("Error restoring confirmation reaction on {}: {}", message, e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => err!("Error checking submission {}: {}", message, e),
+    }
+}
+
+/// A moderator cleared all reactions on a message, which takes the
+/// submit reaction and the votes with it, so drop the submission and
+/// its votes as well.
+pub(crate) async fn on_reactions_cleared(api: &dyn DiscordApi, channel: ChannelId, message: MessageId) {
+    let Some(challenge) = challenge_for_channel(channel) else { return; };
+    clear_votes(message).await;
+    if remove_cleared_submission(message, challenge).await {
+        info!("Removed submission {} for challenge {:?} after all reactions were cleared", message, challenge);
+    }
+
+    // Our confirmation was cleared as well.
+    restore_confirmation(api, channel, message, challenge).await;
+}
+
+/// Same as above, but only a single emoji was cleared.
+pub(crate) async fn on_reaction_emoji_cleared(api: &dyn DiscordApi, r: Reaction) {
+    let Some(challenge) = challenge_for_channel(r.channel_id) else { return; };
+
+    // The submit emoji was cleared; remove the submission and our
+    // confirmation reaction. The latter is allowed to fail.
+    if is_submit_reaction(&r.emoji) {
+        if !remove_cleared_submission(r.message_id, challenge).await { return; }
+        info!("Removed submission {} for challenge {:?} after the submit emoji was cleared", r.message_id, challenge);
+        let _ = api.delete_reaction(r.channel_id, r.message_id, None, confirm_reaction()).await;
+    }
+
+    // The votes were cleared, so forget about them.
+    else if is_vote_reaction(&r.emoji).await {
+        clear_votes(r.message_id).await;
+    }
+
+    // Our confirmation was cleared; put it back if this is still a submission.
+    else if r.emoji == confirm_reaction() {
+        restore_confirmation(api, r.channel_id, r.message_id, challenge).await;
+    }
+}
+
 #[async_trait]
 impl EventHandler for GlyfiEvents {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Some(_work) = begin_work() else { return; };
         match interaction {
             // Ignore commands here.
             Interaction::Command(_) => {}
 
             // Buttons and other components.
             Interaction::Component(mut i) => {
+                if is_duplicate_interaction(i.id) { return; }
                 info!("Processing interaction: {}", i.data.custom_id);
-                let id: InteractionID = match i.data.custom_id.parse() {
+                let id = match InteractionID::decode(&i.data.custom_id) {
                     Ok(id) => id,
                     Err(e) => {
-                        err!("{}", e);
-                        let _ = i.create_response(ctx, CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content(format!("Error: Unknown ID '{}'", i.data.custom_id))
-                                .ephemeral(true)
-                        )).await;
+                        warn!("Rejected interaction ‘{}’: {}", i.data.custom_id, e);
+                        let _ = reply_ephemeral!(ctx, i, "{}", e);
                         return;
                     }
                 };
 
+                // Reject components that have expired.
+                match sql::is_component_expired(&i.data.custom_id).await {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        let _ = reply_ephemeral!(ctx, i, "This has expired. Please run the command again.");
+                        return;
+                    }
+                    Err(e) => err!("Error checking component expiry: {}", e),
+                }
+
                 let res = match id {
-                    InteractionID::ConfirmAnnouncement => act_on_confirm_announcement(&ctx, &mut i).await,
-                    InteractionID::CancelPrompt => act_on_cancel_prompt(&ctx, &mut i).await,
+                    InteractionID::ConfirmAnnouncement { challenge, mtime, prompt } => act_on_confirm_announcement(&ctx, &mut i, challenge, mtime, prompt).await,
+                    InteractionID::CancelPrompt { prompt } => act_on_cancel_prompt(&ctx, &mut i, prompt).await,
+                    InteractionID::DeleteUserData { user } => act_on_delete_user_data(&ctx, &mut i, user).await,
+                    InteractionID::RegeneratePrompt { prompt } => act_on_regenerate_prompt(&ctx, &mut i, prompt).await,
+                    InteractionID::OptOutDms => act_on_opt_out_dms(&ctx, &mut i).await,
+                    InteractionID::Ballot { ballot, action } => act_on_ballot(&ctx, &mut i, ballot, action).await,
+                    InteractionID::PromptPick { pick, action } => act_on_prompt_pick(&ctx, &mut i, pick, action).await,
+                    InteractionID::PollVote { poll, option } => act_on_poll_vote(&ctx, &mut i, poll, option).await,
+                    InteractionID::RemoveVote { message } => act_on_remove_vote(&ctx, &mut i, message).await,
+
+                    // Handled by the collectors in core::paginate() and core::pick().
+                    InteractionID::Paginate { .. } | InteractionID::Pick { .. } => Ok(()),
                 };
 
                 if let Err(e) = res {
@@ -131,83 +1240,112 @@ impl EventHandler for GlyfiEvents {
         }
     }
 
-    /// Check whether a user added the submit emoji.
     async fn reaction_add(&self, ctx: Context, r: Reaction) {
-        let Some((user, message, challenge)) =
-            match_relevant_reaction_event(&ctx, &r).await else { return; };
-
-        // Helper to remove the reaction on error and return.
-        macro_rules! remove_reaction {
-            ($ctx:expr, $r:expr) => {
-                if let Err(e) = $r.delete(&$ctx).await { err!("Error removing reaction: {}", e); }
-                return;
-            };
-        }
+        let Some(_work) = begin_work() else { return; };
+        if is_duplicate_reaction(&r, true) { return; }
+        enqueue_reaction(ctx, r.channel_id, ReactionChange::Added(r)).await;
+    }
 
-        // If someone reacted w/ this emoji to someone else’s message, remove it.
-        if user != message.author.id { remove_reaction!(ctx, r); }
+    async fn reaction_remove(&self, ctx: Context, r: Reaction) {
+        let Some(_work) = begin_work() else { return; };
+        if is_duplicate_reaction(&r, false) { return; }
+        enqueue_reaction(ctx, r.channel_id, ReactionChange::Removed(r)).await;
+    }
 
-        // Check the message for attachments.
-        if message.attachments.len() != 1 {
-            report_user_error(&ctx, user, "Submissions must contain exactly one image").await;
-            remove_reaction!(ctx, r);
-        }
+    async fn reaction_remove_all(&self, ctx: Context, channel: ChannelId, message: MessageId) {
+        let Some(_work) = begin_work() else { return; };
+        forget_reactions(message, None);
+        enqueue_reaction(ctx, channel, ReactionChange::AllCleared(message)).await;
+    }
+
+    async fn reaction_remove_emoji(&self, ctx: Context, r: Reaction) {
+        let Some(_work) = begin_work() else { return; };
+        forget_reactions(r.message_id, Some(&r.emoji));
+        enqueue_reaction(ctx, r.channel_id, ReactionChange::EmojiCleared(r)).await;
+    }
 
-        // Safe because we just checked that that is an attachment.
-        let att = message.attachments.first().unwrap();
+    /// Withdraw the current submissions of members that leave (if enabled) and
+    /// mark them as former members. Their historical stats are kept as-is.
+    async fn guild_member_removal(&self, _: Context, guild: GuildId, user: User, _: Option<Member>) {
+        let Some(_work) = begin_work() else { return; };
+        if guild != config::get().server { return; }
 
-        // Error if the attachment is not an image.
-        //
-        // There doesn’t really seem to be a way of checking what an attachment
-        // actually is (excepting checking the mime type, which I’m not willing
-        // to do), so checking whether the height exists, which it only should
-        // for images, will have to do.
-        if att.height.is_none() {
-            report_user_error(&ctx, user, "Submissions must contain only images").await;
-            remove_reaction!(ctx, r);
+        match sql::get_setting::<bool>(Setting::WithdrawOnLeave).await {
+            Ok(true) => match sql::withdraw_user_submissions(user.id).await {
+                Ok(withdrawn) => for message in withdrawn {
+                    info!("Withdrew submission {} from {} after they left the server", message, user.id);
+                },
+                Err(e) => err!("Error withdrawing submissions from {}: {}", user.id, e),
+            },
+            Ok(false) => {}
+            Err(e) => err!("{}", e),
         }
 
-        // Add the submission.
-        run!(
-            ctx, user,
-            sql::add_submission(message.id, challenge, user, &att.url).await,
-            "Error adding submission"
-        );
+        if let Err(e) = sql::set_former_member(user.id, true).await {
+            err!("Error marking {} as former member: {}", user.id, e);
+        }
 
-        // Done.
-        info!("Added submission {} from {} for challenge {:?}", message.id, user, challenge);
-        if let Err(e) = message.react(ctx, confirm_reaction()).await {
-            err!("Error reacting to submission: {}", e);
+        // This is the last chance we get to see their current name.
+        if let Err(e) = sql::update_user_cache(&user).await {
+            err!("Error updating cached name of {}: {}", user.id, e);
         }
     }
 
-    async fn reaction_remove(&self, ctx: Context, r: Reaction) {
-        // Check if we care about this.
-        let Some((user, message, challenge)) =
-            match_relevant_reaction_event(&ctx, &r).await else { return; };
+    /// Members that rejoin are no longer former members.
+    async fn guild_member_addition(&self, ctx: Context, member: Member) {
+        let Some(_work) = begin_work() else { return; };
+        if member.guild_id != config::get().server { return; }
+        if let Err(e) = sql::set_former_member(member.user.id, false).await {
+            err!("Error unmarking {} as former member: {}", member.user.id, e);
+        }
 
-        // If the reaction that was removed is not the reaction of the
-        // user that sent the message (which I guess can happen if there
-        // is ever some amount of downtime on our part?) then ignore it.
-        if user != message.author.id { return; };
+        if let Err(e) = sql::update_user_cache(&member.user).await {
+            err!("Error updating cached name of {}: {}", member.user.id, e);
+        }
 
-        // Remove the submission.
-        run!(
-            ctx, user,
-            sql::remove_submission(message.id, challenge).await,
-            "Error removing submission"
-        );
+        // Send them a primer, if enabled and they haven’t opted out before.
+        if member.user.bot { return; }
+        match sql::get_setting::<bool>(Setting::WelcomeDm).await {
+            Ok(true) => if let Err(e) = send_welcome_dm(&ctx, &member.user).await {
+                info!("Could not send welcome DM to {}: {}", member.user.id, e);
+            },
+            Ok(false) => {}
+            Err(e) => err!("{}", e),
+        }
+    }
 
-        // Done.
-        info!("Removed submission {} from {} for challenge {:?}", message.id, user, challenge);
+    /// Answer common questions in the submission channels.
+    async fn message(&self, ctx: Context, message: Message) {
+        let Some(_work) = begin_work() else { return; };
+        if message.author.bot || challenge_for_channel(message.channel_id).is_none() { return; }
+        if let Err(e) = autorespond(&ctx, &message).await {
+            err!("Error sending automatic response: {}", e);
+        }
+    }
 
-        // Remove our confirmation reaction. This is allowed to fail in case
-        // it was already removed somehow.
-        let me = ctx.cache.current_user().id;
-        let _ = message.delete_reaction(ctx, Some(me), confirm_reaction()).await;
+    async fn ratelimit(&self, info: RatelimitInfo) {
+        info!("Hit rate limit on {:?} {}; waiting {:?}", info.method, info.path, info.timeout);
+        note_ratelimit(&info);
     }
 
-    async fn ready(&self, _ctx: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        let Some(_work) = begin_work() else { return; };
         info_sync!("Glyfi running with id {}", ready.user.id);
+        preflight::startup_checks(&ctx).await;
+        match resync_submissions(&ctx).await {
+            Ok(report) if report.is_empty() => {}
+            Ok(report) => info!(
+                "Resynced submissions: {} added, {} removed, {} rejected, {} kept after the cutoff",
+                report.added.len(),
+                report.removed.len(),
+                report.rejected.len(),
+                report.kept.len()
+            ),
+            Err(e) => err!("Error resyncing submissions: {}", e),
+        }
+
+        if let Err(e) = resync_reactions(&ctx).await {
+            err!("Error resyncing submission reactions: {}", e);
+        }
     }
 }
@@ -0,0 +1,97 @@
+//! Compare rendered images against golden PNGs in `fixtures/golden`.
+//!
+//! Run the tests with `GLYFI_UPDATE_GOLDEN=1` to (re)write the golden
+//! images after an intended change to a renderer, and check the new
+//! images before committing them.
+
+use std::path::PathBuf;
+use image::{Rgba, RgbaImage};
+use imageproc::filter::gaussian_blur_f32;
+
+/// Images are blurred this much before comparing them, so that small
+/// differences in anti-aliasing or resampling don’t count.
+const BLUR_SIGMA: f32 = 1.0;
+
+/// A pixel differs if any channel differs by more than this after blurring.
+const PIXEL_TOLERANCE: u8 = 24;
+
+/// An image matches if at most this fraction of its pixels differ.
+const MAX_DIFFERING: f64 = 0.001;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("golden")
+}
+
+/// Count the pixels that differ noticeably between two images of the same
+/// size, and produce an image highlighting them in red.
+fn compare(expected: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+    let (a, b) = (gaussian_blur_f32(expected, BLUR_SIGMA), gaussian_blur_f32(actual, BLUR_SIGMA));
+    let mut diff = actual.clone();
+    let mut differing = 0;
+    for (x, y, p) in diff.enumerate_pixels_mut() {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        if pa.0.iter().zip(pb.0).any(|(ca, cb)| ca.abs_diff(cb) > PIXEL_TOLERANCE) {
+            differing += 1;
+            *p = Rgba([255, 0, 0, 255]);
+        }
+    }
+
+    (differing, diff)
+}
+
+/// Check that an image matches the golden image `name`.
+///
+/// On a mismatch, the rendered image and a diff are written to
+/// `target/golden` so they can be inspected.
+pub fn check(name: &str, actual: &RgbaImage) {
+    let path = golden_dir().join(format!("{}.png", name));
+    if std::env::var_os("GLYFI_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(img) => img.to_rgba8(),
+        Err(e) => panic!("Could not load {} ({}); run with GLYFI_UPDATE_GOLDEN=1 to create it", path.display(), e),
+    };
+
+    assert_eq!(expected.dimensions(), actual.dimensions(), "Size of ‘{}’ changed", name);
+
+    let (differing, diff) = compare(&expected, actual);
+    let total = (actual.width() * actual.height()) as usize;
+    if differing as f64 > total as f64 * MAX_DIFFERING {
+        let out = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden");
+        std::fs::create_dir_all(&out).unwrap();
+        actual.save(out.join(format!("{}.actual.png", name))).unwrap();
+        diff.save(out.join(format!("{}.diff.png", name))).unwrap();
+        panic!(
+            "‘{}’ differs from its golden image in {} of {} pixels; see {}",
+            name, differing, total, out.display(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank() -> RgbaImage {
+        RgbaImage::from_pixel(64, 64, Rgba([255, 255, 255, 255]))
+    }
+
+    #[test]
+    fn small_shifts_are_tolerated() {
+        let (mut a, mut b) = (blank(), blank());
+        for x in 10..50 { a.put_pixel(x, 32, Rgba([200, 200, 200, 255])); }
+        for x in 10..50 { b.put_pixel(x, 33, Rgba([200, 200, 200, 255])); }
+        assert_eq!(compare(&a, &b).0, 0);
+    }
+
+    #[test]
+    fn missing_shapes_are_not_tolerated() {
+        let mut a = blank();
+        for x in 10..30 { for y in 10..30 { a.put_pixel(x, y, Rgba([0, 0, 0, 255])); } }
+        assert!(compare(&a, &blank()).0 > 100);
+    }
+}
@@ -0,0 +1,269 @@
+//! Where log messages end up: stderr, optionally a file that is rotated
+//! daily and whenever it gets too large, and the log channel on Discord.
+//! Use the macros in [`crate::core`] to log something.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use chrono::{DateTime, NaiveDate, Utc};
+use poise::serenity_prelude as ser;
+use tokio::sync::mpsc;
+use crate::{clock, redact, sql};
+use crate::core::{Pacer, safe_truncate};
+use crate::redact::Privacy;
+use crate::sql::Setting;
+
+/// How many rotated log files to keep around.
+const ROTATED_FILES_TO_KEEP: usize = 7;
+
+/// How many messages can wait to be posted in the log channel; anything
+/// logged while the queue is full is dropped there.
+const DISCORD_QUEUE_SIZE: usize = 64;
+
+/// Maximum length of a Discord message.
+const MAX_MESSAGE_LEN: usize = 2000;
+
+/// How important a message is; each level includes the ones before it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "Error",
+            Level::Warn => "Warning",
+            Level::Info => "Info",
+            Level::Debug => "Debug",
+        })
+    }
+}
+
+/// Logging options.
+pub struct Config {
+    /// Least important messages written to stderr and the log file.
+    pub level: Level,
+    pub file: Option<PathBuf>,
+
+    /// Size in bytes at which the log file is rotated.
+    pub max_file_size: u64,
+
+    /// Least important messages posted in the log channel.
+    pub discord_level: Level,
+
+    /// What to hide in log messages; applies to all outputs.
+    pub privacy: Privacy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            file: None,
+            max_file_size: 10 * 1024 * 1024,
+            discord_level: Level::Warn,
+            privacy: Privacy::Standard,
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+static DISCORD: OnceLock<mpsc::Sender<String>> = OnceLock::new();
+
+/// A log file and the state needed to decide when to rotate it.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+
+    /// Day on which the current file was started.
+    day: NaiveDate,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: u64, now: DateTime<Utc>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let meta = file.metadata()?;
+        let day = meta.modified().map(|t| DateTime::<Utc>::from(t).date_naive()).unwrap_or(now.date_naive());
+        Ok(Self { path, file, day, size: meta.len(), max_size })
+    }
+
+    /// Append a line, rotating the file first if it is from another day
+    /// or the line would make it too large.
+    fn write(&mut self, now: DateTime<Utc>, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if now.date_naive() != self.day || (self.size != 0 && self.size + len > self.max_size) {
+            self.rotate(now)?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Move the current file out of the way, named after the day it was
+    /// started on, start a new one, and delete old ones.
+    fn rotate(&mut self, now: DateTime<Utc>) -> std::io::Result<()> {
+        let base = format!("{}.{}", self.path.display(), self.day);
+        let mut rotated = PathBuf::from(&base);
+        for i in 1.. {
+            if !rotated.exists() { break; }
+            rotated = PathBuf::from(format!("{}.{}", base, i));
+        }
+
+        std::fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.day = now.date_naive();
+        self.size = 0;
+        self.delete_old()
+    }
+
+    fn delete_old(&self) -> std::io::Result<()> {
+        let Some(name) = self.path.file_name().and_then(|n| n.to_str()) else { return Ok(()); };
+        let prefix = format!("{}.", name);
+        let dir = match self.path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut rotated = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_str().is_some_and(|n| n.starts_with(&prefix)))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect::<Vec<_>>();
+
+        // Newest first.
+        rotated.sort_by(|a, b| b.cmp(a));
+        for (_, old) in rotated.iter().skip(ROTATED_FILES_TO_KEEP) {
+            std::fs::remove_file(old)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Set up logging. Messages logged before this are written to stderr only.
+pub fn init(config: Config) -> Result<(), String> {
+    if let Some(path) = &config.file {
+        let file = LogFile::open(path.clone(), config.max_file_size, clock::now())
+            .map_err(|e| format!("Could not open log file {}: {}", path.display(), e))?;
+        *FILE.lock().unwrap() = Some(file);
+    }
+
+    CONFIG.set(config).map_err(|_| "Logging was already initialised".into())
+}
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Get the configured privacy mode.
+pub fn privacy() -> Privacy {
+    config().privacy
+}
+
+/// Log a message to stderr and the log file only.
+fn log_local(level: Level, message: &str) {
+    write_local(level, &redact::scrub(message));
+}
+
+/// Write an already scrubbed message to stderr and the log file.
+fn write_local(level: Level, message: &str) {
+    eprintln!("[{}]: {}", level, message);
+    let mut file = FILE.lock().unwrap();
+    if let Some(f) = file.as_mut() {
+        let now = clock::now();
+        let line = format!("{} [{}] {}", now.format("%Y-%m-%d %H:%M:%S"), level, message);
+        if let Err(e) = f.write(now, &line) { eprintln!("[Error]: Could not write to log file: {}", e); }
+    }
+}
+
+/// Log a message.
+pub fn log(level: Level, message: &str) {
+    let config = config();
+    let message = &redact::scrub(message);
+    if level <= config.level { write_local(level, message); }
+    if level <= config.discord_level {
+        if let Some(tx) = DISCORD.get() {
+            let text = safe_truncate(format!("**[{}]** {}", level, message), MAX_MESSAGE_LEN);
+            let _ = tx.try_send(text);
+        }
+    }
+}
+
+/// Start posting messages in the log channel. Only the first call has
+/// any effect.
+///
+/// Problems with posting are only logged locally, since logging them
+/// here would just cause more of them.
+pub fn start_discord(ctx: ser::Context) {
+    let (tx, mut rx) = mpsc::channel::<String>(DISCORD_QUEUE_SIZE);
+    if DISCORD.set(tx).is_err() { return; }
+    tokio::spawn(async move {
+        let mut pacer = Pacer::new();
+        while let Some(text) = rx.recv().await {
+            let channel = match sql::get_setting::<u64>(Setting::LogChannel).await {
+                Ok(0) => continue,
+                Ok(id) => ser::ChannelId::new(id),
+                Err(e) => {
+                    log_local(Level::Error, &format!("Could not get log channel: {}", e));
+                    continue;
+                }
+            };
+
+            pacer.wait().await;
+            let message = ser::CreateMessage::new()
+                .content(text)
+                .allowed_mentions(ser::CreateAllowedMentions::new());
+            if let Err(e) = channel.send_message(&ctx, message).await {
+                log_local(Level::Error, &format!("Could not post in log channel {}: {}", channel, e));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use super::*;
+
+    #[test]
+    fn log_file_is_rotated_daily_and_by_size() {
+        let dir = std::env::temp_dir().join(format!("glyfi-log-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("glyfi.log");
+        let day = Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap();
+        let mut file = LogFile::open(path.clone(), 100, day).unwrap();
+        file.day = day.date_naive();
+
+        // Stays in one file until it’s full.
+        let line = "x".repeat(39);
+        file.write(day, &line).unwrap();
+        file.write(day, &line).unwrap();
+        assert!(!dir.join("glyfi.log.2024-01-03").exists());
+        file.write(day, &line).unwrap();
+        assert!(dir.join("glyfi.log.2024-01-03").exists());
+
+        // Rotated again on the same day.
+        file.write(day, &line).unwrap();
+        file.write(day, &line).unwrap();
+        assert!(dir.join("glyfi.log.2024-01-03.1").exists());
+
+        // And on the next day.
+        file.write(day + Duration::days(1), "y").unwrap();
+        assert!(dir.join("glyfi.log.2024-01-03.2").exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "y\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
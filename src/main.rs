@@ -1,53 +1,147 @@
-#![allow(unused)]
+#![allow(clippy::needless_return)]
 
-mod server_data;
+mod archive;
+mod assets;
+mod backfill;
+mod ballots;
+mod clock;
 mod core;
 mod commands;
+mod config;
 mod sql;
 mod events;
+mod panel;
+mod polls;
+mod redact;
+mod renderer;
+mod results;
+mod scheduler;
+mod templates;
+mod certificates;
+mod digest;
+mod discord;
+mod dm;
+mod logging;
+mod preflight;
+mod prompt_picks;
+mod receipts;
+#[cfg(debug_assertions)]
+mod seed;
+mod tally;
+mod watermark;
 
-use std::sync::Arc;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod tests;
+
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 use poise::serenity_prelude as ser;
+use sqlx::SqlitePool;
 use clap::Parser;
-use crate::commands::{nickname, profile, queue, update};
-use crate::core::{log_command, terminate};
+use clap::Subcommand;
+use crate::commands::{admin, ballot, calendar, compare, deadline, entry_info, leaderboard, nickname, portfolio, preview, profile, queue, rules, settings, submit, update, votes, week, weekinfo};
+use crate::config::Config;
+use crate::core::{log_command, terminate, track_command};
 use crate::events::GlyfiEvents;
-use crate::server_data::SERVER_ID;
+use crate::logging::Level;
+use crate::redact::Privacy;
+
+/// What the bot needs to shut down gracefully.
+pub struct AppState {
+    pub pool: SqlitePool,
 
-/// Global context. Ugly, but this is the best way I can think
-/// of to support graceful shutdown on Ctrl+C etc.
-static mut __GLYFI_CONTEXT: Option<ser::Context> = None;
-static mut __GLYFI_FRAMEWORK: Option<Arc<ser::ShardManager>> = None;
-static mut __GLYFI_RUNTIME: Option<tokio::runtime::Handle> = None;
+    /// Used to disconnect on shutdown; set once the framework is built.
+    pub shard_manager: OnceLock<Arc<ser::ShardManager>>,
+}
 
 /// User data.
-#[derive(Default)]
 pub struct Data;
 
 /// Basic types.
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
+type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
 type Res = Result<(), Error>;
 
 /// Clopts.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Config file with the bot token and the IDs of the server, its
+    /// channels, and emoji.
+    #[clap(long, default_value = "config.toml")]
+    config: PathBuf,
+
     /// Whether to register the commands.
     #[clap(long, short)]
     register: bool,
-}
 
-/// Only to be called by [`terminate()`].
-pub async unsafe fn __glyfi_terminate_bot() {
-    if let Some(fw) = __GLYFI_FRAMEWORK.as_ref() { fw.shutdown_all().await; }
+    /// Least important messages to log.
+    #[clap(long, value_enum, default_value_t = Level::Info)]
+    log_level: Level,
+
+    /// Also log to this file. It is rotated daily and whenever it
+    /// exceeds `--log-file-max-size`.
+    #[clap(long)]
+    log_file: Option<PathBuf>,
+
+    /// Size in MiB at which the log file is rotated.
+    #[clap(long, default_value_t = 10)]
+    log_file_max_size: u64,
+
+    /// Least important messages to post in the log channel.
+    #[clap(long, value_enum, default_value_t = Level::Warn)]
+    discord_log_level: Level,
+
+    /// What to hide in the logs.
+    #[clap(long, value_enum, default_value_t = Privacy::Standard)]
+    log_privacy: Privacy,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// This is called from a thread that is not part of the runtime.
-unsafe fn __glyfi_ctrlc_impl() {
-    let handle = __GLYFI_RUNTIME.as_ref().unwrap();
-    let _guard = handle.enter();
-    handle.block_on(terminate());
+/// Tasks to run instead of starting the bot.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Record votes for old weeks from the reactions on their submissions.
+    BackfillVotes {
+        /// Weeks to backfill, as ‘a..b’ (inclusive) or a single week.
+        #[clap(long, value_parser = backfill::parse_weeks)]
+        weeks: std::ops::RangeInclusive<i64>,
+
+        /// Emoji people voted with; defaults to the current vote emoji.
+        #[clap(long)]
+        emoji: Option<String>,
+    },
+
+    /// Fill the DB with made-up users, weeks, submissions, and votes.
+    /// Only available in debug builds.
+    #[cfg(debug_assertions)]
+    Seed {
+        /// Seed for the random number generator; the same seed always
+        /// produces the same data.
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Number of weeks to generate; the last one is the current week.
+        #[clap(long, default_value_t = 12)]
+        weeks: i64,
+
+        /// Number of users to generate.
+        #[clap(long, default_value_t = 25)]
+        users: u64,
+
+        /// Seed even if the DB already contains submissions.
+        #[clap(long)]
+        force: bool,
+
+        /// DB to fill; this must not be the bot’s own DB.
+        #[clap(long)]
+        db: String,
+    },
 }
 
 /// Register bot commands.
@@ -56,7 +150,7 @@ async fn register_impl(http: impl AsRef<ser::Http>, framework: &poise::Framework
     poise::builtins::register_in_guild(
         http,
         &framework.options().commands,
-        SERVER_ID,
+        config::get().server,
     ).await?;
     info_sync!("Commands registered.");
     Ok(())
@@ -72,52 +166,116 @@ async fn main() {
         std::process::abort();
     }));
 
-    // Save runtime.
-    unsafe { __GLYFI_RUNTIME = Some(tokio::runtime::Handle::current()); }
+    let args = Args::parse();
+    if let Err(e) = logging::init(logging::Config {
+        level: args.log_level,
+        file: args.log_file.clone(),
+        max_file_size: args.log_file_max_size * 1024 * 1024,
+        discord_level: args.discord_log_level,
+        privacy: args.log_privacy,
+    }) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    #[cfg(debug_assertions)]
+    if let Some(Command::Seed { seed, weeks, users, force, db }) = args.command {
+        if let Err(e) = seed::check_db_path(&db) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
 
-    // Register the SIGINT handler.
-    //
-    // Do this *after* saving the runtime as the handler will
-    // attempt to enter the runtime.
-    ctrlc::set_handler(|| unsafe { __glyfi_ctrlc_impl() }).expect("Failed to register SIGINT handler");
+        let pool = sql::init_db(&db).await;
+        if let Err(e) = seed::seed(seed, weeks, users, force).await {
+            eprintln!("Error seeding DB: {}", e);
+            std::process::exit(1);
+        }
+
+        pool.close().await;
+        return;
+    }
 
     // Initialise the database.
-    unsafe { sql::__glyfi_init_db().await; }
+    let pool = sql::init_db(sql::DB_PATH).await;
+
+    // Load the config.
+    let config = match Config::load(&args.config).and_then(config::init) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::BackfillVotes { weeks, emoji }) = &args.command {
+        let res = backfill::run(&config.token, weeks.clone(), emoji.as_deref()).await;
+        pool.close().await;
+        if let Err(e) = res {
+            eprintln!("Error backfilling votes: {}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let state = Arc::new(AppState { pool, shard_manager: OnceLock::new() });
+
+    // Register the SIGINT handler. It is called from a thread that is
+    // not part of the runtime, so enter the runtime first.
+    let handle = tokio::runtime::Handle::current();
+    let ctrlc_state = state.clone();
+    ctrlc::set_handler(move || {
+        let _guard = handle.enter();
+        handle.block_on(terminate(&ctrlc_state));
+    }).expect("Failed to register SIGINT handler");
 
-    let args = Args::parse();
     let fw = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             pre_command: |ctx| Box::pin(async move { log_command(ctx).await; }),
+            command_check: Some(|ctx| Box::pin(track_command(ctx))),
             commands: vec![
+                admin(),
+                ballot(),
+                calendar(),
+                compare(),
+                deadline(),
+                entry_info(),
+                leaderboard(),
                 nickname(),
+                preview(),
+                portfolio(),
                 profile(),
                 queue(),
+                rules(),
+                settings(),
+                submit(),
                 update(),
+                votes(),
+                week(),
+                weekinfo(),
             ],
             ..Default::default()
         })
 
         .setup(move |ctx, _, framework| {
-            unsafe {
-                __GLYFI_CONTEXT = Some(ctx.clone());
-                __GLYFI_FRAMEWORK = Some(framework.shard_manager().clone());
-            };
-
             Box::pin(async move {
                 if args.register { register_impl(ctx, framework).await?; }
+                scheduler::start(ctx.clone());
+                logging::start_discord(ctx.clone());
+                assets::start(ctx.clone());
                 info_sync!("Setup done");
                 info_sync!("\x1b[1;33mRemember to double-check command permissions before deploying!\x1b[m");
-                Ok(Default::default())
+                Ok(Data)
             })
         })
         .build();
 
-    ser::ClientBuilder::new(server_data::DISCORD_BOT_TOKEN, ser::GatewayIntents::all())
+    let mut client = ser::ClientBuilder::new(&config.token, ser::GatewayIntents::all())
         .framework(fw)
         .event_handler(GlyfiEvents)
         .await
-        .unwrap()
-        .start()
-        .await
         .unwrap();
+
+    let _ = state.shard_manager.set(client.shard_manager.clone());
+    client.start().await.unwrap();
 }
@@ -0,0 +1,219 @@
+use std::io::Cursor;
+use image::{DynamicImage, GenericImage, ImageFormat, Rgba, RgbaImage};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use poise::ChoiceParameter;
+use poise::serenity_prelude as ser;
+use crate::{archive, Error, info, preflight, Res, sql};
+use crate::sql::{Challenge, Setting, WeekMessage};
+
+/// Width and height of a single entry in the panel.
+const TILE_SIZE: u32 = 512;
+
+/// Gap between tiles, and around the edge of the panel.
+const GAP: u32 = 16;
+
+/// Maximum number of tiles per row.
+const MAX_COLUMNS: u32 = 5;
+
+/// Background colour of the panel.
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// JPEG qualities to fall back to, in order, if a panel is too large as a PNG.
+const JPEG_QUALITIES: [u8; 3] = [90, 75, 60];
+
+/// Maximum number of attachments on a message.
+const MAX_ATTACHMENTS: usize = 10;
+
+/// An encoded panel image.
+pub struct PanelImage {
+    pub data: Vec<u8>,
+    pub extension: &'static str,
+}
+
+/// Lay out images in a grid.
+pub fn composite(images: &[DynamicImage]) -> Result<RgbaImage, Error> {
+    let count = images.len().max(1) as u32;
+    let columns = (count as f64).sqrt().ceil().clamp(1.0, MAX_COLUMNS as f64) as u32;
+    let rows = count.div_ceil(columns);
+    let mut panel = RgbaImage::from_pixel(
+        columns * (TILE_SIZE + GAP) + GAP,
+        rows * (TILE_SIZE + GAP) + GAP,
+        BACKGROUND,
+    );
+
+    for (i, img) in images.iter().enumerate() {
+        let i = i as u32;
+        let tile = img.resize(TILE_SIZE, TILE_SIZE, FilterType::Lanczos3).to_rgba8();
+
+        // Centre the image in its tile.
+        let x = GAP + (i % columns) * (TILE_SIZE + GAP) + (TILE_SIZE - tile.width()) / 2;
+        let y = GAP + (i / columns) * (TILE_SIZE + GAP) + (TILE_SIZE - tile.height()) / 2;
+        panel.copy_from(&tile, x, y)?;
+    }
+
+    Ok(panel)
+}
+
+/// Encode a panel so it fits in `limit` bytes: as a PNG if possible, and
+/// otherwise as increasingly compressed JPEGs. Returns `None` if even
+/// the most compressed version is too large.
+pub fn encode(panel: &RgbaImage, limit: usize) -> Result<Option<PanelImage>, Error> {
+    let mut out = Cursor::new(Vec::new());
+    panel.write_to(&mut out, ImageFormat::Png)?;
+    if out.get_ref().len() <= limit { return Ok(Some(PanelImage { data: out.into_inner(), extension: "png" })); }
+
+    // JPEG doesn’t support transparency.
+    let rgb = DynamicImage::ImageRgba8(panel.clone()).to_rgb8();
+    for quality in JPEG_QUALITIES {
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, quality).encode_image(&rgb)?;
+        if out.len() <= limit { return Ok(Some(PanelImage { data: out, extension: "jpg" })); }
+    }
+
+    Ok(None)
+}
+
+/// Render images into as few panels as possible, each of which fits
+/// in `limit` bytes, by splitting them up until every part fits.
+fn composite_fitting(images: &[DynamicImage], limit: usize) -> Result<Vec<PanelImage>, Error> {
+    if let Some(img) = encode(&composite(images)?, limit)? { return Ok(vec![img]); }
+    if images.len() <= 1 { return Err("A single submission is too large to fit in the upload limit".into()); }
+
+    let (first, second) = images.split_at(images.len() / 2);
+    let mut panels = composite_fitting(first, limit)?;
+    panels.extend(composite_fitting(second, limit)?);
+    Ok(panels)
+}
+
+/// Render the panel for a challenge in a week from its active submissions.
+///
+/// If the panel doesn’t fit in the server’s upload limit, it is compressed
+/// further or split into several images.
+pub async fn render_panel(ctx: &ser::Context, week: i64, challenge: Challenge) -> Result<Vec<PanelImage>, Error> {
+    let submissions = sql::get_submissions(week, challenge).await?;
+    if submissions.is_empty() { return Err(format!("No submissions for week {}", week).into()); }
+
+    let mut images = Vec::with_capacity(submissions.len());
+    for s in &submissions {
+        let message = ser::MessageId::new(s.message as u64);
+        images.push(archive::submission_image(ctx, challenge, message).await
+            .map_err(|e| format!("Error downloading submission {}: {}", message, e))?);
+    }
+
+    // This is CPU-bound, so keep it off the async workers.
+    let limit = preflight::upload_limit(ctx).await? as usize;
+    let panels = tokio::task::spawn_blocking(move || composite_fitting(&images, limit)).await??;
+    if panels.len() > MAX_ATTACHMENTS {
+        return Err(format!("The panel would need {} images, but a message can only have {}", panels.len(), MAX_ATTACHMENTS).into());
+    }
+
+    Ok(panels)
+}
+
+/// Get the channel panels for a challenge are posted in.
+///
+/// Existing panels are looked for in this channel as well, so changing the
+/// setting means older panels can no longer be regenerated.
+async fn panel_channel(challenge: Challenge) -> Result<ser::ChannelId, Error> {
+    Ok(match sql::get_setting::<u64>(Setting::PanelChannel).await? {
+        0 => challenge.submission_channel(),
+        id => ser::ChannelId::new(id),
+    })
+}
+
+/// Render the panel and turn it into attachments.
+async fn panel_attachments(ctx: &ser::Context, week: i64, challenge: Challenge) -> Result<(Vec<ser::CreateAttachment>, usize), Error> {
+    let panels = render_panel(ctx, week, challenge).await?;
+
+    // Entries can’t be spoilered individually in a panel, so spoiler the whole
+    // thing if any of them are. Blind challenges are the exception, since
+    // their entries are only spoilered until voting opens, i.e. until now.
+    let spoiler = !sql::get_setting::<bool>(Setting::blind(challenge)).await?
+        && sql::get_submissions(week, challenge).await?.iter().any(|s| s.spoiler);
+    let largest = panels.iter().map(|p| p.data.len()).max().unwrap_or(0);
+
+    let prefix = if spoiler { "SPOILER_" } else { "" };
+    let attachments = panels.into_iter()
+        .enumerate()
+        .map(|(i, p)| ser::CreateAttachment::bytes(p.data, format!("{}panel-{}.{}", prefix, i + 1, p.extension)))
+        .collect();
+    Ok((attachments, largest))
+}
+
+/// Text posted with the panel, which points to the discussion thread.
+pub(crate) async fn panel_text(week: i64, challenge: Challenge) -> Result<String, Error> {
+    let mut text = format!("## {} Challenge – Week {}", challenge.name(), week);
+    if let Some(thread) = sql::get_week_message(week, challenge, WeekMessage::DiscussionThread).await? {
+        text.push_str(&format!("\nDiscuss the entries in <#{}>", thread));
+    }
+
+    Ok(text)
+}
+
+/// Post the panel for a challenge in a week and remember the message.
+pub async fn post_panel(ctx: &ser::Context, week: i64, challenge: Challenge) -> Res {
+    if sql::get_week_message(week, challenge, WeekMessage::Panel).await?.is_some() {
+        return Err(format!("The panel for week {} has already been posted; regenerate it instead", week).into());
+    }
+
+    let (attachments, largest) = panel_attachments(ctx, week, challenge).await?;
+    let channel = panel_channel(challenge).await?;
+    preflight::preflight_post(ctx, channel, "the panel", Some(largest)).await?;
+    let message = channel.send_message(ctx, ser::CreateMessage::new()
+        .content(panel_text(week, challenge).await?)
+        .add_files(attachments)
+    ).await?;
+
+    sql::set_week_message(week, challenge, WeekMessage::Panel, message.id).await?;
+    info!("Posted panel for {:?} in week {}", challenge, week);
+    Ok(())
+}
+
+/// Rebuild the panel for a challenge in a week and replace the image of
+/// the existing panel message; reactions on it are kept.
+pub async fn regenerate_panel(ctx: &ser::Context, week: i64, challenge: Challenge) -> Res {
+    let Some(message) = sql::get_week_message(week, challenge, WeekMessage::Panel).await? else {
+        return Err(format!("No panel has been posted for week {}", week).into());
+    };
+
+    let (attachments, largest) = panel_attachments(ctx, week, challenge).await?;
+    let channel = panel_channel(challenge).await?;
+    preflight::preflight_post(ctx, channel, "the panel", Some(largest)).await?;
+    let mut edit = ser::EditMessage::new().content(panel_text(week, challenge).await?).remove_all_attachments();
+    for a in attachments { edit = edit.new_attachment(a); }
+
+    channel.edit_message(ctx, message, edit).await?;
+    info!("Regenerated panel for {:?} in week {}", challenge, week);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut};
+    use imageproc::rect::Rect;
+    use crate::golden;
+    use super::*;
+
+    /// Make an entry with a shape on it, so misplaced or badly scaled
+    /// tiles show up in the panel.
+    fn entry(width: u32, height: u32, colour: [u8; 3]) -> DynamicImage {
+        let mut img = RgbaImage::from_pixel(width, height, Rgba([240, 240, 240, 255]));
+        let [r, g, b] = colour;
+        let radius = width.min(height) as i32 / 3;
+        draw_filled_circle_mut(&mut img, (width as i32 / 2, height as i32 / 2), radius, Rgba([r, g, b, 255]));
+        draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(width / 4, height / 4), Rgba([0, 0, 0, 255]));
+        DynamicImage::ImageRgba8(img)
+    }
+
+    #[test]
+    fn panel_matches_golden() {
+        let images = [
+            entry(600, 600, [200, 40, 40]),
+            entry(1024, 300, [40, 160, 40]),
+            entry(200, 800, [40, 40, 200]),
+        ];
+
+        golden::check("panel", &composite(&images).unwrap());
+    }
+}
@@ -0,0 +1,169 @@
+//! Prompt polls, in which members vote on an upcoming prompt.
+//!
+//! When [`Setting::PollChannel`] is set, a poll is opened for each active
+//! challenge every [`Setting::PollDays`] days, on the oldest few prompts on
+//! its shortlist (see `/queue shortlist`). Members vote with the buttons
+//! under the poll and can change their vote until it closes, which is
+//! [`Setting::PollHours`] later; the votes are only shown then. The prompt
+//! with the most votes that is still on the shortlist is added to the queue
+//! and taken off it; a tie goes to the prompt that was shortlisted first.
+
+use chrono::Duration;
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{ButtonStyle, ChannelId, CreateActionRow, CreateButton, CreateMessage, EditMessage, MessageId, UserId};
+use crate::{clock, Error, info, Res, scheduler, sql};
+use crate::core::{InteractionID, timestamp, TimestampStyle};
+use crate::discord::DiscordApi;
+use crate::sql::{Challenge, Poll, PollOption, Setting};
+
+/// A poll needs at least this many prompts to be worth holding.
+const MIN_OPTIONS: usize = 3;
+
+/// Get the channel polls are posted in, if polls are enabled.
+async fn poll_channel() -> Result<Option<ChannelId>, Error> {
+    let id = sql::get_setting::<u64>(Setting::PollChannel).await?;
+    Ok((id != 0).then(|| ChannelId::new(id)))
+}
+
+/// Build the text and buttons of a poll; once it is closed, this shows the
+/// votes and the prompt that was queued, if any, instead.
+fn render(poll: &Poll, options: &[PollOption], queued: Option<&PollOption>) -> Result<(String, Vec<CreateActionRow>), Error> {
+    let challenge = Challenge::try_from(poll.challenge)?;
+    let mut text = format!("**Prompt poll for the {} Challenge**\n", challenge.name());
+    if poll.closed.is_none() {
+        let closes = chrono::DateTime::from_timestamp(poll.closes, 0).unwrap_or_default();
+        text.push_str(&format!(
+            "Vote for the prompt you’d most like to see in an upcoming week! Votes are secret until the poll closes {}, and you can change yours until then.\n",
+            timestamp(closes, TimestampStyle::Relative),
+        ));
+
+        for o in options { text.push_str(&format!("\n**{}.** {}", o.option + 1, o.prompt)); }
+        let buttons = options.iter()
+            .map(|o| CreateButton::new(InteractionID::PollVote { poll: poll.id, option: o.option }.encode())
+                .label((o.option + 1).to_string())
+                .style(ButtonStyle::Primary))
+            .collect();
+
+        return Ok((text, vec![CreateActionRow::Buttons(buttons)]));
+    }
+
+    text.push_str("This poll has closed.\n");
+    for o in options {
+        text.push_str(&format!("\n**{}.** {} – {} vote{}", o.option + 1, o.prompt, o.votes, if o.votes == 1 { "" } else { "s" }));
+    }
+
+    match (queued, winner(options)) {
+        (Some(q), Some(w)) if q.option == w.option => text.push_str(&format!("\n\n‘{}’ won and has been added to the queue. Thanks for voting!", q.prompt)),
+        (Some(q), _) => text.push_str(&format!("\n\n‘{}’ has been added to the queue, since the prompts with more votes are no longer on the shortlist. Thanks for voting!", q.prompt)),
+        (None, Some(_)) => text.push_str("\n\nNone of the prompts that got votes are still on the shortlist, so no prompt was added to the queue."),
+        (None, None) => text.push_str("\n\nNobody voted, so no prompt was added to the queue."),
+    }
+
+    Ok((text, vec![]))
+}
+
+/// Get the prompt with the most votes; a tie goes to the one listed first.
+pub fn winner(options: &[PollOption]) -> Option<&PollOption> {
+    let most = options.iter().map(|o| o.votes).max().filter(|&v| v > 0)?;
+    options.iter().find(|o| o.votes == most)
+}
+
+/// Open a poll for a challenge on the oldest prompts on its shortlist.
+/// Returns the poll’s ID.
+pub async fn open_poll(api: &dyn DiscordApi, challenge: Challenge) -> Result<i64, Error> {
+    let Some(channel) = poll_channel().await? else { return Err("Prompt polls are not enabled; set the poll channel first".into()); };
+    if sql::get_latest_poll(challenge).await?.is_some_and(|p| p.closed.is_none()) {
+        return Err(format!("There already is an open poll for the {} Challenge", challenge.name()).into());
+    }
+
+    let size = sql::get_setting::<i64>(Setting::PollOptions).await? as usize;
+    let shortlist = sql::get_shortlist(challenge).await?;
+    if shortlist.len() < MIN_OPTIONS {
+        return Err(format!("The shortlist for the {} Challenge needs at least {} prompts for a poll", challenge.name(), MIN_OPTIONS).into());
+    }
+
+    let now = clock::now();
+    let closes = now + Duration::hours(sql::get_setting::<i64>(Setting::PollHours).await?);
+    let shortlist = shortlist.into_iter().take(size).collect::<Vec<_>>();
+    let id = sql::create_poll(challenge, channel, now.timestamp(), closes.timestamp(), &shortlist).await?;
+
+    let poll = sql::get_poll(id).await?.ok_or("Poll disappeared")?;
+    let (text, rows) = render(&poll, &sql::get_poll_options(id).await?, None)?;
+    let message = api.send_message(channel, CreateMessage::new().content(text).components(rows)).await?;
+    sql::set_poll_message(id, message).await?;
+    info!("Opened prompt poll {} for the {:?} Challenge", id, challenge);
+    Ok(id)
+}
+
+/// Open a poll for every active challenge whose last one was long enough
+/// ago, if its shortlist has enough prompts.
+async fn open_due_polls(api: &dyn DiscordApi) -> Res {
+    if poll_channel().await?.is_none() { return Ok(()); }
+    let interval = Duration::days(sql::get_setting::<i64>(Setting::PollDays).await?);
+    let now = clock::now().timestamp();
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        if !scheduler::is_active(challenge).await? { continue; }
+        if let Some(last) = sql::get_latest_poll(challenge).await? {
+            if last.closed.is_none() || now - last.opened < interval.num_seconds() { continue; }
+        }
+
+        if sql::get_shortlist(challenge).await?.len() < MIN_OPTIONS { continue; }
+        open_poll(api, challenge).await?;
+    }
+
+    Ok(())
+}
+
+/// Record someone’s vote in a poll. Returns the prompt they voted for.
+pub async fn vote(id: i64, voter: UserId, option: i64) -> Result<String, Error> {
+    let Some(poll) = sql::get_poll(id).await? else { return Err("This poll doesn’t exist".into()); };
+    if poll.closed.is_some() || clock::now().timestamp() >= poll.closes { return Err("This poll has closed".into()); }
+    let Some(o) = sql::get_poll_options(id).await?.into_iter().find(|o| o.option == option) else {
+        return Err("Invalid option".into());
+    };
+
+    sql::vote_in_poll(id, voter, option).await?;
+    Ok(o.prompt)
+}
+
+/// Close a poll, queue the winning prompt, and show the results.
+///
+/// Prompts that were taken off the shortlist while the poll was open, e.g.
+/// because a winner picked one, can’t win; the next best prompt does.
+pub async fn close_poll(api: &dyn DiscordApi, poll: &Poll) -> Res {
+    let options = sql::get_poll_options(poll.id).await?;
+    let shortlist = sql::get_shortlist(Challenge::try_from(poll.challenge)?).await?;
+    let available = options.iter()
+        .filter(|o| o.shortlisted.is_some_and(|id| shortlist.iter().any(|&(s, _)| s == id)))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let winner = winner(&available);
+    let queued = match sql::close_poll(poll.id, winner, clock::now().timestamp()).await? {
+        Some(queued) => {
+            info!("Closed prompt poll {}; the winner is entry {} in the queue", poll.id, queued);
+            winner
+        }
+        None => {
+            info!("Closed prompt poll {} without a winner", poll.id);
+            None
+        }
+    };
+
+    let Some(message) = poll.message else { return Ok(()); };
+    let poll = sql::get_poll(poll.id).await?.ok_or("Poll disappeared")?;
+    let (text, rows) = render(&poll, &options, queued)?;
+    api.edit_message(ChannelId::new(poll.channel as u64), MessageId::new(message as u64), EditMessage::new()
+        .content(text)
+        .components(rows)
+    ).await
+}
+
+/// Close the polls that are over and open new ones that are due.
+pub async fn run_polls(api: &dyn DiscordApi) -> Res {
+    for poll in sql::get_due_polls(clock::now().timestamp()).await? {
+        close_poll(api, &poll).await?;
+    }
+
+    open_due_polls(api).await
+}
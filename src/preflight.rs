@@ -0,0 +1,163 @@
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::{ChannelId, Mentionable, Permissions, PremiumTier};
+use crate::{config, err, Error, info, Res, sql};
+use crate::sql::{Challenge, Setting};
+
+/// Permissions we need in the submission channels.
+const SUBMISSION_CHANNEL_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::EMBED_LINKS)
+    .union(Permissions::ATTACH_FILES)
+    .union(Permissions::ADD_REACTIONS)
+    .union(Permissions::MANAGE_MESSAGES);
+
+/// Permissions we need in channels we only post embeds in.
+const POST_CHANNEL_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::EMBED_LINKS);
+
+/// Maximum size of an attachment on a server without boosts (or boost level 1).
+const DEFAULT_UPLOAD_LIMIT: u64 = 10 * 1024 * 1024;
+
+/// Get the channel admins should be alerted in, if any.
+pub async fn admin_channel() -> Result<Option<ChannelId>, Error> {
+    let id = sql::get_setting::<u64>(Setting::AdminChannel).await?;
+    Ok((id != 0).then(|| ChannelId::new(id)))
+}
+
+/// Tell the admins about a problem they need to fix. This is always
+/// logged, and also posted in the admin channel if there is one.
+pub async fn alert_admins(ctx: &ser::Context, text: &str) {
+    err!("{}", text);
+    let channel = match admin_channel().await {
+        Ok(Some(channel)) => channel,
+        Ok(None) => return,
+        Err(e) => {
+            err!("Error getting admin channel: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = channel.say(ctx, format!("⚠️ {}", text)).await {
+        err!("Could not alert admins in {}: {}", channel, e);
+    }
+}
+
+/// Get the permissions we need in a channel but don’t have.
+pub async fn missing_permissions(ctx: &ser::Context, channel: ChannelId, needed: Permissions) -> Result<Permissions, Error> {
+    let me = ctx.cache.current_user().id;
+    let me = config::get().server.member(ctx, me).await?;
+    let guild = config::get().server.to_partial_guild(ctx).await?;
+    let Some(channel) = channel.to_channel(ctx).await?.guild() else {
+        return Err(format!("{} is not a channel on the server", channel.mention()).into());
+    };
+
+    Ok(needed.difference(guild.user_permissions_in(&channel, &me)))
+}
+
+/// Check that we have certain permissions in a channel. On error,
+/// returns a message that tells admins what to fix.
+pub async fn check_channel(ctx: &ser::Context, channel: ChannelId, needed: Permissions) -> Result<(), String> {
+    match missing_permissions(ctx, channel, needed).await {
+        Ok(missing) if missing.is_empty() => Ok(()),
+        Ok(missing) => Err(format!(
+            "I’m missing the following permissions in {}: {}",
+            channel.mention(),
+            missing.get_permission_names().join(", "),
+        )),
+        Err(e) => Err(format!("I can’t access {}: {}", channel.mention(), e)),
+    }
+}
+
+/// Get the maximum size of an attachment on the server, which
+/// depends on its boost level.
+pub async fn upload_limit(ctx: &ser::Context) -> Result<u64, Error> {
+    let guild = config::get().server.to_partial_guild(ctx).await?;
+    Ok(match guild.premium_tier {
+        PremiumTier::Tier2 => 50 * 1024 * 1024,
+        PremiumTier::Tier3 => 100 * 1024 * 1024,
+        _ => DEFAULT_UPLOAD_LIMIT,
+    })
+}
+
+/// Check that we can post something in a channel before we try to. If
+/// we can’t, the admins are told exactly what’s wrong, since otherwise
+/// all we’d get is a generic HTTP error halfway through posting.
+///
+/// `what` describes the post, e.g. ‘the hall of fame’; `attachment` is the
+/// size in bytes of the file that will be attached, if any.
+pub async fn preflight_post(ctx: &ser::Context, channel: ChannelId, what: &str, attachment: Option<usize>) -> Res {
+    let needed = match attachment {
+        Some(_) => POST_CHANNEL_PERMISSIONS | Permissions::ATTACH_FILES,
+        None => POST_CHANNEL_PERMISSIONS,
+    };
+
+    let mut res = check_channel(ctx, channel, needed).await;
+    if let (Ok(()), Some(size)) = (&res, attachment) {
+        let limit = upload_limit(ctx).await?;
+        if size as u64 > limit {
+            res = Err(format!(
+                "The image is {:.1} MiB, but the server only allows uploads of up to {} MiB",
+                size as f64 / (1024.0 * 1024.0),
+                limit / (1024 * 1024),
+            ));
+        }
+    }
+
+    if let Err(e) = res {
+        let e = format!("Could not post {} in {}: {}", what, channel.mention(), e);
+        alert_admins(ctx, &e).await;
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Check that the submit emoji exists and can be used.
+async fn check_emoji(ctx: &ser::Context) -> Result<(), String> {
+    let emoji = config::get().submit_emoji;
+    match config::get().server.emoji(ctx, emoji).await {
+        Ok(emoji) if emoji.available => Ok(()),
+        Ok(emoji) => Err(format!(
+            "The submit emoji :{}: is unavailable; this usually means the server lost a boost level",
+            emoji.name,
+        )),
+        Err(e) => Err(format!("The submit emoji (ID {}) does not exist on the server: {}", emoji, e)),
+    }
+}
+
+/// Check that everything the bot needs is set up correctly on the server,
+/// and alert the admins about anything that isn’t.
+///
+/// The vote emoji is a standard emoji, so there’s nothing to check there.
+pub async fn startup_checks(ctx: &ser::Context) {
+    let mut problems = Vec::new();
+    if let Err(e) = check_emoji(ctx).await { problems.push(e); }
+
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        let channel = challenge.submission_channel();
+        if let Err(e) = check_channel(ctx, channel, SUBMISSION_CHANNEL_PERMISSIONS).await { problems.push(e); }
+    }
+
+    let mut channels = Vec::new();
+    for setting in [Setting::DigestChannel, Setting::AdminChannel, Setting::LogChannel, Setting::PollChannel] {
+        match sql::get_setting::<u64>(setting).await {
+            Ok(0) => {}
+            Ok(id) => channels.push(ChannelId::new(id)),
+            Err(e) => problems.push(format!("Could not read setting {:?}: {}", setting, e)),
+        }
+    }
+
+    for channel in channels {
+        if let Err(e) = check_channel(ctx, channel, POST_CHANNEL_PERMISSIONS).await { problems.push(e); }
+    }
+
+    if problems.is_empty() {
+        info!("Startup checks passed");
+        return;
+    }
+
+    let list = problems.iter().map(|p| format!("- {}", p)).collect::<Vec<_>>().join("\n");
+    alert_admins(ctx, &format!("Some things need fixing before I can work properly:\n{}", list)).await;
+}
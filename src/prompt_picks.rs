@@ -0,0 +1,191 @@
+//! Winners picking the next prompt.
+//!
+//! When [`Setting::WinnerPicksPrompt`] is enabled, the winner of each
+//! challenge is invited by DM, once the hall of fame is posted, to choose
+//! the next prompt from a shortlist that admins curate with `/queue
+//! shortlist`, or to suggest one of their own. What they pick is posted in
+//! the admin channel and only enters the queue once an admin approves it;
+//! if it is rejected, the winner is asked to pick again.
+
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateButton, CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, UserId};
+use crate::{dm, Error, info, Res, sql, warn};
+use crate::core::{InteractionID, PickAction, safe_truncate};
+use crate::discord::DiscordApi;
+use crate::dm::DmKind;
+use crate::preflight::admin_channel;
+use crate::sql::{Challenge, PromptPick, Setting};
+use crate::tally::Placement;
+
+/// Discord allows at most 25 options per select menu.
+const MAX_OPTIONS: usize = 25;
+
+/// Modal for suggesting a prompt.
+#[derive(Debug, poise::Modal)]
+#[name = "Suggest a prompt"]
+pub struct SuggestionModal {
+    #[name = "Prompt"]
+    #[max_length = 200]
+    pub prompt: String,
+}
+
+/// Check that an invitation belongs to someone and they can still pick.
+async fn check_pick(id: i64, user: UserId) -> Result<PromptPick, Error> {
+    let Some(pick) = sql::get_prompt_pick(id).await? else { return Err("This invitation doesn’t exist".into()); };
+    if pick.user != user.get() as i64 { return Err("This invitation isn’t for you".into()); }
+    if pick.prompt.is_some() { return Err("You have already picked a prompt".into()); }
+    Ok(pick)
+}
+
+/// Build the text and components of an invitation.
+pub async fn render(pick: &PromptPick, intro: &str) -> Result<(String, Vec<CreateActionRow>), Error> {
+    let challenge = Challenge::try_from(pick.challenge)?;
+    let shortlist = sql::get_shortlist(challenge).await?;
+    let mut text = format!("{} You get to pick a prompt for an upcoming {} Challenge.", intro, challenge.name());
+    text.push_str(match shortlist.is_empty() {
+        true => " Suggest one of your own; the admins will look at it before it goes into the queue.",
+        false => " Choose one from the shortlist, or suggest one of your own; the admins will look at it before it goes into the queue.",
+    });
+
+    let mut rows = Vec::new();
+    if !shortlist.is_empty() {
+        let options = shortlist.into_iter()
+            .take(MAX_OPTIONS)
+            .map(|(id, prompt)| CreateSelectMenuOption::new(safe_truncate(prompt, 100), id.to_string()))
+            .collect::<Vec<_>>();
+
+        let id = InteractionID::PromptPick { pick: pick.id, action: PickAction::Choose }.encode();
+        rows.push(CreateActionRow::SelectMenu(CreateSelectMenu::new(id, CreateSelectMenuKind::String { options })
+            .placeholder("Choose a prompt from the shortlist")
+        ));
+    }
+
+    rows.push(CreateActionRow::Buttons(vec![
+        CreateButton::new(InteractionID::PromptPick { pick: pick.id, action: PickAction::Suggest }.encode())
+            .label("Suggest my own")
+            .style(ButtonStyle::Secondary),
+    ]));
+
+    Ok((text, rows))
+}
+
+/// Invite the winner of a challenge in a week to pick the next prompt,
+/// unless that was done already. With a tie for first place, the earliest
+/// of the winning entries decides.
+pub async fn invite(api: &dyn DiscordApi, week: i64, challenge: Challenge, placements: &[Placement]) -> Res {
+    if !sql::get_setting::<bool>(Setting::WinnerPicksPrompt).await? { return Ok(()); }
+    if admin_channel().await?.is_none() {
+        warn!("Not inviting the winner to pick a prompt: there is no admin channel to review it in");
+        return Ok(());
+    }
+
+    let Some(winner) = placements.iter()
+        .take_while(|p| p.place == 1)
+        .find(|p| p.submission.author != sql::PURGED_AUTHOR)
+        else { return Ok(()); };
+
+    let user = UserId::new(winner.submission.author as u64);
+    let (pick, new) = sql::invite_to_pick(week, challenge, user).await?;
+    if !new { return Ok(()); }
+
+    let intro = format!("Congratulations on winning week {} of the {} Challenge!", week, challenge.name());
+    let (text, rows) = render(&pick, &intro).await?;
+    dm::send(api, user, DmKind::Essential, CreateMessage::new().content(text).components(rows)).await?;
+    info!("Invited {} to pick the next {:?} prompt", user, challenge);
+    Ok(())
+}
+
+/// Record the shortlist entry a winner chose, and ask the admins to
+/// review it. Returns the prompt.
+pub async fn choose(api: &dyn DiscordApi, id: i64, user: UserId, value: &str) -> Result<String, Error> {
+    let pick = check_pick(id, user).await?;
+    let entry = value.parse::<i64>().map_err(|_| format!("Invalid shortlist entry ‘{}’", value))?;
+    let Some((_, prompt)) = sql::get_shortlist(Challenge::try_from(pick.challenge)?).await?
+        .into_iter()
+        .find(|&(id, _)| id == entry)
+        else { return Err("That prompt is no longer on the shortlist".into()); };
+
+    if !sql::set_prompt_pick(id, &prompt, Some(entry)).await? { return Err("You have already picked a prompt".into()); }
+    request_review(api, id).await?;
+    Ok(prompt)
+}
+
+/// Record a prompt a winner suggested, and ask the admins to review it.
+pub async fn suggest(api: &dyn DiscordApi, id: i64, user: UserId, prompt: &str) -> Res {
+    check_pick(id, user).await?;
+    if prompt.is_empty() { return Err("The prompt must not be empty".into()); }
+    if !sql::set_prompt_pick(id, prompt, None).await? { return Err("You have already picked a prompt".into()); }
+    request_review(api, id).await
+}
+
+/// Post a winner’s pick in the admin channel, with buttons to approve or
+/// reject it.
+async fn request_review(api: &dyn DiscordApi, id: i64) -> Res {
+    let Some(channel) = admin_channel().await? else { return Err("There is no admin channel to review prompts in".into()); };
+    let pick = sql::get_prompt_pick(id).await?.ok_or("This invitation doesn’t exist")?;
+    let challenge = Challenge::try_from(pick.challenge)?;
+    let text = format!(
+        "<@{}>, who won week {} of the {} Challenge, picked the next prompt: **{}** ({})",
+        pick.user,
+        pick.week,
+        challenge.name(),
+        pick.prompt.as_deref().unwrap_or_default(),
+        if pick.shortlisted.is_some() { "from the shortlist" } else { "their own suggestion" },
+    );
+
+    api.send_message(channel, CreateMessage::new()
+        .content(text)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(InteractionID::PromptPick { pick: id, action: PickAction::Approve }.encode())
+                .label("Add to queue")
+                .style(ButtonStyle::Success),
+            CreateButton::new(InteractionID::PromptPick { pick: id, action: PickAction::Reject }.encode())
+                .label("Reject")
+                .style(ButtonStyle::Danger),
+        ])])
+    ).await?;
+    Ok(())
+}
+
+/// Approve or reject a winner’s pick, and let them know. Returns what to
+/// show the admin who reviewed it.
+pub async fn review(api: &dyn DiscordApi, id: i64, approve: bool) -> Result<String, Error> {
+    let pick = sql::get_prompt_pick(id).await?.ok_or("This invitation doesn’t exist")?;
+    let challenge = Challenge::try_from(pick.challenge)?;
+    let prompt = pick.prompt.clone().unwrap_or_default();
+    let user = UserId::new(pick.user as u64);
+
+    if approve {
+        let Some(queued) = sql::approve_prompt_pick(id).await? else { return Err("This pick has already been reviewed".into()); };
+        info!("Queued prompt {} picked by {} for the {:?} Challenge", queued, user, challenge);
+        let told = notify(api, user, CreateMessage::new().content(format!(
+            "Your prompt for the {} Challenge, ‘{}’, was approved and will be used in an upcoming week. Thanks!",
+            challenge.name(),
+            prompt,
+        ))).await;
+        let note = if told { "" } else { " The winner could not be told by DM." };
+        return Ok(format!("Approved ‘{}’; this is entry {} in the queue.{}", prompt, queued, note));
+    }
+
+    if !sql::reject_prompt_pick(id).await? { return Err("This pick has already been reviewed".into()); }
+    info!("Rejected prompt pick {} by {} for the {:?} Challenge", id, user, challenge);
+    let intro = format!("Sorry, the admins decided not to use ‘{}’.", prompt);
+    let (text, rows) = render(&sql::get_prompt_pick(id).await?.ok_or("This invitation doesn’t exist")?, &intro).await?;
+    Ok(match notify(api, user, CreateMessage::new().content(text).components(rows)).await {
+        true => format!("Rejected ‘{}’; the winner was asked to pick again.", prompt),
+        false => format!("Rejected ‘{}’, but the winner could not be asked by DM to pick again.", prompt),
+    })
+}
+
+/// Tell a winner how their pick was reviewed. The review stands either
+/// way, so failing to reach them is only logged. Returns whether the DM
+/// was sent.
+async fn notify(api: &dyn DiscordApi, user: UserId, message: CreateMessage) -> bool {
+    match dm::send(api, user, DmKind::Essential, message).await {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Could not tell {} how their prompt pick was reviewed: {}", user, e);
+            false
+        }
+    }
+}
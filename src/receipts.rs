@@ -0,0 +1,87 @@
+//! Vote receipts: a list of everything someone has voted for in the week
+//! being voted on, with a button to take back each vote.
+//!
+//! Receipts are shown after a ballot is cast and by `/votes`. Votes cast
+//! with the vote reaction don’t get one of their own: reactions don’t come
+//! with an interaction we could reply to privately, and a DM for every
+//! reaction would be noise that few voters want. `/votes` lists those too.
+
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateButton, MessageId, UserId};
+use crate::{ballots, clock, Error, info, Res, scheduler, sql};
+use crate::core::{InteractionID, MESSAGE_MAX_CONTENT, safe_truncate, timestamp, TimestampStyle};
+use crate::discord::DiscordApi;
+use crate::events::{submission_link, vote_reaction};
+use crate::sql::Challenge;
+
+/// Discord allows at most 5 rows of 5 buttons per message.
+const MAX_BUTTONS: usize = 25;
+
+/// Room kept free at the end of a receipt for the notes about what was
+/// left out.
+const NOTES_RESERVE: usize = 200;
+
+/// Build the text and buttons of someone’s vote receipt.
+pub async fn render(voter: UserId) -> Result<(String, Vec<CreateActionRow>), Error> {
+    let week = ballots::voting_week().await?;
+    let votes = sql::get_voted_submissions(voter, week).await?;
+    if votes.is_empty() { return Ok((format!("You haven’t voted for any entries in week {}.", week), vec![])); }
+
+    let close = scheduler::voting_deadline(week).await?;
+    let mut text = format!(
+        "**Your votes for week {}**\nVoting closes {}; until then, you can take back a vote with the buttons below.\n",
+        week,
+        timestamp(close, TimestampStyle::Relative),
+    );
+
+    let mut buttons = Vec::new();
+    let mut listed = 0;
+    for s in &votes {
+        let challenge = Challenge::try_from(s.challenge)?;
+        let message = MessageId::new(s.message as u64);
+        let name = sql::get_author_name(s.author).await?.unwrap_or_else(|| "Unknown".into());
+        let line = format!("\n- {} Challenge: [entry by {}]({})", challenge.name(), name, submission_link(challenge, message));
+        if text.chars().count() + line.chars().count() > MESSAGE_MAX_CONTENT - NOTES_RESERVE { break; }
+        text.push_str(&line);
+        listed += 1;
+        buttons.push(CreateButton::new(InteractionID::RemoveVote { message }.encode())
+            .label(safe_truncate(format!("Remove vote for {} ({})", name, challenge.name()), 80))
+            .style(ButtonStyle::Danger));
+    }
+
+    if listed < votes.len() {
+        text.push_str(&format!("\n…and {} more; take back some of the votes above to see them.", votes.len() - listed));
+    }
+
+    if listed > MAX_BUTTONS {
+        text.push_str(&format!("\n\nOnly the first {} votes have a button; take back some of those to see the rest.", MAX_BUTTONS));
+    }
+
+    let rows = buttons.into_iter()
+        .take(MAX_BUTTONS)
+        .collect::<Vec<_>>()
+        .chunks(5)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect();
+
+    Ok((text, rows))
+}
+
+/// Take back someone’s vote on an entry in the week being voted on, and
+/// their vote reaction with it.
+pub async fn remove_vote(api: &dyn DiscordApi, voter: UserId, message: MessageId) -> Res {
+    let Some(s) = sql::get_submission(message).await? else { return Err("That entry no longer exists".into()); };
+    let week = ballots::voting_week().await?;
+    if s.week != week || clock::now() >= scheduler::voting_deadline(week).await? {
+        return Err("Voting on that entry has closed".into());
+    }
+
+    if !sql::remove_vote(message, voter).await? { return Err("You haven’t voted for that entry".into()); }
+    info!("{} took back their vote on {} from their receipt", voter, message);
+
+    // The reaction would otherwise suggest the vote still counts. This is
+    // allowed to fail, e.g. if they voted with a ballot.
+    let channel = Challenge::try_from(s.challenge)?.submission_channel();
+    let _ = api.delete_reaction(channel, message, Some(voter), vote_reaction().await).await;
+    Ok(())
+}
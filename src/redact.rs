@@ -0,0 +1,109 @@
+//! Keep sensitive data out of the logs. Every log message is passed
+//! through [`scrub()`]; the other functions are for call sites that log
+//! things [`scrub()`] can’t recognise, such as names or DM contents.
+
+use std::hash::{BuildHasher, Hasher, RandomState};
+use once_cell::sync::Lazy;
+use crate::Context;
+use crate::{config, logging};
+
+/// How much to hide in the logs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Privacy {
+    /// Hide the bot token and the contents of DMs.
+    #[default]
+    Standard,
+
+    /// Also replace Discord IDs with pseudonyms and leave out command
+    /// arguments.
+    Strict,
+}
+
+/// Discord IDs have between 17 and 20 digits.
+const SNOWFLAKE_DIGITS: std::ops::RangeInclusive<usize> = 17..=20;
+
+/// Key for pseudonyms; they are stable for as long as the bot runs so
+/// that messages about the same user can still be correlated.
+static PSEUDONYM_KEY: Lazy<RandomState> = Lazy::new(RandomState::new);
+
+fn strict() -> bool {
+    logging::privacy() == Privacy::Strict
+}
+
+/// Get a pseudonym for an ID.
+fn pseudonym(id: &str) -> String {
+    let mut h = PSEUDONYM_KEY.build_hasher();
+    h.write(id.as_bytes());
+    format!("[id:{:06x}]", h.finish() & 0xFF_FFFF)
+}
+
+/// Replace everything that looks like a Discord ID with a pseudonym.
+fn replace_snowflakes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let len = rest[start..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - start);
+        let digits = &rest[start..start + len];
+
+        // Don’t touch digits that are part of a longer word, e.g. a hash.
+        let before = rest[..start].chars().next_back();
+        let after = rest[start + len..].chars().next();
+        let standalone = !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric());
+
+        out += &rest[..start];
+        if standalone && SNOWFLAKE_DIGITS.contains(&len) { out += &pseudonym(digits); } else { out += digits; }
+        rest = &rest[start + len..];
+    }
+
+    out += rest;
+    out
+}
+
+/// Remove sensitive data from a log message. This also catches values
+/// that end up in error messages, e.g. from the DB.
+pub fn scrub(message: &str) -> String {
+    let mut message = match config::try_get().map(|c| c.token.as_str()).filter(|t| !t.is_empty()) {
+        Some(token) => message.replace(token, "[token]"),
+        None => message.to_string(),
+    };
+
+    if strict() { message = replace_snowflakes(&message); }
+    message
+}
+
+/// Describe the contents of a DM without including them.
+pub fn dm(text: &str) -> String {
+    format!("[DM, {} characters]", text.chars().count())
+}
+
+/// Commands whose arguments include prompts that haven’t been announced
+/// yet. The log may be mirrored to a channel non-admins can read, so
+/// their arguments are always left out.
+const PROMPT_COMMANDS: &[&str] = &["queue shortlist add"];
+
+/// Get the invocation of a command as it should be logged.
+pub fn invocation(ctx: Context<'_>) -> String {
+    let name = &ctx.command().qualified_name;
+    match strict() || PROMPT_COMMANDS.contains(&name.as_str()) {
+        true => format!("/{}", name),
+        false => ctx.invocation_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_snowflakes_are_replaced() {
+        let text = "User 123456789012345678 (a1234567890123456789) voted 3 times on 98765432109876543210";
+        let scrubbed = replace_snowflakes(text);
+        assert!(!scrubbed.contains("123456789012345678 "));
+        assert!(scrubbed.contains("a1234567890123456789"));
+        assert!(scrubbed.contains("voted 3 times"));
+        assert!(!scrubbed.contains("98765432109876543210"));
+
+        // The same ID always gets the same pseudonym.
+        assert_eq!(replace_snowflakes("<@123456789012345678>"), format!("<@{}>", pseudonym("123456789012345678")));
+    }
+}
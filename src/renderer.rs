@@ -0,0 +1,232 @@
+//! Render the announcement images for prompts, so that the host doesn’t
+//! need Python for `weekly_challenges.py`, which is only used if the
+//! config asks for it.
+//!
+//! What an image looks like is described by a template: a TOML file in
+//! [`TEMPLATE_DIR`] named after the challenge, e.g. `glyph.toml`, or after
+//! the challenge and a theme, e.g. `glyph_winter.toml`. Every field of
+//! [`Template`] is optional.
+
+use std::path::Path;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use image::imageops::{FilterType, resize};
+use imageproc::drawing::{draw_text_mut, text_size};
+use poise::ChoiceParameter;
+use serde::Deserialize;
+use crate::{Error, info};
+use crate::core::parse_colour;
+use crate::sql::Challenge;
+
+/// Directory that holds the templates and the files they refer to.
+const TEMPLATE_DIR: &str = "./weekly_challenges/templates";
+
+/// Font used unless a template names another one.
+const DEFAULT_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// Prompts are never shrunk below this size, even if they don’t fit.
+const MIN_PROMPT_SIZE: f32 = 48.0;
+
+/// Layout of an announcement image. Paths are relative to [`TEMPLATE_DIR`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    pub width: u32,
+    pub height: u32,
+
+    /// Image drawn behind the text; it is stretched to fit.
+    pub background_image: Option<String>,
+
+    /// Colours, as `#RRGGBB`. The background colour is only used if there
+    /// is no background image.
+    pub background: String,
+    pub text_colour: String,
+
+    /// Font file; defaults to the bundled DejaVu Sans.
+    pub font: Option<String>,
+
+    /// Line above the prompt; `{challenge}` is replaced with the name of
+    /// the challenge.
+    pub heading: String,
+    pub heading_size: f32,
+
+    /// Size of the prompt; long prompts are wrapped and shrunk to fit.
+    pub prompt_size: f32,
+
+    /// Space left free at the edges, in pixels.
+    pub margin: u32,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            background_image: None,
+            background: "#1E1E3C".into(),
+            text_colour: "#FAF5EB".into(),
+            font: None,
+            heading: "{challenge} Challenge".into(),
+            heading_size: 80.0,
+            prompt_size: 280.0,
+            margin: 120,
+        }
+    }
+}
+
+/// Load the template for a challenge and theme. Without a theme, the
+/// built-in layout is used if there is no template file.
+pub fn load_template(challenge: Challenge, theme: Option<&str>) -> Result<Template, Error> {
+    let prefix = match challenge {
+        Challenge::Glyph => "glyph",
+        Challenge::Ambigram => "ambigram",
+    };
+
+    let name = match theme {
+        Some(theme) => format!("{}_{}.toml", prefix, theme),
+        None => format!("{}.toml", prefix),
+    };
+
+    let path = Path::new(TEMPLATE_DIR).join(name);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && theme.is_none() => return Ok(Template::default()),
+        Err(e) => return Err(format!("Could not read template {}: {}", path.display(), e).into()),
+    };
+
+    toml::from_str(&text).map_err(|e| format!("Invalid template {}: {}", path.display(), e).into())
+}
+
+fn parse_rgba(s: &str) -> Result<Rgba<u8>, Error> {
+    let c = parse_colour(s)?;
+    Ok(Rgba([c.r(), c.g(), c.b(), 255]))
+}
+
+/// Break text into lines that are at most `width` pixels wide. Words that
+/// are too wide by themselves get a line of their own.
+fn wrap(font: &impl Font, scale: PxScale, text: &str, width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if !line.is_empty() && text_size(scale, font, &candidate).0 > width {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        } else {
+            line = candidate;
+        }
+    }
+
+    if !line.is_empty() { lines.push(line); }
+    lines
+}
+
+/// Find the largest size, up to `max`, at which the wrapped text fits into
+/// a box. Returns the size and the lines.
+fn fit(font: &impl Font, max: f32, text: &str, width: u32, height: u32) -> (PxScale, Vec<String>) {
+    let mut size = max;
+    loop {
+        let scale = PxScale::from(size);
+        let lines = wrap(font, scale, text, width);
+        let line_height = font.as_scaled(scale).height();
+        let fits = lines.iter().all(|l| text_size(scale, font, l).0 <= width)
+            && line_height * lines.len() as f32 <= height as f32;
+        if fits || size <= MIN_PROMPT_SIZE { return (scale, lines); }
+        size = (size * 0.9).max(MIN_PROMPT_SIZE);
+    }
+}
+
+/// Draw lines of text centred horizontally, starting at `y`.
+fn draw_lines(img: &mut RgbaImage, colour: Rgba<u8>, font: &impl Font, scale: PxScale, lines: &[String], mut y: f32) {
+    let line_height = font.as_scaled(scale).height();
+    for line in lines {
+        let (w, _) = text_size(scale, font, line);
+        let x = img.width().saturating_sub(w) / 2;
+        draw_text_mut(img, colour, x as i32, y as i32, scale, font, line);
+        y += line_height;
+    }
+}
+
+/// Draw the announcement image for a prompt.
+fn draw_announcement(
+    template: &Template,
+    font: &impl Font,
+    background: Option<RgbaImage>,
+    challenge: Challenge,
+    prompt: &str,
+) -> Result<RgbaImage, Error> {
+    let (w, h) = (template.width, template.height);
+    let text = parse_rgba(&template.text_colour)?;
+    let mut img = match background {
+        Some(bg) if bg.dimensions() == (w, h) => bg,
+        Some(bg) => resize(&bg, w, h, FilterType::Lanczos3),
+        None => RgbaImage::from_pixel(w, h, parse_rgba(&template.background)?),
+    };
+
+    // The heading goes at the top.
+    let margin = template.margin as f32;
+    let heading = template.heading.replace("{challenge}", challenge.name());
+    let heading_scale = PxScale::from(template.heading_size);
+    let heading_height = if heading.is_empty() { 0.0 } else { font.as_scaled(heading_scale).height() };
+    draw_lines(&mut img, text, font, heading_scale, &[heading], margin);
+
+    // The prompt is centred in the space below it.
+    let top = margin + heading_height;
+    let width = w.saturating_sub(2 * template.margin);
+    let height = (h as f32 - top - margin).max(0.0);
+    let (scale, lines) = fit(font, template.prompt_size, prompt, width, height as u32);
+    let block = font.as_scaled(scale).height() * lines.len() as f32;
+    draw_lines(&mut img, text, font, scale, &lines, top + (height - block).max(0.0) / 2.0);
+    Ok(img)
+}
+
+/// Render the announcement image for a prompt to the path the announcement
+/// is posted from, and return that path.
+pub async fn render_announcement(challenge: Challenge, prompt: &str, theme: Option<&str>) -> Result<String, Error> {
+    let template = load_template(challenge, theme)?;
+    let font = match &template.font {
+        Some(f) => FontVec::try_from_vec(std::fs::read(Path::new(TEMPLATE_DIR).join(f))?)?,
+        None => FontVec::try_from_vec(DEFAULT_FONT.to_vec())?,
+    };
+
+    let background = match &template.background_image {
+        Some(bg) => Some(image::open(Path::new(TEMPLATE_DIR).join(bg))?.to_rgba8()),
+        None => None,
+    };
+
+    // Rendering is CPU-bound, so keep it off the async workers.
+    let path = challenge.announcement_image_path();
+    let prompt = prompt.to_string();
+    let out = path.clone();
+    tokio::task::spawn_blocking(move || -> Result<(), Error> {
+        draw_announcement(&template, &font, background, challenge, &prompt)?.save(&out)?;
+        Ok(())
+    }).await??;
+
+    info!("Rendered announcement image for {:?}: {}", challenge, path);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::golden;
+    use super::*;
+
+    fn font() -> FontVec {
+        FontVec::try_from_vec(DEFAULT_FONT.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn long_prompts_are_wrapped_to_fit() {
+        let font = font();
+        let (scale, lines) = fit(&font, 200.0, "uppercase and lowercase A", 800, 600);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| text_size(scale, &font, l).0 <= 800));
+        assert_eq!(lines.join(" "), "uppercase and lowercase A");
+    }
+
+    #[test]
+    fn announcement_matches_golden() {
+        let img = draw_announcement(&Template::default(), &font(), None, Challenge::Glyph, "Q").unwrap();
+        golden::check("announcement", &img);
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use poise::serenity_prelude as ser;
+use poise::ChoiceParameter;
+use crate::{archive, certificates, clock, config, err, Error, info, preflight, prompt_picks, Res, scheduler, sql, tally, templates, warn};
+use crate::core::{badge_emoji, DEFAULT_EMBED_COLOUR, format_number};
+use crate::events::submission_link;
+use crate::sql::{Challenge, Season, Setting, Submission, Week, WeekMessage};
+use crate::tally::HOF_PLACES;
+use crate::templates::Template;
+
+pub use crate::tally::Placement;
+
+/// How many weeks back we look for a hall of fame that wasn’t posted, e.g.
+/// because the bot was offline when voting closed.
+const HOF_CATCH_UP_WEEKS: i64 = 2;
+
+/// Rank the active submissions for a challenge in a week by votes.
+pub async fn ranking(week: i64, challenge: Challenge) -> Result<Vec<Placement>, Error> {
+    Ok(tally::rank(sql::get_submissions(week, challenge).await?))
+}
+
+/// Get each user’s best place in any challenge in a range of weeks.
+pub async fn best_placements(first_week: i64, last_week: i64) -> Result<HashMap<ser::UserId, usize>, Error> {
+    let mut rankings = Vec::new();
+    for week in first_week..=last_week {
+        for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+            rankings.push(ranking(week, challenge).await?);
+        }
+    }
+
+    Ok(tally::best_placements(rankings.iter().flatten()))
+}
+
+/// Merge the results of both challenges in a range of weeks into one
+/// standing, weighted as configured. Returns users and their scores,
+/// best first.
+pub async fn combined_standings(first_week: i64, last_week: i64) -> Result<Vec<(ser::UserId, i64)>, Error> {
+    let weights = [
+        (Challenge::Glyph, sql::get_setting::<i64>(Setting::CombinedGlyphWeight).await?),
+        (Challenge::Ambigram, sql::get_setting::<i64>(Setting::CombinedAmbigramWeight).await?),
+    ];
+
+    let mut rankings = Vec::new();
+    for week in first_week..=last_week {
+        for (challenge, weight) in weights {
+            rankings.push((weight, ranking(week, challenge).await?));
+        }
+    }
+
+    Ok(tally::combined_scores(rankings.iter().map(|(w, r)| (*w, r.as_slice()))))
+}
+
+/// Announce the combined champion of a season whose results are in and
+/// give them the champion role, if one is configured.
+pub async fn honour_combined_champion(ctx: &ser::Context, season: &Season) -> Res {
+    if !sql::get_setting::<bool>(Setting::CombinedChampionship).await? { return Ok(()); }
+    let Some(end_week) = season.end_week else { return Err("Season has not ended yet".into()); };
+    let standings = combined_standings(season.start_week, end_week).await?;
+    let Some(&(_, best)) = standings.first() else { return Ok(()); };
+
+    // Ties share the title.
+    let champions = standings.iter()
+        .take_while(|(_, score)| *score == best)
+        .map(|(u, _)| *u)
+        .collect::<Vec<_>>();
+
+    let names = champions.iter().map(|u| format!("<@{}>", u)).collect::<Vec<_>>().join(" and ");
+    scheduler::post_notice(ctx, &format!(
+        "🏆 The combined champion of {} is {} with {} point{}!",
+        season.name, names, best, if best == 1 { "" } else { "s" },
+    )).await;
+
+    // Someone who left the server shouldn’t keep the others from getting the role.
+    let role = sql::get_setting::<u64>(Setting::ChampionRole).await?;
+    if role != 0 {
+        for user in champions {
+            let res = match config::get().server.member(ctx, user).await {
+                Ok(member) => member.add_role(ctx, ser::RoleId::new(role)).await,
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = res {
+                err!("Could not give the champion role to {}: {}", user, e);
+            }
+        }
+    }
+
+    info!("Announced combined champion of season {}", season.name);
+    Ok(())
+}
+
+/// Close the seasons that have ended once the results of their last week
+/// are in: announce the combined champion and send out certificates.
+pub async fn close_ended_seasons(ctx: &ser::Context) -> Res {
+    for season in sql::get_unclosed_seasons().await? {
+        let Some(end_week) = season.end_week else { continue; };
+        if scheduler::voting_deadline(end_week).await? > clock::now() { continue; }
+        if due_hofs().await?.iter().any(|(week, _)| *week <= end_week) { continue; }
+        if !sql::close_season(season.id).await? { continue; }
+
+        if let Err(e) = honour_combined_champion(ctx, &season).await {
+            err!("Error announcing combined champion: {}", e);
+        }
+
+        if let Err(e) = certificates::send_certificates(ctx, &season).await {
+            err!("Error sending certificates: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a place as e.g. ‘1st’.
+pub fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+
+    format!("{}{}", n, suffix)
+}
+
+/// Get the pronouns and badges of everyone in the hall of fame, to show
+/// next to their names.
+async fn hof_annotations(ctx: &ser::Context, placements: &[Placement]) -> Result<HashMap<i64, String>, Error> {
+    let mut annotations = HashMap::new();
+    for p in placements.iter().filter(|p| p.place <= HOF_PLACES && p.submission.author != sql::PURGED_AUTHOR) {
+        let user = ser::UserId::new(p.submission.author as u64);
+        let mut text = String::new();
+        if let Some(pr) = sql::get_pronouns(user).await? {
+            text.push_str(&format!(" ({})", pr));
+        }
+
+        for a in sql::get_achievements(user).await? {
+            text.push(' ');
+            text.push_str(&badge_emoji(&ctx.cache, a).await);
+        }
+
+        if !text.is_empty() { annotations.insert(p.submission.author, text); }
+    }
+
+    Ok(annotations)
+}
+
+/// Link to a submission, hidden behind a spoiler if it was posted as one.
+fn entry_link(text: &str, challenge: Challenge, s: &Submission) -> String {
+    let link = submission_link(challenge, ser::MessageId::new(s.message as u64));
+    if s.spoiler { format!("||[{}]({})||", text, link) } else { format!("[{}]({})", text, link) }
+}
+
+/// Maximum number of images Discord shows together in a gallery.
+const MAX_GALLERY_IMAGES: usize = 4;
+
+/// What a hall of fame message shows besides the results.
+struct HofExtras {
+    /// Pronouns and badges to show next to people’s names.
+    annotations: HashMap<i64, String>,
+
+    /// Images shown in the gallery, which are attached to the message.
+    images: Vec<ser::CreateAttachment>,
+
+    thread: Option<ser::ChannelId>,
+    discussion: Option<ser::ChannelId>,
+}
+
+/// Get the images of the entries in the hall of fame, to attach to it.
+///
+/// Links to Discord’s CDN expire, so we upload the archived images
+/// instead. Embed images can’t be spoilered, so spoilered entries are
+/// only linked to, and entries whose image we can’t get are left out.
+async fn hof_images(ctx: &ser::Context, challenge: Challenge, placements: &[Placement]) -> Vec<ser::CreateAttachment> {
+    let mut images = Vec::new();
+    for p in placements.iter().filter(|p| p.place <= HOF_PLACES && !p.submission.spoiler && p.submission.author != sql::PURGED_AUTHOR) {
+        if images.len() == MAX_GALLERY_IMAGES { break; }
+        let message = ser::MessageId::new(p.submission.message as u64);
+        let image = match archive::submission_path(ctx, challenge, message).await {
+            Ok(path) => ser::CreateAttachment::path(path).await.map_err(Error::from),
+            Err(e) => Err(e),
+        };
+
+        match image {
+            Ok(image) => images.push(image),
+            Err(e) => warn!("Leaving submission {} out of the hall of fame gallery: {}", message, e),
+        }
+    }
+
+    images
+}
+
+/// Build the hall of fame embeds: the results, followed by the images of
+/// the other entries in the hall of fame, which Discord shows as a gallery.
+fn hof_embeds(week: i64, challenge: Challenge, placements: &[Placement], extras: &HofExtras, corrected: bool) -> Vec<ser::CreateEmbed> {
+    // Embeds are only merged into a gallery if they have the same URL.
+    let url = format!("https://discord.com/channels/{}/{}", config::get().server, challenge.submission_channel());
+    let mut embed = ser::CreateEmbed::new()
+        .colour(DEFAULT_EMBED_COLOUR)
+        .title(format!("Hall of Fame – {} Challenge, Week {}", challenge.name(), week))
+        .url(&url);
+
+    for p in placements.iter().filter(|p| p.place <= HOF_PLACES) {
+        let votes = format!("{} vote{}", p.submission.votes, if p.submission.votes == 1 { "" } else { "s" });
+        embed = embed.field(
+            format!("{} Place", ordinal(p.place)),
+            match p.submission.author {
+                sql::PURGED_AUTHOR => format!("A former member with {}", votes),
+                author => format!(
+                    "<@{}>{} with {}\n{}",
+                    author,
+                    extras.annotations.get(&author).map(String::as_str).unwrap_or_default(),
+                    votes,
+                    entry_link("View entry", challenge, &p.submission),
+                ),
+            },
+            false,
+        );
+    }
+
+    let mut images = extras.images.iter().map(|a| format!("attachment://{}", a.filename));
+    if let Some(first) = images.next() { embed = embed.image(first); }
+    let gallery = images.map(|image| ser::CreateEmbed::new().url(&url).image(image)).collect::<Vec<_>>();
+
+    if let Some(thread) = extras.thread {
+        embed = embed.field("Featured artist", format!("Read the interview in <#{}>", thread), false);
+    }
+
+    if let Some(discussion) = extras.discussion {
+        embed = embed.field("Discussion", format!("Talk about this week in <#{}>", discussion), false);
+    }
+
+    if corrected {
+        embed = embed.footer(ser::CreateEmbedFooter::new("Edited: results corrected"));
+    }
+
+    std::iter::once(embed).chain(gallery).collect()
+}
+
+/// Replace the embeds and images of a hall of fame message.
+fn hof_edit(embeds: Vec<ser::CreateEmbed>, images: &[ser::CreateAttachment]) -> ser::EditMessage {
+    let mut edit = ser::EditMessage::new().embeds(embeds).remove_all_attachments();
+    for image in images { edit = edit.new_attachment(image.clone()); }
+    edit
+}
+
+/// Post the hall of fame for a challenge in a week. If it has already been
+/// posted, the existing message is edited instead of posting a new one.
+pub async fn publish_hof(ctx: &ser::Context, week: i64, challenge: Challenge) -> Res {
+    let placements = ranking(week, challenge).await?;
+    if placements.is_empty() { return Err(format!("No submissions for week {}", week).into()); }
+    sql::record_week_results(week, challenge, &placements.iter()
+        .map(|p| (p.submission.clone(), p.place))
+        .collect::<Vec<_>>()
+    ).await?;
+
+    // The hall of fame is posted in the submission channel.
+    let mut extras = HofExtras {
+        annotations: hof_annotations(ctx, &placements).await?,
+        images: hof_images(ctx, challenge, &placements).await,
+        thread: sql::get_week_message(week, challenge, WeekMessage::FeatureThread).await?.map(|t| ser::ChannelId::new(t.get())),
+        discussion: sql::get_week_message(week, challenge, WeekMessage::DiscussionThread).await?.map(|t| ser::ChannelId::new(t.get())),
+    };
+
+    let channel = challenge.submission_channel();
+    let largest = extras.images.iter().map(|a| a.data.len()).max();
+    preflight::preflight_post(ctx, channel, "the hall of fame", largest).await?;
+    match sql::get_week_message(week, challenge, WeekMessage::HallOfFame).await? {
+        Some(message) => {
+            let embeds = hof_embeds(week, challenge, &placements, &extras, true);
+            channel.edit_message(ctx, message, hof_edit(embeds, &extras.images)).await?;
+            info!("Updated hall of fame for {:?} in week {}", challenge, week);
+        }
+
+        None => {
+            let embeds = hof_embeds(week, challenge, &placements, &extras, false);
+            let mut message = channel.send_message(ctx, ser::CreateMessage::new().embeds(embeds).add_files(extras.images.clone())).await?;
+            sql::set_week_message(week, challenge, WeekMessage::HallOfFame, message.id).await?;
+            info!("Posted hall of fame for {:?} in week {}", challenge, week);
+
+            // Open the featured artist thread and link to it.
+            if extras.thread.is_none() && sql::get_setting::<bool>(Setting::FeaturedArtist).await? {
+                if let Some(thread) = open_feature_thread(ctx, week, challenge, &placements, &message).await? {
+                    extras.thread = Some(thread);
+                    let embeds = hof_embeds(week, challenge, &placements, &extras, false);
+                    message.edit(ctx, hof_edit(embeds, &extras.images)).await?;
+                }
+            }
+
+            // A winner with closed DMs shouldn’t hold up the rest.
+            if let Err(e) = prompt_picks::invite(ctx, week, challenge, &placements).await {
+                err!("Could not invite the winner of {:?} in week {} to pick a prompt: {}", challenge, week, e);
+            }
+        }
+    }
+
+    publish_recap(ctx, week, challenge, &placements).await?;
+    publish_series_results(ctx, week, challenge).await
+}
+
+/// Add up the results of the parts of a series. Returns the weeks the
+/// parts ran in and the standings, best first, once every part has run.
+pub async fn series_standings(series: &sql::Series) -> Result<Option<(Vec<i64>, Vec<(ser::UserId, i64)>)>, Error> {
+    let weeks = sql::get_series_weeks(series).await?;
+    if (weeks.len() as i64) < series.parts { return Ok(None); }
+
+    let challenge = Challenge::try_from(series.challenge)?;
+    let mut rankings = Vec::with_capacity(weeks.len());
+    for &week in &weeks { rankings.push(ranking(week, challenge).await?); }
+    let standings = tally::combined_scores(rankings.iter().map(|r| (1, r.as_slice())));
+    Ok(Some((weeks, standings)))
+}
+
+/// Post the combined results of a series once the hall of fame of its last
+/// part is out, or update them if they have already been posted.
+async fn publish_series_results(ctx: &ser::Context, week: i64, challenge: Challenge) -> Res {
+    let Some(series) = sql::get_week_series(week, challenge).await? else { return Ok(()); };
+    let Some((weeks, standings)) = series_standings(&series).await? else { return Ok(()); };
+    if weeks.last() != Some(&week) { return Ok(()); }
+
+    let mut parts = String::new();
+    for (i, &w) in weeks.iter().enumerate() {
+        let info = sql::weekinfo(Some(w as u64)).await.ok();
+        let prompt = info.as_ref().and_then(|i| i.prompt(challenge)).unwrap_or("?");
+        parts.push_str(&format!("Part {}: {} (week {})\n", i + 1, prompt, w));
+    }
+
+    let mut place = 0;
+    let mut lines = Vec::new();
+    for (i, (user, points)) in standings.iter().enumerate() {
+        if i == 0 || standings[i - 1].1 != *points { place = i + 1; }
+        if place > HOF_PLACES { break; }
+        lines.push(format!("{}: <@{}> with {} point{}", ordinal(place), user, points, if *points == 1 { "" } else { "s" }));
+    }
+
+    let embed = ser::CreateEmbed::new()
+        .colour(DEFAULT_EMBED_COLOUR)
+        .title(format!("Series Results – {}", series.name))
+        .description(parts)
+        .field("Overall", if lines.is_empty() { "Nobody placed in this series.".to_string() } else { lines.join("\n") }, false);
+
+    let channel = challenge.submission_channel();
+    preflight::preflight_post(ctx, channel, "the series results", None).await?;
+    match series.results_message {
+        Some(message) => {
+            channel.edit_message(ctx, ser::MessageId::new(message as u64), ser::EditMessage::new().embed(embed)).await?;
+        }
+
+        None => {
+            let message = channel.send_message(ctx, ser::CreateMessage::new().embed(embed)).await?;
+            sql::set_series_results_message(series.id, message.id).await?;
+        }
+    }
+
+    info!("Published results of series {}", series.name);
+    Ok(())
+}
+
+/// Get the weeks and challenges whose voting has closed but whose hall of
+/// fame hasn’t been posted yet. Special weeks and challenges without any
+/// entries don’t get one.
+pub async fn due_hofs() -> Result<Vec<(i64, Challenge)>, Error> {
+    let current = sql::current_week().await?;
+    let first = (current - HOF_CATCH_UP_WEEKS).max(0);
+    let rows = sql::get_weeks(first, current).await?;
+    let now = clock::now();
+
+    let mut due = Vec::new();
+    for week in first..current {
+        if scheduler::voting_deadline(week).await? > now { continue; }
+        let row = rows.iter().find(|r| r.week == week);
+        for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+            if row.is_some_and(|r| r.kind(challenge) == Week::Special) { continue; }
+            if sql::get_week_message(week, challenge, WeekMessage::HallOfFame).await?.is_some() { continue; }
+            if sql::count_submissions(week, challenge).await? == 0 { continue; }
+            due.push((week, challenge));
+        }
+    }
+
+    Ok(due)
+}
+
+/// Post every hall of fame that is due. One that fails is retried the
+/// next time this runs and doesn’t hold up the others.
+pub async fn publish_due_hofs(ctx: &ser::Context) -> Res {
+    for (week, challenge) in due_hofs().await? {
+        if let Err(e) = publish_hof(ctx, week, challenge).await {
+            err!("Error publishing the hall of fame for {:?} in week {}: {}", challenge, week, e);
+        }
+    }
+
+    close_ended_seasons(ctx).await
+}
+
+/// Start a thread on the hall of fame message in which the winner
+/// is interviewed. Returns the thread, if there is a winner to feature.
+async fn open_feature_thread(
+    ctx: &ser::Context,
+    week: i64,
+    challenge: Challenge,
+    placements: &[Placement],
+    hof: &ser::Message,
+) -> Result<Option<ser::ChannelId>, Error> {
+    // Ties for first place all get featured.
+    let winners = placements.iter()
+        .take_while(|p| p.place == 1)
+        .filter(|p| p.submission.author != sql::PURGED_AUTHOR)
+        .map(|p| format!("<@{}>", p.submission.author))
+        .collect::<Vec<_>>();
+    if winners.is_empty() { return Ok(None); }
+
+    let prompt = sql::weekinfo(Some(week as u64)).await?.prompt(challenge).unwrap_or("(none)").to_string();
+    let text = templates::render(Template::FeaturedArtist, &[
+        ("user", &winners.join(" and ")),
+        ("week", &week.to_string()),
+        ("challenge", challenge.name()),
+        ("prompt", &prompt),
+    ]).await?;
+
+    let thread = hof.channel_id.create_thread_from_message(ctx, hof.id, ser::CreateThread::new(
+        format!("Featured Artist – {} Challenge, Week {}", challenge.name(), week)
+    )).await?;
+
+    thread.say(ctx, text).await?;
+    sql::set_week_message(week, challenge, WeekMessage::FeatureThread, ser::MessageId::new(thread.id.get())).await?;
+    info!("Opened featured artist thread for {:?} in week {}", challenge, week);
+    Ok(Some(thread.id))
+}
+
+/// Find the two adjacent places with the smallest difference in votes.
+fn closest_margin(placements: &[Placement]) -> Option<(&Placement, &Placement)> {
+    placements.windows(2)
+        .filter(|w| w[0].place != w[1].place)
+        .min_by_key(|w| w[0].submission.votes - w[1].submission.votes)
+        .map(|w| (&w[0], &w[1]))
+}
+
+/// Build the recap embed.
+async fn recap_embed(week: i64, challenge: Challenge, placements: &[Placement]) -> Result<ser::CreateEmbed, Error> {
+    let info = sql::weekinfo(Some(week as u64)).await.ok();
+    let prompt = info.as_ref().and_then(|i| i.prompt(challenge)).unwrap_or("?");
+    let turnout = sql::get_turnout(week, challenge).await?;
+    let text = templates::render(Template::Recap, &[
+        ("week", &week.to_string()),
+        ("challenge", challenge.name()),
+        ("prompt", prompt),
+        ("entries", &format_number(placements.len() as i64, None)),
+        ("voters", &format_number(turnout.voters, None)),
+    ]).await?;
+
+    let mut embed = ser::CreateEmbed::new()
+        .colour(DEFAULT_EMBED_COLOUR)
+        .title(format!("Week {} Recap – {} Challenge", week, challenge.name()))
+        .description(text);
+
+    let winners = placements.iter()
+        .filter(|p| p.place <= HOF_PLACES)
+        .map(|p| match p.submission.author {
+            sql::PURGED_AUTHOR => format!("{}: a former member", ordinal(p.place)),
+            author => format!("{}: <@{}>", ordinal(p.place), author),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    embed = embed.field("Winners", winners, false);
+
+    if let Some((a, b)) = closest_margin(placements) {
+        let diff = a.submission.votes - b.submission.votes;
+        embed = embed.field("Closest margin", format!(
+            "{} and {} place were {} vote{} apart",
+            ordinal(a.place),
+            ordinal(b.place),
+            diff,
+            if diff == 1 { "" } else { "s" },
+        ), false);
+    }
+
+    // Link to every entry, as long as that fits in a field.
+    let mut links = String::new();
+    for (i, p) in placements.iter().enumerate() {
+        if p.submission.author == sql::PURGED_AUTHOR { continue; }
+        let link = entry_link(&format!("#{}", i + 1), challenge, &p.submission);
+        if links.len() + link.len() + 3 > 1024 { break; }
+        if !links.is_empty() { links.push_str(" · "); }
+        links.push_str(&link);
+    }
+
+    embed = embed.field("All entries", links, false);
+    Ok(embed)
+}
+
+/// Post the recap for a challenge in a week, or update the existing one.
+async fn publish_recap(ctx: &ser::Context, week: i64, challenge: Challenge, placements: &[Placement]) -> Res {
+    let embed = recap_embed(week, challenge, placements).await?;
+    let channel = challenge.submission_channel();
+    preflight::preflight_post(ctx, channel, "the recap", None).await?;
+    match sql::get_week_message(week, challenge, WeekMessage::Recap).await? {
+        Some(message) => {
+            channel.edit_message(ctx, message, ser::EditMessage::new().embed(embed)).await?;
+        }
+
+        None => {
+            let message = channel.send_message(ctx, ser::CreateMessage::new().embed(embed)).await?;
+            sql::set_week_message(week, challenge, WeekMessage::Recap, message.id).await?;
+        }
+    }
+
+    info!("Published recap for {:?} in week {}", challenge, week);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::SubmissionStatus;
+    use super::*;
+
+    #[test]
+    fn hof_links_to_the_discussion_thread() {
+        config::init_for_tests();
+        let submission = Submission {
+            message: 10,
+            week: 3,
+            challenge: Challenge::Glyph as i64,
+            author: 20,
+            link: String::new(),
+            time: 0,
+            votes: 2,
+            status: SubmissionStatus::Active as i64,
+            spoiler: false,
+            score: 200,
+        };
+
+        let placements = [Placement { place: 1, submission }];
+        let mut extras = HofExtras { annotations: HashMap::new(), images: vec![], thread: None, discussion: None };
+        let has_discussion = |extras: &HofExtras| {
+            let embeds = serde_json::to_value(hof_embeds(3, Challenge::Glyph, &placements, extras, false)).unwrap();
+            embeds[0]["fields"].as_array().unwrap().iter().any(|f| f["value"] == "Talk about this week in <#30>")
+        };
+
+        assert!(!has_discussion(&extras));
+        extras.discussion = Some(ser::ChannelId::new(30));
+        assert!(has_discussion(&extras));
+    }
+}
@@ -0,0 +1,654 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use poise::ChoiceParameter;
+use poise::serenity_prelude as ser;
+use poise::serenity_prelude::Mentionable;
+use tokio::task::JoinHandle;
+use crate::{ballots, clock, digest, err, Error, info, polls, Res, results, sql, warn};
+use crate::dm::{DmBatch, DmKind};
+use crate::core::{begin_work, Pacer, replace_timestamp, shutdown_token, timestamp, TimestampStyle};
+use crate::sql::{Challenge, Setting, WeekMessage};
+
+/// How often the scheduler wakes up to run jobs, in seconds.
+const TICK: i64 = HOUR;
+
+/// Users that submitted something in this many weeks count as active.
+pub const ACTIVE_WEEKS: i64 = 4;
+
+/// Authors of flagged entries are warned this many hours before the withdrawal cutoff.
+const FLAG_WARNING_HOURS: i64 = 6;
+
+/// Cached names older than this (in seconds) are refreshed.
+const NAME_CACHE_MAX_AGE: i64 = 24 * 60 * 60;
+
+/// Tasks are considered due this many seconds early so that an hourly
+/// task isn’t skipped because the scheduler woke up a bit too soon.
+const DUE_SLACK: i64 = 5 * 60;
+
+/// Directory that DB backups are written to.
+const BACKUP_DIR: &str = "./backups";
+
+/// How many DB backups to keep around.
+const BACKUPS_TO_KEEP: usize = 7;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+
+type TaskFuture<'a> = Pin<Box<dyn Future<Output = Res> + Send + 'a>>;
+
+/// A job that the scheduler runs periodically.
+pub struct Task {
+    /// Name used to refer to the task in commands and in the DB.
+    pub name: &'static str,
+    pub description: &'static str,
+
+    /// Minimum time between two runs, in seconds. Since the scheduler only
+    /// wakes up every [`TICK`], anything shorter than that is rounded up.
+    pub interval: i64,
+
+    /// Whether this acts on the challenges themselves (announcements,
+    /// reminders, closing submissions); such tasks are skipped while
+    /// the challenges are paused.
+    pub pausable: bool,
+
+    run: for<'a> fn(&'a ser::Context) -> TaskFuture<'a>,
+}
+
+/// All periodic tasks, in the order in which they are run.
+pub static TASKS: &[Task] = &[
+    Task {
+        name: "auto-extend",
+        description: "Extend the deadline if there are too few entries shortly before it",
+        interval: HOUR,
+        pausable: true,
+        run: |ctx| Box::pin(auto_extend_deadline(ctx)),
+    },
+    Task {
+        name: "flag-warnings",
+        description: "Warn authors of flagged entries before the withdrawal cutoff",
+        interval: HOUR,
+        pausable: true,
+        run: |ctx| Box::pin(warn_flagged_entries(ctx)),
+    },
+    Task {
+        name: "vote-reminder",
+        description: "Ping the vote reminder role before voting closes",
+        interval: HOUR,
+        pausable: true,
+        run: |ctx| Box::pin(send_vote_reminder(ctx)),
+    },
+    Task {
+        name: "ballots",
+        description: "Send ballots to recent participants once voting opens",
+        interval: HOUR,
+        pausable: true,
+        run: |ctx| Box::pin(async { ballots::send_ballots(ctx).await.map(|_| ()) }),
+    },
+    Task {
+        name: "hall-of-fame",
+        description: "Post the hall of fame once voting on a week has closed",
+        interval: HOUR,
+        pausable: true,
+        run: |ctx| Box::pin(results::publish_due_hofs(ctx)),
+    },
+    Task {
+        name: "prompt-polls",
+        description: "Close prompt polls that are over and open new ones",
+        interval: HOUR,
+        pausable: false,
+        run: |ctx| Box::pin(polls::run_polls(ctx)),
+    },
+    Task {
+        name: "refresh-names",
+        description: "Refresh cached names and avatars of active participants",
+        interval: HOUR,
+        pausable: false,
+        run: |ctx| Box::pin(refresh_user_names(ctx)),
+    },
+    Task {
+        name: "expire-components",
+        description: "Remove expired buttons from messages",
+        interval: HOUR,
+        pausable: false,
+        run: |ctx| Box::pin(disable_expired_components(ctx)),
+    },
+    Task {
+        name: "digest",
+        description: "Post the stats digest when it is due",
+        interval: HOUR,
+        pausable: false,
+        run: |ctx| Box::pin(digest::post_digest_if_due(ctx)),
+    },
+    Task {
+        name: "wal-checkpoint",
+        description: "Merge the write-ahead log into the database",
+        interval: DAY,
+        pausable: false,
+        run: |_| Box::pin(sql::truncate_wal()),
+    },
+    Task {
+        name: "backup",
+        description: "Write a backup of the database",
+        interval: DAY,
+        pausable: false,
+        run: |_| Box::pin(backup_db()),
+    },
+];
+
+/// Look up a task by name.
+pub fn find_task(name: &str) -> Option<&'static Task> {
+    TASKS.iter().find(|t| t.name == name)
+}
+
+/// The scheduler task.
+static TASK: OnceLock<JoinHandle<()>> = OnceLock::new();
+
+/// Start the scheduler. Only the first call has any effect.
+pub fn start(ctx: ser::Context) {
+    TASK.get_or_init(|| tokio::spawn(async move {
+        let clock = clock::get();
+        let mut next = clock.now();
+        loop {
+            tokio::select! {
+                _ = shutdown_token().cancelled() => break,
+                _ = clock.sleep_until(next) => {}
+            }
+
+            next = clock.now() + chrono::Duration::seconds(TICK);
+            let Some(_work) = begin_work() else { break; };
+            run_jobs(&ctx).await;
+        }
+    }));
+}
+
+/// Stop the scheduler if it didn’t stop on its own after being cancelled.
+/// Only to be called by [`terminate()`].
+pub fn stop() {
+    if let Some(task) = TASK.get() { task.abort(); }
+}
+
+/// Get the submission deadline of a week.
+///
+/// This is the override set for that week, if any. Otherwise, the current
+/// week ends at the next occurrence of the configured weekday and hour,
+/// and every other week is offset from that by a multiple of 7 days.
+pub async fn deadline(week: i64) -> Result<DateTime<Utc>, Error> {
+    if let Some(ts) = sql::get_week_deadline(week).await? {
+        return Utc.timestamp_opt(ts, 0).single().ok_or_else(|| format!("Invalid deadline for week {}", week).into());
+    }
+
+    default_deadline(week).await
+}
+
+/// Get the deadline a week would have without an override.
+pub async fn default_deadline(week: i64) -> Result<DateTime<Utc>, Error> {
+    let weekday = sql::get_setting::<i64>(Setting::DeadlineWeekday).await?;
+    let hour = sql::get_setting::<i64>(Setting::DeadlineHour).await?;
+    let now = clock::now();
+
+    // Find the next time we hit that weekday and hour.
+    let days = (weekday - now.weekday().num_days_from_monday() as i64).rem_euclid(7);
+    let mut next = now.date_naive()
+        .and_hms_opt(hour as u32, 0, 0)
+        .ok_or("Invalid deadline hour")?
+        .and_utc() + chrono::Duration::days(days);
+    if next <= now { next += chrono::Duration::days(7); }
+
+    let offset = week - sql::current_week().await?;
+    Ok(next + chrono::Duration::days(7 * offset))
+}
+
+/// Get the time until which entries for the current week can be withdrawn.
+pub async fn withdrawal_cutoff() -> Result<DateTime<Utc>, Error> {
+    deadline(sql::current_week().await?).await
+}
+
+/// Get the time at which voting on a week’s entries closes; voting runs
+/// until the deadline of the following week.
+pub async fn voting_deadline(week: i64) -> Result<DateTime<Utc>, Error> {
+    deadline(week + 1).await
+}
+
+/// Get when submissions for a week opened, or will open: the recorded
+/// start, if there is one, and otherwise the previous week’s deadline.
+pub async fn week_start(week: i64) -> Result<DateTime<Utc>, Error> {
+    match sql::get_week_start(week).await? {
+        Some(ts) => Utc.timestamp_opt(ts, 0).single().ok_or_else(|| format!("Invalid start for week {}", week).into()),
+        None => deadline(week - 1).await,
+    }
+}
+
+/// Get the submission window of a week: entries are taken from when the
+/// week starts until its deadline.
+pub async fn submission_window(week: i64) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    Ok((week_start(week).await?, deadline(week).await?))
+}
+
+/// Move on to the next week and record its start. Returns the new week.
+///
+/// If this happens before the current week’s deadline, the new week still
+/// ends a week after that deadline rather than at the same time.
+pub async fn advance_week() -> Result<i64, Error> {
+    let old = deadline(sql::current_week().await?).await?;
+    let week = sql::advance_week().await?;
+    if clock::now() < old && sql::get_week_deadline(week).await?.is_none() {
+        sql::set_week_deadline(week, Some((old + chrono::Duration::days(7)).timestamp())).await?;
+    }
+
+    record_week_start().await?;
+    Ok(week)
+}
+
+/// Record the start of the current week the first time we see it, and
+/// complain if the recorded dates say a later week should be running.
+///
+/// Its deadline is recorded as well unless it was overridden already;
+/// otherwise, it would move on to the next week once it has passed, and
+/// submissions would stay open until the week rolls over.
+pub async fn record_week_start() -> Res {
+    let week = sql::current_week().await?;
+    if sql::get_week_start(week).await?.is_none() {
+        let start = week_start(week).await?.min(clock::now());
+        sql::set_week_start(week, start.timestamp()).await?;
+        if sql::get_week_deadline(week).await?.is_none() {
+            sql::set_week_deadline(week, Some(default_deadline(week).await?.timestamp())).await?;
+        }
+
+        info!("Week {} started {}", week, start);
+    }
+
+    if let Some(running) = sql::week_at(clock::now().timestamp()).await? {
+        if running > week { warn!("The current week is {}, but week {} has already started", week, running); }
+    }
+
+    Ok(())
+}
+
+/// Check whether the challenges are currently paused.
+pub async fn is_paused() -> Result<bool, Error> {
+    Ok(sql::get_paused_until().await?.is_some_and(|until| until > clock::now().timestamp()))
+}
+
+/// Check whether a challenge is running, as opposed to on hiatus. Unlike a
+/// break, a hiatus only affects one challenge and has no set end.
+pub async fn is_active(challenge: Challenge) -> Result<bool, Error> {
+    sql::get_setting::<bool>(Setting::active(challenge)).await
+}
+
+/// Where the prompt for a challenge in an upcoming week comes from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PromptStatus {
+    /// The week is special and doesn’t need one.
+    Special,
+
+    /// The week already has a prompt.
+    Set,
+
+    /// The challenge is on hiatus, so it doesn’t need one.
+    Hiatus,
+
+    /// The week will get the queue entry with this ID.
+    Queued(i64),
+
+    /// The queue will have run out by then.
+    Missing,
+}
+
+/// An upcoming week, as shown by `/calendar`.
+#[derive(Clone, Debug)]
+pub struct CalendarWeek {
+    pub week: i64,
+
+    /// When submissions open; see [`week_start()`].
+    pub start: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+
+    /// How much later than usual the deadline is, if it was moved.
+    pub deadline_moved: Option<chrono::Duration>,
+
+    /// Whether the challenges are on break when the week starts.
+    pub on_break: bool,
+    pub prompts: [(Challenge, PromptStatus); 2],
+}
+
+/// Get the current week and the `count - 1` weeks after it.
+///
+/// Weeks that don’t have a prompt yet are assumed to take the next entry
+/// of the queue, in order.
+pub async fn calendar(count: i64) -> Result<Vec<CalendarWeek>, Error> {
+    let current = sql::current_week().await?;
+    let last = current + count - 1;
+    let rows = sql::get_weeks(current, last).await?;
+    let paused_until = sql::get_paused_until().await?;
+
+    let mut queues = Vec::new();
+    let mut active = Vec::new();
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        queues.push(sql::get_prompts(challenge).await?.into_iter().map(|(id, _)| id));
+        active.push(is_active(challenge).await?);
+    }
+
+    let mut weeks = Vec::with_capacity(count.max(0) as usize);
+    for week in current..=last {
+        let row = rows.iter().find(|r| r.week == week);
+        let start = week_start(week).await?;
+        let deadline = deadline(week).await?;
+        let default = default_deadline(week).await?;
+
+        let mut prompts = [(Challenge::Glyph, PromptStatus::Missing), (Challenge::Ambigram, PromptStatus::Missing)];
+        for (((challenge, status), queue), &active) in prompts.iter_mut().zip(queues.iter_mut()).zip(&active) {
+            *status = match row {
+                _ if !active => PromptStatus::Hiatus,
+                Some(r) if r.kind(*challenge) == sql::Week::Special => PromptStatus::Special,
+                Some(r) if r.prompt(*challenge).is_some() => PromptStatus::Set,
+                _ => queue.next().map_or(PromptStatus::Missing, PromptStatus::Queued),
+            };
+        }
+
+        weeks.push(CalendarWeek {
+            week,
+            start,
+            deadline,
+            deadline_moved: (deadline != default).then(|| deadline - default),
+            on_break: paused_until.is_some_and(|until| until > start.timestamp()),
+            prompts,
+        });
+    }
+
+    Ok(weeks)
+}
+
+/// Post a notice in all submission channels.
+pub async fn post_notice(ctx: &ser::Context, text: &str) {
+    let mut pacer = Pacer::new();
+    for channel in [Challenge::Glyph, Challenge::Ambigram].map(Challenge::submission_channel) {
+        pacer.wait().await;
+        if let Err(e) = channel.say(ctx, text).await {
+            err!("Error posting notice in {}: {}", channel, e);
+        }
+    }
+}
+
+/// Push back the deadline of the current week, tell everyone, and update
+/// the channel topics and announcements that show the old deadline. Returns
+/// the new deadline and what we couldn’t update, so an admin can do that.
+///
+/// Nothing needs to be rescheduled: the jobs that act on the deadline check
+/// it whenever they run. Since voting on last week’s entries runs until
+/// this deadline, that is extended too, and the notice says so.
+pub async fn extend_deadline(ctx: &ser::Context, hours: i64, reason: Option<&str>) -> Result<(DateTime<Utc>, Vec<String>), Error> {
+    let week = sql::current_week().await?;
+    let old = deadline(week).await?;
+    let new = old + chrono::Duration::hours(hours);
+    sql::set_week_deadline(week, Some(new.timestamp())).await?;
+    info!("Extended the deadline of week {} by {} hours to {}", week, hours, new);
+
+    let mut problems = vec![];
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        let channel = challenge.submission_channel();
+        let topic = channel.to_channel(ctx).await.ok().and_then(|c| c.guild()).and_then(|c| c.topic);
+        if let Some(topic) = topic.and_then(|t| replace_timestamp(&t, old, new)) {
+            if let Err(e) = channel.edit(ctx, ser::EditChannel::new().topic(topic)).await {
+                err!("Error updating the topic of {}: {}", channel, e);
+                problems.push(format!("the topic of {}", channel.mention()));
+            }
+        }
+
+        // We can only edit announcements we posted ourselves.
+        let Some(id) = sql::get_week_message(week, challenge, WeekMessage::Announcement).await? else { continue; };
+        match channel.message(ctx, id).await {
+            Ok(m) if m.author.id == ctx.cache.current_user().id => {
+                let Some(content) = replace_timestamp(&m.content, old, new) else { continue; };
+                if let Err(e) = channel.edit_message(ctx, id, ser::EditMessage::new().content(content)).await {
+                    err!("Error updating the {:?} announcement: {}", challenge, e);
+                    problems.push(format!("the {} Challenge announcement", challenge.name()));
+                }
+            }
+            _ => problems.push(format!("the {} Challenge announcement", challenge.name())),
+        }
+    }
+
+    let mut notice = format!("⏰ The deadline for week {} has been extended to {}.", week, timestamp(new, TimestampStyle::DateTime));
+    if let Some(reason) = reason { notice += &format!(" {}", reason); }
+    if week > 0 { notice += &format!(" Voting on week {} stays open until then as well, so its results come out later.", week - 1); }
+    post_notice(ctx, &notice).await;
+    Ok((new, problems))
+}
+
+/// Put a challenge on hiatus, or end its hiatus. Only its own submission
+/// channel is told, since the other challenge carries on.
+pub async fn set_hiatus(ctx: &ser::Context, challenge: Challenge, hiatus: bool) -> Res {
+    sql::set_setting(Setting::active(challenge), &(!hiatus).to_string()).await?;
+    let text = match hiatus {
+        true => format!("The {} Challenge is taking a break for now; we’ll let you know when it is back.", challenge.name()),
+        false => format!("The {} Challenge is back! Submissions are open again.", challenge.name()),
+    };
+
+    info!("{} the {:?} challenge", if hiatus { "Suspended" } else { "Resumed" }, challenge);
+    challenge.submission_channel().say(ctx, text).await?;
+    Ok(())
+}
+
+/// Pause automatic challenge actions until a certain time.
+pub async fn pause(ctx: &ser::Context, until: DateTime<Utc>) -> Res {
+    sql::set_paused_until(Some(until.timestamp())).await?;
+    info!("Pausing challenges until {}", until);
+    post_notice(ctx, &format!("The challenges are on break until {}.", timestamp(until, TimestampStyle::DateTime))).await;
+    Ok(())
+}
+
+/// Resume automatic challenge actions.
+pub async fn resume(ctx: &ser::Context) -> Res {
+    sql::set_paused_until(None).await?;
+    info!("Resuming challenges");
+    post_notice(ctx, "The challenges are back! Submissions are open again.").await;
+    Ok(())
+}
+
+/// Run all periodic jobs.
+async fn run_jobs(ctx: &ser::Context) {
+    if let Err(e) = record_week_start().await { err!("Error recording start of week: {}", e); }
+
+    // Resume once the break is over.
+    match sql::get_paused_until().await {
+        Ok(Some(until)) if until <= clock::now().timestamp() => {
+            if let Err(e) = resume(ctx).await { err!("Error resuming challenges: {}", e); }
+        }
+        Ok(_) => {}
+        Err(e) => err!("Error checking pause state: {}", e),
+    }
+
+    let paused = match is_paused().await {
+        Ok(paused) => paused,
+        Err(e) => {
+            err!("Error checking pause state: {}", e);
+            true
+        }
+    };
+
+    for task in TASKS {
+        if shutdown_token().is_cancelled() { break; }
+        if task.pausable && paused { continue; }
+        let state = match sql::get_task_state(task.name).await {
+            Ok(state) => state,
+            Err(e) => {
+                err!("Error getting state of task {}: {}", task.name, e);
+                continue;
+            }
+        };
+
+        if !is_due(task, &state, clock::now().timestamp()) { continue; }
+        if let Err(e) = run_task(ctx, task).await {
+            err!("Error running task {}: {}", task.name, e);
+        }
+    }
+}
+
+/// Check whether a task should run at `now`. A task that was missed,
+/// e.g. because the bot was down, is due as soon as we’re back.
+pub fn is_due(task: &Task, state: &sql::TaskState, now: i64) -> bool {
+    !state.disabled && state.last_run.is_none_or(|t| now - t >= task.interval - DUE_SLACK)
+}
+
+/// Check whether `now` is in the window of `length` before `end`; used for
+/// warnings and reminders that go out some time before a deadline.
+pub fn in_window_before(now: DateTime<Utc>, end: DateTime<Utc>, length: chrono::Duration) -> bool {
+    now < end && end - now <= length
+}
+
+/// Run a task now, regardless of when it last ran.
+pub async fn run_task(ctx: &ser::Context, task: &Task) -> Res {
+    // Record the attempt even if it fails so a broken task doesn’t
+    // run more often than it is supposed to.
+    sql::set_task_last_run(task.name, clock::now().timestamp()).await?;
+    (task.run)(ctx).await
+}
+
+/// Write a backup of the DB and delete old ones.
+async fn backup_db() -> Res {
+    std::fs::create_dir_all(BACKUP_DIR)?;
+    let path = format!("{}/glyfi-{}.db", BACKUP_DIR, clock::now().format("%Y-%m-%d-%H%M%S"));
+    sql::backup_to(&path).await?;
+    info!("Wrote DB backup to {}", path);
+
+    // The names sort chronologically.
+    let mut backups = std::fs::read_dir(BACKUP_DIR)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "db"))
+        .collect::<Vec<_>>();
+    backups.sort();
+    let excess = backups.len().saturating_sub(BACKUPS_TO_KEEP);
+    for old in &backups[..excess] {
+        if let Err(e) = std::fs::remove_file(old) {
+            err!("Could not delete old backup {}: {}", old.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether the deadline of a week should be extended automatically:
+/// it ends within the hour, an active challenge has too few entries, and
+/// the deadline hasn’t been extended automatically too often yet.
+pub async fn needs_auto_extension(week: i64) -> Result<bool, Error> {
+    let min = sql::get_setting::<i64>(Setting::AutoExtendMinEntries).await?;
+    if min == 0 { return Ok(false); }
+    if !in_window_before(clock::now(), deadline(week).await?, chrono::Duration::seconds(TICK)) { return Ok(false); }
+    if sql::get_auto_extensions(week).await? >= sql::get_setting::<i64>(Setting::AutoExtendLimit).await? { return Ok(false); }
+
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        if is_active(challenge).await? && sql::count_submissions(week, challenge).await? < min { return Ok(true); }
+    }
+
+    Ok(false)
+}
+
+/// Give people more time to submit if there are too few entries shortly
+/// before the deadline.
+async fn auto_extend_deadline(ctx: &ser::Context) -> Res {
+    let week = sql::current_week().await?;
+    if !needs_auto_extension(week).await? { return Ok(()); }
+
+    // Count it first so that a failure below can’t extend it over and over.
+    sql::record_auto_extension(week).await?;
+    let hours = sql::get_setting::<i64>(Setting::AutoExtendHours).await?;
+    let (_, problems) = extend_deadline(ctx, hours, Some("There aren’t many entries yet, so there’s more time to submit.")).await?;
+    if !problems.is_empty() {
+        warn!("Extended the deadline automatically, but couldn’t update {}", problems.join(", "));
+    }
+
+    Ok(())
+}
+
+/// DM the authors of entries that moderators flagged shortly before the
+/// withdrawal cutoff, so they have a chance to withdraw them.
+async fn warn_flagged_entries(ctx: &ser::Context) -> Res {
+    let cutoff = withdrawal_cutoff().await?;
+    if !in_window_before(clock::now(), cutoff, chrono::Duration::hours(FLAG_WARNING_HOURS)) { return Ok(()); }
+
+    let mut batch = DmBatch::new(DmKind::Essential);
+    for (s, reason) in sql::take_unwarned_flags(sql::current_week().await?).await? {
+        batch.send(ctx, ser::UserId::new(s.author as u64), ser::CreateMessage::new().content(format!(
+            "The moderators flagged your [submission]({}) for this week: {}\n\nYou can withdraw it until {}.",
+            s.link,
+            reason,
+            timestamp(cutoff, TimestampStyle::Relative),
+        ))).await;
+    }
+
+    Ok(())
+}
+
+/// Ping the vote reminder role a few hours before voting closes.
+async fn send_vote_reminder(ctx: &ser::Context) -> Res {
+    let role = sql::get_setting::<u64>(Setting::VoteReminderRole).await?;
+    if role == 0 { return Ok(()); }
+
+    // We’re voting on last week’s entries.
+    let week = sql::current_week().await? - 1;
+    if sql::get_last_vote_reminder().await?.is_some_and(|w| w >= week) { return Ok(()); }
+
+    let close = voting_deadline(week).await?;
+    let hours = sql::get_setting::<i64>(Setting::VoteReminderHours).await?;
+    if !in_window_before(clock::now(), close, chrono::Duration::hours(hours)) { return Ok(()); }
+
+    let mut pacer = Pacer::new();
+    for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+        if !is_active(challenge).await? { continue; }
+        let entries = sql::count_submissions(week, challenge).await?;
+        if entries == 0 { continue; }
+
+        let votes = sql::get_turnout(week, challenge).await?.ballots;
+        let text = format!(
+            "{} Voting on the {} Challenge closes {}! There are {} entries, and {} vote{} have been cast so far.",
+            ser::RoleId::new(role).mention(),
+            challenge.name(),
+            timestamp(close, TimestampStyle::Relative),
+            entries,
+            votes,
+            if votes == 1 { "" } else { "s" },
+        );
+
+        pacer.wait().await;
+        challenge.submission_channel().send_message(ctx, ser::CreateMessage::new()
+            .content(text)
+            .allowed_mentions(ser::CreateAllowedMentions::new().roles(vec![role]))
+        ).await?;
+    }
+
+    sql::set_last_vote_reminder(week).await?;
+    info!("Sent vote reminder for week {}", week);
+    Ok(())
+}
+
+/// Remove expired buttons etc. from messages that we can still edit.
+async fn disable_expired_components(ctx: &ser::Context) -> Res {
+    for (channel, message) in sql::take_expired_components().await? {
+        // The message may have been deleted in the meantime.
+        let edit = ser::EditMessage::new().components(vec![]);
+        if let Err(e) = channel.edit_message(ctx, message, edit).await {
+            info!("Could not remove components from message {}: {}", message, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Refresh the cached names and avatars of active participants.
+async fn refresh_user_names(ctx: &ser::Context) -> Res {
+    let users = sql::get_stale_active_users(ACTIVE_WEEKS, NAME_CACHE_MAX_AGE).await?;
+    if users.is_empty() { return Ok(()); }
+
+    info!("Refreshing cached names of {} user(s)", users.len());
+    for id in users {
+        match id.to_user(ctx).await {
+            Ok(user) => sql::update_user_cache(&user).await?,
+            Err(e) => err!("Error fetching user {}: {}", id, e),
+        }
+    }
+
+    Ok(())
+}
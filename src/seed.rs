@@ -0,0 +1,125 @@
+//! Fill a DB with made-up data so commands like `/leaderboard` and
+//! `/weekinfo` and the panel renderer can be tried out locally. Only
+//! available in debug builds, via `glyfi seed --db <path>`.
+
+use chrono::Utc;
+use poise::serenity_prelude::{MessageId, UserId};
+use crate::{info_sync, Res, sql, tally};
+use crate::sql::Challenge;
+
+/// Made-up user IDs start here so they don’t look like real snowflakes.
+const FIRST_USER_ID: u64 = 100_000;
+
+/// Made-up message IDs start here, or after the last submission if
+/// we’re adding to existing data.
+const FIRST_MESSAGE_ID: u64 = 1_000_000;
+
+const SYLLABLES: &[&str] = &["ka", "lo", "mi", "ser", "ta", "vin", "ru", "el", "no", "quo", "ze", "fa"];
+const WORDS: &[&str] = &["swim", "noon", "dollop", "level", "mirror", "pod", "suns", "stats", "wow", "racecar"];
+
+/// Small deterministic RNG (splitmix64); we don’t need anything better.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Get a number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Return true with a probability of `percent`%.
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// Make up a name.
+fn name(rng: &mut Rng) -> String {
+    let mut name = String::new();
+    for _ in 0..2 + rng.below(2) { name += rng.pick(SYLLABLES); }
+    name[..1].to_uppercase() + &name[1..]
+}
+
+/// Make sure we don’t fill the bot’s own DB with made-up data.
+pub fn check_db_path(path: &str) -> Res {
+    let resolve = |p: &str| std::path::absolute(p).map(|abs| abs.canonicalize().unwrap_or(abs));
+    if resolve(path)? == resolve(sql::DB_PATH)? {
+        return Err(format!("Refusing to seed the bot’s DB ‘{}’; pass a different path to --db", sql::DB_PATH).into());
+    }
+
+    Ok(())
+}
+
+/// Populate the DB with `weeks` weeks of data from `users` users.
+pub async fn seed(seed: u64, weeks: i64, users: u64, force: bool) -> Res {
+    if weeks < 1 || users < 2 { return Err("Need at least 1 week and 2 users".into()); }
+    if !force && sql::has_submissions().await? {
+        return Err("The DB already contains submissions; pass --force to seed it anyway".into());
+    }
+
+    let mut rng = Rng(seed);
+
+    // Some users are better than others, which makes for more
+    // interesting leaderboards.
+    let mut people = Vec::new();
+    for i in 0..users {
+        let id = UserId::new(FIRST_USER_ID + i);
+        sql::seed_user(id, &name(&mut rng)).await?;
+        people.push((id, 10 + rng.below(60), 20 + rng.below(60)));
+    }
+
+    let now = Utc::now().timestamp();
+    let first_message = FIRST_MESSAGE_ID.max(sql::max_submission_id().await? as u64);
+    let mut message = first_message;
+    for week in 0..weeks {
+        let letter = (b'A' + rng.below(26) as u8) as char;
+        sql::seed_week(week, &letter.to_string(), rng.pick(WORDS)).await?;
+        let start = now - (weeks - week) * 7 * 24 * 60 * 60;
+
+        for challenge in [Challenge::Glyph, Challenge::Ambigram] {
+            // Submissions.
+            let mut entries = Vec::new();
+            for &(author, skill, activity) in &people {
+                if !rng.chance(activity) { continue; }
+                message += 1;
+                let id = MessageId::new(message);
+                let link = format!("https://placehold.co/512x512/png?text={}", message);
+                let time = start + rng.below(6 * 24 * 60 * 60) as i64;
+                sql::seed_submission(id, week, challenge, author, &link, time).await?;
+                entries.push((id, skill));
+            }
+
+            // Votes; better users get more of them.
+            for &(voter, _, activity) in &people {
+                if !rng.chance(activity) { continue; }
+                for &(entry, skill) in &entries {
+                    if rng.chance(skill) { sql::add_vote(entry, voter, sql::DEFAULT_VOTE_WEIGHT).await?; }
+                }
+            }
+
+            // Record results for weeks that are over.
+            if week < weeks - 1 {
+                let placements = tally::rank(sql::get_submissions(week, challenge).await?);
+                sql::record_week_results(week, challenge, &placements.into_iter()
+                    .map(|p| (p.submission, p.place))
+                    .collect::<Vec<_>>()
+                ).await?;
+            }
+        }
+    }
+
+    sql::set_current_week(weeks - 1).await?;
+    info_sync!("Seeded {} week(s) with {} user(s) and {} submission(s)", weeks, users, message - first_message);
+    Ok(())
+}
@@ -1,12 +1,32 @@
 use std::str::FromStr;
+use std::sync::OnceLock;
 use const_format::formatcp;
-use poise::serenity_prelude::{MessageId, UserId};
-use sqlx::migrate::MigrateDatabase;
+use poise::serenity_prelude::{ChannelId, EmojiId, MessageId, RoleId, User, UserId};
+use sqlx::migrate::{MigrateDatabase, Migrator};
 use sqlx::{FromRow, Sqlite, SqlitePool};
-use crate::{Error, info_sync, Res};
+use crate::{config, Error, info_sync, Res};
+use crate::core::{timestamp, TimestampStyle};
 
 pub const DB_PATH: &str = "glyfi.db";
 
+/// Maximum length of a bio, in characters.
+pub const MAX_BIO_LENGTH: usize = 300;
+
+/// Maximum length of a profile link.
+pub const MAX_LINK_LENGTH: usize = 200;
+
+/// Maximum length of a user’s pronouns, in characters.
+pub const MAX_PRONOUNS_LENGTH: usize = 40;
+
+/// Maximum length of a nickname, in characters.
+const MAX_NICKNAME_LENGTH: usize = 200;
+
+/// Only users that placed in this many weeks count towards percentiles.
+const PERCENTILE_WEEKS: i64 = 12;
+
+/// Author of submissions whose author’s data has been purged.
+pub const PURGED_AUTHOR: i64 = 0;
+
 /// What challenge a submission belongs to.
 #[derive(Copy, Clone, Debug, PartialEq, poise::ChoiceParameter)]
 #[repr(u8)]
@@ -28,6 +48,100 @@ impl Challenge {
 
         return format!("./weekly_challenges/{}.png", name);
     }
+
+    /// Channel that submissions for this challenge are posted in.
+    pub fn submission_channel(self) -> ChannelId {
+        match self {
+            Challenge::Glyph => config::get().glyph_submission_channel,
+            Challenge::Ambigram => config::get().ambigram_submission_channel,
+        }
+    }
+
+    /// Prefix of the columns in the `weeks` table that belong to this challenge.
+    fn column_prefix(self) -> &'static str {
+        match self {
+            Challenge::Glyph => "glyph",
+            Challenge::Ambigram => "ambigram",
+        }
+    }
+
+    /// Constraints used unless admins changed them; see [`get_constraints()`].
+    pub fn default_constraints(self) -> Constraints {
+        match self {
+            // Nothing was checked before constraints could be set, so
+            // don’t start rejecting entries until an admin sets some.
+            Challenge::Glyph | Challenge::Ambigram => Constraints {
+                min_size: 0,
+                max_size: 0,
+                square: false,
+                monochrome: false,
+                animation: true,
+            },
+        }
+    }
+}
+
+/// Machine-readable requirements for the entries of a challenge. These
+/// are listed in the announcement; only the image dimensions and whether
+/// it is animated are checked when something is submitted.
+#[derive(Clone, Debug, PartialEq, FromRow)]
+pub struct Constraints {
+    /// Smallest allowed width and height, in pixels.
+    pub min_size: u32,
+
+    /// Largest allowed width and height, in pixels (0 = no limit).
+    pub max_size: u32,
+
+    /// Whether the canvas must be square.
+    pub square: bool,
+
+    /// Whether entries must be black and white.
+    pub monochrome: bool,
+
+    /// Whether animated entries are allowed.
+    pub animation: bool,
+}
+
+impl Constraints {
+    /// Describe the constraints for the announcement, one per line.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let shape = if self.square { "square" } else { "any shape" };
+        lines.push(match (self.min_size, self.max_size) {
+            (0, 0) => format!("Canvas: {}", shape),
+            (min, 0) => format!("Canvas: {}, at least {}×{} px", shape, min, min),
+            (0, max) => format!("Canvas: {}, at most {}×{} px", shape, max, max),
+            (min, max) => format!("Canvas: {}, {}×{} to {}×{} px", shape, min, min, max, max),
+        });
+
+        lines.push(format!("Colour: {}", if self.monochrome { "black and white only" } else { "allowed" }));
+        lines.push(format!("Animation: {}", if self.animation { "allowed" } else { "not allowed" }));
+        lines
+    }
+
+    /// Check the dimensions of an image; on error, returns a message for
+    /// the user.
+    pub fn check_size(&self, width: u32, height: u32) -> Result<(), String> {
+        if width < self.min_size || height < self.min_size {
+            return Err(format!(
+                "Submissions must be at least {}×{} pixels, but yours is {}×{}",
+                self.min_size, self.min_size, width, height,
+            ));
+        }
+
+        if self.max_size != 0 && (width > self.max_size || height > self.max_size) {
+            return Err(format!(
+                "Submissions must be at most {}×{} pixels, but yours is {}×{}",
+                self.max_size, self.max_size, width, height,
+            ));
+        }
+
+        if self.square && width != height {
+            return Err(format!("Submissions must be square, but yours is {}×{} pixels", width, height));
+        }
+
+        Ok(())
+    }
 }
 
 impl FromStr for Challenge {
@@ -41,12 +155,13 @@ impl FromStr for Challenge {
     }
 }
 
-impl From<i64> for Challenge {
-    fn from(i: i64) -> Self {
+impl TryFrom<i64> for Challenge {
+    type Error = Error;
+    fn try_from(i: i64) -> Result<Self, Self::Error> {
         match i {
-            0 => Challenge::Glyph,
-            1 => Challenge::Ambigram,
-            _ => panic!("Invalid challenge ID {}", i),
+            0 => Ok(Challenge::Glyph),
+            1 => Ok(Challenge::Ambigram),
+            _ => Err(format!("Invalid challenge ID {}", i).into()),
         }
     }
 }
@@ -74,17 +189,512 @@ impl From<i64> for Challenge {
 ///   unless that week was special.
 ///
 /// - Post the top three from the week before the last.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum Week {
     Regular = 0,
     Special = 1,
 }
 
-impl Week {
+/// Messages we post for each challenge every week.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WeekMessage {
+    Announcement,
+    Panel,
+    HallOfFame,
+    Recap,
+
+    /// The featured artist thread. This is started from the hall of fame
+    /// message, so its ID is also the ID of the thread.
+    FeatureThread,
+
+    /// The discussion thread, which is started from the announcement.
+    DiscussionThread,
+}
+
+impl WeekMessage {
+    /// Suffix of the corresponding columns in the `weeks` table.
+    fn column_suffix(self) -> &'static str {
+        match self {
+            WeekMessage::Announcement => "announcement_message",
+            WeekMessage::Panel => "panel_message",
+            WeekMessage::HallOfFame => "hof_message",
+            WeekMessage::Recap => "recap_message",
+            WeekMessage::FeatureThread => "feature_thread",
+            WeekMessage::DiscussionThread => "discussion_thread",
+        }
+    }
+}
+
+/// Status of a submission.
+///
+/// Submissions whose submit reaction is removed are deleted outright;
+/// this is for submissions that should stay in the DB, but no longer
+/// count towards the challenge.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum SubmissionStatus {
+    Active = 0,
+    Withdrawn = 1,
+    Disqualified = 2,
+}
+
+impl SubmissionStatus {
+    pub fn raw(self) -> u8 {
+        self as _
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SubmissionStatus::Active => "Active",
+            SubmissionStatus::Withdrawn => "Withdrawn",
+            SubmissionStatus::Disqualified => "Disqualified",
+        }
+    }
+}
+
+impl TryFrom<i64> for SubmissionStatus {
+    type Error = Error;
+    fn try_from(i: i64) -> Result<Self, Self::Error> {
+        match i {
+            0 => Ok(SubmissionStatus::Active),
+            1 => Ok(SubmissionStatus::Withdrawn),
+            2 => Ok(SubmissionStatus::Disqualified),
+            _ => Err(format!("Invalid submission status {}", i).into()),
+        }
+    }
+}
+
+/// Why a submission was withdrawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum WithdrawalReason {
+    /// The author took back the submit emoji.
+    Author = 0,
+
+    /// The author left the server.
+    LeftServer = 1,
+
+    /// A moderator cleared the reactions on the submission.
+    ReactionsCleared = 2,
+
+    /// The author submitted another entry for the same challenge and week.
+    Replaced = 3,
+
+    /// The submit emoji was gone when we resynced after being offline.
+    Resync = 4,
+}
+
+impl WithdrawalReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            WithdrawalReason::Author => "Withdrawn by the author",
+            WithdrawalReason::LeftServer => "Author left the server",
+            WithdrawalReason::ReactionsCleared => "Reactions cleared",
+            WithdrawalReason::Replaced => "Replaced by a newer entry",
+            WithdrawalReason::Resync => "Submit emoji missing on resync",
+        }
+    }
+}
+
+impl TryFrom<i64> for WithdrawalReason {
+    type Error = Error;
+    fn try_from(i: i64) -> Result<Self, Self::Error> {
+        match i {
+            0 => Ok(WithdrawalReason::Author),
+            1 => Ok(WithdrawalReason::LeftServer),
+            2 => Ok(WithdrawalReason::ReactionsCleared),
+            3 => Ok(WithdrawalReason::Replaced),
+            4 => Ok(WithdrawalReason::Resync),
+            _ => Err(format!("Invalid withdrawal reason {}", i).into()),
+        }
+    }
+}
+
+/// Achievements that users can earn.
+#[derive(Copy, Clone, Debug, PartialEq, poise::ChoiceParameter)]
+#[repr(u8)]
+pub enum Achievement {
+    #[name = "First Submission"]
+    FirstSubmission = 0,
+}
+
+impl Achievement {
     pub fn raw(self) -> u8 {
         self as _
     }
+
+    /// Name shown in profiles.
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::FirstSubmission => "First Submission",
+        }
+    }
+
+    /// Emoji shown next to the name, unless admins configured a
+    /// custom emoji for it; see [`get_badge_emoji()`].
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Achievement::FirstSubmission => "🎉",
+        }
+    }
+}
+
+impl TryFrom<i64> for Achievement {
+    type Error = Error;
+    fn try_from(i: i64) -> Result<Self, Self::Error> {
+        match i {
+            0 => Ok(Achievement::FirstSubmission),
+            _ => Err(format!("Invalid achievement ID {}", i).into()),
+        }
+    }
+}
+
+/// Settings that can be changed at runtime by admins.
+#[derive(Copy, Clone, Debug, PartialEq, poise::ChoiceParameter)]
+pub enum Setting {
+    #[name = "Withdraw current submissions of members that leave"]
+    WithdrawOnLeave,
+
+    #[name = "Weekday of the default deadline (0 = Monday)"]
+    DeadlineWeekday,
+
+    #[name = "Hour (UTC) of the default deadline"]
+    DeadlineHour,
+
+    #[name = "DM new members a primer about the challenges"]
+    WelcomeDm,
+
+    #[name = "Channel ID for the stats digest (0 = disabled)"]
+    DigestChannel,
+
+    #[name = "Months covered by each stats digest"]
+    DigestMonths,
+
+    #[name = "Crown a combined champion each season"]
+    CombinedChampionship,
+
+    #[name = "Weight of the Glyph Challenge in the combined standings"]
+    CombinedGlyphWeight,
+
+    #[name = "Weight of the Ambigram Challenge in the combined standings"]
+    CombinedAmbigramWeight,
+
+    #[name = "Role ID given to the combined champion (0 = none)"]
+    ChampionRole,
+
+    #[name = "Nicknames must be unique"]
+    UniqueNicknames,
+
+    #[name = "Minimum nickname length"]
+    NicknameMinLength,
+
+    #[name = "Hours between nickname changes"]
+    NicknameCooldown,
+
+    #[name = "Add the vote emoji to Glyph Challenge submissions"]
+    SeedGlyphReactions,
+
+    #[name = "Add the vote emoji to Ambigram Challenge submissions"]
+    SeedAmbigramReactions,
+
+    #[name = "Channel ID for alerts to admins (0 = none)"]
+    AdminChannel,
+
+    #[name = "Channel ID for warnings and errors from the log (0 = none)"]
+    LogChannel,
+
+    #[name = "Alert admins when a command fails more than this often in 15 minutes (0 = never)"]
+    CommandFailureAlerts,
+
+    #[name = "Tell admins when asset files such as the announcement generator change"]
+    AssetChangeAlerts,
+
+    #[name = "Clear cached prompt previews when the announcement generator changes"]
+    ClearPreviewsOnAssetChange,
+
+    #[name = "Glyph Challenge entries must be spoilered until voting opens"]
+    BlindGlyph,
+
+    #[name = "Ambigram Challenge entries must be spoilered until voting opens"]
+    BlindAmbigram,
+
+    #[name = "Role ID pinged before voting closes (0 = no reminder)"]
+    VoteReminderRole,
+
+    #[name = "Hours before voting closes to send the vote reminder"]
+    VoteReminderHours,
+
+    #[name = "Open a featured artist thread for each winner"]
+    FeaturedArtist,
+
+    #[name = "Role ID that can preview the next prompt (0 = disabled)"]
+    PreviewRole,
+
+    #[name = "Hours before the announcement that the next prompt can be previewed"]
+    PreviewHours,
+
+    #[name = "Channel ID for submission panels (0 = the submission channel)"]
+    PanelChannel,
+
+    #[name = "Accept links to images as submissions"]
+    LinkSubmissions,
+
+    #[name = "Vote with private ballots instead of reactions"]
+    BallotVoting,
+
+    #[name = "Run the Glyph Challenge (off = on hiatus)"]
+    GlyphActive,
+
+    #[name = "Run the Ambigram Challenge (off = on hiatus)"]
+    AmbigramActive,
+
+    #[name = "Open a discussion thread on each announcement"]
+    DiscussionThreads,
+
+    #[name = "Leaderboard points for 1st place"]
+    PointsFirst,
+
+    #[name = "Leaderboard points for 2nd place"]
+    PointsSecond,
+
+    #[name = "Leaderboard points for 3rd place"]
+    PointsThird,
+
+    #[name = "Number of members shown on the leaderboard"]
+    LeaderboardSize,
+
+    #[name = "Role ID that can see moderation details (0 = admins only)"]
+    ModRole,
+
+    #[name = "Extend the deadline if a challenge has fewer entries than this an hour before (0 = never)"]
+    AutoExtendMinEntries,
+
+    #[name = "Hours to extend the deadline by if there are too few entries"]
+    AutoExtendHours,
+
+    #[name = "Maximum number of automatic deadline extensions per week"]
+    AutoExtendLimit,
+
+    #[name = "A second entry in a week replaces the first (off = it is rejected)"]
+    ReplaceEntries,
+
+    #[name = "Invite each winner to pick the next prompt (needs an admin channel)"]
+    WinnerPicksPrompt,
+
+    #[name = "Channel ID for prompt polls (0 = no polls)"]
+    PollChannel,
+
+    #[name = "Number of prompts in each prompt poll"]
+    PollOptions,
+
+    #[name = "Days between prompt polls"]
+    PollDays,
+
+    #[name = "Hours each prompt poll stays open"]
+    PollHours,
+}
+
+/// What kind of value a setting holds.
+enum SettingKind {
+    Bool,
+    Int { min: i64, max: i64 },
+}
+
+impl Setting {
+    /// Setting that controls whether the bot seeds the vote emoji on
+    /// submissions for a challenge.
+    pub fn seed_reactions(challenge: Challenge) -> Self {
+        match challenge {
+            Challenge::Glyph => Setting::SeedGlyphReactions,
+            Challenge::Ambigram => Setting::SeedAmbigramReactions,
+        }
+    }
+
+    /// Setting that controls whether entries for a challenge must be
+    /// marked as spoilers until voting opens.
+    pub fn blind(challenge: Challenge) -> Self {
+        match challenge {
+            Challenge::Glyph => Setting::BlindGlyph,
+            Challenge::Ambigram => Setting::BlindAmbigram,
+        }
+    }
+
+    /// Setting that controls whether a challenge is running or on hiatus.
+    pub fn active(challenge: Challenge) -> Self {
+        match challenge {
+            Challenge::Glyph => Setting::GlyphActive,
+            Challenge::Ambigram => Setting::AmbigramActive,
+        }
+    }
+
+    /// Key under which this is stored in the DB.
+    fn key(self) -> &'static str {
+        match self {
+            Setting::WithdrawOnLeave => "withdraw_on_leave",
+            Setting::DeadlineWeekday => "deadline_weekday",
+            Setting::DeadlineHour => "deadline_hour",
+            Setting::WelcomeDm => "welcome_dm",
+            Setting::DigestChannel => "digest_channel",
+            Setting::DigestMonths => "digest_months",
+            Setting::CombinedChampionship => "combined_championship",
+            Setting::CombinedGlyphWeight => "combined_glyph_weight",
+            Setting::CombinedAmbigramWeight => "combined_ambigram_weight",
+            Setting::ChampionRole => "champion_role",
+            Setting::UniqueNicknames => "unique_nicknames",
+            Setting::NicknameMinLength => "nickname_min_length",
+            Setting::NicknameCooldown => "nickname_cooldown",
+            Setting::SeedGlyphReactions => "seed_glyph_reactions",
+            Setting::SeedAmbigramReactions => "seed_ambigram_reactions",
+            Setting::AdminChannel => "admin_channel",
+            Setting::LogChannel => "log_channel",
+            Setting::CommandFailureAlerts => "command_failure_alerts",
+            Setting::AssetChangeAlerts => "asset_change_alerts",
+            Setting::ClearPreviewsOnAssetChange => "clear_previews_on_asset_change",
+            Setting::BlindGlyph => "blind_glyph",
+            Setting::BlindAmbigram => "blind_ambigram",
+            Setting::VoteReminderRole => "vote_reminder_role",
+            Setting::VoteReminderHours => "vote_reminder_hours",
+            Setting::FeaturedArtist => "featured_artist",
+            Setting::PreviewRole => "preview_role",
+            Setting::PreviewHours => "preview_hours",
+            Setting::PanelChannel => "panel_channel",
+            Setting::LinkSubmissions => "link_submissions",
+            Setting::BallotVoting => "ballot_voting",
+            Setting::GlyphActive => "glyph_active",
+            Setting::AmbigramActive => "ambigram_active",
+            Setting::DiscussionThreads => "discussion_threads",
+            Setting::PointsFirst => "points_first",
+            Setting::PointsSecond => "points_second",
+            Setting::PointsThird => "points_third",
+            Setting::LeaderboardSize => "leaderboard_size",
+            Setting::ModRole => "mod_role",
+            Setting::AutoExtendMinEntries => "auto_extend_min_entries",
+            Setting::AutoExtendHours => "auto_extend_hours",
+            Setting::AutoExtendLimit => "auto_extend_limit",
+            Setting::ReplaceEntries => "replace_entries",
+            Setting::WinnerPicksPrompt => "winner_picks_prompt",
+            Setting::PollChannel => "poll_channel",
+            Setting::PollOptions => "poll_options",
+            Setting::PollDays => "poll_days",
+            Setting::PollHours => "poll_hours",
+        }
+    }
+
+    /// Value to use if the setting was never set.
+    fn default_value(self) -> &'static str {
+        match self {
+            Setting::WithdrawOnLeave => "true",
+            Setting::DeadlineWeekday => "6",
+            Setting::DeadlineHour => "0",
+            Setting::WelcomeDm => "false",
+            Setting::DigestChannel => "0",
+            Setting::DigestMonths => "1",
+            Setting::CombinedChampionship => "false",
+            Setting::CombinedGlyphWeight => "1",
+            Setting::CombinedAmbigramWeight => "1",
+            Setting::ChampionRole => "0",
+            Setting::UniqueNicknames => "true",
+            Setting::NicknameMinLength => "1",
+            Setting::NicknameCooldown => "0",
+            Setting::SeedGlyphReactions => "false",
+            Setting::SeedAmbigramReactions => "false",
+            Setting::AdminChannel => "0",
+            Setting::LogChannel => "0",
+            Setting::CommandFailureAlerts => "5",
+            Setting::AssetChangeAlerts => "true",
+            Setting::ClearPreviewsOnAssetChange => "true",
+            Setting::BlindGlyph => "false",
+            Setting::BlindAmbigram => "false",
+            Setting::VoteReminderRole => "0",
+            Setting::VoteReminderHours => "6",
+            Setting::FeaturedArtist => "false",
+            Setting::PreviewRole => "0",
+            Setting::PreviewHours => "24",
+            Setting::PanelChannel => "0",
+            Setting::LinkSubmissions => "false",
+            Setting::BallotVoting => "false",
+            Setting::GlyphActive => "true",
+            Setting::AmbigramActive => "true",
+            Setting::DiscussionThreads => "false",
+            Setting::PointsFirst => "3",
+            Setting::PointsSecond => "2",
+            Setting::PointsThird => "1",
+            Setting::LeaderboardSize => "25",
+            Setting::ModRole => "0",
+            Setting::AutoExtendMinEntries => "0",
+            Setting::AutoExtendHours => "24",
+            Setting::AutoExtendLimit => "1",
+            Setting::ReplaceEntries => "false",
+            Setting::WinnerPicksPrompt => "false",
+            Setting::PollChannel => "0",
+            Setting::PollOptions => "4",
+            Setting::PollDays => "7",
+            Setting::PollHours => "48",
+        }
+    }
+
+    fn kind(self) -> SettingKind {
+        match self {
+            Setting::WithdrawOnLeave => SettingKind::Bool,
+            Setting::DeadlineWeekday => SettingKind::Int { min: 0, max: 6 },
+            Setting::DeadlineHour => SettingKind::Int { min: 0, max: 23 },
+            Setting::WelcomeDm => SettingKind::Bool,
+            Setting::DigestChannel => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::DigestMonths => SettingKind::Int { min: 1, max: 12 },
+            Setting::CombinedChampionship => SettingKind::Bool,
+            Setting::CombinedGlyphWeight => SettingKind::Int { min: 0, max: 100 },
+            Setting::CombinedAmbigramWeight => SettingKind::Int { min: 0, max: 100 },
+            Setting::ChampionRole => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::UniqueNicknames => SettingKind::Bool,
+            Setting::NicknameMinLength => SettingKind::Int { min: 1, max: MAX_NICKNAME_LENGTH as i64 },
+            Setting::NicknameCooldown => SettingKind::Int { min: 0, max: 24 * 365 },
+            Setting::SeedGlyphReactions => SettingKind::Bool,
+            Setting::SeedAmbigramReactions => SettingKind::Bool,
+            Setting::AdminChannel => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::LogChannel => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::CommandFailureAlerts => SettingKind::Int { min: 0, max: 1000 },
+            Setting::AssetChangeAlerts => SettingKind::Bool,
+            Setting::ClearPreviewsOnAssetChange => SettingKind::Bool,
+            Setting::BlindGlyph => SettingKind::Bool,
+            Setting::BlindAmbigram => SettingKind::Bool,
+            Setting::VoteReminderRole => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::VoteReminderHours => SettingKind::Int { min: 1, max: 6 * 24 },
+            Setting::FeaturedArtist => SettingKind::Bool,
+            Setting::PreviewRole => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::PreviewHours => SettingKind::Int { min: 1, max: 7 * 24 },
+            Setting::PanelChannel => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::LinkSubmissions => SettingKind::Bool,
+            Setting::BallotVoting => SettingKind::Bool,
+            Setting::GlyphActive => SettingKind::Bool,
+            Setting::AmbigramActive => SettingKind::Bool,
+            Setting::DiscussionThreads => SettingKind::Bool,
+            Setting::PointsFirst => SettingKind::Int { min: 0, max: 100 },
+            Setting::PointsSecond => SettingKind::Int { min: 0, max: 100 },
+            Setting::PointsThird => SettingKind::Int { min: 0, max: 100 },
+            Setting::LeaderboardSize => SettingKind::Int { min: 1, max: 100 },
+            Setting::ModRole => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::AutoExtendMinEntries => SettingKind::Int { min: 0, max: 1000 },
+            Setting::AutoExtendHours => SettingKind::Int { min: 1, max: 7 * 24 },
+            Setting::AutoExtendLimit => SettingKind::Int { min: 1, max: 10 },
+            Setting::ReplaceEntries => SettingKind::Bool,
+            Setting::WinnerPicksPrompt => SettingKind::Bool,
+            Setting::PollChannel => SettingKind::Int { min: 0, max: i64::MAX },
+            Setting::PollOptions => SettingKind::Int { min: 3, max: 5 },
+            Setting::PollDays => SettingKind::Int { min: 1, max: 365 },
+            Setting::PollHours => SettingKind::Int { min: 1, max: 14 * 24 },
+        }
+    }
+
+    /// Check that a value is valid for this setting.
+    pub fn validate(self, value: &str) -> Res {
+        let ok = match self.kind() {
+            SettingKind::Bool => value.parse::<bool>().is_ok(),
+            SettingKind::Int { min, max } => value.parse::<i64>().is_ok_and(|v| (min..=max).contains(&v)),
+        };
+
+        if ok { Ok(()) } else { Err(format!("Invalid value ‘{}’ for setting ‘{}’", value, self.key()).into()) }
+    }
 }
 
 /// Profile for a user.
@@ -109,6 +719,138 @@ pub struct UserProfileData {
     /// Number of submissions.
     pub glyphs_submissions: i64,
     pub ambigrams_submissions: i64,
+
+    /// Whether the user has left the server.
+    pub former_member: bool,
+
+    /// Bio and links the user set.
+    pub bio: Option<String>,
+    pub links: Vec<String>,
+
+    /// Colour of the profile embed, as 0xRRGGBB.
+    pub accent_colour: Option<u32>,
+
+    pub pronouns: Option<String>,
+
+    /// Average place across all published results.
+    pub average_place: Option<f64>,
+
+    /// Percentage of active participants with a worse average place.
+    pub percentile: Option<f64>,
+
+    /// Share of the votes the user got in their best week.
+    pub best_week_share: Option<BestWeekShare>,
+}
+
+/// Vote share of a user in the week they placed best.
+#[derive(Clone, Debug, FromRow)]
+pub struct BestWeekShare {
+    pub week: i64,
+    pub challenge: i64,
+    pub place: i64,
+    pub share: f64,
+}
+
+/// A submission, as stored in the DB.
+#[derive(Clone, Debug, FromRow)]
+pub struct Submission {
+    pub message: i64,
+    pub week: i64,
+    pub challenge: i64,
+    pub author: i64,
+    pub link: String,
+    pub time: i64,
+
+    /// Number of votes; like the score, this is kept in sync with the
+    /// `votes` table, which is what actually records them.
+    pub votes: i64,
+    pub status: i64,
+    pub spoiler: bool,
+
+    /// Sum of the weights of the votes, in percent; see [`add_vote`].
+    pub score: i64,
+}
+
+/// A row of the `users` table.
+#[derive(Clone, Debug, FromRow)]
+pub struct UserRow {
+    pub nickname: Option<String>,
+    pub glyphs_first: i64,
+    pub glyphs_second: i64,
+    pub glyphs_third: i64,
+    pub ambigrams_first: i64,
+    pub ambigrams_second: i64,
+    pub ambigrams_third: i64,
+    pub highest_ranking_glyphs: i64,
+    pub highest_ranking_ambigrams: i64,
+    pub former_member: bool,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub link1: Option<String>,
+    pub link2: Option<String>,
+    pub accent_colour: Option<i64>,
+    pub pronouns: Option<String>,
+}
+
+/// A queued prompt.
+#[derive(Clone, Debug)]
+pub struct Prompt {
+    pub id: i64,
+    pub challenge: Challenge,
+    pub prompt: String,
+    pub notes: Option<String>,
+    pub category: Option<String>,
+    pub theme: Option<String>,
+
+    /// The series this prompt is part of, if any, and which part it is.
+    pub series: Option<i64>,
+    pub series_part: Option<i64>,
+
+    /// Whether an admin confirmed the preview of the announcement.
+    pub approved: bool,
+}
+
+/// A prompt in several parts that run in consecutive weeks.
+#[derive(Clone, Debug, FromRow)]
+pub struct Series {
+    pub id: i64,
+    pub challenge: i64,
+    pub name: String,
+    pub parts: i64,
+
+    /// The combined results, once they have been posted.
+    pub results_message: Option<i64>,
+}
+
+/// Per-user preferences.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct UserPrefs {
+    /// Hide the user’s profile from other users entirely.
+    pub hide_profile: bool,
+
+    /// Hide the user’s submission history from other users.
+    pub hide_submissions: bool,
+
+    /// Show only the user’s nickname to other users.
+    pub nickname_only: bool,
+
+    /// Don’t send the user DMs that they didn’t ask for.
+    pub dm_opt_out: bool,
+
+    /// Send the user a participation certificate at the end of each season.
+    pub certificates: bool,
+}
+
+/// A season, i.e. a range of weeks whose results are aggregated.
+#[derive(Clone, Debug, FromRow)]
+pub struct Season {
+    pub id: i64,
+    pub name: String,
+    pub start_week: i64,
+
+    /// Last week of the season, inclusive; `None` while it is running.
+    pub end_week: Option<i64>,
 }
 
 #[derive(Clone, Debug, FromRow)]
@@ -118,158 +860,235 @@ pub struct WeekInfo {
     pub ambigram_challenge_kind: Option<i8>,
     pub glyph_prompt: Option<String>,
     pub ambigram_prompt: Option<String>,
+    pub glyph_notes: Option<String>,
+    pub ambigram_notes: Option<String>,
+}
+
+impl WeekInfo {
+    pub fn prompt(&self, challenge: Challenge) -> Option<&str> {
+        match challenge {
+            Challenge::Glyph => self.glyph_prompt.as_deref(),
+            Challenge::Ambigram => self.ambigram_prompt.as_deref(),
+        }
+    }
+
+    pub fn notes(&self, challenge: Challenge) -> Option<&str> {
+        match challenge {
+            Challenge::Glyph => self.glyph_notes.as_deref(),
+            Challenge::Ambigram => self.ambigram_notes.as_deref(),
+        }
+    }
+
+    pub fn kind(&self, challenge: Challenge) -> Week {
+        let kind = match challenge {
+            Challenge::Glyph => self.glyph_challenge_kind,
+            Challenge::Ambigram => self.ambigram_challenge_kind,
+        };
+
+        match kind {
+            Some(k) if k == Week::Special as i8 => Week::Special,
+            _ => Week::Regular,
+        }
+    }
 }
 
-static mut __GLYFI_DB_POOL: Option<SqlitePool> = None;
+/// The sqlite connexion pool.
+static POOL: OnceLock<SqlitePool> = OnceLock::new();
 
 /// Get the global sqlite connexion pool.
 fn pool() -> &'static SqlitePool {
-    unsafe { __GLYFI_DB_POOL.as_ref().unwrap() }
+    POOL.get().expect("DB not initialised")
+}
+
+/// Merge the DB into one file.
+pub async fn truncate_wal() -> Res {
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
 }
 
-/*/// Merge the DB into one file.
-pub async fn truncate_wal() {
-    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(pool()).await.unwrap();
+/// Write a consistent copy of the DB to a new file.
+pub async fn backup_to(path: &str) -> Res {
+    sqlx::query("VACUUM INTO ?")
+        .bind(path)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
 }
-*/
 
-/// Only intended to be called by [`terminate()`].
-pub async unsafe fn __glyfi_fini_db() {
-    if let Some(pool) = __GLYFI_DB_POOL.as_ref() { pool.close().await; }
+/// Add a column to a table if the table exists but the column doesn’t.
+async fn ensure_column(table: &str, column: &str, decl: &str) {
+    let missing: bool = sqlx::query_scalar(r#"
+        SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)
+        AND NOT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2);
+    "#)
+        .bind(table)
+        .bind(column)
+        .fetch_one(pool())
+        .await
+        .unwrap();
+
+    if missing {
+        info_sync!("Adding column {} to table {}", column, table);
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, decl))
+            .execute(pool())
+            .await
+            .unwrap();
+    }
 }
 
-/// Only intended to be called by main().
-pub async unsafe fn __glyfi_init_db() {
+/// Open the DB at `path`, creating it if need be; this is [`DB_PATH`]
+/// except for `glyfi seed`. Only intended to be called by main().
+pub async fn init_db(path: &str) -> SqlitePool {
     // Create the database if it doesn’t exist yet.
     info_sync!("Initialising sqlite db...");
-    if let Err(e) = Sqlite::create_database(DB_PATH).await {
+    if let Err(e) = Sqlite::create_database(path).await {
         panic!("Failed to create sqlite db: {}", e);
     }
 
     // Create DB connexion.
-    __GLYFI_DB_POOL = Some(SqlitePool::connect(DB_PATH).await.unwrap());
+    let pool = SqlitePool::connect(path).await.unwrap();
+    if POOL.set(pool.clone()).is_err() { panic!("DB initialised twice"); }
+    migrate().await;
+    pool
+}
 
-    // Create submissions table.
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS submissions (
-            message INTEGER, -- Message ID of the submission.
-            week INTEGER NOT NULL, -- This is just an integer.
-            challenge INTEGER NOT NULL, -- See Challenge enum.
-            author INTEGER NOT NULL, -- Discord user ID of the author.
-            link TEXT NOT NULL, -- Link to the submission.
-            time INTEGER NOT NULL DEFAULT (unixepoch()), -- Time of submission.
-            votes INTEGER NOT NULL DEFAULT 0, -- Number of votes.
-            PRIMARY KEY (message, week, challenge)
-        ) STRICT;
-    "#).execute(pool()).await.unwrap();
-
-    // Cached user profile data (excludes current week, obviously).
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY, -- Discord user ID.
-            nickname TEXT, -- Nickname.
-
-            -- Number of 1st, 2nd, 3rd place finishes in the Glyphs Challenge.
-            glyphs_first INTEGER NOT NULL DEFAULT 0,
-            glyphs_second INTEGER NOT NULL DEFAULT 0,
-            glyphs_third INTEGER NOT NULL DEFAULT 0,
-
-            -- Number of 1st, 2nd, 3rd place finishes in the Ambigram Challenge.
-            ambigrams_first INTEGER NOT NULL DEFAULT 0,
-            ambigrams_second INTEGER NOT NULL DEFAULT 0,
-            ambigrams_third INTEGER NOT NULL DEFAULT 0,
-
-            -- Highest ranking in either challenge.
-            highest_ranking_glyphs INTEGER NOT NULL DEFAULT 0,
-            highest_ranking_ambigrams INTEGER NOT NULL DEFAULT 0
-        ) STRICT;
-    "#).execute(pool()).await.unwrap();
-
-    // The current week. This is a table with a single entry.
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS current_week (
-            week INTEGER NOT NULL
-        ) STRICT;
-    "#).execute(pool()).await.unwrap();
+/// Set up a fresh in-memory DB; only for tests.
+#[cfg(test)]
+pub async fn init_test_db() {
+    init_test_db_from("").await;
+}
 
-    // Prevent inserting additional weeks.
-    sqlx::query(r#"
-        CREATE TRIGGER IF NOT EXISTS current_week_insertion
-        BEFORE INSERT ON current_week
-        WHEN (SELECT COUNT(*) FROM current_week) > 0
-        BEGIN
-            SELECT RAISE(ABORT, "current_week table must not contain more than one entry!");
-        END;
-    "#).execute(pool()).await.unwrap();
-
-    // The user is expected to set this manually, but ensure it exists. This
-    // is allowed to fail due to the trigger above.
-    let _ = sqlx::query("INSERT OR IGNORE INTO current_week (week) VALUES (0)").execute(pool()).await;
-
-    // Table that stores what weeks are/were regular or special.
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS weeks (
-            week INTEGER PRIMARY KEY, -- Week number.
+/// Like [`init_test_db()`], but run `sql` on the empty DB before migrating
+/// it, e.g. to create tables the way an older version did.
+#[cfg(test)]
+pub async fn init_test_db_from(sql: &str) {
+    // Every connexion to an in-memory DB gets its own DB, so make sure
+    // there is only ever one, and that it stays open.
+    if POOL.get().is_none() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let _ = POOL.set(pool);
+    }
 
-            -- See Week enum.
-            glyph_challenge_kind INTEGER,
-            ambigram_challenge_kind INTEGER,
+    // The pool outlives each test, so start over by dropping everything.
+    let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%';")
+        .fetch_all(pool())
+        .await
+        .unwrap();
 
-            -- Prompts.
-            glyph_prompt TEXT,
-            ambigram_prompt TEXT,
+    for table in tables {
+        sqlx::query(&format!("DROP TABLE \"{}\";", table)).execute(pool()).await.unwrap();
+    }
 
-            -- Message ID of the announcement message.
-            glyph_announcement_message INTEGER,
-            ambigram_announcement_message INTEGER,
+    if !sql.is_empty() { sqlx::query(sql).execute(pool()).await.unwrap(); }
+    migrate().await;
+}
 
-            -- Message ID of the submissions panel.
-            glyph_panel_message INTEGER,
-            ambigram_panel_message INTEGER,
+/// Migrations in `migrations/`. Never change one that has been deployed;
+/// add a new one instead.
+static MIGRATOR: Migrator = sqlx::migrate!();
 
-            -- Message ID of the first hall of fame message.
-            glyph_hof_message INTEGER,
-            ambigram_hof_message INTEGER
-        ) STRICT;
-    "#).execute(pool()).await.unwrap();
+/// Bring the schema up to date.
+async fn migrate() {
+    // Databases from before we used migrations may lack columns that were
+    // added over time; add those so they match the first migration.
+    if !table_exists("_sqlx_migrations").await && table_exists("submissions").await {
+        info_sync!("Upgrading DB from before migrations...");
+        upgrade_legacy_schema().await;
+    }
 
-    // Table that stores future prompts.
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS prompts (
-            challenge INTEGER NOT NULL,
-            prompt TEXT NOT NULL
-        ) STRICT;
-    "#).execute(pool()).await.unwrap();
+    MIGRATOR.run(pool()).await.expect("Failed to migrate DB");
 }
 
-/// Add a submission.
-pub async fn add_submission(
-    message: MessageId,
-    challenge: Challenge,
+async fn table_exists(table: &str) -> bool {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?);")
+        .bind(table)
+        .fetch_one(pool())
+        .await
+        .unwrap()
+}
+
+/// Add the columns that the first migration has to the tables of the last
+/// version from before we used migrations. The tables it didn’t have yet
+/// are created by the first migration.
+async fn upgrade_legacy_schema() {
+    ensure_column("submissions", "status", "INTEGER NOT NULL DEFAULT 0").await;
+    ensure_column("submissions", "spoiler", "INTEGER NOT NULL DEFAULT 0").await;
+    ensure_column("submissions", "flag", "TEXT").await;
+    ensure_column("submissions", "flag_warned", "INTEGER NOT NULL DEFAULT 0").await;
+    ensure_column("submissions", "by_command", "INTEGER NOT NULL DEFAULT 0").await;
+
+    ensure_column("users", "nickname_changed", "INTEGER").await;
+    ensure_column("users", "bio", "TEXT").await;
+    ensure_column("users", "link1", "TEXT").await;
+    ensure_column("users", "link2", "TEXT").await;
+    ensure_column("users", "accent_colour", "INTEGER").await;
+    ensure_column("users", "pronouns", "TEXT").await;
+    ensure_column("users", "former_member", "INTEGER NOT NULL DEFAULT 0").await;
+    ensure_column("users", "display_name", "TEXT").await;
+    ensure_column("users", "avatar_url", "TEXT").await;
+    ensure_column("users", "cache_updated", "INTEGER NOT NULL DEFAULT 0").await;
+
+    ensure_column("weeks", "deadline", "INTEGER").await;
+    ensure_column("weeks", "glyph_notes", "TEXT").await;
+    ensure_column("weeks", "ambigram_notes", "TEXT").await;
+    ensure_column("weeks", "glyph_recap_message", "INTEGER").await;
+    ensure_column("weeks", "ambigram_recap_message", "INTEGER").await;
+    ensure_column("weeks", "glyph_feature_thread", "INTEGER").await;
+    ensure_column("weeks", "ambigram_feature_thread", "INTEGER").await;
+    ensure_column("weeks", "start", "INTEGER").await;
+
+    ensure_column("prompts", "notes", "TEXT").await;
+    ensure_column("prompts", "category", "TEXT").await;
+    ensure_column("prompts", "theme", "TEXT").await;
+}
+
+/// Add a submission. Returns false if it was already added.
+pub async fn add_submission(
+    message: MessageId,
+    challenge: Challenge,
     author: UserId,
     link: &str,
-) -> Res {
+    spoiler: bool,
+    by_command: bool,
+) -> Result<bool, Error> {
     sqlx::query(r#"
         INSERT INTO submissions (
             message,
             week,
             challenge,
             author,
-            link
-        ) VALUES (?, ?, ?, ?, ?);
+            link,
+            spoiler,
+            by_command
+        ) VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (message, week, challenge) DO NOTHING;
     "#)
         .bind(message.get() as i64)
         .bind(current_week().await?)
         .bind(challenge as i64)
         .bind(author.get() as i64)
         .bind(link)
+        .bind(spoiler)
+        .bind(by_command)
         .execute(pool())
         .await
-        .map(|_| ())
+        .map(|r| r.rows_affected() > 0)
         .map_err(|e| e.into())
 }
 
-/// Get the current week.
+/// Get the current week. Admins move it on with `/week advance`, or
+/// change it with `/week set`.
 pub async fn current_week() -> Result<i64, Error> {
     sqlx::query_scalar("SELECT week FROM current_week LIMIT 1;")
         .fetch_one(pool())
@@ -277,6 +1096,39 @@ pub async fn current_week() -> Result<i64, Error> {
         .map_err(|e| format!("Failed to get current week: {}", e).into())
 }
 
+/// Set the current week, and make sure it has a row in `weeks`.
+pub async fn set_current_week(week: i64) -> Res {
+    let mut tx = pool().begin().await?;
+    sqlx::query("UPDATE current_week SET week = ?;")
+        .bind(week)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO weeks (week) VALUES (?) ON CONFLICT DO NOTHING;")
+        .bind(week)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await.map_err(|e| e.into())
+}
+
+/// Move on to the next week, and make sure it has a row in `weeks`.
+/// Returns the new current week.
+pub async fn advance_week() -> Result<i64, Error> {
+    let mut tx = pool().begin().await?;
+    let week: i64 = sqlx::query_scalar("UPDATE current_week SET week = week + 1 RETURNING week;")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO weeks (week) VALUES (?) ON CONFLICT DO NOTHING;")
+        .bind(week)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(week)
+}
+
 /// Get profile data for a user.
 pub async fn get_user_profile(user: UserId) -> Result<UserProfileData, Error> {
     #[derive(Default, FromRow)]
@@ -290,6 +1142,12 @@ pub async fn get_user_profile(user: UserId) -> Result<UserProfileData, Error> {
         pub ambigrams_third: i64,
         pub highest_ranking_glyphs: i64,
         pub highest_ranking_ambigrams: i64,
+        pub former_member: bool,
+        pub bio: Option<String>,
+        pub link1: Option<String>,
+        pub link2: Option<String>,
+        pub accent_colour: Option<i64>,
+        pub pronouns: Option<String>,
     }
 
     #[derive(Default, FromRow)]
@@ -303,7 +1161,10 @@ pub async fn get_user_profile(user: UserId) -> Result<UserProfileData, Error> {
             nickname,
             glyphs_first, glyphs_second, glyphs_third,
             ambigrams_first, ambigrams_second, ambigrams_third,
-            highest_ranking_glyphs, highest_ranking_ambigrams
+            highest_ranking_glyphs, highest_ranking_ambigrams,
+            former_member,
+            bio, link1, link2,
+            accent_colour, pronouns
         FROM users
         WHERE id = ?;
     "#)
@@ -318,15 +1179,28 @@ pub async fn get_user_profile(user: UserId) -> Result<UserProfileData, Error> {
             SUM(IIF(challenge = {}, 1, 0)) as glyphs_submissions,
             SUM(IIF(challenge = {}, 1, 0)) as ambigrams_submissions
         FROM submissions
-        WHERE author = ?
+        WHERE author = ? AND status = {}
         GROUP BY author;
-    "#, Challenge::Glyph as i64, Challenge::Ambigram as i64))
+    "#, Challenge::Glyph as i64, Challenge::Ambigram as i64, SubmissionStatus::Active as i64))
         .bind(user.get() as i64)
         .fetch_optional(pool())
         .await
         .map_err(|e| format!("Failed to get user profile data: {}", e))?
         .unwrap_or_default();
 
+    let stats = get_placement_stats(user).await?;
+    let best_week_share = sqlx::query_as(r#"
+        SELECT week, challenge, place, CAST(votes AS REAL) / total_votes AS share
+        FROM placements
+        WHERE author = ? AND total_votes > 0
+        ORDER BY place ASC, share DESC, week DESC
+        LIMIT 1;
+    "#)
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| format!("Failed to get user profile data: {}", e))?;
+
     Ok(UserProfileData {
         nickname: first.nickname,
 
@@ -343,100 +1217,2677 @@ pub async fn get_user_profile(user: UserId) -> Result<UserProfileData, Error> {
 
         glyphs_submissions: second.glyphs_submissions,
         ambigrams_submissions: second.ambigrams_submissions,
+
+        former_member: first.former_member,
+
+        bio: first.bio,
+        links: first.link1.into_iter().chain(first.link2).collect(),
+        accent_colour: first.accent_colour.map(|c| c as u32),
+        pronouns: first.pronouns,
+
+        average_place: stats.0,
+        percentile: stats.1,
+        best_week_share,
     })
 }
 
-/// Remove a submission for the current week.
-pub async fn remove_submission(message: MessageId, challenge: Challenge) -> Res {
-    sqlx::query(r#"
+/// Get a user’s average place, and the percentage of active participants
+/// whose average place is worse than theirs.
+async fn get_placement_stats(user: UserId) -> Result<(Option<f64>, Option<f64>), Error> {
+    let averages: Vec<(i64, f64, i64)> = sqlx::query_as(r#"
+        SELECT author, AVG(place), MAX(week)
+        FROM placements
+        WHERE author != ?
+        GROUP BY author;
+    "#)
+        .bind(PURGED_AUTHOR)
+        .fetch_all(pool())
+        .await?;
+
+    let id = user.get() as i64;
+    let Some(&(_, average, last_week)) = averages.iter().find(|(a, _, _)| *a == id) else { return Ok((None, None)); };
+
+    // Only compare against people who took part recently.
+    let cutoff = current_week().await? - PERCENTILE_WEEKS;
+    if last_week <= cutoff { return Ok((Some(average), None)); }
+    let active = averages.iter().filter(|(_, _, w)| *w > cutoff).collect::<Vec<_>>();
+    let worse = active.iter().filter(|(_, a, _)| *a > average).count();
+    Ok((Some(average), Some(worse as f64 * 100.0 / active.len() as f64)))
+}
+
+/// Replace the stored placements for a challenge in a week and update the
+/// placement counts and highest rankings on the profiles of everyone affected.
+///
+/// The counts are recomputed from all placements rather than incremented,
+/// so this can be called again when the results of a week are corrected.
+pub async fn record_week_results(week: i64, challenge: Challenge, placements: &[(Submission, usize)]) -> Res {
+    let total: i64 = placements.iter().map(|(s, _)| s.votes).sum();
+    let mut tx = pool().begin().await?;
+
+    // Anyone who was placed before a correction needs updating too.
+    let mut authors: Vec<i64> = sqlx::query_scalar("SELECT DISTINCT author FROM placements WHERE week = ? AND challenge = ?;")
+        .bind(week)
+        .bind(challenge as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+    authors.extend(placements.iter().map(|(s, _)| s.author));
+    authors.sort_unstable();
+    authors.dedup();
+
+    sqlx::query("DELETE FROM placements WHERE week = ? AND challenge = ?;")
+        .bind(week)
+        .bind(challenge as i64)
+        .execute(&mut *tx)
+        .await?;
+
+    for (s, place) in placements {
+        sqlx::query(r#"
+            INSERT INTO placements (message, week, challenge, author, place, votes, total_votes)
+            VALUES (?, ?, ?, ?, ?, ?, ?);
+        "#)
+            .bind(s.message)
+            .bind(week)
+            .bind(challenge as i64)
+            .bind(s.author)
+            .bind(*place as i64)
+            .bind(s.votes)
+            .bind(total)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for author in authors.into_iter().filter(|&a| a != PURGED_AUTHOR) {
+        sqlx::query("INSERT INTO users (id) VALUES (?) ON CONFLICT (id) DO NOTHING;")
+            .bind(author)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(formatcp!(r#"
+            UPDATE users SET
+                glyphs_first = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {g} AND place = 1),
+                glyphs_second = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {g} AND place = 2),
+                glyphs_third = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {g} AND place = 3),
+                ambigrams_first = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {a} AND place = 1),
+                ambigrams_second = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {a} AND place = 2),
+                ambigrams_third = (SELECT COUNT(*) FROM placements WHERE author = ?1 AND challenge = {a} AND place = 3),
+                highest_ranking_glyphs = IFNULL((SELECT MIN(place) FROM placements WHERE author = ?1 AND challenge = {g}), 0),
+                highest_ranking_ambigrams = IFNULL((SELECT MIN(place) FROM placements WHERE author = ?1 AND challenge = {a}), 0)
+            WHERE id = ?1;
+        "#, g = Challenge::Glyph as i64, a = Challenge::Ambigram as i64))
+            .bind(author)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await.map_err(|e| e.into())
+}
+
+/// Get a user’s active entry for a challenge in the current week, if any.
+pub async fn get_current_entry(author: UserId, challenge: Challenge) -> Result<Option<MessageId>, Error> {
+    sqlx::query_scalar::<_, i64>(r#"
+        SELECT message FROM submissions
+        WHERE week = ? AND challenge = ? AND author = ? AND status = ?;
+    "#)
+        .bind(current_week().await?)
+        .bind(challenge as i64)
+        .bind(author.get() as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_optional(pool())
+        .await
+        .map(|m| m.map(|m| MessageId::new(m as u64)))
+        .map_err(|e| e.into())
+}
+
+/// Withdraw a user’s entry for the current week and add a new one in its
+/// place, all at once. Returns false if the old entry was already gone.
+pub async fn replace_submission(
+    old: MessageId,
+    new: MessageId,
+    challenge: Challenge,
+    link: &str,
+    spoiler: bool,
+    by_command: bool,
+) -> Result<bool, Error> {
+    let week = current_week().await?;
+    let mut tx = pool().begin().await?;
+    let removed: Option<(i64, i64)> = sqlx::query_as(r#"
         DELETE FROM submissions
-        WHERE message = ?
-        AND week = ?
-        AND challenge = ?;
+        WHERE message = ? AND week = ? AND challenge = ? AND status = ?
+        RETURNING author, time;
+    "#)
+        .bind(old.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let Some((author, time)) = removed else { return Ok(false); };
+    record_withdrawal(&mut tx, old.get() as i64, week, challenge as i64, author, time, WithdrawalReason::Replaced).await?;
+    sqlx::query(r#"
+        INSERT INTO submissions (message, week, challenge, author, link, spoiler, by_command)
+        VALUES (?, ?, ?, ?, ?, ?, ?);
+    "#)
+        .bind(new.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(author)
+        .bind(link)
+        .bind(spoiler)
+        .bind(by_command)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Check whether a message is a submission for the current week.
+pub async fn is_submission(message: MessageId, challenge: Challenge) -> Result<bool, Error> {
+    sqlx::query_scalar(r#"
+        SELECT EXISTS (
+            SELECT 1 FROM submissions
+            WHERE message = ?
+            AND week = ?
+            AND challenge = ?
+            AND status = ?
+        );
     "#)
         .bind(message.get() as i64)
         .bind(current_week().await?)
         .bind(challenge as i64)
-        .execute(pool())
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_one(pool())
         .await
-        .map(|_| ())
         .map_err(|e| e.into())
 }
 
-/// Set a user’s nickname.
-pub async fn set_nickname(user: UserId, name: &str) -> Res {
+/// Remove an active submission for the current week, and record why.
+/// Returns false if there was no such submission. Disqualified entries
+/// are kept, so that they can’t be entered again.
+pub async fn remove_submission(message: MessageId, challenge: Challenge, reason: WithdrawalReason) -> Result<bool, Error> {
+    let week = current_week().await?;
+    let mut tx = pool().begin().await?;
+    let removed: Option<(i64, i64)> = sqlx::query_as(r#"
+        DELETE FROM submissions
+        WHERE message = ?
+        AND week = ?
+        AND challenge = ?
+        AND status = ?
+        RETURNING author, time;
+    "#)
+        .bind(message.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let Some((author, time)) = removed else { return Ok(false); };
+    record_withdrawal(&mut tx, message.get() as i64, week, challenge as i64, author, time, reason).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Add an entry to the withdrawal log.
+async fn record_withdrawal(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    message: i64,
+    week: i64,
+    challenge: i64,
+    author: i64,
+    submitted: i64,
+    reason: WithdrawalReason,
+) -> Res {
     sqlx::query(r#"
-        INSERT INTO users (id, nickname) VALUES (?1, ?2)
-        ON CONFLICT (id) DO UPDATE SET nickname = ?2;
+        INSERT INTO withdrawals (message, week, challenge, author, submitted, reason)
+        VALUES (?, ?, ?, ?, ?, ?);
+    "#)
+        .bind(message)
+        .bind(week)
+        .bind(challenge)
+        .bind(author)
+        .bind(submitted)
+        .bind(reason as i64)
+        .execute(&mut **tx)
+        .await?;
+
+    // Votes on an entry that was withdrawn don’t count for anything.
+    sqlx::query("DELETE FROM votes WHERE message = ?;")
+        .bind(message)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Withdraw all of a user’s submissions for the current week because they
+/// left the server. Returns the submissions that were withdrawn.
+pub async fn withdraw_user_submissions(user: UserId) -> Result<Vec<MessageId>, Error> {
+    let week = current_week().await?;
+    let mut tx = pool().begin().await?;
+    let rows: Vec<(i64, i64, i64, i64)> = sqlx::query_as(r#"
+        UPDATE submissions
+        SET status = ?
+        WHERE author = ?
+        AND week = ?
+        AND status = ?
+        RETURNING message, week, challenge, time;
     "#)
+        .bind(SubmissionStatus::Withdrawn as i64)
         .bind(user.get() as i64)
-        .bind(name)
-        .execute(pool())
+        .bind(week)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for &(message, week, challenge, time) in &rows {
+        record_withdrawal(&mut tx, message, week, challenge, user.get() as i64, time, WithdrawalReason::LeftServer).await?;
+    }
+
+    tx.commit().await?;
+    Ok(rows.into_iter().map(|(id, ..)| MessageId::new(id as u64)).collect())
+}
+
+/// An entry in the withdrawal log.
+#[derive(Clone, Debug, FromRow)]
+pub struct Withdrawal {
+    // The log is shown by week, and the entry itself may be gone, so only
+    // the tests look at these.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub message: i64,
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub week: i64,
+    pub challenge: i64,
+    pub author: i64,
+    pub submitted: i64,
+    pub withdrawn: i64,
+    pub reason: i64,
+}
+
+/// Get all withdrawals of entries for a week, in the order they happened.
+pub async fn get_withdrawals(week: i64) -> Result<Vec<Withdrawal>, Error> {
+    sqlx::query_as("SELECT * FROM withdrawals WHERE week = ? ORDER BY withdrawn ASC, rowid ASC;")
+        .bind(week)
+        .fetch_all(pool())
         .await
-        .map(|_| ())
         .map_err(|e| e.into())
 }
 
-/// Set the prompt for a challenge and week.
-/// Returns the id of the prompt in the DB.
-pub async fn add_prompt(challenge: Challenge, prompt: &str) -> Result<i64, Error> {
-    sqlx::query_scalar("INSERT INTO prompts (challenge, prompt) VALUES (?, ?) RETURNING rowid")
-        .bind(challenge.raw())
-        .bind(prompt)
+/// Number of entries for a challenge in a week that moderators dealt with.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct ModerationCounts {
+    pub disqualified: i64,
+    pub flagged: i64,
+}
+
+/// Count the disqualified and flagged entries for a challenge in a week.
+pub async fn get_moderation_counts(week: i64, challenge: Challenge) -> Result<ModerationCounts, Error> {
+    sqlx::query_as(r#"
+        SELECT
+            IFNULL(SUM(status = ?), 0) AS disqualified,
+            IFNULL(SUM(flag IS NOT NULL), 0) AS flagged
+        FROM submissions
+        WHERE week = ? AND challenge = ?;
+    "#)
+        .bind(SubmissionStatus::Disqualified as i64)
+        .bind(week)
+        .bind(challenge as i64)
         .fetch_one(pool())
         .await
         .map_err(|e| e.into())
 }
 
-/// Delete a prompt.
-/// Returns whether a prompt was deleted.
-pub async fn delete_prompt(id: i64) -> Result<bool, Error> {
-    sqlx::query("DELETE FROM prompts WHERE rowid = ?")
-        .bind(id)
+/// Get a submission by its message, whatever its status.
+pub async fn get_submission(message: MessageId) -> Result<Option<Submission>, Error> {
+    sqlx::query_as("SELECT * FROM submissions WHERE message = ? LIMIT 1;")
+        .bind(message.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Disqualify a submission for the current week.
+/// Returns the challenge it belongs to, if there was such a submission.
+pub async fn disqualify_submission(message: MessageId) -> Result<Option<Challenge>, Error> {
+    let challenge: Option<i64> = sqlx::query_scalar(r#"
+        UPDATE submissions
+        SET status = ?
+        WHERE message = ?
+        AND week = ?
+        AND status = ?
+        RETURNING challenge;
+    "#)
+        .bind(SubmissionStatus::Disqualified as i64)
+        .bind(message.get() as i64)
+        .bind(current_week().await?)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_optional(pool())
+        .await?;
+
+    challenge.map(Challenge::try_from).transpose()
+}
+
+/// Flag an active submission for the current week so its author is
+/// warned before the withdrawal cutoff. Returns false if there is no
+/// such submission.
+pub async fn flag_submission(message: MessageId, reason: &str) -> Result<bool, Error> {
+    sqlx::query(r#"
+        UPDATE submissions
+        SET flag = ?, flag_warned = 0
+        WHERE message = ?
+        AND week = ?
+        AND status = ?;
+    "#)
+        .bind(reason)
+        .bind(message.get() as i64)
+        .bind(current_week().await?)
+        .bind(SubmissionStatus::Active as i64)
         .execute(pool())
         .await
         .map(|r| r.rows_affected() > 0)
         .map_err(|e| e.into())
 }
 
+/// Get all flagged submissions in a week whose authors haven’t been
+/// warned yet, and mark them as warned.
+pub async fn take_unwarned_flags(week: i64) -> Result<Vec<(Submission, String)>, Error> {
+    let rows: Vec<(i64, String)> = sqlx::query_as(r#"
+        UPDATE submissions
+        SET flag_warned = 1
+        WHERE week = ?
+        AND status = ?
+        AND author != ?
+        AND flag IS NOT NULL
+        AND flag_warned = 0
+        RETURNING message, flag;
+    "#)
+        .bind(week)
+        .bind(SubmissionStatus::Active as i64)
+        .bind(PURGED_AUTHOR)
+        .fetch_all(pool())
+        .await?;
+
+    let mut flagged = Vec::with_capacity(rows.len());
+    for (message, reason) in rows {
+        if let Some(s) = get_submission(MessageId::new(message as u64)).await? {
+            flagged.push((s, reason));
+        }
+    }
+
+    Ok(flagged)
+}
+
+/// Recount the votes and score of a submission from the `votes` table.
+async fn recount_votes(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, message: i64) -> Res {
+    sqlx::query(r#"
+        UPDATE submissions SET
+            votes = (SELECT COUNT(*) FROM votes WHERE message = ?1),
+            score = (SELECT IFNULL(SUM(weight), 0) FROM votes WHERE message = ?1)
+        WHERE message = ?1;
+    "#)
+        .bind(message)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
 
-/// Get a prompt by id.
-pub async fn get_prompt(id: i64) -> Result<(Challenge, String), Error> {
-    let res: (i64, String) = sqlx::query_as("SELECT challenge, prompt FROM prompts WHERE rowid = ? LIMIT 1")
-        .bind(id)
-        .fetch_optional(pool())
-        .await
-        .map_err(Error::from)
-        .and_then(|r| {
-            r.ok_or_else(|| format!("No prompt with id {}", id).into())
-        })?;
+/// Rebuild the votes and scores of all submissions, or of those in a week,
+/// from the `votes` table, e.g. after editing the DB by hand. Returns the
+/// weeks and challenges of the submissions whose counts were wrong.
+///
+/// When recounting everything, weeks without any rows in `votes` are left
+/// alone: they predate the table, so their counts are all we have.
+pub async fn recount_all_votes(week: Option<i64>) -> Result<Vec<(i64, Challenge)>, Error> {
+    let mut tx = pool().begin().await?;
+    let wrong: Vec<(i64, i64, i64)> = sqlx::query_as(r#"
+        SELECT s.message, s.week, s.challenge
+        FROM submissions s
+        LEFT JOIN (
+            SELECT message, COUNT(*) AS votes, SUM(weight) AS score
+            FROM votes
+            GROUP BY message
+        ) v ON v.message = s.message
+        WHERE (?1 IS NULL OR s.week = ?1)
+            AND (s.votes != IFNULL(v.votes, 0) OR s.score != IFNULL(v.score, 0))
+            AND (?1 IS NOT NULL OR EXISTS (
+                SELECT 1 FROM votes x
+                JOIN submissions y ON y.message = x.message
+                WHERE y.week = s.week AND y.challenge = s.challenge
+            ))
+        ORDER BY s.week ASC, s.challenge ASC;
+    "#)
+        .bind(week)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut affected = Vec::new();
+    for (message, week, challenge) in wrong {
+        recount_votes(&mut tx, message).await?;
+        let entry = (week, Challenge::try_from(challenge)?);
+        if !affected.contains(&entry) { affected.push(entry); }
+    }
+
+    tx.commit().await?;
+    Ok(affected)
+}
+
+/// Record a vote on an active submission, with a weight in percent (see
+/// [`crate::tally::vote_weight`]). People can’t vote for their own submissions. Returns
+/// false if the vote wasn’t counted.
+///
+/// The weight is kept with the ballot, so changing the weights later
+/// doesn’t change votes that were already cast.
+pub async fn add_vote(message: MessageId, voter: UserId, weight: i64) -> Result<bool, Error> {
+    let message = message.get() as i64;
+    let mut tx = pool().begin().await?;
+    let added = sqlx::query(r#"
+        INSERT INTO votes (message, voter, week, challenge, weight)
+        SELECT message, ?2, week, challenge, ?4 FROM submissions
+        WHERE message = ?1 AND author != ?2 AND status = ?3
+        ON CONFLICT DO NOTHING;
+    "#)
+        .bind(message)
+        .bind(voter.get() as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .bind(weight)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
 
-    Ok((Challenge::from(res.0), res.1))
+    if added { recount_votes(&mut tx, message).await?; }
+    tx.commit().await?;
+    Ok(added)
 }
 
+/// Take back a vote. Returns false if there was no such vote.
+pub async fn remove_vote(message: MessageId, voter: UserId) -> Result<bool, Error> {
+    let message = message.get() as i64;
+    let mut tx = pool().begin().await?;
+    let removed = sqlx::query("DELETE FROM votes WHERE message = ? AND voter = ?;")
+        .bind(message)
+        .bind(voter.get() as i64)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
 
-/// Get all prompts for a challenge.
-pub async fn get_prompts(challenge: Challenge) -> Result<Vec<(i64, String)>, Error> {
-    sqlx::query_as("SELECT rowid, prompt FROM prompts WHERE challenge = ? ORDER BY rowid ASC")
-        .bind(challenge.raw())
+    if removed { recount_votes(&mut tx, message).await?; }
+    tx.commit().await?;
+    Ok(removed)
+}
+
+/// Get the active submissions someone voted for in a week, by challenge
+/// and then in the order they were submitted.
+pub async fn get_voted_submissions(voter: UserId, week: i64) -> Result<Vec<Submission>, Error> {
+    sqlx::query_as(r#"
+        SELECT s.* FROM votes v
+        JOIN submissions s ON s.message = v.message
+        WHERE v.voter = ? AND s.week = ? AND s.status = ?
+        ORDER BY s.challenge ASC, s.time ASC, s.message ASC;
+    "#)
+        .bind(voter.get() as i64)
+        .bind(week)
+        .bind(SubmissionStatus::Active as i64)
         .fetch_all(pool())
         .await
         .map_err(|e| e.into())
 }
 
-/// Get stats for a week.
-pub async fn weekinfo(week: Option<u64>) -> Result<WeekInfo, Error> {
-    let week = match week {
-       Some(w) => w as i64,
-       None => current_week().await?,
-    };
+/// Remove all votes on a submission.
+pub async fn clear_votes(message: MessageId) -> Res {
+    let message = message.get() as i64;
+    let mut tx = pool().begin().await?;
+    sqlx::query("DELETE FROM votes WHERE message = ?;")
+        .bind(message)
+        .execute(&mut *tx)
+        .await?;
+
+    recount_votes(&mut tx, message).await?;
+    tx.commit().await.map_err(|e| e.into())
+}
+
+/// Weight of a vote by someone without any weighted role, in percent.
+pub const DEFAULT_VOTE_WEIGHT: i64 = 100;
+
+/// Set the weight of votes by members with a role, in percent.
+pub async fn set_vote_weight(role: RoleId, weight: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO vote_weights (role, weight) VALUES (?1, ?2)
+        ON CONFLICT (role) DO UPDATE SET weight = ?2;
+    "#)
+        .bind(role.get() as i64)
+        .bind(weight)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Stop weighting votes by a role. Returns false if it had no weight.
+pub async fn delete_vote_weight(role: RoleId) -> Result<bool, Error> {
+    sqlx::query("DELETE FROM vote_weights WHERE role = ?;")
+        .bind(role.get() as i64)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Get all roles with a vote weight, heaviest first.
+pub async fn get_vote_weights() -> Result<Vec<(RoleId, i64)>, Error> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as("SELECT role, weight FROM vote_weights ORDER BY weight DESC, role ASC;")
+        .fetch_all(pool())
+        .await?;
+
+    Ok(rows.into_iter().map(|(r, w)| (RoleId::new(r as u64), w)).collect())
+}
+
+/// A vote as shown in the vote audit.
+#[derive(Clone, Debug, FromRow)]
+pub struct Ballot {
+    pub message: i64,
+    pub voter: i64,
+    pub time: i64,
+    pub weight: i64,
+}
 
+/// Get all votes on the active submissions of a challenge in a week, by
+/// submission and then in the order they were cast.
+pub async fn get_ballots(week: i64, challenge: Challenge) -> Result<Vec<Ballot>, Error> {
     sqlx::query_as(r#"
-        SELECT * FROM weeks WHERE week = ? LIMIT 1;
+        SELECT v.message, v.voter, v.time, v.weight
+        FROM votes v
+        JOIN submissions s ON s.message = v.message
+        WHERE s.week = ? AND s.challenge = ? AND s.status = ?
+        ORDER BY v.message ASC, v.time ASC, v.voter ASC;
     "#)
         .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// A ballot handed out for voting without reactions.
+#[derive(Clone, Debug, FromRow)]
+pub struct IssuedBallot {
+    pub id: i64,
+    pub voter: i64,
+    pub week: i64,
+    pub challenge: i64,
+    pub used: Option<i64>,
+}
+
+/// Hand out a ballot for a challenge in a week, unless the voter already
+/// has one. Returns the ballot and whether it is new.
+pub async fn issue_ballot(voter: UserId, week: i64, challenge: Challenge) -> Result<(IssuedBallot, bool), Error> {
+    let new = sqlx::query("INSERT INTO ballots (voter, week, challenge) VALUES (?, ?, ?) ON CONFLICT DO NOTHING;")
+        .bind(voter.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .execute(pool())
+        .await?
+        .rows_affected() > 0;
+
+    let ballot = sqlx::query_as("SELECT * FROM ballots WHERE voter = ? AND week = ? AND challenge = ?;")
+        .bind(voter.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .fetch_one(pool())
+        .await?;
+
+    Ok((ballot, new))
+}
+
+/// Get a ballot by its ID.
+pub async fn get_issued_ballot(id: i64) -> Result<Option<IssuedBallot>, Error> {
+    sqlx::query_as("SELECT * FROM ballots WHERE id = ?;")
+        .bind(id)
         .fetch_optional(pool())
         .await
-        .map_err(|e| format!("Failed to get week info: {}", e))?
-        .ok_or_else(|| format!("No info for week {}", week).into())
-}
\ No newline at end of file
+        .map_err(|e| e.into())
+}
+
+/// Replace the entries picked in one of a ballot’s menus.
+pub async fn set_ballot_choices(ballot: i64, menu: i64, messages: &[MessageId]) -> Res {
+    let mut tx = pool().begin().await?;
+    sqlx::query("DELETE FROM ballot_choices WHERE ballot = ? AND menu = ?;")
+        .bind(ballot)
+        .bind(menu)
+        .execute(&mut *tx)
+        .await?;
+
+    for m in messages {
+        sqlx::query("INSERT INTO ballot_choices (ballot, menu, message) VALUES (?, ?, ?) ON CONFLICT DO NOTHING;")
+            .bind(ballot)
+            .bind(menu)
+            .bind(m.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await.map_err(|e| e.into())
+}
+
+/// Get the entries picked so far on a ballot that hasn’t been cast.
+pub async fn get_ballot_choices(ballot: i64) -> Result<Vec<i64>, Error> {
+    sqlx::query_scalar("SELECT message FROM ballot_choices WHERE ballot = ?;")
+        .bind(ballot)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Cast a ballot: record a vote with a weight (see [`add_vote`]) for each
+/// entry picked on it, and mark it as used. Returns the number of votes
+/// recorded, or `None` if the ballot was already cast.
+pub async fn cast_ballot(ballot: i64, weight: i64) -> Result<Option<usize>, Error> {
+    let mut tx = pool().begin().await?;
+    let voter: Option<i64> = sqlx::query_scalar("UPDATE ballots SET used = unixepoch() WHERE id = ? AND used IS NULL RETURNING voter;")
+        .bind(ballot)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let Some(voter) = voter else { return Ok(None); };
+    let messages: Vec<i64> = sqlx::query_scalar(r#"
+        INSERT INTO votes (message, voter, week, challenge, weight)
+        SELECT s.message, ?2, s.week, s.challenge, ?3
+        FROM ballot_choices c
+        JOIN ballots b ON b.id = c.ballot
+        JOIN submissions s ON s.message = c.message AND s.week = b.week AND s.challenge = b.challenge
+        WHERE c.ballot = ?1 AND s.author != ?2 AND s.status = ?4
+        ON CONFLICT DO NOTHING
+        RETURNING message;
+    "#)
+        .bind(ballot)
+        .bind(voter)
+        .bind(weight)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for &m in &messages { recount_votes(&mut tx, m).await?; }
+    sqlx::query("DELETE FROM ballot_choices WHERE ballot = ?;")
+        .bind(ballot)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(messages.len()))
+}
+
+/// Get everyone who submitted something in the last few weeks and is still
+/// on the server.
+pub async fn get_active_users(weeks: i64) -> Result<Vec<UserId>, Error> {
+    let ids: Vec<i64> = sqlx::query_scalar(r#"
+        SELECT DISTINCT s.author
+        FROM submissions s
+        LEFT JOIN users u ON u.id = s.author
+        WHERE s.week > ? AND s.author != ? AND NOT COALESCE(u.former_member, FALSE)
+        ORDER BY s.author ASC;
+    "#)
+        .bind(current_week().await? - weeks)
+        .bind(PURGED_AUTHOR)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Voter turnout for a challenge in a week.
+#[derive(Clone, Debug)]
+pub struct Turnout {
+    /// Number of people who voted at least once.
+    pub voters: i64,
+
+    /// Total number of votes cast.
+    pub ballots: i64,
+
+    /// Votes of each active entry, fewest first.
+    pub votes_per_entry: Vec<i64>,
+
+    /// Number of voters who also submitted something.
+    pub voters_who_submitted: i64,
+}
+
+/// Get the voter turnout for a challenge in a week.
+pub async fn get_turnout(week: i64, challenge: Challenge) -> Result<Turnout, Error> {
+    let (voters, ballots): (i64, i64) = sqlx::query_as(r#"
+        SELECT COUNT(DISTINCT v.voter), COUNT(*)
+        FROM votes v
+        JOIN submissions s ON s.message = v.message
+        WHERE s.week = ? AND s.challenge = ? AND s.status = ?;
+    "#)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_one(pool())
+        .await?;
+
+    let votes_per_entry = sqlx::query_scalar(r#"
+        SELECT votes FROM submissions
+        WHERE week = ? AND challenge = ? AND status = ?
+        ORDER BY votes ASC;
+    "#)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(pool())
+        .await?;
+
+    let voters_who_submitted = sqlx::query_scalar(r#"
+        SELECT COUNT(DISTINCT v.voter)
+        FROM votes v
+        JOIN submissions s ON s.message = v.message
+        WHERE s.week = ?1 AND s.challenge = ?2 AND s.status = ?3
+        AND v.voter IN (
+            SELECT author FROM submissions
+            WHERE week = ?1 AND challenge = ?2 AND status = ?3
+        );
+    "#)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_one(pool())
+        .await?;
+
+    Ok(Turnout { voters, ballots, votes_per_entry, voters_who_submitted })
+}
+
+/// Mark whether a user has left the server.
+pub async fn set_former_member(user: UserId, former: bool) -> Res {
+    sqlx::query(r#"
+        INSERT INTO users (id, former_member) VALUES (?1, ?2)
+        ON CONFLICT (id) DO UPDATE SET former_member = ?2;
+    "#)
+        .bind(user.get() as i64)
+        .bind(former)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the value of a setting.
+pub async fn get_setting<T: FromStr>(setting: Setting) -> Result<T, Error> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = ? LIMIT 1;")
+        .bind(setting.key())
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| format!("Failed to get setting: {}", e))?;
+
+    value.as_deref()
+        .unwrap_or(setting.default_value())
+        .parse::<T>()
+        .map_err(|_| format!("Setting ‘{}’ has an invalid value", setting.key()).into())
+}
+
+/// Change the value of a setting.
+pub async fn set_setting(setting: Setting, value: &str) -> Res {
+    setting.validate(value)?;
+    sqlx::query(r#"
+        INSERT INTO settings (key, value) VALUES (?1, ?2)
+        ON CONFLICT (key) DO UPDATE SET value = ?2;
+    "#)
+        .bind(setting.key())
+        .bind(value)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Update the cached display name and avatar of a user.
+pub async fn update_user_cache(user: &User) -> Res {
+    sqlx::query(r#"
+        INSERT INTO users (id, display_name, avatar_url, cache_updated) VALUES (?1, ?2, ?3, unixepoch())
+        ON CONFLICT (id) DO UPDATE SET display_name = ?2, avatar_url = ?3, cache_updated = unixepoch();
+    "#)
+        .bind(user.id.get() as i64)
+        .bind(user.global_name.as_ref().unwrap_or(&user.name))
+        .bind(user.face())
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the name to display for the author of a submission; see
+/// [`get_display_name()`]. Purged authors don’t have one.
+pub async fn get_author_name(author: i64) -> Result<Option<String>, Error> {
+    if author == PURGED_AUTHOR { return Ok(None); }
+    get_display_name(UserId::new(author as u64)).await
+}
+
+/// Get the name to display for a user, preferring their nickname
+/// over the cached Discord name.
+pub async fn get_display_name(user: UserId) -> Result<Option<String>, Error> {
+    sqlx::query_scalar("SELECT COALESCE(nickname, display_name) FROM users WHERE id = ? LIMIT 1;")
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)
+        .map_err(|e| e.into())
+}
+
+/// Get users that submitted something in the last `weeks` weeks and
+/// whose cached name is older than `max_age` seconds.
+pub async fn get_stale_active_users(weeks: i64, max_age: i64) -> Result<Vec<UserId>, Error> {
+    let ids: Vec<i64> = sqlx::query_scalar(r#"
+        SELECT DISTINCT s.author
+        FROM submissions s
+        LEFT JOIN users u ON u.id = s.author
+        WHERE s.week > ?
+        AND s.author != ?
+        AND COALESCE(u.cache_updated, 0) < unixepoch() - ?;
+    "#)
+        .bind(current_week().await? - weeks)
+        .bind(PURGED_AUTHOR)
+        .bind(max_age)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Get a user’s preferences.
+pub async fn get_user_prefs(user: UserId) -> Result<UserPrefs, Error> {
+    sqlx::query_as(r#"
+        SELECT hide_profile, hide_submissions, nickname_only, dm_opt_out, certificates
+        FROM user_prefs
+        WHERE id = ?;
+    "#)
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("Failed to get user preferences: {}", e).into())
+}
+
+/// Set a user’s preferences.
+pub async fn set_user_prefs(user: UserId, prefs: &UserPrefs) -> Res {
+    sqlx::query(r#"
+        INSERT INTO user_prefs (id, hide_profile, hide_submissions, nickname_only, dm_opt_out, certificates)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT (id) DO UPDATE SET
+            hide_profile = ?2,
+            hide_submissions = ?3,
+            nickname_only = ?4,
+            dm_opt_out = ?5,
+            certificates = ?6;
+    "#)
+        .bind(user.get() as i64)
+        .bind(prefs.hide_profile)
+        .bind(prefs.hide_submissions)
+        .bind(prefs.nickname_only)
+        .bind(prefs.dm_opt_out)
+        .bind(prefs.certificates)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the raw `users` row of a user, if there is one.
+pub async fn get_user_row(user: UserId) -> Result<Option<UserRow>, Error> {
+    sqlx::query_as("SELECT * FROM users WHERE id = ?;")
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get all submissions by a user, across all weeks.
+pub async fn get_user_submissions(user: UserId) -> Result<Vec<Submission>, Error> {
+    sqlx::query_as("SELECT * FROM submissions WHERE author = ? ORDER BY week ASC, challenge ASC;")
+        .bind(user.get() as i64)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Podium finishes and submissions of a user in one challenge.
+#[derive(Clone, Debug, FromRow)]
+pub struct LeaderboardRow {
+    pub user: i64,
+    pub first: i64,
+    pub second: i64,
+    pub third: i64,
+    pub submissions: i64,
+}
+
+/// Get the podium finishes and active submissions of everyone who has
+/// entered a challenge.
+pub async fn get_leaderboard_rows(challenge: Challenge) -> Result<Vec<LeaderboardRow>, Error> {
+    let (first, second, third) = match challenge {
+        Challenge::Glyph => ("glyphs_first", "glyphs_second", "glyphs_third"),
+        Challenge::Ambigram => ("ambigrams_first", "ambigrams_second", "ambigrams_third"),
+    };
+
+    sqlx::query_as(&format!(r#"
+        SELECT s.author AS user,
+            IFNULL(u.{}, 0) AS first,
+            IFNULL(u.{}, 0) AS second,
+            IFNULL(u.{}, 0) AS third,
+            COUNT(*) AS submissions
+        FROM submissions s
+        LEFT JOIN users u ON u.id = s.author
+        WHERE s.challenge = ? AND s.status = ? AND s.author != ?
+        GROUP BY s.author;
+    "#, first, second, third))
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .bind(PURGED_AUTHOR)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// An entry in a user’s portfolio.
+#[derive(Clone, Debug, FromRow)]
+pub struct PortfolioEntry {
+    pub message: i64,
+    pub week: i64,
+    pub challenge: i64,
+    pub place: i64,
+    pub votes: i64,
+    pub spoiler: bool,
+}
+
+/// Get a user’s best entries: podium finishes first, then the rest by
+/// votes. Only weeks whose results have been published are included.
+pub async fn get_portfolio(user: UserId, limit: i64) -> Result<Vec<PortfolioEntry>, Error> {
+    sqlx::query_as(r#"
+        SELECT p.message, p.week, p.challenge, p.place, p.votes, s.spoiler
+        FROM placements p
+        INNER JOIN submissions s ON s.message = p.message
+        WHERE p.author = ? AND s.status = ?
+        ORDER BY MIN(p.place, 4) ASC, p.votes DESC, p.week DESC
+        LIMIT ?;
+    "#)
+        .bind(user.get() as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .bind(limit)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Delete all data we store about a user.
+///
+/// Their submissions are kept, but anonymised, so that the number
+/// of submissions in past weeks stays the same. Returns the messages of
+/// their submissions, withdrawn ones included, so their archived images
+/// can be deleted.
+pub async fn purge_user(user: UserId) -> Result<Vec<MessageId>, Error> {
+    let id = user.get() as i64;
+    let mut tx = pool().begin().await?;
+
+    let messages: Vec<i64> = sqlx::query_scalar("SELECT message FROM submissions WHERE author = ? UNION SELECT message FROM withdrawals WHERE author = ?;")
+        .bind(id)
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE submissions SET author = ?, link = '' WHERE author = ?;")
+        .bind(PURGED_AUTHOR)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE placements SET author = ? WHERE author = ?;")
+        .bind(PURGED_AUTHOR)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE withdrawals SET author = ? WHERE author = ?;")
+        .bind(PURGED_AUTHOR)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM users WHERE id = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM user_prefs WHERE id = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM achievements WHERE user = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM dm_status WHERE user = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM prompt_picks WHERE user = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM poll_votes WHERE voter = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Vote totals on submissions are kept; we just forget who cast them.
+    sqlx::query("DELETE FROM votes WHERE voter = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM ballot_choices WHERE ballot IN (SELECT id FROM ballots WHERE voter = ?);")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM ballots WHERE voter = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(messages.into_iter().map(|m| MessageId::new(m as u64)).collect())
+}
+
+/// Count all submissions a user has ever made, including withdrawn ones.
+pub async fn count_user_submissions(user: UserId) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE author = ?;")
+        .bind(user.get() as i64)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Award an achievement to a user. Returns false if they already had it.
+pub async fn award_achievement(user: UserId, achievement: Achievement) -> Result<bool, Error> {
+    sqlx::query("INSERT OR IGNORE INTO achievements (user, achievement) VALUES (?, ?);")
+        .bind(user.get() as i64)
+        .bind(achievement.raw() as i64)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() != 0)
+        .map_err(|e| e.into())
+}
+
+/// Get the achievements of a user, in the order they were awarded.
+pub async fn get_achievements(user: UserId) -> Result<Vec<Achievement>, Error> {
+    let ids: Vec<i64> = sqlx::query_scalar("SELECT achievement FROM achievements WHERE user = ? ORDER BY awarded, achievement;")
+        .bind(user.get() as i64)
+        .fetch_all(pool())
+        .await?;
+
+    ids.into_iter().map(Achievement::try_from).collect()
+}
+
+/// Get the custom emoji configured for an achievement, if any.
+pub async fn get_badge_emoji(achievement: Achievement) -> Result<Option<EmojiId>, Error> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT emoji FROM badge_emojis WHERE achievement = ?;")
+        .bind(achievement as i64)
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(id.map(|id| EmojiId::new(id as u64)))
+}
+
+/// Set or clear the custom emoji for an achievement.
+pub async fn set_badge_emoji(achievement: Achievement, emoji: Option<EmojiId>) -> Res {
+    let res = match emoji {
+        Some(emoji) => sqlx::query(r#"
+            INSERT INTO badge_emojis (achievement, emoji) VALUES (?1, ?2)
+            ON CONFLICT (achievement) DO UPDATE SET emoji = ?2;
+        "#).bind(achievement as i64).bind(emoji.get() as i64).execute(pool()).await,
+        None => sqlx::query("DELETE FROM badge_emojis WHERE achievement = ?;")
+            .bind(achievement as i64)
+            .execute(pool())
+            .await,
+    };
+
+    res.map(|_| ()).map_err(|e| e.into())
+}
+
+/// Get the season that is currently running, if any.
+pub async fn current_season() -> Result<Option<Season>, Error> {
+    sqlx::query_as("SELECT * FROM seasons WHERE end_week IS NULL LIMIT 1;")
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Start a new season in the current week.
+pub async fn start_season(name: &str) -> Result<Season, Error> {
+    if let Some(s) = current_season().await? {
+        return Err(format!("Season ‘{}’ is still running", s.name).into());
+    }
+
+    sqlx::query_as("INSERT INTO seasons (name, start_week) VALUES (?, ?) RETURNING *;")
+        .bind(name)
+        .bind(current_week().await?)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the running season, or the one that ended most recently.
+pub async fn latest_season() -> Result<Option<Season>, Error> {
+    sqlx::query_as("SELECT * FROM seasons ORDER BY end_week IS NULL DESC, id DESC LIMIT 1;")
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// End the current season. Its last week is the previous week, since the
+/// current one hasn’t finished yet and has no results.
+pub async fn end_season() -> Result<Season, Error> {
+    let Some(season) = current_season().await? else { return Err("No season is running".into()); };
+    let end_week = current_week().await? - 1;
+    if end_week < season.start_week {
+        return Err(format!("Season ‘{}’ has no finished weeks yet", season.name).into());
+    }
+
+    sqlx::query_as("UPDATE seasons SET end_week = ? WHERE end_week IS NULL RETURNING *;")
+        .bind(end_week)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the seasons that have ended but haven’t been closed yet.
+pub async fn get_unclosed_seasons() -> Result<Vec<Season>, Error> {
+    sqlx::query_as("SELECT * FROM seasons WHERE end_week IS NOT NULL AND closed IS NULL ORDER BY id;")
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Mark a season as closed. Returns false if it already was.
+pub async fn close_season(id: i64) -> Result<bool, Error> {
+    let res = sqlx::query("UPDATE seasons SET closed = unixepoch() WHERE id = ? AND closed IS NULL;")
+        .bind(id)
+        .execute(pool())
+        .await?;
+
+    Ok(res.rows_affected() != 0)
+}
+
+/// Count the active submissions of each user in a range of weeks.
+pub async fn count_submissions_by_user(first_week: i64, last_week: i64) -> Result<Vec<(UserId, i64)>, Error> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as(r#"
+        SELECT author, COUNT(*)
+        FROM submissions
+        WHERE week BETWEEN ? AND ? AND status = ? AND author != ?
+        GROUP BY author;
+    "#)
+        .bind(first_week)
+        .bind(last_week)
+        .bind(SubmissionStatus::Active.raw() as i64)
+        .bind(PURGED_AUTHOR)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(rows.into_iter().map(|(u, c)| (UserId::new(u as u64), c)).collect())
+}
+
+/// Get the users with the most active submissions in a time range, most active first.
+pub async fn get_most_active_users(from: i64, to: i64, limit: i64) -> Result<Vec<(UserId, i64)>, Error> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as(r#"
+        SELECT author, COUNT(*) AS count
+        FROM submissions
+        WHERE time >= ? AND time < ? AND status = ? AND author != ?
+        GROUP BY author
+        ORDER BY count DESC, MIN(time) ASC
+        LIMIT ?;
+    "#)
+        .bind(from)
+        .bind(to)
+        .bind(SubmissionStatus::Active.raw() as i64)
+        .bind(PURGED_AUTHOR)
+        .bind(limit)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(rows.into_iter().map(|(u, c)| (UserId::new(u as u64), c)).collect())
+}
+
+/// Get users whose first submission ever was made in a time range.
+pub async fn get_newcomers(from: i64, to: i64) -> Result<Vec<UserId>, Error> {
+    let ids: Vec<i64> = sqlx::query_scalar(r#"
+        SELECT author
+        FROM submissions
+        WHERE author != ?
+        GROUP BY author
+        HAVING MIN(time) >= ? AND MIN(time) < ?
+        ORDER BY MIN(time) ASC;
+    "#)
+        .bind(PURGED_AUTHOR)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Get the total number of votes cast in each week that had submissions
+/// in a time range, in order.
+pub async fn get_votes_by_week(from: i64, to: i64) -> Result<Vec<(i64, i64)>, Error> {
+    sqlx::query_as(r#"
+        SELECT week, SUM(votes)
+        FROM submissions
+        WHERE week IN (SELECT DISTINCT week FROM submissions WHERE time >= ? AND time < ?) AND status = ?
+        GROUP BY week
+        ORDER BY week ASC;
+    "#)
+        .bind(from)
+        .bind(to)
+        .bind(SubmissionStatus::Active.raw() as i64)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Start tracking an interactive component so it can be expired later.
+pub async fn register_component(custom_id: &str, lifetime_secs: i64, channel: ChannelId, message: MessageId) -> Res {
+    sqlx::query(r#"
+        INSERT INTO components (custom_id, expires, channel, message)
+        VALUES (?1, unixepoch() + ?2, ?3, ?4)
+        ON CONFLICT (custom_id) DO UPDATE SET
+            expires = unixepoch() + ?2,
+            channel = ?3,
+            message = ?4,
+            cleaned = 0;
+    "#)
+        .bind(custom_id)
+        .bind(lifetime_secs)
+        .bind(channel.get() as i64)
+        .bind(message.get() as i64)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Check whether a component has expired. Components we don’t
+/// track are never considered expired.
+pub async fn is_component_expired(custom_id: &str) -> Result<bool, Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM components WHERE custom_id = ? AND expires <= unixepoch());")
+        .bind(custom_id)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Mark all newly expired components as cleaned up, returning the messages
+/// they were attached to, if known. Components that expired a long time ago
+/// are forgotten entirely.
+pub async fn take_expired_components() -> Result<Vec<(ChannelId, MessageId)>, Error> {
+    const FORGET_AFTER_SECS: i64 = 30 * 24 * 60 * 60;
+    sqlx::query("DELETE FROM components WHERE expires <= unixepoch() - ?;")
+        .bind(FORGET_AFTER_SECS)
+        .execute(pool())
+        .await?;
+
+    let rows: Vec<(Option<i64>, Option<i64>)> = sqlx::query_as(r#"
+        UPDATE components
+        SET cleaned = 1
+        WHERE expires <= unixepoch() AND cleaned = 0
+        RETURNING channel, message;
+    "#)
+        .fetch_all(pool())
+        .await?;
+
+    let mut messages = rows.into_iter()
+        .filter_map(|(c, m)| Some((ChannelId::new(c? as u64), MessageId::new(m? as u64))))
+        .collect::<Vec<_>>();
+
+    // Several components may be on the same message.
+    messages.sort();
+    messages.dedup();
+    Ok(messages)
+}
+
+/// Add an automatic response. Returns its id.
+pub async fn add_autoresponse(trigger: &str, response: &str) -> Result<i64, Error> {
+    sqlx::query_scalar("INSERT INTO autoresponses (trigger, response) VALUES (?, ?) RETURNING rowid;")
+        .bind(trigger)
+        .bind(response)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Delete an automatic response.
+/// Returns whether it existed.
+pub async fn delete_autoresponse(id: i64) -> Result<bool, Error> {
+    sqlx::query("DELETE FROM autoresponses WHERE rowid = ?;")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Get all automatic responses as (id, trigger, response).
+pub async fn get_autoresponses() -> Result<Vec<(i64, String, String)>, Error> {
+    sqlx::query_as("SELECT rowid, trigger, response FROM autoresponses ORDER BY rowid ASC;")
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Allow or deny linked submissions from a host.
+pub async fn set_link_host(host: &str, allowed: bool) -> Res {
+    sqlx::query(r#"
+        INSERT INTO link_hosts (host, allowed) VALUES (?1, ?2)
+        ON CONFLICT (host) DO UPDATE SET allowed = ?2;
+    "#)
+        .bind(host)
+        .bind(allowed)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Remove a host from the allow or deny list. Returns false if it wasn’t on either.
+pub async fn delete_link_host(host: &str) -> Result<bool, Error> {
+    sqlx::query("DELETE FROM link_hosts WHERE host = ?;")
+        .bind(host)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Get all hosts on the allow and deny lists as (host, allowed).
+pub async fn get_link_hosts() -> Result<Vec<(String, bool)>, Error> {
+    sqlx::query_as("SELECT host, allowed FROM link_hosts ORDER BY host ASC;")
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the text of a template, if it has been set.
+pub async fn get_template(name: &str) -> Result<Option<String>, Error> {
+    sqlx::query_scalar("SELECT text FROM templates WHERE name = ? LIMIT 1;")
+        .bind(name)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Set the text of a template.
+pub async fn set_template(name: &str, text: &str) -> Res {
+    sqlx::query(r#"
+        INSERT INTO templates (name, text) VALUES (?1, ?2)
+        ON CONFLICT (name) DO UPDATE SET text = ?2;
+    "#)
+        .bind(name)
+        .bind(text)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the constraints on entries for a challenge.
+pub async fn get_constraints(challenge: Challenge) -> Result<Constraints, Error> {
+    let constraints: Option<Constraints> = sqlx::query_as(r#"
+        SELECT min_size, max_size, square, monochrome, animation
+        FROM challenge_constraints
+        WHERE challenge = ?;
+    "#)
+        .bind(challenge.raw())
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(constraints.unwrap_or_else(|| challenge.default_constraints()))
+}
+
+/// Set the constraints on entries for a challenge, or go back to the
+/// defaults if `None` is passed.
+pub async fn set_constraints(challenge: Challenge, constraints: Option<&Constraints>) -> Res {
+    let res = match constraints {
+        Some(c) => sqlx::query(r#"
+            INSERT INTO challenge_constraints (challenge, min_size, max_size, square, monochrome, animation)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (challenge) DO UPDATE SET
+                min_size = ?2,
+                max_size = ?3,
+                square = ?4,
+                monochrome = ?5,
+                animation = ?6;
+        "#)
+            .bind(challenge.raw())
+            .bind(c.min_size)
+            .bind(c.max_size)
+            .bind(c.square)
+            .bind(c.monochrome)
+            .bind(c.animation)
+            .execute(pool())
+            .await,
+        None => sqlx::query("DELETE FROM challenge_constraints WHERE challenge = ?;")
+            .bind(challenge.raw())
+            .execute(pool())
+            .await,
+    };
+
+    res.map(|_| ()).map_err(|e| e.into())
+}
+
+/// Get until when the challenges are paused, if they are.
+pub async fn get_paused_until() -> Result<Option<i64>, Error> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = 'paused_until' LIMIT 1;")
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(value.and_then(|v| v.parse().ok()))
+}
+
+/// Pause the challenges until a certain time, or unpause them.
+pub async fn set_paused_until(until: Option<i64>) -> Res {
+    let res = match until {
+        Some(until) => sqlx::query(r#"
+            INSERT INTO settings (key, value) VALUES ('paused_until', ?1)
+            ON CONFLICT (key) DO UPDATE SET value = ?1;
+        "#).bind(until.to_string()).execute(pool()).await,
+        None => sqlx::query("DELETE FROM settings WHERE key = 'paused_until';").execute(pool()).await,
+    };
+
+    res.map(|_| ()).map_err(|e| e.into())
+}
+
+/// Get the emoji people vote with, as set by admins.
+pub async fn get_vote_emoji() -> Result<Option<String>, Error> {
+    sqlx::query_scalar("SELECT value FROM settings WHERE key = 'vote_emoji' LIMIT 1;")
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Set the emoji people vote with, or go back to the default.
+pub async fn set_vote_emoji(emoji: Option<&str>) -> Res {
+    let res = match emoji {
+        Some(emoji) => sqlx::query(r#"
+            INSERT INTO settings (key, value) VALUES ('vote_emoji', ?1)
+            ON CONFLICT (key) DO UPDATE SET value = ?1;
+        "#).bind(emoji).execute(pool()).await,
+        None => sqlx::query("DELETE FROM settings WHERE key = 'vote_emoji';").execute(pool()).await,
+    };
+
+    res.map(|_| ()).map_err(|e| e.into())
+}
+
+/// Get the start of the period covered by the last stats digest.
+pub async fn get_last_digest() -> Result<Option<i64>, Error> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = 'last_digest' LIMIT 1;")
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(value.and_then(|v| v.parse().ok()))
+}
+
+/// Record the start of the period covered by the last stats digest.
+pub async fn set_last_digest(period_start: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO settings (key, value) VALUES ('last_digest', ?1)
+        ON CONFLICT (key) DO UPDATE SET value = ?1;
+    "#)
+        .bind(period_start.to_string())
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Record whether a DM to a user could be delivered; `error` is None
+/// if it was.
+pub async fn record_dm_result(user: UserId, error: Option<&str>) -> Res {
+    let query = match error {
+        None => r#"
+            INSERT INTO dm_status (user, failures, last_success) VALUES (?1, 0, unixepoch())
+            ON CONFLICT (user) DO UPDATE SET failures = 0, last_success = unixepoch();
+        "#,
+        Some(_) => r#"
+            INSERT INTO dm_status (user, failures, last_failure, last_error) VALUES (?1, 1, unixepoch(), ?2)
+            ON CONFLICT (user) DO UPDATE SET
+                failures = failures + 1,
+                last_failure = unixepoch(),
+                last_error = ?2;
+        "#,
+    };
+
+    sqlx::query(query)
+        .bind(user.get() as i64)
+        .bind(error)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get how many DMs to a user failed in a row.
+pub async fn get_dm_failures(user: UserId) -> Result<i64, Error> {
+    let failures: Option<i64> = sqlx::query_scalar("SELECT failures FROM dm_status WHERE user = ? LIMIT 1;")
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(failures.unwrap_or(0))
+}
+
+/// Persisted state of a periodic task.
+#[derive(Default)]
+pub struct TaskState {
+    pub last_run: Option<i64>,
+    pub disabled: bool,
+}
+
+/// Get the state of a periodic task.
+pub async fn get_task_state(name: &str) -> Result<TaskState, Error> {
+    let res: Option<(Option<i64>, bool)> = sqlx::query_as("SELECT last_run, disabled FROM tasks WHERE name = ? LIMIT 1;")
+        .bind(name)
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(res.map(|(last_run, disabled)| TaskState { last_run, disabled }).unwrap_or_default())
+}
+
+/// Record when a periodic task last ran.
+pub async fn set_task_last_run(name: &str, time: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO tasks (name, last_run) VALUES (?1, ?2)
+        ON CONFLICT (name) DO UPDATE SET last_run = ?2;
+    "#)
+        .bind(name)
+        .bind(time)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Enable or disable a periodic task.
+pub async fn set_task_disabled(name: &str, disabled: bool) -> Res {
+    sqlx::query(r#"
+        INSERT INTO tasks (name, disabled) VALUES (?1, ?2)
+        ON CONFLICT (name) DO UPDATE SET disabled = ?2;
+    "#)
+        .bind(name)
+        .bind(disabled)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the last week a vote reminder was sent for.
+pub async fn get_last_vote_reminder() -> Result<Option<i64>, Error> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = 'last_vote_reminder' LIMIT 1;")
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(value.and_then(|v| v.parse().ok()))
+}
+
+/// Record that a vote reminder was sent for a week.
+pub async fn set_last_vote_reminder(week: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO settings (key, value) VALUES ('last_vote_reminder', ?1)
+        ON CONFLICT (key) DO UPDATE SET value = ?1;
+    "#)
+        .bind(week.to_string())
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get everyone who hides their profile from other users.
+pub async fn get_hidden_profiles() -> Result<Vec<UserId>, Error> {
+    let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM user_prefs WHERE hide_profile;")
+        .fetch_all(pool())
+        .await?;
+
+    Ok(ids.into_iter().map(|id| UserId::new(id as u64)).collect())
+}
+
+/// Find users whose nickname contains a string, excluding hidden profiles.
+pub async fn search_nicknames(partial: &str, limit: i64) -> Result<Vec<(UserId, String)>, Error> {
+    let pattern = format!("%{}%", partial.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+    let rows: Vec<(i64, String)> = sqlx::query_as(r#"
+        SELECT u.id, u.nickname
+        FROM users u
+        LEFT JOIN user_prefs p ON p.id = u.id
+        WHERE u.nickname LIKE ? ESCAPE '\' AND NOT COALESCE(p.hide_profile, 0)
+        ORDER BY u.nickname
+        LIMIT ?;
+    "#)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(rows.into_iter().map(|(id, n)| (UserId::new(id as u64), n)).collect())
+}
+
+/// Find the user with a nickname, ignoring case.
+pub async fn find_user_by_nickname(name: &str) -> Result<Option<UserId>, Error> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE nickname = ? COLLATE NOCASE LIMIT 1;")
+        .bind(name)
+        .fetch_optional(pool())
+        .await?;
+
+    Ok(id.map(|id| UserId::new(id as u64)))
+}
+
+/// Check whether a character may not appear in nicknames: control
+/// characters, invisible formatting characters that can be used to
+/// impersonate others, and characters that Discord treats specially.
+fn is_disallowed_nickname_char(c: char) -> bool {
+    c.is_control() || matches!(c,
+        '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{115F}' | '\u{1160}' | '\u{180E}' |
+        '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{206F}' |
+        '\u{3164}' | '\u{FE00}'..='\u{FE0F}' | '\u{FEFF}' | '\u{FFA0}' |
+        '@' | '<' | '>' | '`'
+    )
+}
+
+/// Clean up a link for a profile; it must be a plain http(s) URL.
+fn sanitise_link(link: &str) -> Result<String, Error> {
+    let link = link.trim();
+    let rest = link.strip_prefix("https://").or_else(|| link.strip_prefix("http://"));
+    let host = rest.and_then(|r| r.split(['/', '?', '#']).next()).unwrap_or("");
+    if link.chars().count() > MAX_LINK_LENGTH
+        || !host.contains('.')
+        || link.chars().any(|c| c.is_whitespace() || is_disallowed_nickname_char(c) && c != '@') {
+        return Err(format!("‘{}’ is not a valid link; links must start with https:// and be at most {} characters long", link, MAX_LINK_LENGTH).into());
+    }
+
+    Ok(link.to_string())
+}
+
+/// Set a user’s bio and links. Empty values clear the corresponding field.
+pub async fn set_bio(user: UserId, bio: &str, links: [&str; 2]) -> Res {
+    // Drop invisible characters and excessive blank lines.
+    let mut cleaned = String::new();
+    for line in bio.lines().map(str::trim_end) {
+        if line.is_empty() && (cleaned.is_empty() || cleaned.ends_with("\n\n")) { continue; }
+        cleaned.extend(line.chars().filter(|c| !is_disallowed_nickname_char(*c) || matches!(c, '@' | '<' | '>' | '`')));
+        cleaned.push('\n');
+    }
+
+    let bio = cleaned.trim();
+    if bio.chars().count() > MAX_BIO_LENGTH {
+        return Err(format!("Bio must be at most {} characters long", MAX_BIO_LENGTH).into());
+    }
+
+    let mut sanitised = [None, None];
+    for (i, l) in links.iter().filter(|l| !l.trim().is_empty()).enumerate() {
+        sanitised[i] = Some(sanitise_link(l)?);
+    }
+
+    sqlx::query(r#"
+        INSERT INTO users (id, bio, link1, link2) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (id) DO UPDATE SET bio = ?2, link1 = ?3, link2 = ?4;
+    "#)
+        .bind(user.get() as i64)
+        .bind(Some(bio).filter(|b| !b.is_empty()))
+        .bind(&sanitised[0])
+        .bind(&sanitised[1])
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set the colour of a user’s profile embed, or reset it to the default.
+pub async fn set_accent_colour(user: UserId, colour: Option<u32>) -> Res {
+    sqlx::query(r#"
+        INSERT INTO users (id, accent_colour) VALUES (?1, ?2)
+        ON CONFLICT (id) DO UPDATE SET accent_colour = ?2;
+    "#)
+        .bind(user.get() as i64)
+        .bind(colour.map(|c| c as i64))
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set a user’s pronouns, or clear them.
+pub async fn set_pronouns(user: UserId, pronouns: Option<&str>) -> Res {
+    let pronouns = pronouns.map(str::trim).filter(|p| !p.is_empty());
+    if let Some(p) = pronouns {
+        if p.chars().count() > MAX_PRONOUNS_LENGTH {
+            return Err(format!("Pronouns must be at most {} characters long", MAX_PRONOUNS_LENGTH).into());
+        }
+
+        if let Some(c) = p.chars().find(|c| is_disallowed_nickname_char(*c)) {
+            return Err(format!("Pronouns must not contain the character U+{:04X}", c as u32).into());
+        }
+    }
+
+    sqlx::query(r#"
+        INSERT INTO users (id, pronouns) VALUES (?1, ?2)
+        ON CONFLICT (id) DO UPDATE SET pronouns = ?2;
+    "#)
+        .bind(user.get() as i64)
+        .bind(pronouns)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get a user’s pronouns, if they set any.
+pub async fn get_pronouns(user: UserId) -> Result<Option<String>, Error> {
+    sqlx::query_scalar("SELECT pronouns FROM users WHERE id = ? LIMIT 1;")
+        .bind(user.get() as i64)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)
+        .map_err(|e| e.into())
+}
+
+/// Remove a user’s bio and links.
+pub async fn clear_bio(user: UserId) -> Res {
+    sqlx::query("UPDATE users SET bio = NULL, link1 = NULL, link2 = NULL WHERE id = ?;")
+        .bind(user.get() as i64)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set a user’s nickname, after checking that it is allowed.
+pub async fn set_nickname(user: UserId, name: &str) -> Res {
+    let name = name.trim();
+    let len = name.chars().count();
+    let min = get_setting::<usize>(Setting::NicknameMinLength).await?;
+    if len < min { return Err(format!("Nickname must be at least {} character{} long", min, if min == 1 { "" } else { "s" }).into()); }
+    if len > MAX_NICKNAME_LENGTH { return Err(format!("Nickname must be at most {} characters long", MAX_NICKNAME_LENGTH).into()); }
+    if let Some(c) = name.chars().find(|c| is_disallowed_nickname_char(*c)) {
+        return Err(format!("Nickname must not contain the character U+{:04X}", c as u32).into());
+    }
+
+    // Check when it was last changed.
+    let cooldown = get_setting::<i64>(Setting::NicknameCooldown).await? * 60 * 60;
+    if cooldown != 0 {
+        let changed: Option<i64> = sqlx::query_scalar("SELECT nickname_changed FROM users WHERE id = ?;")
+            .bind(user.get() as i64)
+            .fetch_optional(pool())
+            .await?
+            .flatten();
+
+        let next = changed.and_then(|c| chrono::DateTime::from_timestamp(c + cooldown, 0));
+        if let Some(next) = next.filter(|n| *n > chrono::Utc::now()) {
+            return Err(format!("You can change your nickname again {}", timestamp(next, TimestampStyle::Relative)).into());
+        }
+    }
+
+    // Check that no-one else has it.
+    if get_setting::<bool>(Setting::UniqueNicknames).await? {
+        if let Some(other) = find_user_by_nickname(name).await? {
+            if other != user { return Err("That nickname is already taken".into()); }
+        }
+    }
+
+    sqlx::query(r#"
+        INSERT INTO users (id, nickname, nickname_changed) VALUES (?1, ?2, unixepoch())
+        ON CONFLICT (id) DO UPDATE SET nickname = ?2, nickname_changed = unixepoch();
+    "#)
+        .bind(user.get() as i64)
+        .bind(name)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set the prompt for a challenge and week.
+/// Returns the id of the prompt in the DB.
+pub async fn add_prompt(
+    challenge: Challenge,
+    prompt: &str,
+    notes: Option<&str>,
+    category: Option<&str>,
+) -> Result<i64, Error> {
+    sqlx::query_scalar("INSERT INTO prompts (challenge, prompt, notes, category) VALUES (?, ?, ?, ?) RETURNING rowid")
+        .bind(challenge.raw())
+        .bind(prompt)
+        .bind(notes)
+        .bind(category)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Delete a prompt.
+/// Returns whether a prompt was deleted.
+pub async fn delete_prompt(id: i64) -> Result<bool, Error> {
+    sqlx::query("DELETE FROM prompts WHERE rowid = ?")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+
+/// Change the text of a prompt. Its preview has to be confirmed again.
+/// Returns whether the prompt still exists.
+pub async fn update_prompt(
+    id: i64,
+    prompt: &str,
+    notes: Option<&str>,
+    category: Option<&str>,
+) -> Result<bool, Error> {
+    sqlx::query("UPDATE prompts SET prompt = ?, notes = ?, category = ?, approved = FALSE WHERE rowid = ?")
+        .bind(prompt)
+        .bind(notes)
+        .bind(category)
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Columns of the `prompts` table, in the order [`get_prompt()`] selects them.
+type PromptRow = (i64, String, Option<String>, Option<String>, Option<String>, Option<i64>, Option<i64>, bool);
+
+/// Mark the preview of a prompt as confirmed.
+/// Returns whether the prompt still exists.
+pub async fn approve_prompt(id: i64) -> Result<bool, Error> {
+    sqlx::query("UPDATE prompts SET approved = TRUE WHERE rowid = ?")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Get a prompt by id.
+pub async fn get_prompt(id: i64) -> Result<Prompt, Error> {
+    let res: PromptRow = sqlx::query_as(
+        "SELECT challenge, prompt, notes, category, theme, series, series_part, approved FROM prompts WHERE rowid = ? LIMIT 1"
+    )
+        .bind(id)
+        .fetch_optional(pool())
+        .await
+        .map_err(Error::from)
+        .and_then(|r| {
+            r.ok_or_else(|| format!("No prompt with id {}", id).into())
+        })?;
+
+    Ok(Prompt {
+        id,
+        challenge: Challenge::try_from(res.0)?,
+        prompt: res.1,
+        notes: res.2,
+        category: res.3,
+        theme: res.4,
+        series: res.5,
+        series_part: res.6,
+        approved: res.7,
+    })
+}
+
+/// Set the theme of the announcement image for a prompt.
+pub async fn set_prompt_theme(id: i64, theme: Option<&str>) -> Res {
+    sqlx::query("UPDATE prompts SET theme = ? WHERE rowid = ?;")
+        .bind(theme)
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the themes available for a challenge’s announcement images.
+pub async fn get_themes(challenge: Challenge) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar("SELECT name FROM themes WHERE challenge = ? ORDER BY name;")
+        .bind(challenge as i64)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Make a theme available for a challenge.
+pub async fn add_theme(challenge: Challenge, name: &str) -> Res {
+    sqlx::query("INSERT INTO themes (challenge, name) VALUES (?, ?) ON CONFLICT DO NOTHING;")
+        .bind(challenge as i64)
+        .bind(name)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Remove a theme. Prompts that use it go back to the default theme.
+/// Returns false if there was no such theme.
+pub async fn remove_theme(challenge: Challenge, name: &str) -> Result<bool, Error> {
+    let mut tx = pool().begin().await?;
+    let removed = sqlx::query("DELETE FROM themes WHERE challenge = ? AND name = ?;")
+        .bind(challenge as i64)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
+
+    sqlx::query("UPDATE prompts SET theme = NULL WHERE challenge = ? AND theme = ?;")
+        .bind(challenge as i64)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(removed)
+}
+
+
+/// Get the shortlist of a challenge, oldest first.
+pub async fn get_shortlist(challenge: Challenge) -> Result<Vec<(i64, String)>, Error> {
+    sqlx::query_as("SELECT id, prompt FROM shortlist WHERE challenge = ? ORDER BY id;")
+        .bind(challenge.raw())
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Put a prompt on the shortlist of a challenge. Returns its ID.
+pub async fn add_to_shortlist(challenge: Challenge, prompt: &str) -> Result<i64, Error> {
+    sqlx::query_scalar("INSERT INTO shortlist (challenge, prompt) VALUES (?, ?) RETURNING id;")
+        .bind(challenge.raw())
+        .bind(prompt)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Take a prompt off the shortlist.
+/// Returns false if there was no such entry.
+pub async fn remove_from_shortlist(id: i64) -> Result<bool, Error> {
+    sqlx::query("DELETE FROM shortlist WHERE id = ?;")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// A winner invited to pick the next prompt.
+#[derive(Clone, Debug, FromRow)]
+pub struct PromptPick {
+    pub id: i64,
+    pub week: i64,
+    pub challenge: i64,
+    pub user: i64,
+    pub prompt: Option<String>,
+    pub shortlisted: Option<i64>,
+}
+
+/// Invite the winner of a challenge in a week to pick the next prompt,
+/// unless someone was invited for it already. Returns the invitation and
+/// whether it is new.
+pub async fn invite_to_pick(week: i64, challenge: Challenge, user: UserId) -> Result<(PromptPick, bool), Error> {
+    let new = sqlx::query("INSERT INTO prompt_picks (week, challenge, user) VALUES (?, ?, ?) ON CONFLICT DO NOTHING;")
+        .bind(week)
+        .bind(challenge.raw())
+        .bind(user.get() as i64)
+        .execute(pool())
+        .await?
+        .rows_affected() > 0;
+
+    let pick = sqlx::query_as("SELECT * FROM prompt_picks WHERE week = ? AND challenge = ?;")
+        .bind(week)
+        .bind(challenge.raw())
+        .fetch_one(pool())
+        .await?;
+
+    Ok((pick, new))
+}
+
+/// Get an invitation to pick a prompt by its ID.
+pub async fn get_prompt_pick(id: i64) -> Result<Option<PromptPick>, Error> {
+    sqlx::query_as("SELECT * FROM prompt_picks WHERE id = ?;")
+        .bind(id)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Record what a winner picked. Returns false if they already picked
+/// something that hasn’t been rejected.
+pub async fn set_prompt_pick(id: i64, prompt: &str, shortlisted: Option<i64>) -> Result<bool, Error> {
+    sqlx::query("UPDATE prompt_picks SET prompt = ?, shortlisted = ? WHERE id = ? AND prompt IS NULL;")
+        .bind(prompt)
+        .bind(shortlisted)
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// Add a picked prompt to the queue and take it off the shortlist.
+/// Returns its queue entry, or `None` if it was already reviewed.
+pub async fn approve_prompt_pick(id: i64) -> Result<Option<i64>, Error> {
+    let mut tx = pool().begin().await?;
+    let pick: Option<PromptPick> = sqlx::query_as("SELECT * FROM prompt_picks WHERE id = ? AND prompt IS NOT NULL AND queued IS NULL;")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(pick) = pick else { return Ok(None); };
+
+    // An admin reviewed it to get here, so it needs no further approval.
+    let queued: i64 = sqlx::query_scalar("INSERT INTO prompts (challenge, prompt, approved) VALUES (?, ?, 1) RETURNING rowid;")
+        .bind(pick.challenge)
+        .bind(&pick.prompt)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE prompt_picks SET queued = ? WHERE id = ?;")
+        .bind(queued)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM shortlist WHERE id = ?;")
+        .bind(pick.shortlisted)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(queued))
+}
+
+/// Turn down what a winner picked so they can pick again.
+/// Returns false if it was already reviewed.
+pub async fn reject_prompt_pick(id: i64) -> Result<bool, Error> {
+    sqlx::query("UPDATE prompt_picks SET prompt = NULL, shortlisted = NULL WHERE id = ? AND prompt IS NOT NULL AND queued IS NULL;")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .map_err(|e| e.into())
+}
+
+/// A poll on the next prompt.
+#[derive(Clone, Debug, FromRow)]
+pub struct Poll {
+    pub id: i64,
+    pub challenge: i64,
+    pub channel: i64,
+    pub message: Option<i64>,
+    pub opened: i64,
+    pub closes: i64,
+    pub closed: Option<i64>,
+}
+
+/// A prompt in a poll, and how many votes it has.
+#[derive(Clone, Debug, FromRow)]
+pub struct PollOption {
+    pub option: i64,
+    pub prompt: String,
+    pub shortlisted: Option<i64>,
+    pub votes: i64,
+}
+
+/// Open a poll on the given shortlist entries. Returns its ID.
+pub async fn create_poll(challenge: Challenge, channel: ChannelId, opened: i64, closes: i64, options: &[(i64, String)]) -> Result<i64, Error> {
+    let mut tx = pool().begin().await?;
+    let id: i64 = sqlx::query_scalar("INSERT INTO polls (challenge, channel, opened, closes) VALUES (?, ?, ?, ?) RETURNING id;")
+        .bind(challenge.raw())
+        .bind(channel.get() as i64)
+        .bind(opened)
+        .bind(closes)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    for (i, (shortlisted, prompt)) in options.iter().enumerate() {
+        sqlx::query("INSERT INTO poll_options (poll, option, prompt, shortlisted) VALUES (?, ?, ?, ?);")
+            .bind(id)
+            .bind(i as i64)
+            .bind(prompt)
+            .bind(shortlisted)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Remember the message a poll was posted as.
+pub async fn set_poll_message(poll: i64, message: MessageId) -> Res {
+    sqlx::query("UPDATE polls SET message = ? WHERE id = ?;")
+        .bind(message.get() as i64)
+        .bind(poll)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get a poll by its ID.
+pub async fn get_poll(id: i64) -> Result<Option<Poll>, Error> {
+    sqlx::query_as("SELECT * FROM polls WHERE id = ?;")
+        .bind(id)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the most recent poll for a challenge, open or not.
+pub async fn get_latest_poll(challenge: Challenge) -> Result<Option<Poll>, Error> {
+    sqlx::query_as("SELECT * FROM polls WHERE challenge = ? ORDER BY opened DESC, id DESC LIMIT 1;")
+        .bind(challenge.raw())
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the open polls that should have closed by `now`.
+pub async fn get_due_polls(now: i64) -> Result<Vec<Poll>, Error> {
+    sqlx::query_as("SELECT * FROM polls WHERE closed IS NULL AND closes <= ? ORDER BY id;")
+        .bind(now)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the prompts in a poll, in order, with their votes.
+pub async fn get_poll_options(poll: i64) -> Result<Vec<PollOption>, Error> {
+    sqlx::query_as(r#"
+        SELECT o.option, o.prompt, o.shortlisted, COUNT(v.voter) AS votes
+        FROM poll_options o
+        LEFT JOIN poll_votes v ON v.poll = o.poll AND v.option = o.option
+        WHERE o.poll = ?
+        GROUP BY o.option
+        ORDER BY o.option;
+    "#)
+        .bind(poll)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Record someone’s vote in a poll, replacing any earlier one.
+pub async fn vote_in_poll(poll: i64, voter: UserId, option: i64) -> Res {
+    sqlx::query("INSERT INTO poll_votes (poll, voter, option) VALUES (?1, ?2, ?3) ON CONFLICT (poll, voter) DO UPDATE SET option = ?3;")
+        .bind(poll)
+        .bind(voter.get() as i64)
+        .bind(option)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Close a poll. If there is a winner that is still on the shortlist, it
+/// is added to the queue and taken off the shortlist. Returns its queue
+/// entry, or `None` if nothing was queued or the poll was already closed.
+pub async fn close_poll(poll: i64, winner: Option<&PollOption>, closed: i64) -> Result<Option<i64>, Error> {
+    let mut tx = pool().begin().await?;
+    let challenge: Option<i64> = sqlx::query_scalar("UPDATE polls SET closed = ? WHERE id = ? AND closed IS NULL RETURNING challenge;")
+        .bind(closed)
+        .bind(poll)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let (Some(challenge), Some(winner)) = (challenge, winner) else {
+        tx.commit().await?;
+        return Ok(None);
+    };
+
+    let removed = sqlx::query("DELETE FROM shortlist WHERE id = ?;")
+        .bind(winner.shortlisted)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() > 0;
+    if !removed {
+        tx.commit().await?;
+        return Ok(None);
+    }
+
+    let queued: i64 = sqlx::query_scalar("INSERT INTO prompts (challenge, prompt) VALUES (?, ?) RETURNING rowid;")
+        .bind(challenge)
+        .bind(&winner.prompt)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE polls SET queued = ? WHERE id = ?;")
+        .bind(queued)
+        .bind(poll)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(queued))
+}
+
+/// Get all prompts for a challenge, in the order they will be used.
+///
+/// The parts of a series are kept together, in order, where the earliest
+/// of them was queued, so that they run in consecutive weeks.
+pub async fn get_prompts(challenge: Challenge) -> Result<Vec<(i64, String)>, Error> {
+    sqlx::query_as(r#"
+        SELECT rowid, prompt FROM prompts
+        WHERE challenge = ?
+        ORDER BY
+            CASE WHEN series IS NULL THEN rowid
+                 ELSE (SELECT MIN(p.rowid) FROM prompts p WHERE p.series = prompts.series)
+            END ASC,
+            series_part ASC,
+            rowid ASC;
+    "#)
+        .bind(challenge.raw())
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Group queued prompts into a series; they become its parts in the
+/// order given.
+pub async fn create_series(challenge: Challenge, name: &str, prompts: &[i64]) -> Result<Series, Error> {
+    if prompts.len() < 2 { return Err("A series needs at least two parts".into()); }
+    let mut tx = pool().begin().await?;
+    let series: Series = sqlx::query_as("INSERT INTO series (challenge, name, parts) VALUES (?, ?, ?) RETURNING *;")
+        .bind(challenge.raw())
+        .bind(name)
+        .bind(prompts.len() as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    for (i, &id) in prompts.iter().enumerate() {
+        let row: Option<(i64, Option<i64>)> = sqlx::query_as("SELECT challenge, series FROM prompts WHERE rowid = ?;")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        match row {
+            None => return Err(format!("No queue entry with ID {}", id).into()),
+            Some((c, _)) if c != challenge.raw() as i64 => return Err(format!("Queue entry {} is for the other challenge", id).into()),
+            Some((_, Some(_))) => return Err(format!("Queue entry {} is already part of a series", id).into()),
+            Some(_) => {}
+        }
+
+        sqlx::query("UPDATE prompts SET series = ?, series_part = ? WHERE rowid = ?;")
+            .bind(series.id)
+            .bind(i as i64 + 1)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(series)
+}
+
+/// Ungroup the queued parts of a series. Weeks that already ran as part
+/// of it keep it, so that it can still be looked up.
+/// Returns the number of queue entries that were ungrouped.
+pub async fn dissolve_series(id: i64) -> Result<u64, Error> {
+    sqlx::query("UPDATE prompts SET series = NULL, series_part = NULL WHERE series = ?;")
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.into())
+}
+
+/// Get a series by ID.
+pub async fn get_series(id: i64) -> Result<Option<Series>, Error> {
+    sqlx::query_as("SELECT * FROM series WHERE id = ?;")
+        .bind(id)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the weeks in which parts of a series ran, in order.
+pub async fn get_series_weeks(series: &Series) -> Result<Vec<i64>, Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT week FROM weeks WHERE {}_series = ? ORDER BY week ASC;",
+        Challenge::try_from(series.challenge)?.column_prefix(),
+    ))
+        .bind(series.id)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the series the prompt of a challenge in a week belonged to, if any.
+pub async fn get_week_series(week: i64, challenge: Challenge) -> Result<Option<Series>, Error> {
+    let id: Option<i64> = sqlx::query_scalar(&format!(
+        "SELECT {}_series FROM weeks WHERE week = ? LIMIT 1;",
+        challenge.column_prefix(),
+    ))
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)?;
+
+    match id {
+        Some(id) => get_series(id).await,
+        None => Ok(None),
+    }
+}
+
+/// Take a prompt that was announced in a week off the queue, recording
+/// the week if it is part of a series.
+pub async fn take_prompt(week: i64, prompt: &Prompt) -> Res {
+    record_prompt_week(week, prompt).await?;
+    delete_prompt(prompt.id).await?;
+    Ok(())
+}
+
+/// Record that a queued prompt ran in a week. This only matters for the
+/// parts of a series, which must run in consecutive weeks.
+pub async fn record_prompt_week(week: i64, prompt: &Prompt) -> Res {
+    let (Some(id), Some(part)) = (prompt.series, prompt.series_part) else { return Ok(()); };
+    let series = get_series(id).await?.ok_or("This series no longer exists")?;
+    let weeks = get_series_weeks(&series).await?;
+    let earlier = weeks.iter().filter(|&&w| w < week).collect::<Vec<_>>();
+    if earlier.len() as i64 != part - 1 || earlier.last().is_some_and(|&&w| w != week - 1) {
+        return Err(format!("Part {} of ‘{}’ must run in the week after part {}", part, series.name, part - 1).into());
+    }
+
+    sqlx::query(&format!(r#"
+        INSERT INTO weeks (week, {0}_series) VALUES (?1, ?2)
+        ON CONFLICT (week) DO UPDATE SET {0}_series = ?2;
+    "#, prompt.challenge.column_prefix()))
+        .bind(week)
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Record where the combined results of a series were posted.
+pub async fn set_series_results_message(id: i64, message: MessageId) -> Res {
+    sqlx::query("UPDATE series SET results_message = ? WHERE id = ?;")
+        .bind(message.get() as i64)
+        .bind(id)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set or clear the notes for a challenge in a week.
+pub async fn set_week_notes(week: i64, challenge: Challenge, notes: Option<&str>) -> Res {
+    sqlx::query(&format!(r#"
+        INSERT INTO weeks (week, {0}_notes) VALUES (?1, ?2)
+        ON CONFLICT (week) DO UPDATE SET {0}_notes = ?2;
+    "#, challenge.column_prefix()))
+        .bind(week)
+        .bind(notes)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Override the deadline of a week, or reset it to the default schedule.
+pub async fn set_week_deadline(week: i64, deadline: Option<i64>) -> Res {
+    sqlx::query(r#"
+        INSERT INTO weeks (week, deadline) VALUES (?1, ?2)
+        ON CONFLICT (week) DO UPDATE SET deadline = ?2;
+    "#)
+        .bind(week)
+        .bind(deadline)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the deadline override of a week, if any.
+pub async fn get_week_deadline(week: i64) -> Result<Option<i64>, Error> {
+    sqlx::query_scalar("SELECT deadline FROM weeks WHERE week = ? LIMIT 1;")
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)
+        .map_err(|e| e.into())
+}
+
+/// Get how often the deadline of a week was extended automatically.
+pub async fn get_auto_extensions(week: i64) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT auto_extensions FROM weeks WHERE week = ?;")
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map(|n| n.unwrap_or(0))
+        .map_err(|e| e.into())
+}
+
+/// Count an automatic extension of the deadline of a week.
+pub async fn record_auto_extension(week: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO weeks (week, auto_extensions) VALUES (?1, 1)
+        ON CONFLICT (week) DO UPDATE SET auto_extensions = auto_extensions + 1;
+    "#)
+        .bind(week)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Record when a week started.
+pub async fn set_week_start(week: i64, start: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO weeks (week, start) VALUES (?1, ?2)
+        ON CONFLICT (week) DO UPDATE SET start = ?2;
+    "#)
+        .bind(week)
+        .bind(start)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get when a week started, if that was recorded.
+pub async fn get_week_start(week: i64) -> Result<Option<i64>, Error> {
+    sqlx::query_scalar("SELECT start FROM weeks WHERE week = ? LIMIT 1;")
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)
+        .map_err(|e| e.into())
+}
+
+/// Get the week that was running at a point in time, going by the
+/// recorded start dates. Returns `None` if no week had started by then.
+pub async fn week_at(time: i64) -> Result<Option<i64>, Error> {
+    sqlx::query_scalar("SELECT week FROM weeks WHERE start <= ? ORDER BY start DESC, week DESC LIMIT 1;")
+        .bind(time)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get all active submissions for a challenge in a week, oldest first.
+pub async fn get_submissions(week: i64, challenge: Challenge) -> Result<Vec<Submission>, Error> {
+    sqlx::query_as(r#"
+        SELECT * FROM submissions
+        WHERE week = ? AND challenge = ? AND status = ?
+        ORDER BY time ASC, message ASC;
+    "#)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the active entries of a challenge in a week that were submitted
+/// with the submit emoji rather than the command.
+pub async fn get_reaction_submissions(week: i64, challenge: Challenge) -> Result<Vec<MessageId>, Error> {
+    let messages: Vec<i64> = sqlx::query_scalar(r#"
+        SELECT message FROM submissions
+        WHERE week = ? AND challenge = ? AND status = ? AND by_command = 0;
+    "#)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_all(pool())
+        .await?;
+
+    Ok(messages.into_iter().map(|m| MessageId::new(m as u64)).collect())
+}
+
+/// Check whether the prompt of a challenge in a week has been announced,
+/// i.e. whether it’s ok to show it publicly.
+pub async fn is_prompt_announced(week: i64, challenge: Challenge) -> Result<bool, Error> {
+    Ok(week < current_week().await? || get_week_message(week, challenge, WeekMessage::Announcement).await?.is_some())
+}
+
+/// Get a message posted for a challenge in a week, if it was posted.
+pub async fn get_week_message(week: i64, challenge: Challenge, kind: WeekMessage) -> Result<Option<MessageId>, Error> {
+    let id: Option<i64> = sqlx::query_scalar(&format!(
+        "SELECT {}_{} FROM weeks WHERE week = ? LIMIT 1;",
+        challenge.column_prefix(),
+        kind.column_suffix(),
+    ))
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map(Option::flatten)?;
+
+    Ok(id.map(|id| MessageId::new(id as u64)))
+}
+
+/// Record a message posted for a challenge in a week.
+pub async fn set_week_message(week: i64, challenge: Challenge, kind: WeekMessage, message: MessageId) -> Res {
+    sqlx::query(&format!(r#"
+        INSERT INTO weeks (week, {0}_{1}) VALUES (?1, ?2)
+        ON CONFLICT (week) DO UPDATE SET {0}_{1} = ?2;
+    "#, challenge.column_prefix(), kind.column_suffix()))
+        .bind(week)
+        .bind(message.get() as i64)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Get the number of active submissions for a challenge in a week.
+pub async fn count_submissions(week: i64, challenge: Challenge) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE week = ? AND challenge = ? AND status = ?;")
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(SubmissionStatus::Active as i64)
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get stats for a week.
+pub async fn weekinfo(week: Option<u64>) -> Result<WeekInfo, Error> {
+    let week = match week {
+       Some(w) => w as i64,
+       None => current_week().await?,
+    };
+
+    sqlx::query_as(r#"
+        SELECT * FROM weeks WHERE week = ? LIMIT 1;
+    "#)
+        .bind(week)
+        .fetch_optional(pool())
+        .await
+        .map_err(|e| format!("Failed to get week info: {}", e))?
+        .ok_or_else(|| format!("No info for week {}", week).into())
+}
+
+/// Get the rows of the `weeks` table for a range of weeks; weeks that
+/// don’t have a row yet are left out.
+pub async fn get_weeks(from: i64, to: i64) -> Result<Vec<WeekInfo>, Error> {
+    sqlx::query_as("SELECT * FROM weeks WHERE week BETWEEN ? AND ? ORDER BY week;")
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Check whether the DB contains any submissions.
+#[cfg(debug_assertions)]
+pub async fn has_submissions() -> Result<bool, Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM submissions);")
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Get the highest submission message ID, or 0 if there are none.
+#[cfg(debug_assertions)]
+pub async fn max_submission_id() -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(message), 0) FROM submissions;")
+        .fetch_one(pool())
+        .await
+        .map_err(|e| e.into())
+}
+
+/// Add a user with a name; only for seeding a development DB.
+#[cfg(debug_assertions)]
+pub async fn seed_user(user: UserId, name: &str) -> Res {
+    sqlx::query(r#"
+        INSERT INTO users (id, display_name, cache_updated) VALUES (?1, ?2, unixepoch())
+        ON CONFLICT (id) DO UPDATE SET display_name = ?2, cache_updated = unixepoch();
+    "#)
+        .bind(user.get() as i64)
+        .bind(name)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Set the prompts of a week; only for seeding a development DB.
+#[cfg(debug_assertions)]
+pub async fn seed_week(week: i64, glyph_prompt: &str, ambigram_prompt: &str) -> Res {
+    sqlx::query(r#"
+        INSERT INTO weeks (week, glyph_prompt, ambigram_prompt) VALUES (?1, ?2, ?3)
+        ON CONFLICT (week) DO UPDATE SET glyph_prompt = ?2, ambigram_prompt = ?3;
+    "#)
+        .bind(week)
+        .bind(glyph_prompt)
+        .bind(ambigram_prompt)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+/// Add a submission to any week; only for seeding a development DB.
+#[cfg(debug_assertions)]
+pub async fn seed_submission(message: MessageId, week: i64, challenge: Challenge, author: UserId, link: &str, time: i64) -> Res {
+    sqlx::query(r#"
+        INSERT INTO submissions (message, week, challenge, author, link, time)
+        VALUES (?, ?, ?, ?, ?, ?);
+    "#)
+        .bind(message.get() as i64)
+        .bind(week)
+        .bind(challenge as i64)
+        .bind(author.get() as i64)
+        .bind(link)
+        .bind(time)
+        .execute(pool())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
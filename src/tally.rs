@@ -0,0 +1,306 @@
+//! Ranking and scoring of entries. Everything here works on plain data so
+//! it can be tested without a DB; see [`crate::results`] for the parts
+//! that load the data and post the results.
+
+use std::collections::HashMap;
+use poise::serenity_prelude::{RoleId, UserId};
+use crate::sql::{self, LeaderboardRow, Submission, SubmissionStatus};
+
+/// Number of places shown in the hall of fame; only these score points.
+pub const HOF_PLACES: usize = 3;
+
+/// A submission and its place in the ranking.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    /// 1-based; submissions with the same score share a place.
+    pub place: usize,
+    pub submission: Submission,
+}
+
+/// Rank the active submissions by their score, i.e. their weighted votes.
+/// Ties share a place, and the place after a tie is skipped, e.g. 1, 2, 2,
+/// 4. Within a place, earlier submissions come first.
+pub fn rank(mut submissions: Vec<Submission>) -> Vec<Placement> {
+    submissions.retain(|s| s.status == SubmissionStatus::Active as i64);
+    submissions.sort_by(|a, b| b.score.cmp(&a.score)
+        .then(a.time.cmp(&b.time))
+        .then(a.message.cmp(&b.message))
+    );
+
+    let mut placements: Vec<Placement> = Vec::with_capacity(submissions.len());
+    for (i, s) in submissions.into_iter().enumerate() {
+        let place = match placements.last() {
+            Some(p) if p.submission.score == s.score => p.place,
+            _ => i + 1,
+        };
+
+        placements.push(Placement { place, submission: s });
+    }
+
+    placements
+}
+
+/// Get the weight of a vote by someone with some roles, in percent, given
+/// the weights of roles. If several of their roles have a weight, the
+/// highest one counts.
+pub fn vote_weight(weights: &[(RoleId, i64)], roles: &[RoleId]) -> i64 {
+    weights.iter()
+        .filter(|(r, _)| roles.contains(r))
+        .map(|&(_, w)| w)
+        .max()
+        .unwrap_or(sql::DEFAULT_VOTE_WEIGHT)
+}
+
+/// Format a weight or score in percent as a number of votes, e.g. 1.5.
+pub fn format_weight(weight: i64) -> String {
+    match weight % 100 {
+        0 => format!("{}", weight / 100),
+        _ => format!("{:.2}", weight as f64 / 100.0).trim_end_matches('0').to_string(),
+    }
+}
+
+/// Points awarded for a place in a week; only the hall of fame places count.
+pub fn points(place: usize) -> i64 {
+    (HOF_PLACES + 1).saturating_sub(place) as i64
+}
+
+/// Get each user’s best place across several rankings.
+pub fn best_placements<'a>(placements: impl IntoIterator<Item = &'a Placement>) -> HashMap<UserId, usize> {
+    let mut best = HashMap::new();
+    for p in placements {
+        if p.submission.author == sql::PURGED_AUTHOR { continue; }
+        let place = best.entry(UserId::new(p.submission.author as u64)).or_insert(p.place);
+        *place = p.place.min(*place);
+    }
+
+    best
+}
+
+/// Add up the points of users across several rankings, each multiplied
+/// by a weight. Returns users and their scores, best first; users with
+/// the same score are ordered by ID so the result is deterministic.
+pub fn combined_scores<'a>(rankings: impl IntoIterator<Item = (i64, &'a [Placement])>) -> Vec<(UserId, i64)> {
+    let mut scores: HashMap<UserId, i64> = HashMap::new();
+    for (weight, placements) in rankings {
+        for p in placements {
+            if p.place > HOF_PLACES { break; }
+            if p.submission.author == sql::PURGED_AUTHOR { continue; }
+            *scores.entry(UserId::new(p.submission.author as u64)).or_default() += weight * points(p.place);
+        }
+    }
+
+    let mut standings = scores.into_iter().collect::<Vec<_>>();
+    standings.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    standings
+}
+
+/// A member’s standing on the leaderboard of a challenge.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub user: UserId,
+    pub points: i64,
+    pub podiums: [i64; HOF_PLACES],
+    pub submissions: i64,
+}
+
+/// Rank members by points for their podium finishes, given the points for
+/// each place, and then by how much they submitted. Returns at most `size`
+/// standings, best first; users tied on both are ordered by ID.
+pub fn leaderboard(rows: &[LeaderboardRow], points: [i64; HOF_PLACES], size: usize) -> Vec<Standing> {
+    let mut standings = rows.iter()
+        .map(|r| {
+            let podiums = [r.first, r.second, r.third];
+            Standing {
+                user: UserId::new(r.user as u64),
+                points: podiums.iter().zip(points).map(|(n, p)| n * p).sum(),
+                podiums,
+                submissions: r.submissions,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    standings.sort_by(|a, b| b.points.cmp(&a.points)
+        .then(b.submissions.cmp(&a.submissions))
+        .then(a.user.cmp(&b.user))
+    );
+
+    standings.truncate(size);
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+
+    /// Generate submissions with distinct message IDs. Some of them are
+    /// by purged authors.
+    fn submissions() -> impl Strategy<Value = Vec<Submission>> {
+        prop::collection::vec((sql::PURGED_AUTHOR..6i64, 0..5i64, 0..100i64, 0..3i64, 0..5i64), 0..20).prop_map(|v| {
+            v.into_iter().enumerate().map(|(i, (author, votes, time, status, extra))| Submission {
+                message: 1000 + i as i64,
+                week: 0,
+                challenge: 0,
+                author,
+                link: String::new(),
+                time,
+                votes,
+                status,
+                spoiler: false,
+                score: votes * 100 + extra * 50,
+            }).collect()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn only_active_entries_are_ranked(subs in submissions()) {
+            let active = subs.iter().filter(|s| s.status == SubmissionStatus::Active as i64).count();
+            let ranking = rank(subs);
+            prop_assert_eq!(ranking.len(), active);
+            prop_assert!(ranking.iter().all(|p| p.submission.status == SubmissionStatus::Active as i64));
+        }
+
+        #[test]
+        fn places_follow_scores(subs in submissions()) {
+            let ranking = rank(subs);
+            for (i, p) in ranking.iter().enumerate() {
+                // Nobody can rank above someone with a higher score.
+                prop_assert!(ranking[..i].iter().all(|q| q.submission.score >= p.submission.score));
+
+                // Equal scores share a place; otherwise the place is one
+                // more than the number of entries ahead.
+                let ahead = ranking.iter().filter(|q| q.submission.score > p.submission.score).count();
+                prop_assert_eq!(p.place, ahead + 1);
+            }
+        }
+
+        #[test]
+        fn ties_are_ordered_by_time(subs in submissions()) {
+            let ranking = rank(subs);
+            for w in ranking.windows(2) {
+                if w[0].place == w[1].place {
+                    prop_assert!(w[0].submission.time <= w[1].submission.time);
+                }
+            }
+        }
+
+        #[test]
+        fn ranking_ignores_input_order(subs in submissions(), seed in any::<u64>()) {
+            let mut shuffled = subs.clone();
+            let len = shuffled.len().max(1);
+            shuffled.rotate_left(seed as usize % len);
+            shuffled.reverse();
+
+            let a = rank(subs).into_iter().map(|p| (p.place, p.submission.message)).collect::<Vec<_>>();
+            let b = rank(shuffled).into_iter().map(|p| (p.place, p.submission.message)).collect::<Vec<_>>();
+            prop_assert_eq!(a, b);
+        }
+
+        #[test]
+        fn best_placement_is_minimum(subs in submissions(), more in submissions()) {
+            let (a, b) = (rank(subs), rank(more));
+            let best = best_placements(a.iter().chain(&b));
+            for p in a.iter().chain(&b).filter(|p| p.submission.author != sql::PURGED_AUTHOR) {
+                let user = UserId::new(p.submission.author as u64);
+                prop_assert!(best[&user] <= p.place);
+            }
+        }
+
+        #[test]
+        fn scores_scale_with_weights(subs in submissions(), more in submissions(), w1 in 0..5i64, w2 in 0..5i64) {
+            let (a, b) = (rank(subs), rank(more));
+            let scores = combined_scores([(w1, a.as_slice()), (w2, b.as_slice())]);
+            let single = |placements: &[Placement], user: UserId| placements.iter()
+                .filter(|p| p.place <= HOF_PLACES && p.submission.author == user.get() as i64)
+                .map(|p| points(p.place))
+                .sum::<i64>();
+
+            for &(user, score) in &scores {
+                prop_assert_eq!(score, w1 * single(&a, user) + w2 * single(&b, user));
+            }
+
+            // Best first.
+            prop_assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+        }
+
+        #[test]
+        fn purged_authors_keep_their_place_but_score_nothing(subs in submissions(), more in submissions()) {
+            // Their entries still take up places...
+            let purged = subs.iter().filter(|s| s.author == sql::PURGED_AUTHOR && s.status == SubmissionStatus::Active as i64).count();
+            let (a, b) = (rank(subs), rank(more));
+            prop_assert_eq!(a.iter().filter(|p| p.submission.author == sql::PURGED_AUTHOR).count(), purged);
+
+            // ...but they don’t show up in any standings.
+            let authors = a.iter().chain(&b)
+                .map(|p| p.submission.author)
+                .filter(|&author| author != sql::PURGED_AUTHOR)
+                .collect::<std::collections::HashSet<_>>();
+            prop_assert_eq!(best_placements(a.iter().chain(&b)).len(), authors.len());
+            let scores = combined_scores([(1, a.as_slice()), (1, b.as_slice())]);
+            prop_assert!(scores.iter().all(|(u, _)| authors.contains(&(u.get() as i64))));
+        }
+
+        #[test]
+        fn zero_weight_ignores_a_challenge(subs in submissions(), more in submissions()) {
+            let (a, b) = (rank(subs), rank(more));
+            let only_a = combined_scores([(1, a.as_slice())]);
+            let with_b = combined_scores([(1, a.as_slice()), (0, b.as_slice())]);
+            let nonzero = with_b.into_iter().filter(|(_, s)| *s != 0).collect::<Vec<_>>();
+            prop_assert_eq!(only_a.into_iter().filter(|(_, s)| *s != 0).collect::<Vec<_>>(), nonzero);
+        }
+    }
+
+    #[test]
+    fn shared_place_skips_the_next() {
+        let sub = |message, votes| Submission {
+            message,
+            week: 0,
+            challenge: 0,
+            author: message,
+            link: String::new(),
+            time: message,
+            votes,
+            status: SubmissionStatus::Active as i64,
+            spoiler: false,
+            score: votes * 100,
+        };
+
+        let places = rank(vec![sub(1, 2), sub(2, 5), sub(3, 2), sub(4, 1)])
+            .into_iter()
+            .map(|p| (p.submission.message, p.place))
+            .collect::<Vec<_>>();
+        assert_eq!(places, vec![(2, 1), (1, 2), (3, 2), (4, 4)]);
+    }
+
+    #[test]
+    fn heaviest_role_decides_the_weight() {
+        let (jury, boosters, other) = (RoleId::new(1), RoleId::new(2), RoleId::new(3));
+        let weights = [(jury, 300), (boosters, 150)];
+        assert_eq!(vote_weight(&weights, &[]), sql::DEFAULT_VOTE_WEIGHT);
+        assert_eq!(vote_weight(&weights, &[other]), sql::DEFAULT_VOTE_WEIGHT);
+        assert_eq!(vote_weight(&weights, &[boosters, other]), 150);
+        assert_eq!(vote_weight(&weights, &[boosters, jury]), 300);
+        assert_eq!(vote_weight(&[], &[jury]), sql::DEFAULT_VOTE_WEIGHT);
+    }
+
+    #[test]
+    fn weights_are_shown_as_votes() {
+        assert_eq!(format_weight(100), "1");
+        assert_eq!(format_weight(300), "3");
+        assert_eq!(format_weight(150), "1.5");
+        assert_eq!(format_weight(1025), "10.25");
+        assert_eq!(format_weight(0), "0");
+    }
+
+    #[test]
+    fn leaderboard_uses_configured_points() {
+        let row = |user, first, second, third, submissions| LeaderboardRow { user, first, second, third, submissions };
+        let rows = [row(1, 1, 0, 0, 3), row(2, 0, 2, 0, 5), row(3, 0, 0, 4, 9), row(4, 0, 2, 0, 2)];
+        let order = |points, size| leaderboard(&rows, points, size).iter().map(|s| (s.user.get(), s.points)).collect::<Vec<_>>();
+
+        // Ties on points go to whoever submitted more.
+        assert_eq!(order([3, 2, 1], 10), vec![(3, 4), (2, 4), (4, 4), (1, 3)]);
+        assert_eq!(order([10, 2, 1], 2), vec![(1, 10), (3, 4)]);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{Error, Res, sql};
+use crate::sql::Challenge;
+
+/// Texts that admins can edit at runtime.
+///
+/// Templates may contain placeholders of the form `{name}`, which are
+/// replaced when the template is rendered.
+#[derive(Copy, Clone, Debug, PartialEq, poise::ChoiceParameter)]
+pub enum Template {
+    #[name = "General rules"]
+    Rules,
+
+    #[name = "Glyph Challenge rules"]
+    GlyphRules,
+
+    #[name = "Ambigram Challenge rules"]
+    AmbigramRules,
+
+    /// Placeholders: `{name}`, `{glyph_channel}`, `{ambigram_channel}`.
+    #[name = "Welcome DM"]
+    Welcome,
+
+    /// Placeholders: `{user}`.
+    #[name = "First submission reply"]
+    FirstSubmission,
+
+    /// Placeholders: `{week}`, `{challenge}`, `{prompt}`, `{entries}`, `{voters}`.
+    #[name = "Weekly recap"]
+    Recap,
+
+    /// Placeholders: `{user}`, `{week}`, `{challenge}`, `{prompt}`.
+    #[name = "Featured artist interview"]
+    FeaturedArtist,
+}
+
+impl Template {
+    /// Key under which this is stored in the DB.
+    fn key(self) -> &'static str {
+        match self {
+            Template::Rules => "rules",
+            Template::GlyphRules => "glyph_rules",
+            Template::AmbigramRules => "ambigram_rules",
+            Template::Welcome => "welcome",
+            Template::FirstSubmission => "first_submission",
+            Template::Recap => "recap",
+            Template::FeaturedArtist => "featured_artist",
+        }
+    }
+
+    /// Text to use if an admin hasn’t set one.
+    fn default_text(self) -> &'static str {
+        match self {
+            Template::Rules => concat!(
+                "- Post your entry as a single image in the submission channel.\n",
+                "- React to your own message with the submit emoji to enter it.\n",
+                "- Remove the reaction to withdraw your entry.",
+            ),
+            Template::GlyphRules => "- Design a glyph for this week’s prompt.",
+            Template::AmbigramRules => "- Design an ambigram for this week’s prompt.",
+            Template::Welcome => concat!(
+                "Welcome, {name}! Every week, we run a Glyph Challenge in {glyph_channel} ",
+                "and an Ambigram Challenge in {ambigram_channel}.\n\n",
+                "To take part, post your entry as an image in the channel and react to it ",
+                "with the submit emoji. Once the week is over, everyone can vote on the entries, ",
+                "and the top three make it into the hall of fame.\n\n",
+                "Use `/rules` on the server for the full rules.",
+            ),
+            Template::FirstSubmission => "Congratulations on your first submission, {user}! 🎉",
+            Template::Recap => concat!(
+                "Thanks to everyone who took part in week {week} of the {challenge} Challenge! ",
+                "The prompt was **{prompt}**, and we got {entries} entries from which {voters} people picked their favourites.",
+            ),
+            Template::FeaturedArtist => concat!(
+                "Congratulations on winning week {week} of the {challenge} Challenge, {user}! ",
+                "We’d love to hear more about your entry:\n\n",
+                "1. How did you come up with your design for **{prompt}**?\n",
+                "2. What was the hardest part to get right?\n",
+                "3. What tools did you use?\n",
+                "4. Is there anything else you’d like to share?",
+            ),
+        }
+    }
+
+    /// The rules template for a challenge.
+    pub fn rules_for(challenge: Challenge) -> Self {
+        match challenge {
+            Challenge::Glyph => Template::GlyphRules,
+            Challenge::Ambigram => Template::AmbigramRules,
+        }
+    }
+}
+
+/// Get the raw text of a template.
+pub async fn get(template: Template) -> Result<String, Error> {
+    Ok(sql::get_template(template.key()).await?.unwrap_or_else(|| template.default_text().to_string()))
+}
+
+/// Change the text of a template.
+pub async fn set(template: Template, text: &str) -> Res {
+    sql::set_template(template.key(), text).await
+}
+
+/// Get a template and fill in its placeholders.
+pub async fn render(template: Template, vars: &[(&str, &str)]) -> Result<String, Error> {
+    let mut text = get(template).await?;
+    for (name, value) in vars {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+
+    Ok(text)
+}
+
+/// Get the full rules for a challenge.
+pub async fn rules(challenge: Challenge) -> Result<String, Error> {
+    Ok(format!("{}\n{}", get(Template::Rules).await?, get(Template::rules_for(challenge)).await?))
+}
+
+/// Append the rules for a challenge to an error message.
+pub async fn with_rules(message: &str, challenge: Challenge) -> String {
+    match rules(challenge).await {
+        Ok(rules) => format!("{}\n\n**Rules**\n{}", message, rules),
+        Err(_) => message.to_string(),
+    }
+}
@@ -0,0 +1,1263 @@
+//! End-to-end tests of the submission and voting flows, run against
+//! [`MockDiscord`] and an in-memory DB.
+
+use std::sync::Arc;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use poise::serenity_prelude::{ChannelId, CreateActionRow, MessageId, ReactionType, RoleId, UserId};
+use tokio::sync::{Mutex, MutexGuard};
+use crate::clock::{self, mock::MockClock};
+use crate::config;
+use crate::discord::mock::MockDiscord;
+use crate::events::{on_reaction_add, on_reaction_remove, vote_reaction};
+use crate::{backfill, ballots, events, polls, prompt_picks, receipts, results, scheduler, tally};
+use crate::scheduler::PromptStatus;
+use crate::sql::{self, Challenge, Setting, SubmissionStatus, WithdrawalReason};
+
+/// The DB is global, so tests must not run at the same time.
+static DB_LOCK: Mutex<()> = Mutex::const_new(());
+
+const ALICE: UserId = UserId::new(101);
+const BOB: UserId = UserId::new(102);
+const CAROL: UserId = UserId::new(103);
+const DAVE: UserId = UserId::new(104);
+
+/// The schema of the last version from before we used migrations.
+const LEGACY_SCHEMA: &str = r#"
+    CREATE TABLE submissions (
+        message INTEGER,
+        week INTEGER NOT NULL,
+        challenge INTEGER NOT NULL,
+        author INTEGER NOT NULL,
+        link TEXT NOT NULL,
+        time INTEGER NOT NULL DEFAULT (unixepoch()),
+        votes INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (message, week, challenge)
+    ) STRICT;
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY,
+        nickname TEXT,
+        glyphs_first INTEGER NOT NULL DEFAULT 0,
+        glyphs_second INTEGER NOT NULL DEFAULT 0,
+        glyphs_third INTEGER NOT NULL DEFAULT 0,
+        ambigrams_first INTEGER NOT NULL DEFAULT 0,
+        ambigrams_second INTEGER NOT NULL DEFAULT 0,
+        ambigrams_third INTEGER NOT NULL DEFAULT 0,
+        highest_ranking_glyphs INTEGER NOT NULL DEFAULT 0,
+        highest_ranking_ambigrams INTEGER NOT NULL DEFAULT 0
+    ) STRICT;
+    CREATE TABLE current_week (week INTEGER NOT NULL) STRICT;
+    CREATE TRIGGER current_week_insertion
+    BEFORE INSERT ON current_week
+    WHEN (SELECT COUNT(*) FROM current_week) > 0
+    BEGIN
+        SELECT RAISE(ABORT, "current_week table must not contain more than one entry!");
+    END;
+    CREATE TABLE weeks (
+        week INTEGER PRIMARY KEY,
+        glyph_challenge_kind INTEGER,
+        ambigram_challenge_kind INTEGER,
+        glyph_prompt TEXT,
+        ambigram_prompt TEXT,
+        glyph_announcement_message INTEGER,
+        ambigram_announcement_message INTEGER,
+        glyph_panel_message INTEGER,
+        ambigram_panel_message INTEGER,
+        glyph_hof_message INTEGER,
+        ambigram_hof_message INTEGER
+    ) STRICT;
+    CREATE TABLE prompts (challenge INTEGER NOT NULL, prompt TEXT NOT NULL) STRICT;
+"#;
+
+/// Get exclusive access to a fresh DB.
+async fn setup() -> (MutexGuard<'static, ()>, MockDiscord) {
+    let guard = DB_LOCK.lock().await;
+    config::init_for_tests();
+    sql::init_test_db().await;
+    set_clock(wednesday());
+    (guard, MockDiscord::new())
+}
+
+/// A Wednesday at noon; with the default settings, the week ends at the
+/// start of the following Sunday.
+fn wednesday() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap()
+}
+
+fn sunday() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 7, 0, 0, 0).unwrap()
+}
+
+/// Replace the clock with one stopped at `time`.
+fn set_clock(time: DateTime<Utc>) -> Arc<MockClock> {
+    let clock = Arc::new(MockClock::new(time));
+    clock::set(clock.clone());
+    clock
+}
+
+fn glyph_channel() -> ChannelId {
+    Challenge::Glyph.submission_channel()
+}
+
+fn submit_emoji() -> ReactionType {
+    ReactionType::Custom { animated: false, id: config::get().submit_emoji, name: Some("submit".into()) }
+}
+
+fn confirm_emoji() -> ReactionType {
+    ReactionType::Unicode("✅".into())
+}
+
+/// Post an image as a user and submit it.
+async fn submit(api: &MockDiscord, user: UserId) -> MessageId {
+    let message = api.post(glyph_channel(), user, vec![api.image("entry.png")]);
+    on_reaction_add(api, api.react(&message, user, submit_emoji(), true)).await;
+    message.id
+}
+
+/// Vote on a submission, or take the vote back.
+async fn vote(api: &MockDiscord, message: MessageId, voter: UserId, added: bool) {
+    let message = api.messages.lock().unwrap()[&message].clone();
+    let r = api.react(&message, voter, vote_reaction().await, added);
+    match added {
+        true => on_reaction_add(api, r).await,
+        false => on_reaction_remove(api, r).await,
+    }
+}
+
+async fn votes_of(message: MessageId) -> i64 {
+    sql::get_submission(message).await.unwrap().unwrap().votes
+}
+
+#[tokio::test]
+async fn submitting_adds_entry_and_confirms() {
+    let (_guard, api) = setup().await;
+    let id = submit(&api, ALICE).await;
+
+    let s = sql::get_submission(id).await.unwrap().expect("submission was not added");
+    assert_eq!(s.author, ALICE.get() as i64);
+    assert_eq!(s.challenge, Challenge::Glyph as i64);
+    assert!(api.has_reaction(id, api.me, &confirm_emoji()));
+
+    // First submission ever, so we celebrate in the channel.
+    let sent = api.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, glyph_channel());
+    assert_eq!(sent[0].1["message_reference"]["message_id"], id.to_string());
+}
+
+#[tokio::test]
+async fn redelivered_reaction_does_not_double_submit() {
+    let (_guard, api) = setup().await;
+    let message = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    let r = api.react(&message, ALICE, submit_emoji(), true);
+    on_reaction_add(&api, r.clone()).await;
+    on_reaction_add(&api, r).await;
+
+    assert_eq!(sql::count_submissions(0, Challenge::Glyph).await.unwrap(), 1);
+    assert_eq!(api.sent.lock().unwrap().len(), 1);
+    assert!(api.dms.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn cleared_reactions_are_not_taken_for_redeliveries() {
+    let (_guard, api) = setup().await;
+    let message = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    let r = api.react(&message, BOB, vote_reaction().await, true);
+    assert!(!events::is_duplicate_reaction(&r, true));
+    assert!(events::is_duplicate_reaction(&r, true));
+
+    events::forget_reactions(message.id, Some(&r.emoji));
+    assert!(!events::is_duplicate_reaction(&r, true));
+    events::forget_reactions(message.id, None);
+    assert!(!events::is_duplicate_reaction(&r, true));
+}
+
+#[tokio::test]
+async fn invalid_submission_is_rejected() {
+    let (_guard, api) = setup().await;
+    let message = api.post(glyph_channel(), ALICE, vec![]);
+    on_reaction_add(&api, api.react(&message, ALICE, submit_emoji(), true)).await;
+
+    assert!(sql::get_submission(message.id).await.unwrap().is_none());
+    assert!(!api.has_reaction(message.id, ALICE, &submit_emoji()));
+
+    // The author is told why.
+    let dms = api.dms.lock().unwrap();
+    assert_eq!(dms.len(), 1);
+    assert_eq!(dms[0].0, ALICE);
+}
+
+#[tokio::test]
+async fn submissions_must_meet_the_constraints() {
+    let (_guard, api) = setup().await;
+    let mut constraints = Challenge::Glyph.default_constraints();
+    constraints.min_size = 200;
+    constraints.square = true;
+    constraints.animation = false;
+    sql::set_constraints(Challenge::Glyph, Some(&constraints)).await.unwrap();
+
+    for att in [api.image_sized("entry.png", 100, 100), api.image_sized("entry.png", 300, 200), api.image("entry.gif")] {
+        let message = api.post(glyph_channel(), ALICE, vec![att]);
+        on_reaction_add(&api, api.react(&message, ALICE, submit_emoji(), true)).await;
+        assert!(sql::get_submission(message.id).await.unwrap().is_none());
+    }
+
+    let dms = api.dms.lock().unwrap();
+    assert_eq!(dms.len(), 3);
+    assert!(dms[0].1["content"].as_str().unwrap().contains("at least 200×200 pixels, but yours is 100×100"));
+    assert!(dms[1].1["content"].as_str().unwrap().contains("must be square"));
+    assert!(dms[2].1["content"].as_str().unwrap().contains("Animated submissions"));
+}
+
+#[tokio::test]
+async fn linked_submissions_respect_the_host_lists() {
+    let (_guard, api) = setup().await;
+    let submit_link = |link: &str| {
+        let mut message = api.post(glyph_channel(), ALICE, vec![]);
+        message.content = format!("My entry: {}", link);
+        api.messages.lock().unwrap().insert(message.id, message.clone());
+        on_reaction_add(&api, api.react(&message, ALICE, submit_emoji(), true))
+    };
+
+    // Links are only accepted if enabled.
+    submit_link("https://i.imgur.com/entry.png").await;
+    sql::set_setting(Setting::LinkSubmissions, "true").await.unwrap();
+
+    // Nothing is accepted until a host is allowed.
+    submit_link("https://i.imgur.com/entry.png").await;
+    sql::set_link_host("imgur.com", true).await.unwrap();
+    sql::set_link_host("evil.imgur.com", false).await.unwrap();
+    submit_link("https://evil.imgur.com/entry.png").await;
+    submit_link("https://example.com/entry.png").await;
+    submit_link("http://i.imgur.com/entry.png").await;
+    assert!(sql::get_submissions(0, Challenge::Glyph).await.unwrap().is_empty());
+
+    submit_link("https://i.imgur.com/entry.png").await;
+    let entries = sql::get_submissions(0, Challenge::Glyph).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].link, "https://i.imgur.com/entry.png");
+
+    let dms = api.dms.lock().unwrap();
+    assert!(dms[0].1["content"].as_str().unwrap().contains("not linked"));
+    assert!(dms[1].1["content"].as_str().unwrap().contains("No hosts are allowed"));
+    assert!(dms[2].1["content"].as_str().unwrap().contains("Links to evil.imgur.com are not allowed"));
+    assert!(dms[3].1["content"].as_str().unwrap().contains("must be hosted on one of: imgur.com"));
+    assert!(dms[4].1["content"].as_str().unwrap().contains("must use https"));
+}
+
+#[tokio::test]
+async fn cannot_submit_someone_elses_message() {
+    let (_guard, api) = setup().await;
+    let message = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    on_reaction_add(&api, api.react(&message, BOB, submit_emoji(), true)).await;
+
+    assert!(sql::get_submission(message.id).await.unwrap().is_none());
+    assert!(!api.has_reaction(message.id, BOB, &submit_emoji()));
+}
+
+#[tokio::test]
+async fn withdrawing_removes_entry_and_our_reactions() {
+    let (_guard, api) = setup().await;
+    sql::set_setting(Setting::SeedGlyphReactions, "true").await.unwrap();
+    let id = submit(&api, ALICE).await;
+    assert!(api.has_reaction(id, api.me, &vote_reaction().await));
+
+    let message = api.messages.lock().unwrap()[&id].clone();
+    on_reaction_remove(&api, api.react(&message, ALICE, submit_emoji(), false)).await;
+
+    assert!(sql::get_submission(id).await.unwrap().is_none());
+    assert!(!api.has_reaction(id, api.me, &confirm_emoji()));
+    assert!(!api.has_reaction(id, api.me, &vote_reaction().await));
+}
+
+#[tokio::test]
+async fn votes_are_counted_once_and_not_for_yourself() {
+    let (_guard, api) = setup().await;
+    let id = submit(&api, ALICE).await;
+
+    vote(&api, id, BOB, true).await;
+    vote(&api, id, BOB, true).await;
+    vote(&api, id, ALICE, true).await;
+    vote(&api, id, api.me, true).await;
+    assert_eq!(votes_of(id).await, 1);
+
+    vote(&api, id, CAROL, true).await;
+    assert_eq!(votes_of(id).await, 2);
+
+    vote(&api, id, BOB, false).await;
+    assert_eq!(votes_of(id).await, 1);
+}
+
+#[tokio::test]
+async fn votes_stop_counting_once_voting_closes() {
+    let (_guard, api) = setup().await;
+    let clock = set_clock(wednesday());
+    let id = submit(&api, ALICE).await;
+    vote(&api, id, BOB, true).await;
+
+    sql::set_week_deadline(1, Some((sunday() + Duration::days(7)).timestamp())).await.unwrap();
+    clock.set(sunday() + Duration::days(7));
+    vote(&api, id, CAROL, true).await;
+    vote(&api, id, BOB, false).await;
+    assert_eq!(votes_of(id).await, 1);
+}
+
+#[tokio::test]
+async fn cleared_reactions_only_count_while_voting_is_open() {
+    let (_guard, api) = setup().await;
+    let clock = set_clock(wednesday());
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+    let dave = submit(&api, DAVE).await;
+    vote(&api, alice, CAROL, true).await;
+    vote(&api, bob, CAROL, true).await;
+    vote(&api, bob, DAVE, true).await;
+
+    // Clearing the vote emoji drops the votes.
+    let message = api.messages.lock().unwrap()[&alice].clone();
+    events::on_reaction_emoji_cleared(&api, api.react(&message, CAROL, vote_reaction().await, false)).await;
+    assert_eq!(votes_of(alice).await, 0);
+
+    // Clearing everything also withdraws the entry.
+    api.reactions.lock().unwrap().retain(|(m, _, _)| *m != dave);
+    events::on_reactions_cleared(&api, glyph_channel(), dave).await;
+    assert!(sql::get_submission(dave).await.unwrap().is_none());
+    let withdrawals = sql::get_withdrawals(0).await.unwrap();
+    assert_eq!((withdrawals.len(), withdrawals[0].reason), (1, WithdrawalReason::ReactionsCleared as i64));
+
+    // Once voting has closed, the votes and the entry stay.
+    sql::set_week_deadline(0, Some(sunday().timestamp())).await.unwrap();
+    sql::set_week_deadline(1, Some((sunday() + Duration::days(7)).timestamp())).await.unwrap();
+    clock.set(sunday() + Duration::days(7));
+    api.reactions.lock().unwrap().retain(|(m, _, _)| *m != bob);
+    events::on_reactions_cleared(&api, glyph_channel(), bob).await;
+    assert_eq!(votes_of(bob).await, 2);
+    assert!(api.has_reaction(bob, api.me, &confirm_emoji()));
+}
+
+#[tokio::test]
+async fn vote_emoji_can_be_changed() {
+    let (_guard, api) = setup().await;
+    let id = submit(&api, ALICE).await;
+    sql::set_vote_emoji(Some("<:upvote:555>")).await.unwrap();
+
+    // The old emoji no longer counts.
+    let message = api.messages.lock().unwrap()[&id].clone();
+    on_reaction_add(&api, api.react(&message, BOB, ReactionType::Unicode("👍".into()), true)).await;
+    assert_eq!(votes_of(id).await, 0);
+
+    // The new one does, even if it was renamed since.
+    let renamed = ReactionType::Custom { animated: false, id: 555.into(), name: Some("vote".into()) };
+    on_reaction_add(&api, api.react(&message, BOB, renamed, true)).await;
+    assert_eq!(votes_of(id).await, 1);
+}
+
+#[tokio::test]
+async fn votes_decide_the_ranking() {
+    let (_guard, api) = setup().await;
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+    let carol = submit(&api, CAROL).await;
+
+    vote(&api, bob, ALICE, true).await;
+    vote(&api, bob, CAROL, true).await;
+    vote(&api, alice, BOB, true).await;
+    vote(&api, carol, BOB, true).await;
+
+    let ranking = results::ranking(0, Challenge::Glyph).await.unwrap();
+    let places = ranking.iter()
+        .map(|p| (p.place, MessageId::new(p.submission.message as u64)))
+        .collect::<Vec<_>>();
+
+    // Ties share a place and are ordered by submission time.
+    assert_eq!(places, vec![(1, bob), (2, alice), (2, carol)]);
+
+    let turnout = sql::get_turnout(0, Challenge::Glyph).await.unwrap();
+    assert_eq!(turnout.voters, 3);
+    assert_eq!(turnout.ballots, 4);
+}
+
+#[tokio::test]
+async fn deadlines_follow_the_clock() {
+    let (_guard, _api) = setup().await;
+    assert_eq!(scheduler::deadline(0).await.unwrap(), sunday());
+    assert_eq!(scheduler::deadline(2).await.unwrap(), sunday() + Duration::days(14));
+    assert_eq!(scheduler::voting_deadline(0).await.unwrap(), sunday() + Duration::days(7));
+
+    // Once the deadline has passed, the next one is a week later.
+    set_clock(sunday());
+    assert_eq!(scheduler::deadline(0).await.unwrap(), sunday() + Duration::days(7));
+
+    // Overrides take precedence.
+    sql::set_week_deadline(0, Some(wednesday().timestamp() + 60)).await.unwrap();
+    assert_eq!(scheduler::deadline(0).await.unwrap(), wednesday() + Duration::minutes(1));
+}
+
+#[tokio::test]
+async fn entries_cannot_be_withdrawn_after_the_cutoff() {
+    let (_guard, api) = setup().await;
+    let clock = set_clock(wednesday());
+    let id = submit(&api, ALICE).await;
+
+    // Pin the deadline; otherwise, it moves on to next Sunday once it has
+    // passed, since nothing here ends the week.
+    sql::set_week_deadline(0, Some(sunday().timestamp())).await.unwrap();
+    clock.set(sunday() + Duration::seconds(1));
+
+    let message = api.messages.lock().unwrap()[&id].clone();
+    on_reaction_remove(&api, api.react(&message, ALICE, submit_emoji(), false)).await;
+    assert!(sql::get_submission(id).await.unwrap().is_some());
+    assert_eq!(api.dms.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn reminders_go_out_shortly_before_a_deadline() {
+    let (_guard, _api) = setup().await;
+    let clock = set_clock(wednesday());
+    let hours = Duration::hours(sql::get_setting::<i64>(Setting::VoteReminderHours).await.unwrap());
+    let close = scheduler::deadline(0).await.unwrap();
+    let in_window = || scheduler::in_window_before(clock::now(), close, hours);
+
+    assert!(!in_window());
+    clock.set(close - hours - Duration::seconds(1));
+    assert!(!in_window());
+    clock.set(close - hours);
+    assert!(in_window());
+    clock.advance(hours - Duration::seconds(1));
+    assert!(in_window());
+    clock.advance(Duration::seconds(1));
+    assert!(!in_window());
+}
+
+#[tokio::test]
+async fn missed_tasks_catch_up() {
+    let (_guard, _api) = setup().await;
+    let clock = set_clock(wednesday());
+    let task = scheduler::find_task("backup").unwrap();
+    let due = || async { scheduler::is_due(task, &sql::get_task_state(task.name).await.unwrap(), clock::now().timestamp()) };
+
+    // Never ran, so it’s due right away.
+    assert!(due().await);
+
+    sql::set_task_last_run(task.name, clock::now().timestamp()).await.unwrap();
+    assert!(!due().await);
+
+    // Waking up slightly early still counts.
+    clock.advance(Duration::hours(24) - Duration::minutes(1));
+    assert!(due().await);
+
+    // As does being down for a long time.
+    clock.advance(Duration::days(30));
+    assert!(due().await);
+
+    sql::set_task_disabled(task.name, true).await.unwrap();
+    assert!(!due().await);
+}
+
+#[tokio::test]
+async fn breaks_end_on_time() {
+    let (_guard, _api) = setup().await;
+    let clock = set_clock(wednesday());
+    sql::set_paused_until(Some((wednesday() + Duration::days(2)).timestamp())).await.unwrap();
+    assert!(scheduler::is_paused().await.unwrap());
+
+    clock.advance(Duration::days(2) - Duration::seconds(1));
+    assert!(scheduler::is_paused().await.unwrap());
+    clock.advance(Duration::seconds(1));
+    assert!(!scheduler::is_paused().await.unwrap());
+}
+
+#[tokio::test]
+async fn calendar_assigns_queued_prompts_in_order() {
+    let (_guard, _api) = setup().await;
+    let first = sql::add_prompt(Challenge::Glyph, "A", None, None).await.unwrap();
+    let second = sql::add_prompt(Challenge::Glyph, "B", None, None).await.unwrap();
+    sql::set_week_deadline(1, Some((sunday() + Duration::days(9)).timestamp())).await.unwrap();
+
+    let weeks = scheduler::calendar(3).await.unwrap();
+    assert_eq!(weeks.iter().map(|w| w.week).collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(weeks[0].deadline, sunday());
+    assert_eq!(weeks[1].start, sunday());
+    assert_eq!(weeks[1].deadline_moved, Some(Duration::days(2)));
+    assert_eq!(weeks[2].start, sunday() + Duration::days(9));
+
+    let glyph = weeks.iter().map(|w| w.prompts[0].1).collect::<Vec<_>>();
+    assert_eq!(glyph, [PromptStatus::Queued(first), PromptStatus::Queued(second), PromptStatus::Missing]);
+    assert!(weeks.iter().all(|w| w.prompts[1].1 == PromptStatus::Missing));
+}
+
+#[tokio::test]
+async fn hall_of_fame_is_due_once_voting_closes() {
+    let (_guard, _api) = setup().await;
+    sql::set_current_week(2).await.unwrap();
+    for week in [0, 1] {
+        sql::seed_submission(MessageId::new(500 + week as u64), week, Challenge::Glyph, ALICE, "https://cdn.example/x.png", 0).await.unwrap();
+    }
+
+    // Voting on week 1 runs until the end of week 2.
+    assert_eq!(results::due_hofs().await.unwrap(), [(0, Challenge::Glyph)]);
+
+    sql::set_week_message(0, Challenge::Glyph, sql::WeekMessage::HallOfFame, MessageId::new(900)).await.unwrap();
+    assert!(results::due_hofs().await.unwrap().is_empty());
+
+    // Otherwise, week 2 only ends once the week rolls over.
+    sql::set_week_deadline(2, Some(sunday().timestamp())).await.unwrap();
+    set_clock(sunday());
+    assert_eq!(results::due_hofs().await.unwrap(), [(1, Challenge::Glyph)]);
+}
+
+#[tokio::test]
+async fn week_starts_map_times_to_weeks() {
+    let (_guard, _api) = setup().await;
+
+    // Without a recorded start, a week starts when the previous one ends.
+    assert_eq!(scheduler::week_start(1).await.unwrap(), sunday());
+
+    sql::set_week_start(0, (sunday() - Duration::days(7)).timestamp()).await.unwrap();
+    sql::set_week_start(1, (sunday() + Duration::hours(2)).timestamp()).await.unwrap();
+    assert_eq!(scheduler::week_start(1).await.unwrap(), sunday() + Duration::hours(2));
+
+    assert_eq!(sql::week_at((sunday() - Duration::days(8)).timestamp()).await.unwrap(), None);
+    assert_eq!(sql::week_at(wednesday().timestamp()).await.unwrap(), Some(0));
+    assert_eq!(sql::week_at((sunday() + Duration::hours(1)).timestamp()).await.unwrap(), Some(0));
+    assert_eq!(sql::week_at((sunday() + Duration::hours(2)).timestamp()).await.unwrap(), Some(1));
+}
+
+#[tokio::test]
+async fn results_update_profile_stats() {
+    let (_guard, api) = setup().await;
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+    vote(&api, bob, ALICE, true).await;
+
+    let record = |ranking: Vec<tally::Placement>| async move {
+        let placements = ranking.into_iter().map(|p| (p.submission, p.place)).collect::<Vec<_>>();
+        sql::record_week_results(0, Challenge::Glyph, &placements).await.unwrap();
+    };
+
+    // Recording the same results twice must not count them twice.
+    let ranking = results::ranking(0, Challenge::Glyph).await.unwrap();
+    record(ranking.clone()).await;
+    record(ranking.clone()).await;
+    let profile = sql::get_user_profile(BOB).await.unwrap();
+    assert_eq!((profile.glyphs_first, profile.highest_ranking_glyphs), (1, 1));
+    let profile = sql::get_user_profile(ALICE).await.unwrap();
+    assert_eq!((profile.glyphs_first, profile.glyphs_second, profile.highest_ranking_glyphs), (0, 1, 2));
+
+    // A correction moves the placements over.
+    let mut corrected = ranking;
+    for p in &mut corrected { p.place = if p.submission.message == alice.get() as i64 { 1 } else { 2 }; }
+    record(corrected).await;
+    let profile = sql::get_user_profile(ALICE).await.unwrap();
+    assert_eq!((profile.glyphs_first, profile.glyphs_second, profile.highest_ranking_glyphs), (1, 0, 1));
+    let profile = sql::get_user_profile(BOB).await.unwrap();
+    assert_eq!((profile.glyphs_first, profile.glyphs_second, profile.ambigrams_first), (0, 1, 0));
+
+    // The leaderboard reads the same counts.
+    let rows = sql::get_leaderboard_rows(Challenge::Glyph).await.unwrap();
+    let standings = tally::leaderboard(&rows, [3, 2, 1], 10);
+    assert_eq!(standings.iter().map(|s| (s.user, s.points, s.submissions)).collect::<Vec<_>>(), vec![(ALICE, 3, 1), (BOB, 2, 1)]);
+    assert!(sql::get_leaderboard_rows(Challenge::Ambigram).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn migrating_the_vote_emoji_recounts_missed_votes() {
+    let (_guard, api) = setup().await;
+    let id = submit(&api, ALICE).await;
+    let before = votes_of(id).await;
+    let thumbs = ReactionType::Unicode("👍".into());
+
+    // Bob voted while we weren’t listening, now that voting on the entry
+    // has opened.
+    let message = api.messages.lock().unwrap()[&id].clone();
+    api.react(&message, BOB, thumbs.clone(), true);
+    sql::set_current_week(1).await.unwrap();
+
+    let wrong = ReactionType::Unicode("🎉".into());
+    assert!(events::migrate_vote_emoji(&api, &wrong, "<:upvote:555>", true).await.is_err());
+
+    let added = events::migrate_vote_emoji(&api, &thumbs, "<:upvote:555>", true).await.unwrap();
+    assert_eq!(added, 1);
+    assert_eq!(votes_of(id).await, before + 1);
+    assert!(matches!(vote_reaction().await, ReactionType::Custom { id, .. } if id == 555));
+
+    // Both submission channels were told.
+    assert_eq!(api.sent.lock().unwrap().iter().filter(|(_, m)| m["content"].as_str().unwrap_or_default().contains("<:upvote:555>")).count(), 2);
+}
+
+#[tokio::test]
+async fn backfill_records_votes_from_reactions() {
+    let (_guard, api) = setup().await;
+    sql::set_current_week(3).await.unwrap();
+    let entry = MessageId::new(500);
+    sql::seed_submission(entry, 1, Challenge::Glyph, ALICE, "https://cdn.example/x.png", 0).await.unwrap();
+
+    // Reactions from before votes were recorded; ours and the author’s don’t count.
+    let thumbs = ReactionType::Unicode("👍".into());
+    for user in [api.me, ALICE, BOB, CAROL] {
+        api.reactions.lock().unwrap().insert((entry, user, thumbs.to_string()));
+    }
+
+    assert_eq!(backfill::parse_weeks("0..2"), Ok(0..=2));
+    assert!(backfill::parse_weeks("2..1").is_err());
+    assert_eq!(backfill::backfill_votes(&api, 0..=2, &thumbs).await.unwrap(), 2);
+    assert_eq!(votes_of(entry).await, 2);
+
+    // Running it again adds nothing.
+    assert_eq!(backfill::backfill_votes(&api, 0..=2, &thumbs).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn databases_from_before_migrations_are_upgraded() {
+    let _guard = DB_LOCK.lock().await;
+    config::init_for_tests();
+    sql::init_test_db_from(&format!(r#"{LEGACY_SCHEMA}
+        INSERT INTO current_week (week) VALUES (4);
+        INSERT INTO users (id, nickname, glyphs_first) VALUES (101, 'alice', 1);
+        INSERT INTO prompts (challenge, prompt) VALUES (0, 'Ж');
+        INSERT INTO submissions (message, week, challenge, author, link) VALUES (500, 4, 0, 101, 'https://cdn.example/x.png');
+        INSERT INTO submissions (message, week, challenge, author, link, votes) VALUES (501, 2, 0, 101, 'https://cdn.example/y.png', 3);
+    "#)).await;
+    set_clock(wednesday());
+
+    // Old data is kept, and new columns and tables are usable.
+    assert_eq!(sql::current_week().await.unwrap(), 4);
+    let entry = sql::get_submission(MessageId::new(500)).await.unwrap().unwrap();
+    assert_eq!((entry.votes, entry.spoiler), (0, false));
+    let alice = sql::get_user_row(ALICE).await.unwrap().unwrap();
+    assert_eq!((alice.nickname.as_deref(), alice.glyphs_first, alice.bio), (Some("alice"), 1, None));
+    assert_eq!(sql::get_prompts(Challenge::Glyph).await.unwrap().len(), 1);
+    assert_eq!(sql::get_turnout(4, Challenge::Glyph).await.unwrap().ballots, 0);
+    sql::set_link_host("imgur.com", true).await.unwrap();
+
+    // Weeks from before individual votes were recorded are left alone.
+    assert_eq!(sql::recount_all_votes(None).await.unwrap(), vec![]);
+    assert_eq!(votes_of(MessageId::new(501)).await, 3);
+}
+
+#[tokio::test]
+async fn old_duplicate_entries_are_replaced_unless_ranked() {
+    let _guard = DB_LOCK.lock().await;
+    config::init_for_tests();
+    sql::init_test_db_from(&format!(r#"{LEGACY_SCHEMA}
+        INSERT INTO current_week (week) VALUES (4);
+        INSERT INTO weeks (week, glyph_hof_message) VALUES (2, 900);
+        INSERT INTO submissions (message, week, challenge, author, link, time, votes) VALUES (500, 2, 0, 101, 'https://cdn.example/a.png', 10, 2);
+        INSERT INTO submissions (message, week, challenge, author, link, time, votes) VALUES (501, 2, 0, 101, 'https://cdn.example/b.png', 20, 1);
+        INSERT INTO submissions (message, week, challenge, author, link, time) VALUES (600, 4, 0, 101, 'https://cdn.example/c.png', 30);
+        INSERT INTO submissions (message, week, challenge, author, link, time) VALUES (601, 4, 0, 101, 'https://cdn.example/d.png', 40);
+    "#)).await;
+    set_clock(wednesday());
+
+    // Both entries of a published week stay as they were ranked.
+    let active = SubmissionStatus::Active as i64;
+    for message in [500, 501] {
+        assert_eq!(sql::get_submission(MessageId::new(message)).await.unwrap().unwrap().status, active);
+    }
+    assert!(sql::get_withdrawals(2).await.unwrap().is_empty());
+
+    // Otherwise, the earlier one is gone, like a replaced entry.
+    assert!(sql::get_submission(MessageId::new(600)).await.unwrap().is_none());
+    assert_eq!(sql::get_submission(MessageId::new(601)).await.unwrap().unwrap().status, active);
+    let withdrawals = sql::get_withdrawals(4).await.unwrap();
+    assert_eq!(withdrawals.len(), 1);
+    assert_eq!((withdrawals[0].message, withdrawals[0].reason), (600, WithdrawalReason::Replaced as i64));
+}
+
+#[tokio::test]
+async fn weighted_votes_decide_the_ranking() {
+    let (_guard, api) = setup().await;
+    let jury = RoleId::new(900);
+    sql::set_vote_weight(jury, 300).await.unwrap();
+    api.roles.lock().unwrap().insert(CAROL, vec![jury]);
+
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+
+    // Two ordinary votes lose to one from the jury.
+    vote(&api, alice, BOB, true).await;
+    vote(&api, alice, DAVE, true).await;
+    vote(&api, bob, CAROL, true).await;
+
+    let ranking = results::ranking(0, Challenge::Glyph).await.unwrap();
+    let places = ranking.iter()
+        .map(|p| (p.place, MessageId::new(p.submission.message as u64), p.submission.votes, p.submission.score))
+        .collect::<Vec<_>>();
+    assert_eq!(places, vec![(1, bob, 1, 300), (2, alice, 2, 200)]);
+
+    // Ballots keep the weight they were cast with.
+    sql::delete_vote_weight(jury).await.unwrap();
+    let ballots = sql::get_ballots(0, Challenge::Glyph).await.unwrap();
+    let carol = ballots.iter().find(|b| b.voter == CAROL.get() as i64).unwrap();
+    assert_eq!((carol.message, carol.weight), (bob.get() as i64, 300));
+    assert_eq!(ballots.iter().filter(|b| b.weight == sql::DEFAULT_VOTE_WEIGHT).count(), 2);
+
+    // Taking the vote back removes its weight.
+    vote(&api, bob, CAROL, false).await;
+    assert_eq!(sql::get_submission(bob).await.unwrap().unwrap().score, 0);
+}
+
+#[tokio::test]
+async fn resync_catches_up_on_missed_submissions() {
+    let (_guard, api) = setup().await;
+    let bob = submit(&api, BOB).await;
+
+    // Dave used the command, so his entry never had a submit emoji.
+    let dave = api.post(glyph_channel(), DAVE, vec![api.image("entry.png")]);
+    let image = events::check_submission(&dave, Challenge::Glyph).await.unwrap();
+    events::accept_submission(&api, &dave, Challenge::Glyph, &image, true).await.unwrap();
+
+    // While we were offline, Alice submitted, Bob withdrew, and Carol
+    // submitted something that isn’t an image.
+    let alice = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    api.react(&alice, ALICE, submit_emoji(), true);
+    let message = api.messages.lock().unwrap()[&bob].clone();
+    api.react(&message, BOB, submit_emoji(), false);
+    let carol = api.post(glyph_channel(), CAROL, vec![]);
+    api.react(&carol, CAROL, submit_emoji(), true);
+
+    let report = events::resync_submissions(&api).await.unwrap();
+    assert_eq!(report.added, vec![(glyph_channel(), alice.id)]);
+    assert_eq!(report.removed, vec![(glyph_channel(), bob)]);
+    assert_eq!(report.rejected, vec![(glyph_channel(), carol.id)]);
+    assert!(report.kept.is_empty());
+
+    let entries = sql::get_submissions(0, Challenge::Glyph).await.unwrap();
+    assert_eq!(entries.iter().map(|s| s.message).collect::<Vec<_>>(), vec![dave.id.get() as i64, alice.id.get() as i64]);
+    let withdrawals = sql::get_withdrawals(0).await.unwrap();
+    assert_eq!(withdrawals.iter().map(|w| WithdrawalReason::try_from(w.reason).unwrap()).collect::<Vec<_>>(), vec![WithdrawalReason::Resync]);
+    assert!(!api.has_reaction(carol.id, CAROL, &submit_emoji()));
+    assert!(api.dms.lock().unwrap().iter().any(|(u, _)| *u == CAROL));
+
+    // Nothing is left to do the second time.
+    assert!(events::resync_submissions(&api).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn ballots_are_private_and_single_use() {
+    let (_guard, api) = setup().await;
+    sql::set_setting(Setting::BallotVoting, "true").await.unwrap();
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+
+    // Voting on week 0 opens once week 1 starts.
+    sql::set_current_week(1).await.unwrap();
+    assert_eq!(ballots::send_ballots(&api).await.unwrap(), 2);
+    assert_eq!(api.dms.lock().unwrap().iter().filter(|(u, _)| *u == ALICE || *u == BOB).count(), 2);
+    assert_eq!(ballots::send_ballots(&api).await.unwrap(), 0);
+
+    // Reactions don’t count.
+    vote(&api, alice, CAROL, true).await;
+    assert_eq!(votes_of(alice).await, 0);
+
+    let (ballot, new) = sql::issue_ballot(BOB, 0, Challenge::Glyph).await.unwrap();
+    assert!(!new);
+    assert!(ballots::choose(ballot.id, BOB, 0, &[bob.to_string()]).await.is_err());
+    assert!(ballots::choose(ballot.id, CAROL, 0, &[alice.to_string()]).await.is_err());
+    ballots::choose(ballot.id, BOB, 0, &[alice.to_string()]).await.unwrap();
+
+    // Nothing counts until the ballot is cast, and it can only be cast once.
+    assert_eq!(votes_of(alice).await, 0);
+    assert_eq!(ballots::cast(&api, ballot.id, BOB).await.unwrap(), 1);
+    assert_eq!(votes_of(alice).await, 1);
+    assert!(ballots::cast(&api, ballot.id, BOB).await.is_err());
+    assert!(ballots::choose(ballot.id, BOB, 0, &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn large_ballots_are_split_into_pages() {
+    let (_guard, api) = setup().await;
+    let entries = (0..120).map(|i| MessageId::new(10_000 + i)).collect::<Vec<_>>();
+    for (i, &m) in entries.iter().enumerate() {
+        sql::add_submission(m, Challenge::Glyph, UserId::new(2_000 + i as u64), "https://cdn.example/x.png", false, false).await.unwrap();
+    }
+
+    sql::set_current_week(1).await.unwrap();
+    let (ballot, _) = sql::issue_ballot(ALICE, 0, Challenge::Glyph).await.unwrap();
+    let (text, rows) = ballots::render(&ballot, 0).await.unwrap();
+    assert!(text.contains("120 entries"));
+    assert_eq!(rows.len(), 5);
+
+    // Entries past the first 100 are on the next page, and picks on both count.
+    ballots::choose(ballot.id, ALICE, 0, &[entries[0].to_string()]).await.unwrap();
+    ballots::choose(ballot.id, ALICE, 4, &[entries[110].to_string()]).await.unwrap();
+    let (text, rows) = ballots::show_page(ballot.id, ALICE, 1).await.unwrap();
+    assert!(text.contains("page 2"));
+    assert_eq!(rows.len(), 2);
+    assert_eq!(ballots::cast(&api, ballot.id, ALICE).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn withdrawals_are_logged() {
+    let (_guard, api) = setup().await;
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+
+    let message = api.messages.lock().unwrap()[&alice].clone();
+    on_reaction_remove(&api, api.react(&message, ALICE, submit_emoji(), false)).await;
+    assert_eq!(sql::withdraw_user_submissions(BOB).await.unwrap(), vec![bob]);
+
+    let log = sql::get_withdrawals(0).await.unwrap();
+    let entries = log.iter()
+        .map(|w| (MessageId::new(w.message as u64), UserId::new(w.author as u64), WithdrawalReason::try_from(w.reason).unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![(alice, ALICE, WithdrawalReason::Author), (bob, BOB, WithdrawalReason::LeftServer)]);
+    assert!(log.iter().all(|w| w.week == 0 && w.withdrawn >= w.submitted));
+    assert!(sql::get_withdrawals(1).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn purged_authors_are_anonymised() {
+    let (_guard, api) = setup().await;
+    let first = submit(&api, ALICE).await;
+    let message = api.messages.lock().unwrap()[&first].clone();
+    on_reaction_remove(&api, api.react(&message, ALICE, submit_emoji(), false)).await;
+    let second = submit(&api, ALICE).await;
+    vote(&api, second, BOB, true).await;
+
+    let mut purged = sql::purge_user(ALICE).await.unwrap();
+    purged.sort();
+    assert_eq!(purged, vec![first, second]);
+
+    let s = sql::get_submission(second).await.unwrap().unwrap();
+    assert_eq!(s.author, sql::PURGED_AUTHOR);
+    assert_eq!(s.votes, 1);
+    assert_eq!(sql::get_author_name(s.author).await.unwrap(), None);
+    assert!(sql::get_withdrawals(0).await.unwrap().iter().all(|w| w.author == sql::PURGED_AUTHOR));
+    assert!(sql::get_stale_active_users(scheduler::ACTIVE_WEEKS, 0).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn announcements_open_a_discussion_thread() {
+    let (_guard, api) = setup().await;
+    let announcement = api.post(ChannelId::new(50), ALICE, vec![]);
+
+    // Threads are only opened if enabled.
+    let reply = crate::commands::record_announcement(&api, Challenge::Glyph, Some(announcement.channel_id), announcement.id, None).await.unwrap();
+    assert!(!reply.contains("opened"));
+    assert!(api.threads.lock().unwrap().is_empty());
+
+    sql::set_setting(Setting::DiscussionThreads, "true").await.unwrap();
+    let reply = crate::commands::record_announcement(&api, Challenge::Glyph, Some(announcement.channel_id), announcement.id, None).await.unwrap();
+    let (thread, message, name) = api.threads.lock().unwrap()[0].clone();
+    assert_eq!((message, name.as_str()), (announcement.id, "Week 0 discussion – Glyph Challenge"));
+    assert!(reply.ends_with(&format!("and opened <#{}>", thread)));
+
+    // Only one thread is opened, and the panel points to it.
+    crate::commands::record_announcement(&api, Challenge::Glyph, Some(announcement.channel_id), announcement.id, None).await.unwrap();
+    assert_eq!(api.threads.lock().unwrap().len(), 1);
+    assert!(crate::panel::panel_text(0, Challenge::Glyph).await.unwrap().contains(&format!("<#{}>", thread)));
+    assert!(!crate::panel::panel_text(0, Challenge::Ambigram).await.unwrap().contains("Discuss"));
+}
+
+#[tokio::test]
+async fn series_run_in_consecutive_weeks() {
+    let (_guard, _api) = setup().await;
+    let upper = sql::add_prompt(Challenge::Glyph, "A", None, None).await.unwrap();
+    let other = sql::add_prompt(Challenge::Glyph, "B", None, None).await.unwrap();
+    let lower = sql::add_prompt(Challenge::Glyph, "a", None, None).await.unwrap();
+    let series = sql::create_series(Challenge::Glyph, "Cases", &[upper, lower]).await.unwrap();
+    assert!(sql::create_series(Challenge::Glyph, "Again", &[lower, other]).await.is_err());
+
+    // The parts are queued back to back.
+    let queue = sql::get_prompts(Challenge::Glyph).await.unwrap().into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    assert_eq!(queue, [upper, lower, other]);
+
+    // Announcements say which part is which.
+    let upper = sql::get_prompt(upper).await.unwrap();
+    let lower = sql::get_prompt(lower).await.unwrap();
+    assert!(crate::commands::prompt_announcement_text(&lower).await.unwrap().contains("**Part 2 of 2** of *Cases*"));
+
+    // The second part has to run right after the first, and each part is
+    // taken off the queue once it runs.
+    assert!(sql::take_prompt(0, &lower).await.is_err());
+    sql::take_prompt(0, &upper).await.unwrap();
+    assert!(sql::take_prompt(2, &lower).await.is_err());
+    assert!(results::series_standings(&series).await.unwrap().is_none());
+    sql::take_prompt(1, &lower).await.unwrap();
+    let queue = sql::get_prompts(Challenge::Glyph).await.unwrap().into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    assert_eq!(queue, [other]);
+
+    // Points from both parts add up.
+    for (message, week, author) in [(500, 0, ALICE), (501, 0, BOB), (502, 1, BOB), (503, 1, CAROL)] {
+        sql::seed_submission(MessageId::new(message), week, Challenge::Glyph, author, "https://cdn.example/x.png", 0).await.unwrap();
+    }
+
+    for (message, voter) in [(500, CAROL), (500, DAVE), (501, DAVE), (502, ALICE), (502, DAVE), (503, DAVE)] {
+        sql::add_vote(MessageId::new(message), voter, sql::DEFAULT_VOTE_WEIGHT).await.unwrap();
+    }
+
+    let (weeks, standings) = results::series_standings(&series).await.unwrap().unwrap();
+    assert_eq!(weeks, [0, 1]);
+    assert_eq!(standings, [(BOB, 5), (ALICE, 3), (CAROL, 2)]);
+    assert_eq!(sql::get_week_series(1, Challenge::Glyph).await.unwrap().map(|s| s.id), Some(series.id));
+}
+
+#[tokio::test]
+async fn challenges_can_go_on_hiatus_separately() {
+    let (_guard, api) = setup().await;
+    let glyph = sql::add_prompt(Challenge::Glyph, "A", None, None).await.unwrap();
+    let ambigram = sql::add_prompt(Challenge::Ambigram, "swims", None, None).await.unwrap();
+    sql::set_setting(Setting::active(Challenge::Glyph), "false").await.unwrap();
+
+    // The queue is kept for when the challenge comes back.
+    let weeks = scheduler::calendar(2).await.unwrap();
+    assert!(weeks.iter().all(|w| w.prompts[0].1 == PromptStatus::Hiatus));
+    assert_eq!(weeks[0].prompts[1].1, PromptStatus::Queued(ambigram));
+
+    // Entries are turned away with an explanation.
+    let message = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    on_reaction_add(&api, api.react(&message, ALICE, submit_emoji(), true)).await;
+    assert!(sql::get_submission(message.id).await.unwrap().is_none());
+    assert_eq!(api.dms.lock().unwrap().len(), 1);
+
+    sql::set_setting(Setting::active(Challenge::Glyph), "true").await.unwrap();
+    assert_eq!(scheduler::calendar(1).await.unwrap()[0].prompts[0].1, PromptStatus::Queued(glyph));
+    submit(&api, ALICE).await;
+    assert_eq!(sql::count_submissions(0, Challenge::Glyph).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn edited_prompts_must_be_confirmed_again() {
+    let (_guard, _api) = setup().await;
+    let id = sql::add_prompt(Challenge::Glyph, "A", None, None).await.unwrap();
+    assert!(!sql::get_prompt(id).await.unwrap().approved);
+    assert!(sql::approve_prompt(id).await.unwrap());
+    assert!(sql::get_prompt(id).await.unwrap().approved);
+
+    sql::update_prompt(id, "B", None, None).await.unwrap();
+    assert!(!sql::get_prompt(id).await.unwrap().approved);
+
+    // Confirming a cancelled entry does nothing.
+    assert!(sql::delete_prompt(id).await.unwrap());
+    assert!(!sql::approve_prompt(id).await.unwrap());
+}
+
+#[tokio::test]
+async fn portfolios_put_podium_finishes_first() {
+    let (_guard, _api) = setup().await;
+    for (message, week, author) in [(500, 0, ALICE), (501, 0, BOB), (502, 0, CAROL), (503, 0, DAVE), (504, 1, ALICE), (505, 2, ALICE)] {
+        sql::seed_submission(MessageId::new(message), week, Challenge::Glyph, author, "https://cdn.example/x.png", 0).await.unwrap();
+    }
+
+    // Alice is 4th with many votes in week 0 and 2nd with few in week 1.
+    let place = |message: u64, votes: i64, place: usize| async move {
+        let mut s = sql::get_submission(MessageId::new(message)).await.unwrap().unwrap();
+        s.votes = votes;
+        (s, place)
+    };
+
+    let week0 = vec![place(501, 20, 1).await, place(502, 15, 2).await, place(503, 12, 3).await, place(500, 10, 4).await];
+    sql::record_week_results(0, Challenge::Glyph, &week0).await.unwrap();
+    sql::record_week_results(1, Challenge::Glyph, &[place(504, 2, 2).await]).await.unwrap();
+
+    // Week 2 has no results yet, so its entry is left out.
+    let portfolio = sql::get_portfolio(ALICE, 12).await.unwrap();
+    assert_eq!(portfolio.iter().map(|e| (e.message, e.place)).collect::<Vec<_>>(), vec![(504, 2), (500, 4)]);
+    assert_eq!(sql::get_portfolio(ALICE, 1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn moderation_counts_cover_one_challenge() {
+    let (_guard, api) = setup().await;
+    let alice = submit(&api, ALICE).await;
+    let bob = submit(&api, BOB).await;
+    submit(&api, CAROL).await;
+    assert!(sql::flag_submission(alice, "Traced").await.unwrap());
+    assert_eq!(sql::disqualify_submission(bob).await.unwrap(), Some(Challenge::Glyph));
+
+    let counts = sql::get_moderation_counts(0, Challenge::Glyph).await.unwrap();
+    assert_eq!((counts.disqualified, counts.flagged), (1, 1));
+    let counts = sql::get_moderation_counts(0, Challenge::Ambigram).await.unwrap();
+    assert_eq!((counts.disqualified, counts.flagged), (0, 0));
+}
+
+#[tokio::test]
+async fn disqualified_entries_cannot_be_entered_again() {
+    let (_guard, api) = setup().await;
+    let bob = submit(&api, BOB).await;
+    assert_eq!(sql::disqualify_submission(bob).await.unwrap(), Some(Challenge::Glyph));
+
+    let message = api.messages.lock().unwrap()[&bob].clone();
+    on_reaction_remove(&api, api.react(&message, BOB, submit_emoji(), false)).await;
+    on_reaction_add(&api, api.react(&message, BOB, submit_emoji(), true)).await;
+
+    let entry = sql::get_submission(bob).await.unwrap().unwrap();
+    assert_eq!(entry.status, SubmissionStatus::Disqualified as i64);
+    assert!(sql::get_withdrawals(0).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn deadlines_are_extended_if_entries_are_scarce() {
+    let (_guard, api) = setup().await;
+    sql::set_setting(Setting::AutoExtendMinEntries, "2").await.unwrap();
+    submit(&api, ALICE).await;
+    submit(&api, BOB).await;
+
+    // Only a challenge that is running needs enough entries.
+    sql::set_setting(Setting::active(Challenge::Ambigram), "false").await.unwrap();
+    set_clock(sunday() - Duration::minutes(30));
+    assert!(!scheduler::needs_auto_extension(0).await.unwrap());
+
+    sql::set_setting(Setting::active(Challenge::Ambigram), "true").await.unwrap();
+    assert!(scheduler::needs_auto_extension(0).await.unwrap());
+
+    // Not too early, and not more often than allowed.
+    set_clock(sunday() - Duration::hours(2));
+    assert!(!scheduler::needs_auto_extension(0).await.unwrap());
+    set_clock(sunday() - Duration::minutes(30));
+    sql::record_auto_extension(0).await.unwrap();
+    assert!(!scheduler::needs_auto_extension(0).await.unwrap());
+    sql::set_setting(Setting::AutoExtendLimit, "2").await.unwrap();
+    assert!(scheduler::needs_auto_extension(0).await.unwrap());
+}
+
+#[tokio::test]
+async fn second_entries_are_rejected_or_replace_the_first() {
+    let (_guard, api) = setup().await;
+    let first = submit(&api, ALICE).await;
+    let second = submit(&api, ALICE).await;
+
+    // By default, the second entry is turned away.
+    let entries = sql::get_submissions(0, Challenge::Glyph).await.unwrap();
+    assert_eq!(entries.iter().map(|s| s.message as u64).collect::<Vec<_>>(), vec![first.get()]);
+    assert!(!api.has_reaction(second, ALICE, &submit_emoji()));
+    assert_eq!(api.dms.lock().unwrap().len(), 1);
+
+    // Otherwise, it takes the place of the first.
+    sql::set_setting(Setting::ReplaceEntries, "true").await.unwrap();
+    let third = submit(&api, ALICE).await;
+    let entries = sql::get_submissions(0, Challenge::Glyph).await.unwrap();
+    assert_eq!(entries.iter().map(|s| s.message as u64).collect::<Vec<_>>(), vec![third.get()]);
+    assert!(!api.has_reaction(first, ALICE, &submit_emoji()));
+    assert_eq!(api.dms.lock().unwrap().len(), 2);
+
+    let withdrawals = sql::get_withdrawals(0).await.unwrap();
+    assert_eq!(withdrawals.iter().map(|w| (w.message as u64, WithdrawalReason::try_from(w.reason).unwrap())).collect::<Vec<_>>(), vec![(first.get(), WithdrawalReason::Replaced)]);
+
+    // Entries for the other challenge don’t count.
+    let ambigram = api.post(Challenge::Ambigram.submission_channel(), ALICE, vec![api.image("entry.png")]);
+    on_reaction_add(&api, api.react(&ambigram, ALICE, submit_emoji(), true)).await;
+    assert_eq!(sql::count_submissions(0, Challenge::Ambigram).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn winners_pick_prompts_that_admins_approve() {
+    let (_guard, api) = setup().await;
+    let admin_channel = ChannelId::new(77);
+    sql::set_setting(Setting::WinnerPicksPrompt, "true").await.unwrap();
+    sql::set_setting(Setting::AdminChannel, &admin_channel.to_string()).await.unwrap();
+    let shortlisted = sql::add_to_shortlist(Challenge::Glyph, "Ŋ").await.unwrap().to_string();
+    let dms_to_alice = || api.dms.lock().unwrap().iter().filter(|(u, _)| *u == ALICE).count();
+    let reviews = || api.sent.lock().unwrap().iter().filter(|(c, _)| *c == admin_channel).count();
+
+    // The winner is invited once.
+    submit(&api, ALICE).await;
+    let placements = results::ranking(0, Challenge::Glyph).await.unwrap();
+    prompt_picks::invite(&api, 0, Challenge::Glyph, &placements).await.unwrap();
+    prompt_picks::invite(&api, 0, Challenge::Glyph, &placements).await.unwrap();
+    assert_eq!(dms_to_alice(), 1);
+    let (pick, _) = sql::invite_to_pick(0, Challenge::Glyph, ALICE).await.unwrap();
+
+    // Only they can pick, and only once until it is reviewed.
+    assert!(prompt_picks::choose(&api, pick.id, BOB, &shortlisted).await.is_err());
+    assert_eq!(prompt_picks::choose(&api, pick.id, ALICE, &shortlisted).await.unwrap(), "Ŋ");
+    assert!(prompt_picks::suggest(&api, pick.id, ALICE, "Ω").await.is_err());
+    assert_eq!(reviews(), 1);
+
+    // If the admins reject it, they are asked to pick again.
+    prompt_picks::review(&api, pick.id, false).await.unwrap();
+    assert_eq!(dms_to_alice(), 2);
+    prompt_picks::suggest(&api, pick.id, ALICE, "Ω").await.unwrap();
+    assert_eq!(reviews(), 2);
+
+    // Once approved, it is queued; the shortlist keeps what they didn’t pick.
+    let approved = prompt_picks::review(&api, pick.id, true).await.unwrap();
+    assert!(approved.contains("Ω"));
+    assert!(prompt_picks::review(&api, pick.id, false).await.is_err());
+    assert_eq!(dms_to_alice(), 3);
+    let (queued, _) = sql::get_prompts(Challenge::Glyph).await.unwrap().into_iter().find(|(_, p)| p == "Ω").unwrap();
+    assert!(sql::get_prompt(queued).await.unwrap().approved);
+    assert_eq!(sql::get_shortlist(Challenge::Glyph).await.unwrap().len(), 1);
+
+    // A winner who can’t be reached by DM doesn’t undo the review.
+    api.closed_dms.lock().unwrap().insert(ALICE);
+    let (pick, _) = sql::invite_to_pick(1, Challenge::Glyph, ALICE).await.unwrap();
+    prompt_picks::suggest(&api, pick.id, ALICE, "Ψ").await.unwrap();
+    let approved = prompt_picks::review(&api, pick.id, true).await.unwrap();
+    assert!(approved.contains("could not be told"));
+    assert!(sql::get_prompts(Challenge::Glyph).await.unwrap().iter().any(|(_, p)| p == "Ψ"));
+}
+
+#[tokio::test]
+async fn submissions_are_only_taken_while_the_week_is_open() {
+    let (_guard, api) = setup().await;
+
+    // Images posted before the week started can’t be entered.
+    set_clock(sunday() - Duration::days(8));
+    let old = api.post(glyph_channel(), ALICE, vec![api.image("entry.png")]);
+    set_clock(wednesday());
+    on_reaction_add(&api, api.react(&old, ALICE, submit_emoji(), true)).await;
+    assert!(sql::get_submission(old.id).await.unwrap().is_none());
+    assert!(!api.has_reaction(old.id, ALICE, &submit_emoji()));
+    assert_eq!(api.dms.lock().unwrap().len(), 1);
+
+    // Nor can anything once the deadline has passed, even if the week
+    // hasn’t been rolled over yet; it is recorded when the week starts so
+    // it doesn’t move on to next Sunday.
+    scheduler::record_week_start().await.unwrap();
+    set_clock(sunday());
+    assert_eq!(scheduler::deadline(0).await.unwrap(), sunday());
+    let late = submit(&api, BOB).await;
+    assert!(sql::get_submission(late).await.unwrap().is_none());
+    let dms = api.dms.lock().unwrap();
+    assert_eq!(dms.len(), 2);
+    assert_eq!(dms[1].0, BOB);
+    assert!(dms[1].1["content"].as_str().unwrap().contains("closed"));
+}
+
+#[tokio::test]
+async fn prompt_polls_queue_the_most_popular_prompt() {
+    let (_guard, api) = setup().await;
+    let poll_channel = ChannelId::new(88);
+    let polls_posted = || api.sent.lock().unwrap().iter().filter(|(c, _)| *c == poll_channel).count();
+    for prompt in ["A", "B", "C", "D", "E"] {
+        sql::add_to_shortlist(Challenge::Glyph, prompt).await.unwrap();
+    }
+
+    // Nothing happens without a poll channel.
+    polls::run_polls(&api).await.unwrap();
+    assert_eq!(polls_posted(), 0);
+
+    // Only challenges with enough prompts on the shortlist get a poll.
+    sql::set_setting(Setting::PollChannel, &poll_channel.to_string()).await.unwrap();
+    polls::run_polls(&api).await.unwrap();
+    assert_eq!(polls_posted(), 1);
+    assert!(sql::get_latest_poll(Challenge::Ambigram).await.unwrap().is_none());
+    let poll = sql::get_latest_poll(Challenge::Glyph).await.unwrap().unwrap();
+    assert_eq!(sql::get_poll_options(poll.id).await.unwrap().len(), 4);
+
+    // Votes can be changed while the poll is open.
+    assert_eq!(polls::vote(poll.id, ALICE, 0).await.unwrap(), "A");
+    assert_eq!(polls::vote(poll.id, ALICE, 2).await.unwrap(), "C");
+    polls::vote(poll.id, BOB, 2).await.unwrap();
+    polls::vote(poll.id, CAROL, 1).await.unwrap();
+    assert!(polls::vote(poll.id, DAVE, 4).await.is_err());
+    polls::run_polls(&api).await.unwrap();
+    assert_eq!(polls_posted(), 1);
+
+    // Once it closes, the winner is queued and taken off the shortlist.
+    set_clock(wednesday() + Duration::hours(48));
+    polls::run_polls(&api).await.unwrap();
+    assert!(polls::vote(poll.id, DAVE, 0).await.is_err());
+    assert!(sql::get_prompts(Challenge::Glyph).await.unwrap().iter().any(|(_, p)| p == "C"));
+    let shortlist = sql::get_shortlist(Challenge::Glyph).await.unwrap();
+    assert_eq!(shortlist.iter().map(|(_, p)| p.as_str()).collect::<Vec<_>>(), ["A", "B", "D", "E"]);
+    let edits = api.edits.lock().unwrap().clone();
+    assert_eq!(edits.len(), 1);
+    assert!(edits[0].1["content"].as_str().unwrap().contains("‘C’ won"));
+
+    // The next poll opens once enough time has passed since the last one.
+    polls::run_polls(&api).await.unwrap();
+    assert_eq!(polls_posted(), 1);
+    set_clock(wednesday() + Duration::days(7));
+    polls::run_polls(&api).await.unwrap();
+    assert_eq!(polls_posted(), 2);
+    assert_eq!(sql::get_poll(poll.id).await.unwrap().unwrap().closed, Some((wednesday() + Duration::hours(48)).timestamp()));
+
+    // A prompt that left the shortlist in the meantime can’t win.
+    let poll = sql::get_latest_poll(Challenge::Glyph).await.unwrap().unwrap();
+    assert_eq!(polls::vote(poll.id, ALICE, 0).await.unwrap(), "A");
+    assert_eq!(polls::vote(poll.id, BOB, 1).await.unwrap(), "B");
+    polls::vote(poll.id, CAROL, 1).await.unwrap();
+    let b = shortlist.iter().find(|(_, p)| p == "B").unwrap().0;
+    assert!(sql::remove_from_shortlist(b).await.unwrap());
+    set_clock(wednesday() + Duration::days(9));
+    polls::run_polls(&api).await.unwrap();
+    let queued = sql::get_prompts(Challenge::Glyph).await.unwrap();
+    assert!(queued.iter().any(|(_, p)| p == "A"));
+    assert!(!queued.iter().any(|(_, p)| p == "B"));
+    let edits = api.edits.lock().unwrap().clone();
+    assert!(edits[1].1["content"].as_str().unwrap().contains("‘A’ has been added to the queue, since"));
+}
+
+#[test]
+fn poll_ties_go_to_the_first_prompt() {
+    let option = |option, votes| sql::PollOption { option, prompt: option.to_string(), shortlisted: None, votes };
+    assert_eq!(polls::winner(&[option(0, 1), option(1, 3), option(2, 3)]).unwrap().option, 1);
+    assert!(polls::winner(&[option(0, 0), option(1, 0)]).is_none());
+}
+
+#[tokio::test]
+async fn advancing_the_week_creates_its_row() {
+    let (_guard, _api) = setup().await;
+    set_clock(sunday() + Duration::hours(1));
+    assert_eq!(sql::advance_week().await.unwrap(), 1);
+    assert_eq!(sql::current_week().await.unwrap(), 1);
+    assert_eq!(sql::get_weeks(1, 2).await.unwrap().iter().map(|w| w.week).collect::<Vec<_>>(), [1]);
+
+    // The new week starts when the last one ended, and its deadline is
+    // recorded straight away.
+    scheduler::record_week_start().await.unwrap();
+    assert_eq!(sql::get_week_start(1).await.unwrap(), Some(sunday().timestamp()));
+    assert_eq!(sql::get_week_deadline(1).await.unwrap(), Some((sunday() + Duration::days(7)).timestamp()));
+
+    sql::set_current_week(5).await.unwrap();
+    assert_eq!(sql::current_week().await.unwrap(), 5);
+    assert_eq!(sql::get_weeks(5, 6).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn advancing_early_keeps_a_full_week() {
+    let (_guard, _api) = setup().await;
+    scheduler::record_week_start().await.unwrap();
+    assert_eq!(scheduler::advance_week().await.unwrap(), 1);
+
+    // The week starts now, but still ends a week after the last deadline.
+    assert_eq!(sql::get_week_start(1).await.unwrap(), Some(wednesday().timestamp()));
+    assert_eq!(scheduler::deadline(1).await.unwrap(), sunday() + Duration::days(7));
+    assert_eq!(scheduler::voting_deadline(0).await.unwrap(), sunday() + Duration::days(7));
+}
+
+#[tokio::test]
+async fn votes_can_be_taken_back_from_the_receipt() {
+    let (_guard, api) = setup().await;
+    let alice = submit(&api, ALICE).await;
+    let carol = submit(&api, CAROL).await;
+    vote(&api, alice, BOB, true).await;
+    vote(&api, carol, BOB, true).await;
+
+    // Receipts cover the week being voted on.
+    sql::set_current_week(1).await.unwrap();
+    let (text, rows) = receipts::render(BOB).await.unwrap();
+    assert!(text.contains(&alice.to_string()) && text.contains(&carol.to_string()));
+    assert_eq!(rows.len(), 1);
+
+    receipts::remove_vote(&api, BOB, alice).await.unwrap();
+    assert_eq!(votes_of(alice).await, 0);
+    assert_eq!(votes_of(carol).await, 1);
+    assert!(!api.has_reaction(alice, BOB, &vote_reaction().await));
+    assert!(receipts::remove_vote(&api, BOB, alice).await.is_err());
+
+    let (text, _) = receipts::render(BOB).await.unwrap();
+    assert!(!text.contains(&alice.to_string()) && text.contains(&carol.to_string()));
+    assert!(receipts::render(DAVE).await.unwrap().1.is_empty());
+}
+
+#[tokio::test]
+async fn long_receipts_fit_in_a_message() {
+    let (_guard, api) = setup().await;
+    for author in 200..240 {
+        let entry = submit(&api, UserId::new(author)).await;
+        vote(&api, entry, BOB, true).await;
+    }
+
+    sql::set_current_week(1).await.unwrap();
+    let (text, rows) = receipts::render(BOB).await.unwrap();
+    assert!(text.chars().count() <= 2000);
+    assert!(text.contains("more; take back some"));
+    let listed = text.matches("\n- ").count();
+    assert_eq!(rows.iter().map(|r| match r {
+        CreateActionRow::Buttons(b) => b.len(),
+        _ => 0,
+    }).sum::<usize>(), listed);
+}
@@ -0,0 +1,82 @@
+//! Mark prompt previews so a leaked screenshot can’t be mistaken for the
+//! announcement. Only the copies we send are marked; the image on disk,
+//! which is what gets announced, is left alone.
+
+use std::io::Cursor;
+use ab_glyph::{FontRef, PxScale};
+use image::{ImageFormat, Rgba, RgbaImage};
+use image::imageops::overlay;
+use imageproc::drawing::{draw_text_mut, text_size};
+use imageproc::geometric_transformations::{Interpolation, rotate_about_center};
+use poise::serenity_prelude::CreateAttachment;
+use crate::Error;
+
+/// Font used for the watermark.
+const FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+const TEXT: &str = "PREVIEW – not announced";
+
+/// Colour of the text; translucent so the preview can still be judged.
+const COLOUR: Rgba<u8> = Rgba([200, 30, 30, 140]);
+
+/// How much of the diagonal the text spans.
+const TEXT_SPAN: f32 = 0.75;
+
+/// Draw the watermark across an image, from the bottom left to the top right.
+pub fn watermark(img: &mut RgbaImage) {
+    let font = FontRef::try_from_slice(FONT).expect("Watermark font is invalid");
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let diagonal = w.hypot(h);
+
+    // Scale the text to the size of the image.
+    let (tw, _) = text_size(PxScale::from(100.0), &font, TEXT);
+    let scale = PxScale::from(100.0 * TEXT_SPAN * diagonal / tw.max(1) as f32);
+    let (tw, th) = text_size(scale, &font, TEXT);
+
+    // Draw it horizontally on a layer large enough to hold it at any
+    // angle, then turn that to match the diagonal.
+    let side = diagonal.ceil() as u32;
+    let mut layer = RgbaImage::from_pixel(side, side, Rgba([COLOUR[0], COLOUR[1], COLOUR[2], 0]));
+    let x = side.saturating_sub(tw) / 2;
+    let y = side.saturating_sub(th) / 2;
+    draw_text_mut(&mut layer, COLOUR, x as i32, y as i32, scale, &font, TEXT);
+    let layer = rotate_about_center(&layer, -h.atan2(w), Interpolation::Bilinear, Rgba([0, 0, 0, 0]));
+
+    let offset_x = (w as i64 - side as i64) / 2;
+    let offset_y = (h as i64 - side as i64) / 2;
+    overlay(img, &layer, offset_x, offset_y);
+}
+
+/// Watermark a PNG.
+pub fn watermark_png(png: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut img = image::load_from_memory(png)?.to_rgba8();
+    watermark(&mut img);
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png)?;
+    Ok(out.into_inner())
+}
+
+/// Load a generated image and watermark it so it can be sent as a preview.
+pub async fn preview_attachment(path: &str, filename: &str) -> Result<CreateAttachment, Error> {
+    let png = tokio::fs::read(path).await?;
+
+    // Rendering is CPU-bound, so keep it off the async workers.
+    let png = tokio::task::spawn_blocking(move || watermark_png(&png)).await??;
+    Ok(CreateAttachment::bytes(png, filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::drawing::draw_filled_rect_mut;
+    use imageproc::rect::Rect;
+    use crate::golden;
+    use super::*;
+
+    #[test]
+    fn watermark_matches_golden() {
+        let mut img = RgbaImage::from_pixel(960, 540, Rgba([250, 245, 235, 255]));
+        draw_filled_rect_mut(&mut img, Rect::at(80, 120).of_size(800, 300), Rgba([30, 30, 60, 255]));
+        watermark(&mut img);
+        golden::check("watermark", &img);
+    }
+}